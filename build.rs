@@ -1,9 +1,16 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
-use cargo_metadata::{CargoOpt, Metadata, MetadataCommand};
+use cargo_metadata::{CargoOpt, DependencyKind, Metadata, MetadataCommand, PackageId};
 use project_root::get_project_root;
 use serde::Serialize;
-use std::{env, error::Error, fs::write, path::PathBuf, result::Result};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    error::Error,
+    fs::write,
+    path::PathBuf,
+    result::Result,
+};
 
 #[derive(Serialize, Debug)]
 struct Package<'a> {
@@ -12,6 +19,55 @@ struct Package<'a> {
     license: &'a str,
 }
 
+/// `CycloneDX` Software Bill of Materials
+///
+/// Ref: <https://cyclonedx.org/docs/1.4/json/>
+///
+/// NOTE: `serialNumber` and `metadata.timestamp` are intentionally omitted
+/// such that the generated SBOM is reproducible for a given `Cargo.lock`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Bom<'a> {
+    #[serde(rename = "bomFormat")]
+    format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    metadata: BomMetadata<'a>,
+    components: Vec<Component<'a>>,
+    dependencies: Vec<BomDependency>,
+}
+
+#[derive(Serialize, Debug)]
+struct BomMetadata<'a> {
+    component: Component<'a>,
+}
+
+#[derive(Serialize, Debug)]
+struct Component<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: String,
+    name: &'a str,
+    version: String,
+    purl: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<License<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct License<'a> {
+    expression: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BomDependency {
+    #[serde(rename = "ref")]
+    bom_ref: String,
+    depends_on: Vec<String>,
+}
+
 /// get the list of dependencies of this crate
 fn get_dependencies(metadata: &Metadata) -> Vec<&str> {
     metadata
@@ -22,6 +78,136 @@ fn get_dependencies(metadata: &Metadata) -> Vec<&str> {
         .unwrap_or_default()
 }
 
+/// get the resolved dependency graph of this crate, including transitive
+/// dependencies.
+///
+/// Development and build dependencies of this crate are not included, as they
+/// are not shipped as part of the crate.
+fn get_dependency_graph(
+    metadata: &Metadata,
+) -> Result<BTreeMap<&PackageId, BTreeSet<&PackageId>>, Box<dyn Error>> {
+    let resolve = metadata
+        .resolve
+        .as_ref()
+        .ok_or("cargo metadata did not include the dependency graph")?;
+    let root = metadata
+        .packages
+        .iter()
+        .find(|package| package.name == env!("CARGO_PKG_NAME"))
+        .map(|package| &package.id)
+        .ok_or("unable to find this crate in cargo metadata")?;
+
+    let nodes = resolve
+        .nodes
+        .iter()
+        .map(|node| (&node.id, node))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut graph = BTreeMap::new();
+    let mut pending = vec![root];
+    while let Some(id) = pending.pop() {
+        if graph.contains_key(id) {
+            continue;
+        }
+        let node = nodes.get(id).ok_or_else(|| format!("missing node {id}"))?;
+
+        let deps = node
+            .deps
+            .iter()
+            .filter(|dep| {
+                id != root
+                    || dep
+                        .dep_kinds
+                        .iter()
+                        .any(|kind| kind.kind == DependencyKind::Normal)
+            })
+            .map(|dep| &dep.pkg)
+            .collect::<BTreeSet<_>>();
+
+        pending.extend(deps.iter().copied());
+        graph.insert(id, deps);
+    }
+
+    Ok(graph)
+}
+
+/// generate a `CycloneDX` SBOM of this crate and its transitive dependencies
+fn get_sbom(metadata: &Metadata) -> Result<String, Box<dyn Error>> {
+    let graph = get_dependency_graph(metadata)?;
+
+    let packages = metadata
+        .packages
+        .iter()
+        .map(|package| (&package.id, package))
+        .collect::<BTreeMap<_, _>>();
+
+    let purl = |id: &PackageId| -> Result<String, Box<dyn Error>> {
+        let package = packages
+            .get(id)
+            .ok_or_else(|| format!("missing package {id}"))?;
+        Ok(format!("pkg:cargo/{}@{}", package.name, package.version))
+    };
+
+    let mut root = None;
+    let mut components = vec![];
+    let mut dependencies = vec![];
+    for (id, deps) in &graph {
+        let package = packages
+            .get(id)
+            .ok_or_else(|| format!("missing package {id}"))?;
+
+        let purl_str = purl(id)?;
+        let component = Component {
+            kind: if package.name == env!("CARGO_PKG_NAME") {
+                "application"
+            } else {
+                "library"
+            },
+            bom_ref: purl_str.clone(),
+            name: package.name.as_ref(),
+            version: package.version.to_string(),
+            purl: purl_str.clone(),
+            licenses: package
+                .license
+                .iter()
+                .map(|expression| License { expression })
+                .collect(),
+        };
+
+        if package.name == env!("CARGO_PKG_NAME") {
+            root = Some(component);
+        } else {
+            components.push(component);
+        }
+
+        let mut depends_on = deps
+            .iter()
+            .map(|dep| purl(dep))
+            .collect::<Result<Vec<_>, _>>()?;
+        depends_on.sort();
+        dependencies.push(BomDependency {
+            bom_ref: purl_str,
+            depends_on,
+        });
+    }
+
+    components.sort_by(|a, b| a.bom_ref.cmp(&b.bom_ref));
+    dependencies.sort_by(|a, b| a.bom_ref.cmp(&b.bom_ref));
+
+    let bom = Bom {
+        format: "CycloneDX",
+        spec_version: "1.4",
+        version: 1,
+        metadata: BomMetadata {
+            component: root.ok_or("unable to find this crate in the dependency graph")?,
+        },
+        components,
+        dependencies,
+    };
+
+    Ok(serde_json::to_string_pretty(&bom)?)
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     println!(
         "cargo:rerun-if-changed={}",
@@ -56,8 +242,12 @@ fn main() -> Result<(), Box<dyn Error>> {
         });
     }
 
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+
     let as_string = serde_json::to_string_pretty(&licenses)?;
-    let path = PathBuf::from(env::var("OUT_DIR")?).join("licenses.json");
-    write(path, as_string)?;
+    write(out_dir.join("licenses.json"), as_string)?;
+
+    let sbom = get_sbom(&metadata)?;
+    write(out_dir.join("sbom.cdx.json"), sbom)?;
     Ok(())
 }
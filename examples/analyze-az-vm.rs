@@ -10,11 +10,18 @@ use azure_mgmt_compute::models::{
     ResourceWithOptionalLocation, VirtualMachineExtension, VirtualMachineExtensionProperties,
 };
 use clap::Parser;
-use freta::{argparse::parse_key_val, Client, Error, ImageFormat, Result};
+use freta::{
+    argparse::parse_key_val,
+    integrations::{Capture, CaptureSource, CaptureTarget, Progress},
+    models::artifacts::REPORT_JSON,
+    Client, Error, ImageFormat, Result,
+};
+use futures::{future::BoxFuture, StreamExt};
 use serde_json::json;
 use std::{io::stderr, path::PathBuf, sync::Arc};
 use tracing::{info, level_filters::LevelFilter};
 use tracing_subscriber::EnvFilter;
+use url::Url;
 use uuid::Uuid;
 
 // https://learn.microsoft.com/en-us/azure/virtual-machines/extensions/custom-script-linux#extension-schema
@@ -37,6 +44,97 @@ struct Args {
     output: Option<PathBuf>,
 }
 
+/// Captures Azure VMs in a resource group by running AVML on the VM, which
+/// uploads the image directly
+struct AzureVmSource {
+    compute_client: azure_mgmt_compute::Client,
+    subscription_id: String,
+    group: String,
+}
+
+impl CaptureSource for AzureVmSource {
+    fn name(&self) -> &str {
+        "azure-vm"
+    }
+
+    fn format(&self) -> ImageFormat {
+        ImageFormat::Lime
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<CaptureTarget>>> {
+        Box::pin(async {
+            let mut targets = vec![];
+            let mut pages = self
+                .compute_client
+                .virtual_machines_client()
+                .list(&self.group, &self.subscription_id)
+                .into_stream();
+            while let Some(page) = pages.next().await {
+                for vm in page?.value {
+                    if let (Some(id), Some(name)) = (vm.resource.id, vm.resource.name) {
+                        targets.push(CaptureTarget { id, name });
+                    }
+                }
+            }
+            Ok(targets)
+        })
+    }
+
+    fn capture<'a>(
+        &'a self,
+        target: &'a str,
+        upload_url: &'a Url,
+        progress: Progress<'a>,
+    ) -> BoxFuture<'a, Result<Capture>> {
+        Box::pin(async move {
+            let vm = self
+                .compute_client
+                .virtual_machines_client()
+                .get(&self.group, target, &self.subscription_id)
+                .await?;
+
+            let settings = json!({
+                "fileUris": [
+                    "https://github.com/microsoft/avml/releases/download/v0.10.0/avml"
+                ]
+            });
+
+            let protected_settings = json!({
+                "commandToExecute": format!("./avml /root/{}.lime --compress --delete --sas-url '{upload_url}'", Uuid::new_v4()),
+            });
+
+            let extension_parameters = VirtualMachineExtension {
+                resource_with_optional_location: ResourceWithOptionalLocation {
+                    location: Some(vm.resource.location),
+                    ..Default::default()
+                },
+                properties: Some(VirtualMachineExtensionProperties {
+                    publisher: Some(EXTENSION_PUBLISHER.to_string()),
+                    type_: Some(EXTENSION_NAME.to_string()),
+                    type_handler_version: Some(EXTENSION_VERSION.to_string()),
+                    settings: Some(settings),
+                    protected_settings: Some(protected_settings),
+                    ..Default::default()
+                }),
+            };
+
+            progress(&format!("launching avml on {target}"));
+            self.compute_client
+                .virtual_machine_extensions_client()
+                .create_or_update(
+                    &self.group,
+                    target,
+                    EXTENSION_PUBLISHER,
+                    extension_parameters,
+                    &self.subscription_id,
+                )
+                .await?;
+
+            Ok(Capture::Uploaded)
+        })
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt()
@@ -54,65 +152,26 @@ async fn main() -> Result<()> {
     let client = Client::new().await?;
 
     let creds = Arc::new(DefaultAzureCredential::default());
-    let compute_client = azure_mgmt_compute::Client::builder(creds).build();
-
-    let vm = compute_client
-        .virtual_machines_client()
-        .get(&cmd.group, &cmd.vm_name, &cmd.subscription_id)
-        .await?;
+    let source = AzureVmSource {
+        compute_client: azure_mgmt_compute::Client::builder(creds).build(),
+        subscription_id: cmd.subscription_id,
+        group: cmd.group.clone(),
+    };
 
     let mut tags = cmd.tags.unwrap_or_default();
     tags.push(("name".to_string(), cmd.vm_name.clone()));
-    tags.push(("group".to_string(), cmd.group.clone()));
+    tags.push(("group".to_string(), cmd.group));
 
-    let image = client.images_create(ImageFormat::Lime, tags).await?;
+    let progress = |message: &str| info!("{message}");
+    let image = client
+        .capture(&source, &cmd.vm_name, tags, &Default::default(), &progress)
+        .await?;
 
     info!("image: {}", image.image_id);
 
-    let image_url = image
-        .image_url
-        .clone()
-        .ok_or(Error::InvalidResponse("missing image_url"))?;
-
-    let settings = json!({
-        "fileUris": [
-            "https://github.com/microsoft/avml/releases/download/v0.10.0/avml"
-        ]
-    });
-
-    let protected_settings = json!({
-        "commandToExecute": format!("./avml /root/{}.lime --compress --delete --sas-url '{image_url}'", Uuid::new_v4()),
-    });
-
-    let extension_parameters = VirtualMachineExtension {
-        resource_with_optional_location: ResourceWithOptionalLocation {
-            location: Some(vm.resource.location),
-            ..Default::default()
-        },
-        properties: Some(VirtualMachineExtensionProperties {
-            publisher: Some(EXTENSION_PUBLISHER.to_string()),
-            type_: Some(EXTENSION_NAME.to_string()),
-            type_handler_version: Some(EXTENSION_VERSION.to_string()),
-            settings: Some(settings),
-            protected_settings: Some(protected_settings),
-            ..Default::default()
-        }),
-    };
-
-    compute_client
-        .virtual_machine_extensions_client()
-        .create_or_update(
-            &cmd.group,
-            &cmd.vm_name,
-            EXTENSION_PUBLISHER,
-            extension_parameters,
-            &cmd.subscription_id,
-        )
-        .await?;
-
     if let Some(output) = cmd.output {
         client
-            .artifacts_download(image.image_id, "report.json", output)
+            .artifacts_download(image.image_id, REPORT_JSON, output)
             .await?;
     }
     Ok(())
@@ -6,12 +6,18 @@
 //! VM in Azure, with the resulting image being uploaded to Project Freta.
 
 use clap::{Parser, Subcommand};
-use freta::{argparse::parse_key_val, Client, Error, Image, ImageFormat, Result};
+use freta::{
+    argparse::parse_key_val,
+    integrations::{run_plugin, Capture, CaptureSource, CaptureTarget, PluginCommand, Progress},
+    Client, Error, ImageFormat, Result,
+};
+use futures::future::BoxFuture;
 use powershell_script::PsScriptBuilder;
 use serde::Deserialize;
 use std::{io::stderr, path::PathBuf};
 use tracing::{info, level_filters::LevelFilter};
 use tracing_subscriber::EnvFilter;
+use url::Url;
 use uuid::Uuid;
 
 #[derive(Parser)]
@@ -26,6 +32,9 @@ enum Commands {
     List,
     /// image a single VM
     ImageVm(ImageOpt),
+    /// act as the `hyperv` capture source for `freta capture`
+    #[command(subcommand)]
+    Plugin(PluginCommand),
 }
 
 #[derive(Parser)]
@@ -111,38 +120,80 @@ fn get_vm_id(vm_name: &str) -> Result<Uuid> {
     ))
 }
 
-async fn create_snapshot(
-    vm_name: String,
-    mut tags: Vec<(String, String)>,
-    client: &Client,
-) -> Result<Image> {
-    let vm_id = get_vm_id(&vm_name)?;
-
-    let snapshot_id = Uuid::new_v4();
-    info!("creating hyperv snapshot id: {}", snapshot_id);
-
-    run(format!(
-        "get-vm -id {vm_id} | checkpoint-vm -snapshotname {snapshot_id}"
-    ))?;
-
-    let output = run(format!(
-        "get-vm -id {vm_id} | get-vmsnapshot -name {snapshot_id} | select id, path | convertto-json"
-    ))?;
-    let snapshot: Snapshot = serde_json::from_str(&output)?;
-    let path = snapshot
-        .path
-        .join("Snapshots")
-        .join(format!("{}.VMRS", snapshot.id));
-
-    tags.push(("name".to_string(), vm_name.clone()));
-    let image = client.images_upload(ImageFormat::Vmrs, tags, path).await?;
-    info!("image_id: {}", image.image_id);
-
-    run(format!(
-        "get-vm -id {vm_id} | get-vmsnapshot -name {snapshot_id} | remove-vmsnapshot"
-    ))?;
-
-    Ok(image)
+/// Captures Hyper-V VMs by creating a checkpoint, uploading the saved memory
+/// state of the checkpoint, and then removing the checkpoint
+struct HyperVSource;
+
+impl CaptureSource for HyperVSource {
+    fn name(&self) -> &str {
+        "hyperv"
+    }
+
+    fn format(&self) -> ImageFormat {
+        ImageFormat::Vmrs
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<CaptureTarget>>> {
+        Box::pin(async {
+            Ok(list_vms()?
+                .0
+                .into_iter()
+                .map(|vm| CaptureTarget {
+                    id: vm.id.to_string(),
+                    name: vm.name,
+                })
+                .collect())
+        })
+    }
+
+    fn capture<'a>(
+        &'a self,
+        target: &'a str,
+        _upload_url: &'a Url,
+        progress: Progress<'a>,
+    ) -> BoxFuture<'a, Result<Capture>> {
+        Box::pin(async move {
+            let vm_id = get_vm_id(target)?;
+
+            let snapshot_id = Uuid::new_v4();
+            progress(&format!("creating hyperv snapshot id: {snapshot_id}"));
+
+            run(format!(
+                "get-vm -id {vm_id} | checkpoint-vm -snapshotname {snapshot_id}"
+            ))?;
+
+            let output = run(format!(
+                "get-vm -id {vm_id} | get-vmsnapshot -name {snapshot_id} | select id, path | convertto-json"
+            ))?;
+            let snapshot: Snapshot = serde_json::from_str(&output)?;
+            let path = snapshot
+                .path
+                .join("Snapshots")
+                .join(format!("{}.VMRS", snapshot.id));
+
+            Ok(Capture::File {
+                path,
+                remove_after_upload: false,
+            })
+        })
+    }
+
+    fn cleanup<'a>(&'a self, target: &'a str, capture: &'a Capture) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            // the saved memory state is named using the id of the snapshot
+            let Capture::File { path, .. } = capture else {
+                return Ok(());
+            };
+            let Some(snapshot_id) = path.file_stem().and_then(|x| x.to_str()) else {
+                return Ok(());
+            };
+            let vm_id = get_vm_id(target)?;
+            run(format!(
+                "get-vm -id {vm_id} | get-vmsnapshot | where id -eq '{snapshot_id}' | remove-vmsnapshot"
+            ))?;
+            Ok(())
+        })
+    }
 }
 
 #[tokio::main]
@@ -159,22 +210,32 @@ async fn main() -> Result<()> {
 
     let cmd = Args::parse();
 
-    let client = Client::new().await?;
-
     match cmd.command {
         Commands::List => {
-            let vms = list_vms()?;
-            for vm in vms.0 {
+            for vm in HyperVSource.list_targets().await? {
                 info!("{}", vm.name);
             }
         }
         Commands::ImageVm(opts) => {
-            let image =
-                create_snapshot(opts.vm_name, opts.tags.unwrap_or_default(), &client).await?;
+            let client = Client::new().await?;
+            let mut tags = opts.tags.unwrap_or_default();
+            tags.push(("name".to_string(), opts.vm_name.clone()));
+            let progress = |message: &str| info!("{message}");
+            let image = client
+                .capture(
+                    &HyperVSource,
+                    &opts.vm_name,
+                    tags,
+                    &Default::default(),
+                    &progress,
+                )
+                .await?;
+            info!("image_id: {}", image.image_id);
             if opts.monitor {
                 client.images_monitor(image.image_id).await?;
             }
         }
+        Commands::Plugin(command) => run_plugin(&HyperVSource, command).await?,
     }
 
     Ok(())
@@ -15,11 +15,10 @@ use axum::{
 };
 use clap::Parser;
 use freta::{
-    models::webhooks::{hmac_sha512, WebhookEvent, WebhookEventType, DIGEST_HEADER},
+    models::webhooks::{receiver::receive, WebhookEventType},
     Client, Error, ImageId, Result, Secret,
 };
-use serde_json::Value;
-use std::{io::stderr, net::SocketAddr, string::ToString};
+use std::{io::stderr, net::SocketAddr};
 use tracing::{error, info, level_filters::LevelFilter};
 use tracing_subscriber::EnvFilter;
 
@@ -33,6 +32,11 @@ struct Config {
 
     #[arg(long, env = "FRETA_HMAC_TOKEN")]
     hmac_token: Option<Secret>,
+
+    /// HMAC token accepted in addition to `--hmac-token` while rotating the
+    /// HMAC token of the webhook
+    #[arg(long, env = "FRETA_HMAC_TOKEN_PREVIOUS", requires = "hmac_token")]
+    hmac_token_previous: Option<Secret>,
 }
 
 #[tokio::main]
@@ -51,7 +55,13 @@ async fn main() -> Result<()> {
 
     let app = Router::new()
         .route(API_ENDPOINT, post(webhook_receiver))
-        .with_state(config.hmac_token);
+        .with_state(
+            config
+                .hmac_token
+                .into_iter()
+                .chain(config.hmac_token_previous)
+                .collect::<Vec<_>>(),
+        );
     let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     info!("starting service on {}", addr);
 
@@ -65,75 +75,35 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-/// Deserialize & validate the HMAC for the webhook
-fn parse_and_validate(
-    bytes: &[u8],
-    hmac_header: Option<String>,
-    hmac_token: Option<Secret>,
-) -> std::result::Result<WebhookEvent, Box<dyn std::error::Error>> {
-    let event: WebhookEvent = serde_json::from_slice(bytes)?;
-
-    // Note: `WebhookEvent.hmac_sha512` will reserialize and then hmac the
-    // event.  This validates the raw bytes that came from the webhook body
-    if let Some(token) = hmac_token {
-        let Some(from_header) = hmac_header else {
-            return Err("hmac header is required".into());
-        };
-
-        let hmac = hmac_sha512(bytes, &token)?;
-        if !compare(&from_header, &hmac) {
-            return Err("hmac does not match".into());
-        }
-    }
-
-    Ok(event)
-}
-
-/// Comparison in constant time.
-fn compare(a: &str, b: &str) -> bool {
-    if a.len() != b.len() {
-        return false;
-    }
-
-    let mut result = 0;
-
-    for (x, y) in a.bytes().zip(b.bytes()) {
-        result |= x ^ y;
-    }
-    result == 0
-}
-
 /// retrieve the report for an image and log the extracted kernel banner
 async fn show_kernel_banner_from_report(image_id: ImageId) -> Result<()> {
     let client = Client::new().await?;
-    let report = client.artifacts_get(image_id, "report.json").await?;
-    let report_decoded: Value = serde_json::from_slice(&report)?;
-    let banner = report_decoded.get("info").and_then(|x| x.get("banner"));
-    info!("report: image_id:{image_id} banner:{banner:?}");
+    let report = client.report(image_id).await?;
+    info!("report: image_id:{image_id} banner:{}", report.info.banner);
     Ok(())
 }
 
 /// Webhook endpoint that handles receiving the webhook from Freta
 ///
 /// # Inputs
-/// * `hmac_token` - Optional HMAC token to validate the webhook payload
-///    This is set by the command line arguments
-/// * `headers` - HTTP Headers from the request, this is used to pull out the HMAC digest
+/// * `hmac_tokens` - HMAC tokens used to validate the webhook payload, which
+///   includes the previous token while rotating the HMAC token.  This is set
+///   by the command line arguments
+/// * `headers` - HTTP Headers from the request, this is used to pull out the HMAC digest.
+///   Servers built using `axum` can use the `VerifiedEvent` extractor from
+///   `freta::models::webhooks::receiver` instead, with the `receiver` feature
 /// * `body` - HTTP Body.  Note, this uses the raw request instead deserializing
-///    in the middleware because we need to verify the HMAC digest prior to
-///    deserialization
+///   in the middleware because we need to verify the HMAC digest prior to
+///   deserialization
 async fn webhook_receiver(
-    State(hmac_token): State<Option<Secret>>,
+    State(hmac_tokens): State<Vec<Secret>>,
     headers: HeaderMap,
     body: Bytes,
 ) -> impl IntoResponse {
-    // get the digest header, treating parsing errors as if the digest does not
-    // exist
-    let hmac_header = headers
-        .get(DIGEST_HEADER)
-        .and_then(|h| h.to_str().map(ToString::to_string).ok());
-
-    let event = match parse_and_validate(&body, hmac_header, hmac_token) {
+    // `receive` verifies the raw bytes of the body, falling back to the
+    // canonical serialization of the body in case an intermediary
+    // re-serialized the payload
+    let event = match receive(&headers, &body, &hmac_tokens) {
         Ok(e) => e,
         Err(err) => {
             error!("unable to parse webhook payload: {err:?}");
@@ -143,6 +113,13 @@ async fn webhook_receiver(
 
     info!("decoded {event:?}");
 
+    // If the webhook was created with `include_tags`, image related events
+    // include the tags of the image, which can be used to route the event
+    // without having to query the service for the image.
+    if let Some(team) = event.tag("team") {
+        info!("routing event {} to team {team}", event.event_id);
+    }
+
     // This is a an example as to how to respond to events for a given image.
     if event.event_type == WebhookEventType::ImageAnalysisCompleted {
         if let Some(image_id) = event.image {
@@ -61,58 +61,126 @@
 )]
 
 use clap::{Parser, Subcommand, ValueEnum};
-use cli_table::{print_stdout, Cell, CellStruct, Style, Table};
 use freta::{
-    argparse::parse_key_val,
-    models::webhooks::{WebhookEventId, WebhookEventType, WebhookId},
-    Client, ClientId, Config, Error, ImageFormat, ImageId, ImageState, OwnerId, Result, Secret,
+    argparse::{expand_args, merge_tags, parse_key_val, parse_tags_file},
+    cli::{
+        confirm, print_data, print_field, run_images, run_webhooks, serialize_stream, write_stdout,
+        BatchArgs, OutputFormat, OutputOptions, Shutdown, TimeFormat,
+    },
+    integrations::{CaptureSource, ExternalSource},
+    models::{
+        analysis::symbols::SymbolLayout,
+        artifacts::{ArtifactFilter, Layout},
+        collections::CollectionName,
+        policies::PolicyAction,
+        schedules::{CronExpression, Schedule, ScheduleId, ScheduledAction},
+        tokens::TokenId,
+    },
+    Client, ClientId, Config, Error, ImageFormat, ImageId, IpFamily, ProxyAuth, ProxyConfig,
+    Result, Secret, UploadOptions,
 };
-use futures::{future::try_join_all, Stream, StreamExt};
-use serde::ser::{SerializeSeq, Serializer};
-use serde_json::{ser::PrettyFormatter, Value};
+use futures::{future::ready, TryStreamExt};
+use serde::Serialize;
 use std::{
+    collections::BTreeMap,
     fmt::{Display, Formatter},
-    io::{stderr, stdout},
+    io::stderr,
+    num::NonZeroU32,
     path::PathBuf,
-    pin::Pin,
+    process::ExitCode,
+    sync::OnceLock,
+    time::Duration,
 };
-use tokio::io::{self, AsyncWriteExt};
-use tracing::{info, level_filters::LevelFilter};
+use time::OffsetDateTime;
+use tokio::time::sleep;
+use tracing::{error, info, level_filters::LevelFilter};
 use tracing_subscriber::EnvFilter;
 use url::Url;
 
 /// Third-party library license details
 const LICENSES: &str = include_str!(concat!(env!("OUT_DIR"), "/licenses.json"));
 
-/// The default fields for image list output used in `CSV` and `Table` format
-const IMAGE_LIST_FIELDS: &[&str] = &["image_id", "owner_id", "state", "format"];
+/// `CycloneDX` SBOM of the client, including transitive dependencies
+const SBOM: &str = include_str!(concat!(env!("OUT_DIR"), "/sbom.cdx.json"));
+
+/// Exit code used when authenticating to the service fails or the user does
+/// not have permission
+const AUTH_EXIT_CODE: u8 = 3;
+
+/// Exit code used when the service responds that a resource does not exist
+const NOT_FOUND_EXIT_CODE: u8 = 4;
+
+/// Exit code used when the service throttles the request or a quota is
+/// exceeded
+const THROTTLED_EXIT_CODE: u8 = 5;
+
+/// Exit code used when the service rejects the request for other reasons
+const REJECTED_EXIT_CODE: u8 = 6;
+
+/// Exit code used when the service fails to handle the request
+const SERVICE_EXIT_CODE: u8 = 7;
+
+/// Exit code used when the current EULA has not been accepted
+const EULA_EXIT_CODE: u8 = 8;
+
+/// Exit code used when the digest of a file does not match the expected
+/// digest
+const INTEGRITY_EXIT_CODE: u8 = 9;
+
+/// File the HTTP trace is written to when `--trace-http` is used without a path
+const DEFAULT_TRACE_HTTP_PATH: &str = "freta-http-trace.log";
+
+/// File requested via `--trace-http`, which takes precedence over
+/// `Config.trace_http_path`
+static TRACE_HTTP_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// How often `schedule run` checks whether the saved schedules are due
+const SCHEDULE_POLL_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Parser)]
-#[clap(version, author, about = Some("Project Freta client"))]
+#[clap(
+    version,
+    author,
+    about = Some("Project Freta client"),
+    after_help = "Arguments of the form `@path` are replaced with the lines of the file at `path`.  Use `@@` for a literal `@`.",
+    after_long_help = "Arguments of the form `@path` are replaced with the lines of the file at `path`.  Use `@@` for a literal `@`.\n\nExit codes: 1 for general errors, 2 for invalid arguments, 3 for authentication or permission errors, 4 when a resource is not found, 5 when throttled or a quota is exceeded, 6 when the service rejects the request, 7 when the service fails, 8 when the current EULA has not been accepted, and 9 when the digest of a downloaded or verified file does not match."
+)]
 /// Freta client
 struct Args {
+    #[arg(long, short, global = true)]
+    /// skip confirmation prompts for destructive commands
+    yes: bool,
+
+    #[arg(long, global = true)]
+    /// show timestamps in `table` and `csv` output relative to now, such as
+    /// `3h ago`, rather than as RFC 3339 timestamps in UTC
+    relative_time: bool,
+
+    #[arg(long, global = true, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = DEFAULT_TRACE_HTTP_PATH)]
+    /// append requests sent to the Freta API and their responses to a file,
+    /// with credentials redacted and bodies truncated.  Use `--trace-http=FILE`
+    /// to specify the file
+    trace_http: Option<PathBuf>,
+
     #[command(subcommand)]
     /// Freta subcommands
     subcommand: SubCommands,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-/// Output formats for `list` commands
-enum OutputFormat {
-    /// Output in JSON format
+/// Output formats for the `licenses` command
+enum LicensesFormat {
+    /// License information for direct dependencies in JSON format
     Json,
-    /// Output in table format
-    Table,
-    /// Output in CSV format
-    Csv,
+    /// `CycloneDX` SBOM including transitive dependencies
+    Cyclonedx,
 }
 
-impl Display for OutputFormat {
+impl Display for LicensesFormat {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            OutputFormat::Json => write!(f, "json"),
-            OutputFormat::Table => write!(f, "table"),
-            OutputFormat::Csv => write!(f, "csv"),
+            LicensesFormat::Json => write!(f, "json"),
+            LicensesFormat::Cyclonedx => write!(f, "cyclonedx"),
         }
     }
 }
@@ -133,13 +201,31 @@ enum SubCommands {
         subcommands: ConfigCommands,
     },
     /// Login to the service
-    Login,
+    Login {
+        #[arg(long)]
+        /// reuse an existing Azure CLI login (`az login`) rather than prompting with a device code.  use `config update --use-azure-cli true` to always do so
+        azure_cli: bool,
+    },
     /// Logout of the service
     Logout,
     /// Display the license information for third-party libraries
-    Licenses,
+    Licenses {
+        #[arg(long, default_value_t=LicensesFormat::Json)]
+        /// output format
+        format: LicensesFormat,
+    },
     /// Display basic information for the service
-    Info,
+    Info {
+        #[arg(long, alias = "query", value_name = "PATH")]
+        /// extract a single field of the service information, such as `api_version` or `current_eula`, and print it without quoting
+        field: Option<String>,
+    },
+    /// Measure the round-trip latency to the service
+    Ping {
+        #[arg(long, default_value_t = 5)]
+        /// number of requests to send
+        count: u32,
+    },
     /// Manage images
     Images {
         #[clap(subcommand)]
@@ -152,12 +238,143 @@ enum SubCommands {
         /// Artifacts subcommands
         subcommands: ArtifactsCommands,
     },
+    /// Detect drift in the analysis of images relative to a baseline
+    Baseline {
+        #[clap(subcommand)]
+        /// baseline specific subcommands
+        subcommands: BaselineCommands,
+    },
+    /// Automatically act on images once their analysis completes
+    Policy {
+        #[clap(subcommand)]
+        /// policy specific subcommands
+        subcommands: PolicyCommands,
+    },
+    /// Periodically act on images, such as reanalyzing them to pick up new detection content
+    Schedule {
+        #[clap(subcommand)]
+        /// schedule specific subcommands
+        subcommands: ScheduleCommands,
+    },
+    /// Capture memory from a target using a `freta-capture-<SOURCE>` plugin, and upload it as a new image
+    Capture {
+        #[arg(long)]
+        /// name of the capture source, such as `vmware`
+        source: String,
+
+        /// name of the target to capture.  if not specified, the targets available from the source are listed
+        target: Option<String>,
+
+        #[arg(long, default_value_t = ImageFormat::Raw)]
+        /// format of the images captured by the source
+        format: ImageFormat,
+
+        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// specify multiple times to include multiple key/value pairs
+        tags: Option<Vec<(String, String)>>,
+
+        #[clap(long, value_name = "PATH", value_parser = parse_tags_file)]
+        /// read tags from a `.json` or `.env` file.  tags specified with `--tags` take precedence
+        tags_file: Option<BTreeMap<String, String>>,
+
+        #[arg(long)]
+        /// monitor the analysis of the image until it completes
+        monitor: bool,
+    },
+    /// Manage collections of related images
+    Collections {
+        #[clap(subcommand)]
+        /// collection specific subcommands
+        subcommands: CollectionsCommands,
+    },
     /// Manage webhooks
     Webhooks {
         #[clap(subcommand)]
         /// webhook specific subcommands
         subcommands: WebhooksCommands,
     },
+    /// Manage personal access tokens used by headless machines
+    Tokens {
+        #[clap(subcommand)]
+        /// token specific subcommands
+        subcommands: TokensCommands,
+    },
+    /// Manage the local artifact cache
+    Cache {
+        #[clap(subcommand)]
+        /// cache specific subcommands
+        subcommands: CacheCommands,
+    },
+    /// Report analysis outcomes to CI systems
+    #[cfg(feature = "ci")]
+    Ci {
+        #[clap(subcommand)]
+        /// ci specific subcommands
+        subcommands: CiCommands,
+    },
+}
+
+#[derive(Subcommand)]
+/// local artifact cache subcommands
+enum CacheCommands {
+    /// show the size and location of the local artifact cache
+    Stats,
+}
+
+#[cfg(feature = "ci")]
+#[derive(Subcommand)]
+/// CI integration subcommands
+enum CiCommands {
+    /// monitor the analysis of an image and post the outcome as a commit status, summarizing the findings on the pull request if specified
+    Report {
+        /// image id
+        image_id: ImageId,
+
+        #[arg(
+            long,
+            value_name = "OWNER/REPO",
+            required_unless_present = "azure_devops"
+        )]
+        /// report to a GitHub repository
+        github: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "ORGANIZATION_URL",
+            conflicts_with = "github",
+            requires = "project"
+        )]
+        /// report to an Azure DevOps organization, such as `https://dev.azure.com/contoso`
+        azure_devops: Option<Url>,
+
+        #[arg(long, requires = "azure_devops")]
+        /// Azure DevOps project
+        project: Option<String>,
+
+        #[arg(long, requires = "azure_devops")]
+        /// Azure DevOps repository name or id.  defaults to the project name
+        repository: Option<String>,
+
+        #[arg(long)]
+        /// commit to post the status to
+        sha: String,
+
+        #[arg(long)]
+        /// pull request to comment on with the findings summary
+        pull_request: Option<u64>,
+
+        #[arg(long, env = "FRETA_CI_TOKEN", hide_env_values = true)]
+        /// token used to authenticate to the CI system
+        token: Secret,
+
+        #[arg(long, default_value = freta::ci::DEFAULT_CONTEXT)]
+        /// name used to identify the status
+        context: String,
+
+        #[arg(long)]
+        /// URL linked from the status
+        target_url: Option<Url>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -169,6 +386,13 @@ enum EulaCommands {
     Accept,
     /// reject the current EULA
     Reject,
+    /// show whether the current EULA has been accepted, exiting with a
+    /// nonzero exit code if it has not
+    Status {
+        #[arg(long, default_value_t=OutputFormat::Json)]
+        /// output format
+        output: OutputFormat,
+    },
 }
 
 #[derive(Subcommand)]
@@ -182,6 +406,26 @@ enum ArtifactsCommands {
         #[arg(long, default_value_t=OutputFormat::Json)]
         /// print in table mode
         output: OutputFormat,
+
+        #[arg(long)]
+        /// include the size, content type, and last modified time of each artifact
+        details: bool,
+
+        #[arg(long)]
+        /// only list artifacts whose name starts with this prefix
+        prefix: Option<String>,
+
+        #[arg(long, value_name = "PATTERN")]
+        /// only list artifacts whose name matches this pattern, such as `processes/*/memory.dmp`.  `*` matches any characters and `?` matches a single character
+        glob: Option<String>,
+
+        #[arg(long, value_name = "COUNT")]
+        /// number of artifacts requested from Azure Storage per page, up to 5000
+        max_results: Option<NonZeroU32>,
+
+        #[arg(long, value_name = "COUNT")]
+        /// stop after listing this many artifacts
+        limit: Option<usize>,
     },
     /// Get an artifact for an image
     Get {
@@ -192,190 +436,219 @@ enum ArtifactsCommands {
         path: String,
 
         #[clap(long)]
-        /// output path
+        /// output path.  the path, size, and SHA-256 digest of the downloaded artifact are printed
         output: Option<PathBuf>,
-    },
-}
 
-#[derive(Subcommand)]
-/// webhook specific subcommands
-enum WebhooksCommands {
-    /// Create a new webhook
-    Create {
-        /// webhook url
-        url: Url,
-
-        /// webhook event types to monitor
-        #[clap(required = true)]
-        event_types: Vec<WebhookEventType>,
-
-        #[clap(long)]
-        /// webhook hmsecret
-        hmac_token: Option<Secret>,
-    },
-    /// Delete an existing webhook
-    Delete {
-        /// unique identifier for the webhook
-        webhook_id: WebhookId,
-    },
-    /// Get an existing webhook
-    Get {
-        /// unique identifier for the webhook
-        webhook_id: WebhookId,
+        #[clap(long, value_name = "HEX")]
+        /// fail if the SHA-256 digest of the artifact does not match
+        sha256: Option<String>,
     },
-    /// Update an existing webhook
-    Update {
-        /// webhook id
-        webhook_id: WebhookId,
+    /// Download every artifact for an image, preserving the artifact paths
+    DownloadAll {
+        /// image id
+        image_id: ImageId,
 
-        /// webhook url
-        url: Url,
+        /// output directory
+        output: PathBuf,
+    },
+    /// Upload a file as an artifact for an image, such as custom symbols to use when the image is reanalyzed
+    Upload {
+        /// image id
+        image_id: ImageId,
 
-        /// webhook event types to monitor
-        #[clap(required = true)]
-        event_types: Vec<WebhookEventType>,
+        /// name of the artifact
+        name: String,
 
-        #[clap(long)]
-        /// webhook hmsecret
-        hmac_token: Option<Secret>,
-    },
-    /// List existing webhooks
-    List {
-        #[arg(long, default_value_t=OutputFormat::Json)]
-        /// print in table mode
-        output: OutputFormat,
+        /// path of the file to upload
+        path: PathBuf,
     },
-    /// List webhook logs
-    Logs {
-        /// unique identifier for the webhook
-        webhook_id: WebhookId,
+    /// Show the artifacts for an image organized by category
+    Tree {
+        /// image id
+        image_id: ImageId,
 
-        #[arg(long, default_value_t=OutputFormat::Json)]
-        /// print in table mode
-        output: OutputFormat,
-    },
-    /// Test an existing webhook
-    Ping {
-        /// unique identifier for the webhook
-        webhook_id: WebhookId,
+        #[arg(long)]
+        /// print the layout as JSON rather than as a tree
+        json: bool,
     },
-    /// Resend an event to a webhook
-    Resend {
-        /// unique identifier for the webhook
-        webhook_id: WebhookId,
+    /// Download the debug symbols resolved during the analysis of an image
+    Symbols {
+        /// image id
+        image_id: ImageId,
+
+        /// output directory
+        output: PathBuf,
 
-        /// unique identifier for the webhook event
-        webhook_event_id: WebhookEventId,
+        #[arg(long, value_enum, default_value_t=SymbolLayout::Symsrv)]
+        /// directory layout for the symbols
+        layout: SymbolLayout,
     },
 }
 
-/// Image specific subcommands
 #[derive(Subcommand)]
-enum ImagesCommands {
-    /// get information about an image
-    Get {
+/// baseline specific subcommands
+enum BaselineCommands {
+    /// Save the analysis of an image as a baseline
+    Set {
         /// image id
         image_id: ImageId,
+
+        #[arg(long, default_value = "default")]
+        /// name of the baseline
+        name: String,
     },
-    /// monitor the analysis of specific images
-    Monitor {
-        /// image ids
-        #[arg(required = true)]
-        image_ids: Vec<ImageId>,
-    },
-    /// delete specific images
-    Delete {
-        /// image ids
-        #[arg(required = true)]
-        image_ids: Vec<ImageId>,
-    },
-    /// reanalyze specific images
-    Reanalyze {
-        /// image ids
-        #[arg(required = true)]
-        image_ids: Vec<ImageId>,
+    /// Get a baseline
+    Get {
+        #[arg(long, default_value = "default")]
+        /// name of the baseline
+        name: String,
     },
-    /// list available images
-    List {
-        #[arg(long)]
+    /// Compare the analysis of an image to a baseline
+    Compare {
         /// image id
-        image_id: Option<ImageId>,
+        image_id: ImageId,
 
-        #[arg(long)]
-        /// owner id
-        owner_id: Option<OwnerId>,
+        #[arg(long, default_value = "default")]
+        /// name of the baseline
+        name: String,
 
         #[arg(long)]
-        /// state
-        state: Option<ImageState>,
+        /// exit with an error if the image differs from the baseline
+        fail_on_drift: bool,
+    },
+}
 
+#[derive(Subcommand)]
+/// schedule specific subcommands
+enum ScheduleCommands {
+    /// Save a schedule that periodically acts on images
+    Add {
+        #[arg(long, value_name = "EXPRESSION")]
+        /// when to run, as a 5 field cron expression evaluated in UTC, such as `0 3 * * 1` for 03:00 every Monday
+        cron: CronExpression,
+
+        #[command(subcommand)]
+        /// action taken each time the schedule runs
+        action: ScheduleActionCommands,
+    },
+    /// List the saved schedules
+    List,
+    /// Remove a saved schedule
+    Remove {
+        /// schedule id
+        schedule_id: ScheduleId,
+    },
+    /// Run the saved schedules as they become due, until interrupted
+    Run {
         #[arg(long)]
-        /// include sample images
-        include_samples: bool,
-
-        #[arg(long, default_value_t=OutputFormat::Json)]
-        /// print in table mode
-        output: OutputFormat,
+        /// run the schedules that are currently due, then exit
+        once: bool,
 
-        #[arg(long, action = clap::ArgAction::Append)]
-        /// fields to include when using csv and table output format.  specify multiple times to include multiple fields
-        fields: Option<Vec<String>>,
+        #[command(flatten)]
+        /// batch processing options
+        batch: BatchArgs,
     },
-    /// create a new image record.  note: the image must be uploaded using other tools such as azcopy.
-    Create {
-        /// image format
-        format: ImageFormat,
+}
 
-        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
-        /// specify multiple times to include multiple key/value pairs
-        tags: Option<Vec<(String, String)>>,
-    },
-    /// create an upload an image
-    Upload {
-        /// image path
-        path: PathBuf,
+#[derive(Subcommand)]
+/// actions taken by schedules
+enum ScheduleActionCommands {
+    /// reanalyze the images that have all of the specified tags
+    Reanalyze {
+        #[clap(long = "tag", value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append, required = true)]
+        /// tags the images must have.  specify multiple times to include multiple key/value pairs
+        tags: Vec<(String, String)>,
 
-        #[clap(long)]
-        /// image format
-        format: Option<ImageFormat>,
+        #[arg(long, value_name = "VERSION")]
+        /// analyze the images using this version of the analysis engine, rather than the current version
+        engine_version: Option<String>,
+    },
+}
 
-        #[clap(long)]
-        /// monitor
-        monitor: bool,
+#[derive(Subcommand)]
+/// policy specific subcommands
+enum PolicyCommands {
+    /// Apply the policies to images, then delete the images whose retention
+    /// period has ended
+    Run {
+        #[arg(long = "image-id", value_name = "IMAGE_ID")]
+        /// images to apply the policies to.  specify multiple times to include multiple images
+        image_ids: Vec<ImageId>,
 
-        #[clap(long)]
-        /// monitor until completed and then emit the analysis result
-        show_result: bool,
+        #[arg(long)]
+        /// policy file to use rather than `~/.config/freta/policies.yaml`
+        file: Option<PathBuf>,
 
-        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
-        /// specify multiple times to include multiple key/value pairs
-        tags: Option<Vec<(String, String)>>,
+        #[arg(long)]
+        /// show the actions the policies require without taking them
+        dry_run: bool,
     },
-    /// update the configuration for an image
-    Update {
-        /// image id
-        image_id: ImageId,
+}
 
-        #[clap(long)]
-        /// images that are shared are readable to any authenticated user
-        shareable: Option<bool>,
+#[derive(Subcommand)]
+/// collection specific subcommands
+enum CollectionsCommands {
+    /// Create a new collection
+    Create {
+        /// unique name of the collection
+        name: CollectionName,
 
-        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
-        /// specify multiple times to include multiple key/value pairs
-        tags: Option<Vec<(String, String)>>,
+        /// images initially in the collection
+        image_ids: Vec<ImageId>,
+
+        #[arg(long)]
+        /// description of the collection
+        description: Option<String>,
     },
-    /// Download an image to a local file.  NOTE: This is only available for successfully analyzed images.
-    Download {
+    /// Add an image to a collection
+    AddImage {
+        /// unique name of the collection
+        name: CollectionName,
+
         /// image id
         image_id: ImageId,
+    },
+    /// Get a collection
+    Get {
+        /// unique name of the collection
+        name: CollectionName,
+    },
+    /// List collections
+    List {
+        #[arg(long, default_value_t=OutputFormat::Json)]
+        /// print in table mode
+        output: OutputFormat,
+    },
+}
 
-        /// output path
-        path: PathBuf,
+#[derive(Subcommand)]
+/// personal access token specific subcommands
+enum TokensCommands {
+    /// Create a personal access token.  the secret of the token is only shown once.  use it on a headless machine with `freta config update --pat`
+    Create {
+        /// name of the token, such as the machine that uses it
+        name: String,
+
+        #[arg(long)]
+        /// number of days until the token expires.  if not specified, the service decides when the token expires
+        expires_in_days: Option<u32>,
+    },
+    /// List personal access tokens
+    List {
+        #[arg(long, default_value_t=OutputFormat::Json)]
+        /// print in table mode
+        output: OutputFormat,
+    },
+    /// Revoke a personal access token
+    Revoke {
+        /// token id
+        token_id: TokenId,
     },
 }
 
 /// Config specific subcommands
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum ConfigCommands {
     /// reset configuration to default
     Reset,
@@ -396,10 +669,22 @@ enum ConfigCommands {
         /// empty string to remove an existing client secret
         client_secret: Option<String>,
 
+        #[clap(long)]
+        /// personal access token used instead of an AAD login, as created by `freta tokens create`.  Use an empty string to remove an existing token
+        pat: Option<String>,
+
         #[clap(long)]
         /// alternate Freta instance URL
         api_url: Option<Url>,
 
+        #[clap(long, value_name = "URL", action = clap::ArgAction::Append)]
+        /// URL of an instance of the Freta API that requests are retried against when connecting to the primary instance fails.  specify multiple times to include multiple instances, in the order they are tried
+        api_fallback_url: Option<Vec<Url>>,
+
+        #[clap(long)]
+        /// remove all existing fallback URLs
+        clear_api_fallback_urls: bool,
+
         #[clap(long)]
         /// alternate Scope for the Azure Identity request.  Use an empty string
         /// to remove an existing scope
@@ -408,9 +693,131 @@ enum ConfigCommands {
         #[clap(long)]
         /// do not load or save cached login tokens
         ignore_login_cache: Option<bool>,
+
+        #[clap(long)]
+        /// acquire tokens using an existing Azure CLI login (`az login`) rather than prompting with a device code
+        use_azure_cli: Option<bool>,
+
+        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// tags added to every image created by the client.  specify multiple times to include multiple key/value pairs.  Use an empty value to remove an existing default tag
+        default_tag: Option<Vec<(String, String)>>,
+
+        #[clap(long)]
+        /// remove all existing default tags
+        clear_default_tags: bool,
+
+        #[clap(long)]
+        /// maximum size of the local artifact cache in bytes.  the cache is disabled by default, or when set to 0
+        cache_max_bytes: Option<u64>,
+
+        #[clap(long)]
+        /// always prompt for confirmation of destructive commands, even when `--yes` is used
+        always_confirm_destructive: Option<bool>,
+
+        #[clap(long)]
+        /// save the service information to disk, such that it is reused across commands until it expires
+        persist_service_info: Option<bool>,
+
+        #[clap(long)]
+        /// IP address family used to connect to the service and Azure Storage
+        ip_family: Option<IpFamily>,
+
+        #[clap(long, value_name = "HOST=ADDRESS", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// resolve a host name to the specified address rather than using DNS.  specify multiple times to include multiple hosts.  Use an empty address to remove an existing override
+        dns_override: Option<Vec<(String, String)>>,
+
+        #[clap(long)]
+        /// remove all existing DNS overrides
+        clear_dns_overrides: bool,
+
+        #[clap(long, value_name = "URL")]
+        /// HTTP proxy used to connect to the Freta API, such as `http://proxy.contoso.com:8080`.  Use an empty string to remove an existing proxy
+        api_proxy: Option<String>,
+
+        #[clap(long, value_name = "AUTH")]
+        /// authentication to the API proxy, as `basic:USERNAME:PASSWORD` or `header:VALUE` to send a `Proxy-Authorization` value such as a `Negotiate` token.  Use an empty string to remove existing authentication
+        api_proxy_auth: Option<String>,
+
+        #[clap(long, value_name = "URL")]
+        /// HTTP proxy used to upload and download blobs from Azure Storage.  Use an empty string to remove an existing proxy
+        storage_proxy: Option<String>,
+
+        #[clap(long, value_name = "AUTH")]
+        /// authentication to the storage proxy, in the same form as `--api-proxy-auth`.  Use an empty string to remove existing authentication
+        storage_proxy_auth: Option<String>,
+
+        #[clap(long, value_name = "NAME=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// header added to every request sent to the Freta API, such as those required by an API gateway.  specify multiple times to include multiple headers.  Use an empty value to remove an existing header
+        extra_header: Option<Vec<(String, String)>>,
+
+        #[clap(long)]
+        /// remove all existing extra headers
+        clear_extra_headers: bool,
+
+        #[clap(long, action = clap::ArgAction::Append)]
+        /// scope requested in addition to the primary scope when acquiring tokens, which must be for the same resource as the primary scope.  specify multiple times to include multiple scopes
+        additional_scope: Option<Vec<String>>,
+
+        #[clap(long)]
+        /// remove all existing additional scopes
+        clear_additional_scopes: bool,
+
+        #[clap(long)]
+        /// append requests sent to the Freta API and their responses to this file.  Use an empty string to disable tracing
+        trace_http_path: Option<PathBuf>,
+
+        #[clap(long)]
+        /// number of blocks uploaded concurrently when uploading images
+        upload_concurrency: Option<usize>,
+
+        #[clap(long)]
+        /// maximum number of attempts for requests to the Freta API that fail transiently.  Use 1 to disable retries
+        retry_max_attempts: Option<u32>,
+
+        #[clap(long)]
+        /// total time allowed for a request to the Freta API, including retries, in seconds.  Use 0 for no limit
+        retry_deadline_secs: Option<u64>,
     },
 }
 
+/// Update a proxy in the configuration from the `config update` arguments
+///
+/// An empty `url` removes the proxy and an empty `auth` removes its
+/// authentication.
+fn update_proxy(
+    proxy: &mut Option<ProxyConfig>,
+    url: Option<String>,
+    auth: Option<String>,
+) -> Result<()> {
+    if let Some(url) = url {
+        if url.is_empty() {
+            *proxy = None;
+        } else {
+            let url = Url::parse(&url).map_err(|_| Error::Other("invalid proxy url", url))?;
+            match proxy {
+                Some(existing) => existing.url = url,
+                None => *proxy = Some(ProxyConfig { url, auth: None }),
+            }
+        }
+    }
+
+    if let Some(auth) = auth {
+        let Some(existing) = proxy else {
+            return Err(Error::Other(
+                "invalid proxy auth",
+                "the proxy url must be set first".into(),
+            ));
+        };
+        existing.auth = if auth.is_empty() {
+            None
+        } else {
+            Some(auth.parse::<ProxyAuth>()?)
+        };
+    }
+
+    Ok(())
+}
+
 /// implementation for config specific subcommands
 async fn config(subcommands: ConfigCommands) -> Result<()> {
     let config = match subcommands {
@@ -425,9 +832,33 @@ async fn config(subcommands: ConfigCommands) -> Result<()> {
             tenant_id,
             client_id,
             client_secret,
+            pat,
             api_url,
+            api_fallback_url,
+            clear_api_fallback_urls,
             scope,
             ignore_login_cache,
+            use_azure_cli,
+            default_tag,
+            clear_default_tags,
+            cache_max_bytes,
+            always_confirm_destructive,
+            persist_service_info,
+            ip_family,
+            dns_override,
+            clear_dns_overrides,
+            api_proxy,
+            api_proxy_auth,
+            storage_proxy,
+            storage_proxy_auth,
+            extra_header,
+            clear_extra_headers,
+            additional_scope,
+            clear_additional_scopes,
+            trace_http_path,
+            upload_concurrency,
+            retry_max_attempts,
+            retry_deadline_secs,
         } => {
             let mut config = Config::load().await?;
 
@@ -439,6 +870,16 @@ async fn config(subcommands: ConfigCommands) -> Result<()> {
                 config.api_url = api_url;
             }
 
+            if clear_api_fallback_urls {
+                config.api_fallback_urls.clear();
+            }
+
+            for url in api_fallback_url.unwrap_or_default() {
+                if !config.api_fallback_urls.contains(&url) {
+                    config.api_fallback_urls.push(url);
+                }
+            }
+
             if let Some(client_id) = client_id {
                 config.client_id = ClientId::new(client_id);
             }
@@ -461,10 +902,121 @@ async fn config(subcommands: ConfigCommands) -> Result<()> {
                 }
             }
 
+            // if the pat is an empty string, unset the pat in the config
+            if let Some(pat) = pat {
+                if pat.is_empty() {
+                    config.pat = None;
+                } else {
+                    config.pat = Some(Secret::new(pat));
+                }
+            }
+
             if let Some(ignore_login_cache) = ignore_login_cache {
                 config.ignore_login_cache = ignore_login_cache;
             }
 
+            if let Some(use_azure_cli) = use_azure_cli {
+                config.use_azure_cli = use_azure_cli;
+            }
+
+            if clear_default_tags {
+                config.default_tags.clear();
+            }
+
+            // if the value of a default tag is an empty string, remove the tag
+            for (key, value) in default_tag.unwrap_or_default() {
+                if value.is_empty() {
+                    config.default_tags.remove(&key);
+                } else {
+                    config.default_tags.insert(key, value);
+                }
+            }
+
+            if let Some(cache_max_bytes) = cache_max_bytes {
+                config.cache_max_bytes = cache_max_bytes;
+            }
+
+            if let Some(always_confirm_destructive) = always_confirm_destructive {
+                config.always_confirm_destructive = always_confirm_destructive;
+            }
+
+            if let Some(persist_service_info) = persist_service_info {
+                config.persist_service_info = persist_service_info;
+            }
+
+            if let Some(ip_family) = ip_family {
+                config.ip_family = ip_family;
+            }
+
+            if clear_dns_overrides {
+                config.dns_overrides.clear();
+            }
+
+            // if the address of a DNS override is an empty string, remove the override
+            for (host, address) in dns_override.unwrap_or_default() {
+                if address.is_empty() {
+                    config.dns_overrides.remove(&host);
+                } else {
+                    let address = address
+                        .parse()
+                        .map_err(|_| Error::Other("invalid dns override address", address))?;
+                    config.dns_overrides.insert(host, address);
+                }
+            }
+
+            update_proxy(&mut config.api_proxy, api_proxy, api_proxy_auth)?;
+            update_proxy(&mut config.storage_proxy, storage_proxy, storage_proxy_auth)?;
+
+            if clear_extra_headers {
+                config.extra_headers.clear();
+            }
+
+            // if the value of an extra header is an empty string, remove the header
+            for (name, value) in extra_header.unwrap_or_default() {
+                if value.is_empty() {
+                    config.extra_headers.remove(&name);
+                } else {
+                    config.extra_headers.insert(name, Secret::new(value));
+                }
+            }
+
+            if clear_additional_scopes {
+                config.additional_scopes.clear();
+            }
+
+            for scope in additional_scope.unwrap_or_default() {
+                if !config.additional_scopes.contains(&scope) {
+                    config.additional_scopes.push(scope);
+                }
+            }
+
+            // if the trace path is an empty string, disable tracing
+            if let Some(trace_http_path) = trace_http_path {
+                if trace_http_path.as_os_str().is_empty() {
+                    config.trace_http_path = None;
+                } else {
+                    config.trace_http_path = Some(trace_http_path);
+                }
+            }
+
+            if let Some(upload_concurrency) = upload_concurrency {
+                if upload_concurrency == 0 {
+                    return Err(Error::Other(
+                        "invalid upload concurrency",
+                        "must be at least 1".into(),
+                    ));
+                }
+                config.upload_concurrency = upload_concurrency;
+            }
+
+            if let Some(retry_max_attempts) = retry_max_attempts {
+                config.retry.max_attempts = retry_max_attempts.max(1);
+            }
+
+            if let Some(retry_deadline_secs) = retry_deadline_secs {
+                config.retry.deadline_secs = retry_deadline_secs;
+            }
+
             config.save().await?;
             info!("config updated");
             config
@@ -475,132 +1027,404 @@ async fn config(subcommands: ConfigCommands) -> Result<()> {
     Ok(())
 }
 
+/// Cache specific subcommands
+async fn cache(subcommands: CacheCommands) -> Result<()> {
+    match subcommands {
+        CacheCommands::Stats => print_data(Client::cache_stats().await?),
+    }
+}
+
+/// CI integration subcommands
+#[cfg(feature = "ci")]
+async fn ci(subcommands: CiCommands) -> Result<()> {
+    use freta::ci::{AzureDevOpsTarget, CiTarget, GitHubTarget};
+
+    let client = new_client().await?;
+    match subcommands {
+        CiCommands::Report {
+            image_id,
+            github,
+            azure_devops,
+            project,
+            repository,
+            sha,
+            pull_request,
+            token,
+            context,
+            target_url,
+        } => {
+            let target = match (github, azure_devops, project) {
+                (Some(repository), _, _) => CiTarget::GitHub(GitHubTarget {
+                    pull_request,
+                    context,
+                    target_url,
+                    ..GitHubTarget::new(repository, sha, token)
+                }),
+                (None, Some(organization_url), Some(project)) => {
+                    let repository = repository.unwrap_or_else(|| project.clone());
+                    CiTarget::AzureDevOps(AzureDevOpsTarget {
+                        pull_request,
+                        context,
+                        target_url,
+                        ..AzureDevOpsTarget::new(organization_url, project, repository, sha, token)
+                    })
+                }
+                _ => {
+                    return Err(Error::Other(
+                        "invalid ci target",
+                        "missing repository".into(),
+                    ))
+                }
+            };
+            let summary = client.ci_report(image_id, &target).await?;
+            print_data(summary)
+        }
+    }
+}
+
 /// Artifact specific subcommands
-async fn artifacts(subcommands: ArtifactsCommands) -> Result<()> {
-    let client = Client::new().await?;
+async fn artifacts(subcommands: ArtifactsCommands, time_format: TimeFormat) -> Result<()> {
+    let client = new_client().await?;
     match subcommands {
-        ArtifactsCommands::List { image_id, output } => {
-            let stream = client.artifacts_list(image_id);
-            serialize_stream(output, None, None, stream).await
+        ArtifactsCommands::List {
+            image_id,
+            output,
+            details,
+            prefix,
+            glob,
+            max_results,
+            limit,
+        } => {
+            let filter = ArtifactFilter {
+                prefix,
+                glob,
+                max_results,
+                limit,
+            };
+            if details {
+                let stream = client.artifacts_list_entries_filtered(image_id, filter);
+                serialize_stream(output, time_format, None, None, stream).await
+            } else {
+                let stream = client.artifacts_list_filtered(image_id, filter);
+                serialize_stream(output, time_format, None, None, stream).await
+            }
         }
         ArtifactsCommands::Get {
             image_id,
             path,
             output,
+            sha256,
+        } => match (&output, &sha256) {
+            (Some(output), Some(sha256)) => client
+                .artifacts_download_verified(image_id, path, output, sha256)
+                .await
+                .map(print_data)?,
+            (Some(output), None) => client
+                .artifacts_download(image_id, path, output)
+                .await
+                .map(print_data)?,
+            (None, Some(sha256)) => {
+                let blob = client
+                    .artifacts_get_verified(image_id, path, sha256)
+                    .await?;
+                write_stdout(&blob).await
+            }
+            (None, None) => {
+                let blob = client.artifacts_get(image_id, path).await?;
+                write_stdout(&blob).await
+            }
+        },
+        ArtifactsCommands::DownloadAll { image_id, output } => client
+            .artifacts_download_all(image_id, output)
+            .await
+            .map(print_data)?,
+        ArtifactsCommands::Upload {
+            image_id,
+            name,
+            path,
         } => {
-            if let Some(output) = &output {
-                client.artifacts_download(image_id, path, output).await
+            let stats = client.artifacts_upload(image_id, name, path).await?;
+            info!("upload complete: {stats}");
+            Ok(())
+        }
+        ArtifactsCommands::Tree { image_id, json } => {
+            let names: Vec<String> = client.artifacts_list(image_id).try_collect().await?;
+            let layout = names.into_iter().collect::<Layout>();
+            if json {
+                print_data(layout)
             } else {
-                let blob = client.artifacts_get(image_id, path).await?;
-                write_stdout(&blob).await?;
+                print!("{}", render_layout(&layout));
                 Ok(())
             }
         }
+        ArtifactsCommands::Symbols {
+            image_id,
+            output,
+            layout,
+        } => client
+            .symbols_download(image_id, output, layout)
+            .await
+            .map(print_data)?,
     }
 }
 
-/// Images specific subcommands
-async fn images(subcommands: ImagesCommands) -> Result<()> {
-    let client = Client::new().await?;
+/// Baseline specific subcommands
+async fn baseline(subcommands: BaselineCommands) -> Result<()> {
     match subcommands {
-        ImagesCommands::Get { image_id } => client.images_get(image_id).await.map(print_data)?,
-        ImagesCommands::List {
+        BaselineCommands::Set { image_id, name } => {
+            let client = new_client().await?;
+            client.baseline_set(&name, image_id).await.map(print_data)?
+        }
+        BaselineCommands::Get { name } => Client::baseline_get(&name).await.map(print_data)?,
+        BaselineCommands::Compare {
             image_id,
-            owner_id,
-            state,
-            include_samples,
-            output,
-            fields,
+            name,
+            fail_on_drift,
         } => {
-            let stream = client.images_list(image_id, owner_id, state, include_samples);
-            let fields = fields.unwrap_or(
-                IMAGE_LIST_FIELDS
-                    .iter()
-                    .map(ToString::to_string)
-                    .collect::<Vec<_>>(),
-            );
-            serialize_stream(output, Some(fields), Some(("{\"images\":", "}")), stream).await
+            let client = new_client().await?;
+            let drift = client.baseline_compare(&name, image_id).await?;
+            let has_drift = drift.has_drift();
+            print_data(drift)?;
+            if fail_on_drift && has_drift {
+                return Err(Error::Other("image differs from the baseline", name));
+            }
+            Ok(())
         }
-        ImagesCommands::Delete { image_ids } => {
-            let mut result = vec![];
-            for image_id in image_ids {
-                result.push(client.images_delete(image_id).await?);
+    }
+}
+
+/// Actions taken by `policy run`
+#[derive(Serialize)]
+struct PolicyRun {
+    /// actions required by the policies
+    actions: Vec<PolicyAction>,
+    /// images deleted as their retention period ended
+    expired: Vec<ImageId>,
+}
+
+/// Schedule specific subcommands
+async fn schedule(subcommands: ScheduleCommands) -> Result<()> {
+    match subcommands {
+        ScheduleCommands::Add { cron, action } => {
+            let action = match action {
+                ScheduleActionCommands::Reanalyze {
+                    tags,
+                    engine_version,
+                } => ScheduledAction::Reanalyze {
+                    tags: tags.into_iter().collect(),
+                    engine_version,
+                },
+            };
+            Client::schedules_add(cron, action).await.map(print_data)?
+        }
+        ScheduleCommands::List => Client::schedules_list().await.map(print_data)?,
+        ScheduleCommands::Remove { schedule_id } => Client::schedules_remove(schedule_id)
+            .await
+            .map(print_data)?,
+        ScheduleCommands::Run { once, batch } => {
+            let client = new_client().await?;
+            let shutdown = &Shutdown::listen()?;
+            loop {
+                let now = OffsetDateTime::now_utc();
+                let due = Client::schedules_list()
+                    .await?
+                    .into_iter()
+                    .filter(|schedule| schedule.is_due(now));
+                for schedule in due {
+                    if let Err(err) = run_schedule(&client, &schedule, &batch, shutdown).await {
+                        error!("schedule {} failed: {err}", schedule.schedule_id);
+                    }
+                    // interrupted runs are repeated once the scheduler restarts
+                    if shutdown.is_requested() {
+                        break;
+                    }
+                    Client::schedules_record_run(schedule.schedule_id, now).await?;
+                }
+                if once {
+                    return Ok(());
+                }
+                tokio::select! {
+                    () = sleep(SCHEDULE_POLL_INTERVAL) => {}
+                    () = shutdown.wait() => {
+                        info!("stopped running schedules");
+                        return Ok(());
+                    }
+                }
             }
-            print_data(result)
         }
-        ImagesCommands::Reanalyze { image_ids } => {
-            let mut result = vec![];
+    }
+}
+
+/// Take the action of a schedule using the batch engine
+async fn run_schedule(
+    client: &Client,
+    schedule: &Schedule,
+    batch: &BatchArgs,
+    shutdown: &Shutdown,
+) -> Result<()> {
+    let run = client.schedules_select(schedule).await?;
+    info!(
+        "running schedule {} on {} images",
+        run.schedule_id,
+        run.image_ids.len()
+    );
+    match &schedule.action {
+        ScheduledAction::Reanalyze { engine_version, .. } => batch
+            .run(run.image_ids, shutdown, |image_id| {
+                client.images_reanalyze_with_version(image_id, engine_version.clone())
+            })
+            .await?
+            .finish(),
+    }
+}
+
+/// Capture memory using a capture source plugin
+async fn capture(
+    source: &str,
+    target: Option<String>,
+    format: ImageFormat,
+    tags: BTreeMap<String, String>,
+    monitor: bool,
+) -> Result<()> {
+    let source = ExternalSource::find(source, format)?;
+    let Some(target) = target else {
+        return source.list_targets().await.map(print_data)?;
+    };
+
+    let client = new_client().await?;
+    let progress = |message: &str| info!("{}: {message}", source.name());
+    let image = client
+        .capture(&source, &target, tags, &UploadOptions::default(), &progress)
+        .await?;
+    if monitor {
+        client.images_monitor(image.image_id).await?;
+    }
+    print_data(client.images_get(image.image_id).await?)
+}
+
+/// Policy specific subcommands
+async fn policy(subcommands: PolicyCommands) -> Result<()> {
+    match subcommands {
+        PolicyCommands::Run {
+            image_ids,
+            file,
+            dry_run,
+        } => {
+            let policies = Client::policies_load(file.as_deref()).await?;
+            let client = new_client().await?;
+            let mut actions = vec![];
             for image_id in image_ids {
-                result.push(client.images_reanalyze(image_id).await?);
+                if dry_run {
+                    actions.extend(client.policies_evaluate(&policies, image_id).await?);
+                } else {
+                    actions.extend(client.policies_apply(&policies, image_id).await?);
+                }
             }
-            print_data(result)
+            let expired = if dry_run {
+                vec![]
+            } else {
+                client.policies_enforce_retention().await?
+            };
+            print_data(PolicyRun { actions, expired })
         }
-        ImagesCommands::Create { format, tags } => client
-            .images_create(format, tags.unwrap_or_default())
+    }
+}
+
+/// Collection specific subcommands
+async fn collections(subcommands: CollectionsCommands, time_format: TimeFormat) -> Result<()> {
+    let client = new_client().await?;
+    match subcommands {
+        CollectionsCommands::Create {
+            name,
+            image_ids,
+            description,
+        } => client
+            .collections_create(name, description, image_ids)
             .await
             .map(print_data)?,
-        ImagesCommands::Update {
-            image_id,
-            tags,
-            shareable,
-        } => client
-            .images_update(image_id, tags, shareable)
+        CollectionsCommands::AddImage { name, image_id } => client
+            .collections_add_image(&name, image_id)
             .await
             .map(print_data)?,
-        ImagesCommands::Upload {
-            path,
-            format,
-            tags,
-            monitor,
-            show_result,
-        } => {
-            let format = if let Some(format) = format {
-                format
-            } else if let Some(ext) = path.extension() {
-                let ext_str = ext.to_string_lossy().to_lowercase();
-                let ignore_case = true;
-                ImageFormat::from_str(&ext_str, ignore_case)
-                    .map_err(|_| Error::Extension(ext_str.into()))?
-            } else {
-                return Err(Error::Extension("missing file extension".into()));
-            };
-
-            let image = client
-                .images_upload(format, tags.unwrap_or_default(), &path)
-                .await?;
-            if monitor || show_result {
-                client.images_monitor(image.image_id).await?;
-            }
-            if show_result {
-                let result = client.artifacts_get(image.image_id, "report.json").await?;
-                write_stdout(&result).await?;
-            }
-            Ok(())
+        CollectionsCommands::Get { name } => client.collections_get(&name).await.map(print_data)?,
+        CollectionsCommands::List { output } => {
+            let stream = client.collections_list();
+            serialize_stream(
+                output,
+                time_format,
+                None,
+                Some(("{\"collections\":", "}")),
+                stream,
+            )
+            .await
         }
-        ImagesCommands::Download { image_id, path } => client.images_download(image_id, path).await,
-        ImagesCommands::Monitor { image_ids } => {
-            // in the previous methods processing a list of `ImageId`, the
-            // implementing function was called sequentially.  For `monitor`,
-            // however, we want to check the status of each of the provided
-            // images concurrently as these can be a long running operation.
-            // This operation should fail as soon as any of the images fail.
-            try_join_all(
-                image_ids
-                    .into_iter()
-                    .map(|image_id| client.images_monitor(image_id)),
+    }
+}
+
+/// Personal access token specific subcommands
+async fn tokens(subcommands: TokensCommands, yes: bool, time_format: TimeFormat) -> Result<()> {
+    let client = new_client().await?;
+    match subcommands {
+        TokensCommands::Create {
+            name,
+            expires_in_days,
+        } => client
+            .tokens_create(name, expires_in_days)
+            .await
+            .map(print_data)?,
+        TokensCommands::List { output } => {
+            let stream = client.tokens_list();
+            serialize_stream(
+                output,
+                time_format,
+                None,
+                Some(("{\"tokens\":", "}")),
+                stream,
             )
-            .await?;
-            Ok(())
+            .await
+        }
+        TokensCommands::Revoke { token_id } => {
+            let token = client
+                .tokens_list()
+                .try_filter(|x| ready(x.token_id == token_id))
+                .try_next()
+                .await?
+                .ok_or_else(|| Error::Other("token not found", token_id.to_string()))?;
+            confirm("revoke the token", &token, yes, client.config())?;
+            client.tokens_revoke(token_id).await.map(print_data)?
         }
     }
 }
 
-/// helper function to write to stdout
-async fn write_stdout(data: &[u8]) -> Result<()> {
-    io::stdout().write_all(data).await.map_err(|e| Error::Io {
-        message: "writing to stdout".into(),
-        source: e,
-    })
+/// Render the artifacts of an image as an indented tree, grouped by category
+fn render_layout(layout: &Layout) -> String {
+    let mut lines = vec![];
+    lines.extend(layout.report.iter().cloned());
+
+    if !layout.logs.is_empty() {
+        lines.push("logs/".to_string());
+        lines.extend(layout.logs.iter().map(|name| format!("  {name}")));
+    }
+
+    if !layout.processes.is_empty() {
+        lines.push("processes/".to_string());
+        for (pid, names) in &layout.processes {
+            lines.push(format!("  {pid}/"));
+            let prefix = format!("processes/{pid}/");
+            lines.extend(
+                names
+                    .iter()
+                    .map(|name| format!("    {}", name.strip_prefix(&prefix).unwrap_or(name))),
+            );
+        }
+    }
+
+    if !layout.unknown.is_empty() {
+        lines.push("other/".to_string());
+        lines.extend(layout.unknown.iter().map(|name| format!("  {name}")));
+    }
+
+    lines.iter().map(|line| format!("{line}\n")).collect()
 }
 
 /// perform eula subcommands
@@ -611,15 +1435,16 @@ async fn write_stdout(data: &[u8]) -> Result<()> {
 /// 1. Getting the EULA from the service fails
 /// 2. Writing the EULA to the stdout fails
 /// 3. Sending the acceptance or rejection of the EULA to the service fails
-async fn eula(opts: EulaCommands) -> Result<()> {
-    let client = Client::new().await?;
+/// 4. The current EULA has not been accepted, when getting the status
+async fn eula(opts: EulaCommands, time_format: TimeFormat) -> Result<()> {
+    let client = new_client().await?;
     match opts {
         EulaCommands::Get => {
             let eula = client.eula().await?;
             write_stdout(&eula).await?;
         }
         EulaCommands::Accept => {
-            let info = client.info().await?;
+            let info = client.info_cached().await?;
             let config = client.user_config_get().await?;
             client
                 .user_config_update(Some(info.current_eula), config.include_samples)
@@ -631,251 +1456,133 @@ async fn eula(opts: EulaCommands) -> Result<()> {
                 .user_config_update(None, config.include_samples)
                 .await?;
         }
+        EulaCommands::Status { output } => {
+            let status = client.eula_status().await?;
+            if output == OutputFormat::Json {
+                print_data(&status)?;
+            } else {
+                let stream = Box::pin(futures::stream::iter([Ok(status.clone())]));
+                serialize_stream(output, time_format, None, None, stream).await?;
+            }
+            status.into_result()?;
+        }
     }
 
     Ok(())
 }
 
 /// Request basic service information
-async fn info() -> Result<()> {
-    let client = Client::new().await?;
+async fn info(field: Option<String>) -> Result<()> {
+    let client = new_client().await?;
     let info = client.info().await?;
+    if field.is_some() {
+        return print_field(info, field.as_deref());
+    }
     let as_str = serde_json::to_string_pretty(&info)?;
     println!("{as_str}");
 
     Ok(())
 }
 
-/// Webhook specific subcommands
-async fn webhooks(subcommands: WebhooksCommands) -> Result<()> {
-    let client = Client::new().await?;
-    match subcommands {
-        WebhooksCommands::Create {
-            url,
-            event_types,
-            hmac_token,
-        } => client
-            .webhook_create(url, event_types.into_iter().collect(), hmac_token)
-            .await
-            .map(print_data)?,
-        WebhooksCommands::Delete { webhook_id } => {
-            client.webhook_delete(webhook_id).await.map(print_data)?
-        }
-        WebhooksCommands::Get { webhook_id } => {
-            client.webhook_get(webhook_id).await.map(print_data)?
-        }
-        WebhooksCommands::Ping { webhook_id } => {
-            let result = client.webhook_ping(webhook_id).await?;
-            write_stdout(&result).await?;
-            Ok(())
-        }
-        WebhooksCommands::Update {
-            webhook_id,
-            url,
-            event_types,
-            hmac_token,
-        } => client
-            .webhook_update(
-                webhook_id,
-                url,
-                event_types.into_iter().collect(),
-                hmac_token,
-            )
-            .await
-            .map(print_data)?,
-        WebhooksCommands::List { output } => {
-            let stream = client.webhooks_list();
-            serialize_stream(output, None, Some(("{\"webhooks\":", "}")), stream).await
-        }
-        WebhooksCommands::Logs { webhook_id, output } => {
-            let stream = client.webhooks_logs(webhook_id);
-            serialize_stream(output, None, Some(("{\"webhook_events\":", "}")), stream).await
-        }
-        WebhooksCommands::Resend {
-            webhook_id,
-            webhook_event_id,
-        } => client
-            .webhook_resend(webhook_id, webhook_event_id)
-            .await
-            .map(print_data)?,
-    }
-}
-
-/// Print a `Serialize`-able object as JSON to stdout
-fn print_data<D>(data: D) -> Result<()>
-where
-    D: serde::Serialize,
-{
-    serde_json::to_writer_pretty(stdout(), &data)?;
-    Ok(())
-}
-
-/// Convert a `serde_json::Value` into a `CellStruct`
-///
-/// This handles converting records into a `CellStruct` for use in the table
-/// creation.
-fn to_cell(value: &Value) -> Result<CellStruct> {
-    let as_cell = match value {
-        Value::String(s) => s.cell(),
-        Value::Number(n) => n.to_string().cell(),
-        Value::Bool(b) => b.to_string().cell(),
-        Value::Null => "null".cell(),
-        Value::Array(_) | Value::Object(_) => serde_json::to_string(value)?.cell(),
-    };
-    Ok(as_cell)
+/// Summary of round-trip latency measurements, in milliseconds
+#[derive(Serialize)]
+struct LatencySummary {
+    /// minimum latency
+    min_ms: f64,
+    /// average latency
+    avg_ms: f64,
+    /// maximum latency
+    max_ms: f64,
 }
 
-/// Build and display a table from a stream of `Serialize`-trait objects
-///
-/// # Errors
-///
-/// 1. If the stream errors, the error is returned
-/// 2. If the record cannot be serialized, the error is returned
-async fn table_serialize_stream<V>(
-    fields: Option<Vec<String>>,
-    mut stream: Pin<Box<impl Stream<Item = std::result::Result<V, crate::Error>>>>,
-) -> Result<()>
-where
-    V: serde::Serialize,
-{
-    let mut table: Vec<Vec<CellStruct>> = Vec::new();
-    let mut title = vec![];
-    let mut have_title = false;
-
-    while let Some(entry) = stream.next().await {
-        let entry = entry?;
-        let entry = serde_json::to_value(entry)?;
-
-        if let Some(obj) = entry.as_object() {
-            let mut row = vec![];
-            for (key, value) in obj {
-                if !fields.as_ref().map_or(true, |y| y.contains(key)) {
-                    continue;
-                }
-                if !have_title {
-                    title.push(key.cell().bold(true));
-                }
-                row.push(to_cell(value)?);
-            }
-            have_title = true;
-            table.push(row);
-        } else {
-            table.push(vec![to_cell(&entry)?]);
+impl LatencySummary {
+    /// Summarize a set of latency measurements
+    fn new(measurements: &[Duration]) -> Self {
+        let min = measurements.iter().min().copied().unwrap_or_default();
+        let max = measurements.iter().max().copied().unwrap_or_default();
+        let total = measurements.iter().sum::<Duration>();
+        let avg = u32::try_from(measurements.len())
+            .ok()
+            .and_then(|count| total.checked_div(count))
+            .unwrap_or_default();
+
+        Self {
+            min_ms: min.as_secs_f64() * 1000.0,
+            avg_ms: avg.as_secs_f64() * 1000.0,
+            max_ms: max.as_secs_f64() * 1000.0,
         }
     }
+}
 
-    let table = table.table().title(title).bold(true);
-
-    print_stdout(table).map_err(|e| Error::Io {
-        message: "writing result table".into(),
-        source: e,
-    })?;
+/// Measure the round-trip latency to the service
+async fn ping(count: u32) -> Result<()> {
+    let client = new_client().await?;
+
+    let mut authenticated = vec![];
+    let mut unauthenticated = vec![];
+    for _ in 0..count {
+        let latency = client.ping().await?;
+        info!(
+            "authenticated: {:?} unauthenticated: {:?}",
+            latency.authenticated, latency.unauthenticated
+        );
+        authenticated.push(latency.authenticated);
+        unauthenticated.push(latency.unauthenticated);
+    }
 
-    Ok(())
+    print_data(BTreeMap::from([
+        ("authenticated", LatencySummary::new(&authenticated)),
+        ("unauthenticated", LatencySummary::new(&unauthenticated)),
+    ]))
 }
 
-/// Display CSV from a stream of `Serialize`-trait objects
-///
-/// This will write the CSV to stdout, with nested types (like Array or Object)
-/// rendered as JSON strings.
-///
-/// # Errors
-///
-/// 1. If the stream errors, the error is returned
-/// 2. If the record cannot be serialized, the error is returned
-async fn csv_serialize_stream<V>(
-    fields: Option<Vec<String>>,
-    mut stream: Pin<Box<impl Stream<Item = std::result::Result<V, crate::Error>>>>,
-) -> Result<()>
-where
-    V: serde::Serialize,
-{
-    let mut ser = csv::Writer::from_writer(std::io::stdout());
-
-    let mut wrote_headers = false;
-    while let Some(entry) = stream.next().await {
-        let entry = entry?;
-        let mut entry = serde_json::to_value(entry)?;
-        if let Some(obj) = entry.as_object_mut() {
-            obj.retain(|key, _| fields.as_ref().map_or(true, |y| y.contains(key)));
-
-            if !wrote_headers {
-                let headers = obj.keys().collect::<Vec<_>>();
-                ser.write_record(headers)?;
-                wrote_headers = true;
-            }
-
-            let mut values = vec![];
-            for (_, value) in &mut *obj {
-                if value.is_object() || value.is_array() {
-                    *value = serde_json::Value::String(serde_json::to_string(value)?);
-                }
-                values.push(value);
-            }
-            ser.serialize(values)?;
-        } else {
-            ser.serialize(&entry)?;
-        }
+/// Create a client using the saved configuration, tracing HTTP requests if
+/// requested via `--trace-http`
+async fn new_client() -> Result<Client> {
+    let mut config = Config::load().await?;
+    if let Some(path) = TRACE_HTTP_PATH.get() {
+        config.trace_http_path = Some(path.clone());
     }
-    Ok(())
+    Client::with_config(config).await
 }
 
-/// Display JSON from a stream of `Serialize`-trait objects
+/// Get the exit code for an error, such that scripts can distinguish
+/// failures without parsing the error message
 ///
-/// This allows iterating over results rather than buffering everything in
-/// memory prior to writing the results.
-///
-/// # Errors
-///
-/// 1. If the stream errors, the error is returned
-/// 2. If the record cannot be serialized, the error is returned
-async fn json_serialize_stream<V>(
-    wrapper: Option<(&str, &str)>,
-    mut stream: Pin<Box<impl Stream<Item = std::result::Result<V, crate::Error>>>>,
-) -> Result<()>
-where
-    V: serde::Serialize,
-{
-    if let Some((prefix, _)) = &wrapper {
-        print!("{prefix}");
-    }
-    let mut ser = serde_json::Serializer::with_formatter(std::io::stdout(), PrettyFormatter::new());
-    let mut serializer = ser.serialize_seq(None)?;
-    while let Some(entry) = stream.next().await {
-        let entry = entry?;
-        serializer.serialize_element(&entry)?;
-    }
-    serializer.end()?;
-    if let Some((_, suffix)) = &wrapper {
-        print!("{suffix}");
-    }
-    Ok(())
+/// Errors not caused by the service use the exit code 1, while invalid
+/// arguments use the exit code 2.
+fn exit_code(err: &Error) -> ExitCode {
+    let code = match (err, err.status()) {
+        (Error::Auth(_) | Error::InvalidToken(_), _) | (_, Some(401 | 403)) => AUTH_EXIT_CODE,
+        (Error::Eula(_), _) => EULA_EXIT_CODE,
+        (Error::DigestMismatch { .. }, _) => INTEGRITY_EXIT_CODE,
+        (_, Some(404)) => NOT_FOUND_EXIT_CODE,
+        (_, Some(429)) => THROTTLED_EXIT_CODE,
+        (_, Some(400..=499)) => REJECTED_EXIT_CODE,
+        (_, Some(500..=599)) => SERVICE_EXIT_CODE,
+        _ => return ExitCode::FAILURE,
+    };
+    ExitCode::from(code)
 }
 
-/// Display values from a stream of `Serialize`-trait objects
-///
-/// # Errors
-///
-/// 1. If the stream errors, the error is returned
-/// 2. If the record cannot be serialized, the error is returned
-async fn serialize_stream<V>(
-    output: OutputFormat,
-    fields: Option<Vec<String>>,
-    wrapper: Option<(&str, &str)>,
-    stream: Pin<Box<impl Stream<Item = std::result::Result<V, crate::Error>>>>,
-) -> Result<()>
-where
-    V: serde::Serialize,
-{
-    match output {
-        OutputFormat::Table => table_serialize_stream(fields, stream).await,
-        OutputFormat::Csv => csv_serialize_stream(fields, stream).await,
-        OutputFormat::Json => json_serialize_stream(wrapper, stream).await,
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // report the error along with its sources, as when returning the
+            // error from `main`
+            #[allow(clippy::print_stderr, clippy::use_debug)]
+            {
+                eprintln!("Error: {err:?}");
+            }
+            exit_code(&err)
+        }
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+/// Run the command specified by the arguments
+async fn run() -> Result<()> {
     tracing_subscriber::fmt()
         .with_env_filter(
             EnvFilter::builder()
@@ -886,35 +1593,100 @@ async fn main() -> Result<()> {
         .with_writer(stderr)
         .init();
 
-    let cmd = Args::parse();
+    // arguments of the form `@path` are replaced with the lines of the file
+    let args = expand_args(std::env::args_os()).map_err(|e| Error::Io {
+        message: "expanding arguments".into(),
+        source: e,
+    })?;
+    let cmd = Args::parse_from(args);
+    let time_format = TimeFormat::new(cmd.relative_time);
+    if let Some(path) = cmd.trace_http {
+        TRACE_HTTP_PATH.get_or_init(|| path);
+    }
+    let options = OutputOptions {
+        yes: cmd.yes,
+        time_format,
+        trace_http_path: TRACE_HTTP_PATH.get().cloned(),
+    };
     match cmd.subcommand {
         SubCommands::Config { subcommands } => {
             config(subcommands).await?;
         }
-        SubCommands::Login => {
-            Client::new().await?;
+        SubCommands::Login { azure_cli } => {
+            if azure_cli {
+                // replace any cached token from a previous login
+                Client::logout().await?;
+                let mut config = Config::load().await?;
+                config.use_azure_cli = true;
+                Client::with_config(config).await?;
+            } else {
+                new_client().await?;
+            }
         }
         SubCommands::Logout => {
             Client::logout().await?;
         }
-        SubCommands::Info => {
-            info().await?;
+        SubCommands::Info { field } => {
+            info(field).await?;
+        }
+        SubCommands::Ping { count } => {
+            ping(count).await?;
         }
         SubCommands::Images { subcommands } => {
-            images(subcommands).await?;
+            run_images(subcommands, &options).await?;
         }
         SubCommands::Artifacts { subcommands } => {
-            artifacts(subcommands).await?;
+            artifacts(subcommands, time_format).await?;
+        }
+        SubCommands::Baseline { subcommands } => {
+            baseline(subcommands).await?;
+        }
+        SubCommands::Policy { subcommands } => {
+            policy(subcommands).await?;
+        }
+        SubCommands::Capture {
+            source,
+            target,
+            format,
+            tags,
+            tags_file,
+            monitor,
+        } => {
+            capture(
+                &source,
+                target,
+                format,
+                merge_tags(tags_file, tags).unwrap_or_default(),
+                monitor,
+            )
+            .await?;
+        }
+        SubCommands::Schedule { subcommands } => {
+            schedule(subcommands).await?;
+        }
+        SubCommands::Collections { subcommands } => {
+            collections(subcommands, time_format).await?;
         }
         SubCommands::Webhooks { subcommands } => {
-            webhooks(subcommands).await?;
+            run_webhooks(subcommands, &options).await?;
+        }
+        SubCommands::Tokens { subcommands } => {
+            tokens(subcommands, cmd.yes, time_format).await?;
+        }
+        SubCommands::Cache { subcommands } => {
+            cache(subcommands).await?;
         }
         SubCommands::Eula { subcommands } => {
-            eula(subcommands).await?;
+            eula(subcommands, time_format).await?;
         }
-        SubCommands::Licenses => {
-            println!("{LICENSES}");
+        #[cfg(feature = "ci")]
+        SubCommands::Ci { subcommands } => {
+            ci(subcommands).await?;
         }
+        SubCommands::Licenses { format } => match format {
+            LicensesFormat::Json => println!("{LICENSES}"),
+            LicensesFormat::Cyclonedx => println!("{SBOM}"),
+        },
     };
 
     Ok(())
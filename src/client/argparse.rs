@@ -1,6 +1,13 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
-use std::{error::Error, result::Result, str::FromStr};
+use std::{
+    collections::BTreeMap, error::Error, ffi::OsString, fs, io::Error as IoError, path::Path,
+    result::Result, str::FromStr,
+};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+/// Prefix of arguments that are replaced with the contents of a file
+const ARG_FILE_PREFIX: &str = "@";
 
 /// Parse a single key-value pair of `X=Y` into a typed tuple of `(X, Y)`.
 ///
@@ -19,3 +26,221 @@ where
         Err(format!("invalid KEY=value: no `=` found in `{s}`").into())
     }
 }
+
+/// Parse a number of days, such as `30d` or `30`, for use with `--since`
+///
+/// # Errors
+/// Returns an `Err` if the value is not a number of days
+pub fn parse_days(s: &str) -> Result<u32, Box<dyn Error + Send + Sync + 'static>> {
+    let days = s.strip_suffix('d').unwrap_or(s);
+    days.parse()
+        .map_err(|_| format!("invalid number of days: `{s}`").into())
+}
+
+/// Parse a timestamp, either as RFC 3339 such as `2023-09-01T00:00:00Z` or
+/// as a number of days before now such as `7d`
+///
+/// # Errors
+/// Returns an `Err` if the value is neither a timestamp nor a number of days
+pub fn parse_timestamp(s: &str) -> Result<OffsetDateTime, Box<dyn Error + Send + Sync + 'static>> {
+    if let Ok(days) = parse_days(s) {
+        return Ok(OffsetDateTime::now_utc() - Duration::days(days.into()));
+    }
+    OffsetDateTime::parse(s, &Rfc3339).map_err(|_| {
+        format!("invalid timestamp: `{s}`.  expected RFC 3339 or days such as `7d`").into()
+    })
+}
+
+/// Parse a file of tags, for use with `--tags-file`
+///
+/// Files with the `.json` extension contain an object of keys and values.
+/// Other files use the `.env` format of one `KEY=VALUE` per line, where
+/// blank lines and lines starting with `#` are ignored, and values may be
+/// quoted.
+///
+/// # Errors
+/// Returns an `Err` if the file cannot be read or is not in the expected
+/// format
+pub fn parse_tags_file(
+    path: &str,
+) -> Result<BTreeMap<String, String>, Box<dyn Error + Send + Sync + 'static>> {
+    let contents = fs::read_to_string(path)?;
+
+    if Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+    {
+        let tags: BTreeMap<String, serde_json::Value> = serde_json::from_str(&contents)?;
+        return tags
+            .into_iter()
+            .map(|(key, value)| match value {
+                serde_json::Value::String(value) => Ok((key, value)),
+                serde_json::Value::Number(_) | serde_json::Value::Bool(_) => {
+                    Ok((key, value.to_string()))
+                }
+                _ => Err(format!("invalid value for tag `{key}`: {value}").into()),
+            })
+            .collect();
+    }
+
+    let mut tags = BTreeMap::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value): (String, String) = parse_key_val(line)?;
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|quote| {
+                value
+                    .strip_prefix(*quote)
+                    .and_then(|x| x.strip_suffix(*quote))
+            })
+            .unwrap_or(value.as_str());
+        tags.insert(key.trim().to_string(), value.to_string());
+    }
+    Ok(tags)
+}
+
+/// Merge the tags from `--tags-file` with those explicitly specified, with
+/// the explicitly specified tags taking precedence
+///
+/// Returns `None` if neither were provided.
+#[must_use]
+pub fn merge_tags(
+    from_file: Option<BTreeMap<String, String>>,
+    explicit: Option<Vec<(String, String)>>,
+) -> Option<BTreeMap<String, String>> {
+    if from_file.is_none() && explicit.is_none() {
+        return None;
+    }
+    let mut tags = from_file.unwrap_or_default();
+    tags.extend(explicit.unwrap_or_default());
+    Some(tags)
+}
+
+/// Expand arguments of the form `@path` into the lines of the file at
+/// `path`
+///
+/// This allows list-heavy options, such as many image ids, to be provided
+/// from a file.  Blank lines and lines starting with `#` are ignored.  Use
+/// `@@` to pass an argument that starts with a literal `@`.
+///
+/// # Errors
+/// Returns an `Err` if a referenced file cannot be read
+pub fn expand_args<I>(args: I) -> Result<Vec<OsString>, IoError>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut expanded = vec![];
+    for arg in args {
+        let Some(path) = arg.to_str().and_then(|x| x.strip_prefix(ARG_FILE_PREFIX)) else {
+            expanded.push(arg);
+            continue;
+        };
+
+        if path.starts_with(ARG_FILE_PREFIX) {
+            expanded.push(path.into());
+            continue;
+        }
+
+        let contents = fs::read_to_string(path)
+            .map_err(|e| IoError::new(e.kind(), format!("reading arguments from {path}: {e}")))?;
+        expanded.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(OsString::from),
+        );
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(parse_days("30d").ok(), Some(30));
+        assert_eq!(parse_days("7").ok(), Some(7));
+        for value in ["", "d", "-1d", "30h", "thirty"] {
+            assert!(parse_days(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp("2023-09-01T00:00:00Z").ok(),
+            OffsetDateTime::parse("2023-09-01T00:00:00Z", &Rfc3339).ok()
+        );
+        let week_ago = OffsetDateTime::now_utc() - Duration::days(7);
+        assert!(parse_timestamp("7d").is_ok_and(|x| (x - week_ago).abs() < Duration::minutes(1)));
+        for value in ["", "yesterday", "2023-09-01"] {
+            assert!(parse_timestamp(value).is_err(), "{value}");
+        }
+    }
+
+    fn write_temp(name: &str, contents: &str) -> Result<String, IoError> {
+        let path = std::env::temp_dir().join(format!("freta-{}-{name}", std::process::id()));
+        fs::write(&path, contents)?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn test_parse_tags_file() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let env = write_temp(
+            "tags.env",
+            "# incident tags\nteam=ir\n\nexport case=\"1234\"\nnote='a=b'\n",
+        )?;
+        let json = write_temp("tags.json", r#"{"team": "ir", "case": 1234}"#)?;
+        let from_env = parse_tags_file(&env);
+        let from_json = parse_tags_file(&json);
+        fs::remove_file(env)?;
+        fs::remove_file(json)?;
+
+        let expected = BTreeMap::from([
+            ("case".to_string(), "1234".to_string()),
+            ("note".to_string(), "a=b".to_string()),
+            ("team".to_string(), "ir".to_string()),
+        ]);
+        assert_eq!(from_env?, expected);
+
+        let mut from_json = from_json?;
+        assert_eq!(from_json.remove("case").as_deref(), Some("1234"));
+        assert_eq!(from_json.remove("team").as_deref(), Some("ir"));
+
+        let merged = merge_tags(
+            Some(expected),
+            Some(vec![("team".to_string(), "triage".to_string())]),
+        );
+        assert_eq!(
+            merged.and_then(|x| x.get("team").cloned()).as_deref(),
+            Some("triage")
+        );
+        assert!(merge_tags(None, None).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_args() -> Result<(), IoError> {
+        let ids = write_temp("ids.txt", "# images\nfirst\n\n  second  \n")?;
+        let args = ["freta", "images", "delete", &format!("@{ids}"), "@@literal"]
+            .into_iter()
+            .map(OsString::from);
+        let expanded = expand_args(args);
+        fs::remove_file(ids)?;
+
+        assert_eq!(
+            expanded?,
+            ["freta", "images", "delete", "first", "second", "@literal"]
+                .into_iter()
+                .map(OsString::from)
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+}
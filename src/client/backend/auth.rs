@@ -4,17 +4,21 @@ use crate::client::{
     config::{get_config_dir, ClientId, Config, Secret},
     error::{Error, Result},
     io::{read_json, remove_file, write_json},
+    keychain,
+};
+use azure_core::{
+    auth::{AccessToken, TokenCredential},
+    new_http_client,
 };
-use azure_core::{auth::AccessToken, new_http_client};
 use azure_identity::{
     client_credentials_flow,
     device_code_flow::{self},
-    refresh_token,
+    refresh_token, AzureCliCredential,
 };
 use futures::stream::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::{path::PathBuf, time::Duration};
-use time::OffsetDateTime;
+use time::{Duration as TimeDelta, OffsetDateTime};
 use tracing::{error, warn};
 
 /// Developers of the Freta service use this URL as a for a local instance using
@@ -29,6 +33,10 @@ enum TokenType {
     ClientCredentials((AccessToken, Secret)),
     /// AAD Device Code based authentication
     DeviceCode((AccessToken, AccessToken)),
+    /// Token acquired from an existing Azure CLI login
+    AzureCli(AccessToken),
+    /// Personal access token issued by the Freta service
+    Pat(Secret),
     /// Token without authentication.  Used for interaction with local development endpoint
     None,
 }
@@ -46,18 +54,27 @@ pub(crate) struct Auth {
 
 impl Auth {
     /// Create an `Auth` object
-    pub(crate) async fn new(config: &Config) -> Result<Self> {
+    ///
+    /// Expiration times are recorded using the clock of the service, which
+    /// is estimated by adding `clock_skew` to the local time.
+    pub(crate) async fn new(config: &Config, clock_skew: TimeDelta) -> Result<Self> {
         if config.api_url.to_string() == LOCAL_DEVELOPMENT_ENDPOINT {
             return Ok(Self::new_without_auth());
         }
 
+        // personal access tokens are read from the config rather than the
+        // login cache, such that changing the token takes effect immediately
+        if let Some(pat) = &config.pat {
+            return Ok(Self::with_pat(config, pat));
+        }
+
         if !config.ignore_login_cache {
             if let Some(entry) = Self::new_from_cache(config).await? {
                 return Ok(entry);
             }
         }
 
-        Self::new_without_cache(config).await
+        Self::new_without_cache(config, clock_skew).await
     }
 
     /// Create an `Auth` object without authentication
@@ -69,6 +86,18 @@ impl Auth {
         }
     }
 
+    /// Create an `Auth` object from a personal access token
+    ///
+    /// The client does not know when the token expires, so it is never
+    /// refreshed.  Expired tokens are rejected by the service.
+    fn with_pat(config: &Config, pat: &Secret) -> Self {
+        Self {
+            client_id: config.client_id.clone(),
+            token: TokenType::Pat(pat.clone()),
+            expires_on: OffsetDateTime::now_utc() + Duration::from_secs(60 * 60 * 24 * 365),
+        }
+    }
+
     /// Create an `Auth` object, using the existing cache if possible
     async fn new_from_cache(config: &Config) -> Result<Option<Self>> {
         if let Ok(entry) = Self::from_cache().await {
@@ -82,11 +111,13 @@ impl Auth {
     }
 
     /// Create an `Auth` object without using existing cache
-    async fn new_without_cache(config: &Config) -> Result<Self> {
+    async fn new_without_cache(config: &Config, clock_skew: TimeDelta) -> Result<Self> {
         let auth = if let Some(secret) = config.client_secret.as_ref() {
-            Self::with_client_secret(config, secret).await?
+            Self::with_client_secret(config, secret, clock_skew).await?
+        } else if config.use_azure_cli {
+            Self::with_azure_cli_or_service(config, clock_skew).await?
         } else {
-            Self::with_service(config).await?
+            Self::with_service(config, clock_skew).await?
         };
 
         auth.save(config).await?;
@@ -94,15 +125,20 @@ impl Auth {
     }
 
     /// Create an `Auth` object from a client secret
-    async fn with_client_secret(config: &Config, client_secret: &Secret) -> Result<Self> {
-        let scope = config.get_scope();
-        let now = OffsetDateTime::now_utc();
+    async fn with_client_secret(
+        config: &Config,
+        client_secret: &Secret,
+        clock_skew: TimeDelta,
+    ) -> Result<Self> {
+        let scopes = config.get_scopes()?;
+        let scopes = scopes.iter().map(String::as_str).collect::<Vec<_>>();
+        let now = OffsetDateTime::now_utc() + clock_skew;
 
         let response = client_credentials_flow::perform(
             new_http_client(),
             config.client_id.as_str(),
             client_secret.get_secret(),
-            &[&scope],
+            &scopes,
             config.tenant_id.as_str(),
         )
         .await?;
@@ -120,23 +156,52 @@ impl Auth {
         })
     }
 
+    /// Create an `Auth` object from an existing Azure CLI login
+    ///
+    /// The Azure CLI reports expiration times using the local clock.
+    async fn with_azure_cli(config: &Config, clock_skew: TimeDelta) -> Result<Self> {
+        let response = AzureCliCredential::default()
+            .get_token(&config.get_resource())
+            .await?;
+
+        Ok(Self {
+            client_id: config.client_id.clone(),
+            token: TokenType::AzureCli(response.token),
+            expires_on: response.expires_on + clock_skew,
+        })
+    }
+
+    /// Create an `Auth` object from an existing Azure CLI login, falling back
+    /// to the device code flow if the Azure CLI is unavailable or not logged in
+    async fn with_azure_cli_or_service(config: &Config, clock_skew: TimeDelta) -> Result<Self> {
+        match Self::with_azure_cli(config, clock_skew).await {
+            Ok(auth) => Ok(auth),
+            Err(e) => {
+                warn!("unable to get a token from the Azure CLI, using device code login: {e}");
+                Self::with_service(config, clock_skew).await
+            }
+        }
+    }
+
     #[allow(clippy::print_stderr)]
     /// Create an `Auth` object from a device code flow
-    async fn with_service(config: &Config) -> Result<Self> {
+    async fn with_service(config: &Config, clock_skew: TimeDelta) -> Result<Self> {
         let client_id = config.client_id.clone();
-        let scope = config.get_scope();
+        let scopes = config.get_scopes()?;
+        let mut scopes = scopes.iter().map(String::as_str).collect::<Vec<_>>();
+        scopes.push("offline_access");
 
         let device_code_flow = device_code_flow::start(
             new_http_client(),
             &config.tenant_id,
             client_id.as_str(),
-            &[&scope, "offline_access"],
+            &scopes,
         )
         .await?;
 
         eprintln!("{}", device_code_flow.message());
 
-        let now = OffsetDateTime::now_utc();
+        let now = OffsetDateTime::now_utc() + clock_skew;
 
         // poll the device code flow until we get a fresh token
         let mut stream = Box::pin(device_code_flow.stream());
@@ -173,8 +238,9 @@ impl Auth {
         &self,
         config: &Config,
         access_token: &AccessToken,
+        clock_skew: TimeDelta,
     ) -> Result<Self> {
-        let now = OffsetDateTime::now_utc();
+        let now = OffsetDateTime::now_utc() + clock_skew;
         let client_id = config.client_id.clone();
         if self.client_id != client_id {
             return Err(Error::Auth("client_id changed unexpectedly"));
@@ -200,40 +266,62 @@ impl Auth {
     }
 
     /// refresh the client access token
-    pub(crate) async fn refresh_token(&mut self, config: &Config) -> Result<()> {
+    pub(crate) async fn refresh_token(
+        &mut self,
+        config: &Config,
+        clock_skew: TimeDelta,
+    ) -> Result<()> {
         match &self.token {
             TokenType::ClientCredentials((_, secret)) => {
-                let token = Self::with_client_secret(config, secret).await?;
+                let token = Self::with_client_secret(config, secret, clock_skew).await?;
                 self.token = token.token;
                 self.expires_on = token.expires_on;
                 self.save(config).await?;
             }
             TokenType::DeviceCode((_, refresh_token)) => {
-                let token = match self.refresh_device_code(config, refresh_token).await {
+                let token = match self
+                    .refresh_device_code(config, refresh_token, clock_skew)
+                    .await
+                {
                     Ok(token) => token,
                     Err(e) => {
                         error!("Unable to refresh token: {}", e);
-                        Self::with_service(config).await?
+                        Self::with_service(config, clock_skew).await?
                     }
                 };
                 self.token = token.token;
                 self.expires_on = token.expires_on;
                 self.save(config).await?;
             }
-            TokenType::None => {}
+            TokenType::AzureCli(_) => {
+                let token = Self::with_azure_cli_or_service(config, clock_skew).await?;
+                self.token = token.token;
+                self.expires_on = token.expires_on;
+                self.save(config).await?;
+            }
+            TokenType::Pat(_) | TokenType::None => {}
         }
         Ok(())
     }
 
     /// Get the token from the cache, refreshing it if necessary.
-    pub(crate) async fn get_token(&mut self, config: &Config) -> Result<Option<AccessToken>> {
-        if self.expires_on < OffsetDateTime::now_utc() {
-            self.refresh_token(config).await?;
+    ///
+    /// Whether the token expired is determined using the clock of the
+    /// service, which is estimated by adding `clock_skew` to the local time.
+    pub(crate) async fn get_token(
+        &mut self,
+        config: &Config,
+        clock_skew: TimeDelta,
+    ) -> Result<Option<AccessToken>> {
+        if self.expires_on < OffsetDateTime::now_utc() + clock_skew {
+            self.refresh_token(config, clock_skew).await?;
         }
 
         match self.token {
             TokenType::ClientCredentials((ref token, _)) => Ok(Some(token.clone())),
             TokenType::DeviceCode((ref access_token, _)) => Ok(Some(access_token.clone())),
+            TokenType::AzureCli(ref access_token) => Ok(Some(access_token.clone())),
+            TokenType::Pat(ref pat) => Ok(Some(AccessToken::new(pat.get_secret().to_string()))),
             TokenType::None => Ok(None),
         }
     }
@@ -243,17 +331,27 @@ impl Auth {
         get_config_dir().map(|p| p.join("login.cache"))
     }
 
-    /// Save the authentication to disk.
+    /// Save the authentication to the keychain, or to disk if the keychain
+    /// is unavailable.
     async fn save(&self, config: &Config) -> Result<()> {
-        if !config.ignore_login_cache {
-            let path = Self::get_path()?;
+        if config.ignore_login_cache {
+            return Ok(());
+        }
+        let path = Self::get_path()?;
+        if keychain::set(keychain::LOGIN_CACHE, serde_json::to_string(self)?).await? {
+            // remove any plaintext cache from before the keychain was used
+            if path.exists() {
+                remove_file(path).await?;
+            }
+        } else {
             write_json(path, self).await?;
         }
         Ok(())
     }
 
-    /// Remove the cached authentication from disk.
+    /// Remove the cached authentication from the keychain and disk.
     pub(crate) async fn logout() -> Result<()> {
+        keychain::delete(keychain::LOGIN_CACHE).await?;
         let path = Self::get_path()?;
         if path.exists() {
             remove_file(path).await?;
@@ -261,8 +359,12 @@ impl Auth {
         Ok(())
     }
 
-    /// Load the cached authentication from disk.
+    /// Load the cached authentication from the keychain, falling back to
+    /// disk.
     async fn from_cache() -> Result<Self> {
+        if let Some(cached) = keychain::get(keychain::LOGIN_CACHE).await? {
+            return Ok(serde_json::from_str(&cached)?);
+        }
         let path = Self::get_path()?;
         read_json(path).await
     }
@@ -1,103 +1,834 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
-use crate::client::error::{io_err, Result};
+#[cfg(feature = "metrics")]
+use crate::client::metrics::{self, Direction};
+#[cfg(feature = "chaos")]
+use crate::client::{
+    chaos::{self, Fault},
+    config::RetryConfig,
+};
+use crate::client::{
+    config::Config,
+    digest::to_hex,
+    error::{io_err, Error, Result},
+    progress::{ProgressBarHandler, ProgressHandler},
+    uploads::{UploadState, UploadedBlock},
+    TransferStats, UploadOptions,
+};
+use async_trait::async_trait;
+use azure_core::{SeekableStream, TransportOptions};
+use azure_storage::StorageCredentials;
 use azure_storage_blobs::prelude::*;
 use bytes::Bytes;
-use futures::stream::StreamExt;
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressFinish, ProgressStyle};
-use std::path::Path;
+use futures::{
+    io::AsyncRead as AsyncReadBody,
+    stream::{FuturesOrdered, StreamExt},
+    Future,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll},
+    time::Duration,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+#[cfg(feature = "chaos")]
+use tokio::time::sleep;
 use tokio::{
     fs::File,
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    time::{sleep_until, timeout, Instant},
 };
+use tracing::{debug, warn};
 use url::Url;
 
-/// Upload a file to Azure Blob Storage
-pub(crate) async fn blob_upload(mut handle: File, sas: Url) -> Result<()> {
-    let size = handle
-        .metadata()
-        .await
-        .map_err(|e| io_err("reading file size", e))?
-        .len();
+/// maximum number of times a stalled transfer is retried before failing
+const MAX_STALL_RETRIES: usize = 5;
+
+/// largest upload supported when the size of the upload is not known ahead
+/// of time, which determines the minimum block size for such uploads
+const UNKNOWN_SIZE_LIMIT: u64 = 1024 * 1024 * 1024 * 1024;
+
+/// number of blocks uploaded concurrently, unless otherwise configured
+pub(crate) const DEFAULT_UPLOAD_CONCURRENCY: usize = 8;
+
+/// maximum number of blocks in an Azure Storage block blob
+const MAX_BLOCKS: u64 = 50_000;
+
+/// minimum block size used when uploading with fixed block sizes
+const FIXED_BLOCK_SIZE: u64 = 1024 * 1024 * 10;
+
+/// initial block size used when uploading with adaptive block sizes
+const ADAPTIVE_INITIAL_BLOCK_SIZE: u64 = 1024 * 1024 * 8;
+
+/// smallest block size used when uploading with adaptive block sizes
+const ADAPTIVE_MIN_BLOCK_SIZE: u64 = 1024 * 1024 * 4;
+
+/// largest block size used when uploading with adaptive block sizes
+///
+/// Azure Storage supports larger blocks, however each block is held in memory
+/// while being uploaded.
+const ADAPTIVE_MAX_BLOCK_SIZE: u64 = 1024 * 1024 * 100;
+
+/// desired time to upload a single block when using adaptive block sizes
+const ADAPTIVE_TARGET_BLOCK_DURATION: Duration = Duration::from_secs(10);
+
+/// Chooses the size of each block during an upload
+///
+/// With adaptive sizing, the block size starts small and is doubled or halved
+/// based on how long previous blocks took to upload.  In all cases, blocks are
+/// made large enough for the remainder of the file to fit within the maximum
+/// number of blocks.
+#[derive(Debug)]
+struct BlockSizer {
+    /// adjust the block size based on the observed throughput
+    adaptive: bool,
+    /// current preferred block size
+    block_size: u64,
+    /// number of bytes that have not yet been uploaded
+    remaining: u64,
+    /// number of blocks that may still be used
+    blocks_left: u64,
+}
+
+impl BlockSizer {
+    /// Create a `BlockSizer` for a file of `size` bytes
+    fn new(size: u64, adaptive: bool) -> Self {
+        let block_size = if adaptive {
+            ADAPTIVE_INITIAL_BLOCK_SIZE
+        } else {
+            std::cmp::max(FIXED_BLOCK_SIZE, size / MAX_BLOCKS)
+        };
+        Self {
+            adaptive,
+            block_size,
+            remaining: size,
+            blocks_left: MAX_BLOCKS,
+        }
+    }
+
+    /// Account for `blocks` that cover `bytes` of the file, either as they
+    /// are read or as they were uploaded prior to resuming an upload
+    fn reserve(&mut self, bytes: u64, blocks: u64) {
+        self.remaining = self.remaining.saturating_sub(bytes);
+        self.blocks_left = self.blocks_left.saturating_sub(blocks);
+    }
+
+    /// The size of the next block to upload
+    fn next_block_size(&self) -> u64 {
+        let required = self.remaining.div_ceil(self.blocks_left.max(1));
+        std::cmp::max(self.block_size, required)
+    }
+
+    /// Record that a block was uploaded in `elapsed` time
+    fn record(&mut self, elapsed: Duration) {
+        if !self.adaptive {
+            return;
+        }
+
+        if elapsed < ADAPTIVE_TARGET_BLOCK_DURATION / 2 {
+            self.block_size = std::cmp::min(self.block_size * 2, ADAPTIVE_MAX_BLOCK_SIZE);
+        } else if elapsed > ADAPTIVE_TARGET_BLOCK_DURATION * 2 {
+            self.block_size = std::cmp::max(self.block_size / 2, ADAPTIVE_MIN_BLOCK_SIZE);
+        }
+    }
+}
+
+/// content type of binary blobs
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// content type of JSON blobs
+const JSON: &str = "application/json";
+
+/// content type of text blobs
+const TEXT: &str = "text/plain; charset=utf-8";
+
+/// refresh SAS URLs that expire within this duration before issuing requests
+/// using them
+const SAS_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// A SAS URL for Azure Blob Storage, along with when it expires
+#[derive(Debug, Clone)]
+pub(crate) struct SasUrl {
+    /// The SAS URL
+    url: Url,
+    /// When the SAS URL expires, as specified by the `se` query parameter
+    expiry: Option<OffsetDateTime>,
+}
+
+impl SasUrl {
+    /// Create a `SasUrl`, parsing the expiry from the URL
+    pub(crate) fn new(url: Url) -> Self {
+        let expiry = url
+            .query_pairs()
+            .find(|(key, _)| key == "se")
+            .and_then(|(_, value)| OffsetDateTime::parse(&value, &Rfc3339).ok());
+        Self { url, expiry }
+    }
+
+    /// Get the underlying URL
+    pub(crate) const fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Does the SAS URL expire within the specified duration
+    ///
+    /// SAS URLs without a known expiry are assumed to remain valid.
+    pub(crate) fn expires_within(&self, duration: Duration) -> bool {
+        self.expiry
+            .is_some_and(|expiry| expiry <= OffsetDateTime::now_utc() + duration)
+    }
+
+    /// Replace the SAS URL with a fresh one from `refresh` if it is close to
+    /// expiring, returning true if the SAS URL was replaced
+    ///
+    /// If `refresh` fails, the current SAS URL continues to be used until it
+    /// expires, such that SAS URLs that cannot be refreshed, such as those
+    /// used to upload images, remain usable for as long as possible.
+    async fn refresh_if_expiring<R, F>(&mut self, refresh: &R) -> Result<bool>
+    where
+        R: Fn() -> F,
+        F: Future<Output = Result<Url>>,
+    {
+        if !self.expires_within(SAS_REFRESH_MARGIN) {
+            return Ok(false);
+        }
+        debug!("SAS URL is expiring.  refreshing");
+        match refresh().await {
+            Ok(url) => {
+                *self = Self::new(url);
+                Ok(true)
+            }
+            Err(err) if !self.expires_within(Duration::ZERO) => {
+                debug!("unable to refresh SAS URL.  using it until it expires: {err}");
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Detect the content type of a blob from the start of its contents
+///
+/// `data` may end in the middle of a multi-byte UTF-8 character, as only the
+/// first block of an upload is inspected.
+pub(crate) fn detect_content_type(data: &[u8]) -> &'static str {
+    let is_text = match std::str::from_utf8(data) {
+        Ok(text) => !text.contains('\0'),
+        // only tolerate an incomplete character at the end of the data
+        Err(err) => err.error_len().is_none() && !data.contains(&0),
+    };
+    if !is_text {
+        return OCTET_STREAM;
+    }
+
+    match data.iter().find(|x| !x.is_ascii_whitespace()) {
+        Some(b'{' | b'[') => JSON,
+        _ => TEXT,
+    }
+}
+
+/// Get the content type detected by `detect_content_type` matching `value`,
+/// such as when resuming an upload
+fn known_content_type(value: &str) -> &'static str {
+    [JSON, TEXT]
+        .into_iter()
+        .find(|known| *known == value)
+        .unwrap_or(OCTET_STREAM)
+}
+
+/// Await a future, returning `None` if it does not complete within
+/// `stall_timeout`
+///
+/// Used to wait for the next chunk of a transfer, such that `stall_timeout`
+/// bounds the time between chunks rather than the whole transfer.
+async fn with_stall_timeout<F, T>(stall_timeout: Option<Duration>, future: F) -> Option<T>
+where
+    F: Future<Output = T>,
+{
+    match stall_timeout {
+        Some(stall_timeout) => timeout(stall_timeout, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Record that a transfer stalled, returning an error if the transfer has
+/// stalled too many times
+///
+/// The stall is reported to `progress` before the transfer is retried.
+fn record_stall(stalls: &mut usize, context: String, progress: &dyn ProgressHandler) -> Result<()> {
+    *stalls += 1;
+    if *stalls > MAX_STALL_RETRIES {
+        return Err(Error::Stalled(context.into()));
+    }
+    warn!("{context} stalled.  retrying ({stalls}/{MAX_STALL_RETRIES})");
+    progress.on_stall(*stalls);
+    Ok(())
+}
 
-    let block_size = std::cmp::max(1024 * 1024 * 10, size / 50_000);
-    let block_size_usize = block_size.try_into()?;
+/// The time a transfer last made progress, shared between the body of a
+/// request and the task waiting for the request to complete
+#[derive(Debug, Clone)]
+struct Activity(Arc<Mutex<Instant>>);
 
-    let style = ProgressStyle::with_template(
-        "[{elapsed_precise}] [eta:{eta}] [{wide_bar}] {bytes}/{total_bytes} ({bytes_per_sec})",
-    )?;
-    let status = ProgressBar::with_draw_target(Some(size), ProgressDrawTarget::stderr_with_hz(1))
-        .with_style(style)
-        .with_finish(ProgressFinish::AndLeave);
+impl Activity {
+    /// Start tracking the activity of a transfer, starting now
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    /// Record that the transfer made progress
+    fn touch(&self) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Instant::now();
+    }
+
+    /// The time the transfer last made progress
+    fn last(&self) -> Instant {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Await a future, returning `None` if `activity` records no progress within
+/// `stall_timeout`
+///
+/// Unlike `with_stall_timeout`, the future may take any amount of time to
+/// complete as long as it keeps making progress.
+async fn with_idle_timeout<F, T>(
+    stall_timeout: Option<Duration>,
+    activity: &Activity,
+    future: F,
+) -> Option<T>
+where
+    F: Future<Output = T>,
+{
+    let Some(stall_timeout) = stall_timeout else {
+        return Some(future.await);
+    };
+    tokio::pin!(future);
+    loop {
+        tokio::select! {
+            result = &mut future => return Some(result),
+            () = sleep_until(activity.last() + stall_timeout) => {
+                if activity.last() + stall_timeout <= Instant::now() {
+                    return None;
+                }
+            }
+        }
+    }
+}
 
-    let blob_client = BlobClient::from_sas_url(&sas)?;
+/// The body of a block being uploaded
+///
+/// Records each read by the HTTP client in `activity`, such that a block is
+/// only considered stalled once no data has been sent for the stall timeout,
+/// regardless of the size of the block.
+#[derive(Debug, Clone)]
+struct BlockBody {
+    /// Contents of the block
+    data: Bytes,
+    /// Number of bytes of `data` read so far
+    position: usize,
+    /// Time the block was last read
+    activity: Activity,
+}
+
+impl AsyncReadBody for BlockBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = &mut *self;
+        let remaining = this.data.get(this.position..).unwrap_or_default();
+        let count = remaining.len().min(buf.len());
+        if let (Some(dst), Some(src)) = (buf.get_mut(..count), remaining.get(..count)) {
+            dst.copy_from_slice(src);
+        }
+        this.position += count;
+        if count > 0 {
+            this.activity.touch();
+        }
+        Poll::Ready(Ok(count))
+    }
+}
+
+#[async_trait]
+impl SeekableStream for BlockBody {
+    async fn reset(&mut self) -> azure_core::Result<()> {
+        self.position = 0;
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// A block uploaded to Azure Blob Storage by `put_block`
+struct PutBlock {
+    /// Block ID
+    id: Bytes,
+    /// Size of the block in bytes
+    size: u64,
+    /// Number of times uploading the block stalled
+    stalls: usize,
+    /// Time spent uploading the block
+    elapsed: Duration,
+}
+
+/// Upload a single block, retrying the block if it stalls
+///
+/// The block is considered stalled if no data is sent, or no response is
+/// received after sending the block, within `stall_timeout`.
+///
+/// If the SAS URL is close to expiring, a fresh SAS URL is obtained using
+/// `refresh` before each attempt.
+#[allow(clippy::too_many_arguments)]
+async fn put_block<R, F>(
+    blobs: &BlobConnector,
+    mut sas: SasUrl,
+    refresh: &R,
+    index: usize,
+    id: Bytes,
+    data: Bytes,
+    stall_timeout: Option<Duration>,
+    progress: &dyn ProgressHandler,
+) -> Result<PutBlock>
+where
+    R: Fn() -> F,
+    F: Future<Output = Result<Url>>,
+{
+    let start = Instant::now();
+    let size = data.len() as u64;
+    let mut blob_client = blobs.blob_client(sas.url())?;
+    let mut stalls = 0;
+    #[cfg(feature = "chaos")]
+    let mut attempt = 1;
+    loop {
+        if sas.refresh_if_expiring(refresh).await? {
+            blob_client = blobs.blob_client(sas.url())?;
+        }
+        #[cfg(feature = "chaos")]
+        match chaos::inject(
+            "uploading block",
+            &[
+                Fault::TooManyRequests,
+                Fault::ServiceUnavailable,
+                Fault::Timeout,
+            ],
+        ) {
+            Some(Fault::Timeout) => {
+                record_stall(&mut stalls, format!("uploading block {index}"), progress)?;
+                continue;
+            }
+            Some(fault) => {
+                blobs
+                    .retry_injected(&mut attempt, fault, &format!("uploading block {index}"))
+                    .await?;
+                continue;
+            }
+            None => {}
+        }
+        let activity = Activity::new();
+        let body: Box<dyn SeekableStream> = Box::new(BlockBody {
+            data: data.clone(),
+            position: 0,
+            activity: activity.clone(),
+        });
+        let put_block = blob_client.put_block(id.clone(), body).into_future();
+        if let Some(result) = with_idle_timeout(stall_timeout, &activity, put_block).await {
+            result?;
+            return Ok(PutBlock {
+                id,
+                size,
+                stalls,
+                elapsed: start.elapsed(),
+            });
+        }
+        record_stall(&mut stalls, format!("uploading block {index}"), progress)?;
+    }
+}
+
+/// Upload the contents of `handle` to Azure Blob Storage
+///
+/// `size` is the number of bytes that will be read from `handle`, if known.
+/// Streams of unknown size are uploaded using blocks large enough to upload
+/// up to `UNKNOWN_SIZE_LIMIT` bytes.
+///
+/// Up to `UploadOptions.concurrency` blocks are read ahead and uploaded
+/// concurrently, such that as many blocks may be held in memory at once.
+///
+/// If the SAS URL is close to expiring, a fresh SAS URL is obtained using
+/// `refresh` before uploading the next block.
+///
+/// If `state` is provided, the upload continues after the blocks it records,
+/// and it is saved after each block is uploaded such that an interrupted
+/// upload can be resumed.  `handle` must already be positioned after the
+/// recorded blocks.  Blocks are recorded in the order of the file, such that
+/// the saved progress never includes a block that follows one still in
+/// flight.
+///
+/// The SHA-256 digest of the uploaded data is calculated as blocks are read,
+/// unless the upload continues after blocks recorded by `state`.
+pub(crate) async fn blob_upload<H, R, F>(
+    blobs: &BlobConnector,
+    mut handle: H,
+    size: Option<u64>,
+    mut sas: SasUrl,
+    refresh: R,
+    options: &UploadOptions,
+    mut state: Option<&mut UploadState>,
+) -> Result<TransferStats>
+where
+    H: AsyncRead + Unpin,
+    R: Fn() -> F,
+    F: Future<Output = Result<Url>>,
+{
+    let start = Instant::now();
+    let planned_size = size.unwrap_or(UNKNOWN_SIZE_LIMIT);
+    let adaptive = options.adaptive_chunking(planned_size);
+    let concurrency = options
+        .concurrency
+        .unwrap_or(DEFAULT_UPLOAD_CONCURRENCY)
+        .max(1);
+    debug!(
+        "uploading {} bytes (adaptive chunking: {adaptive}, concurrency: {concurrency})",
+        size.map_or_else(|| "an unknown number of".to_string(), |x| x.to_string())
+    );
+    let mut sizer = BlockSizer::new(planned_size, adaptive);
 
     let mut block_list = vec![];
-    for i in 0..usize::MAX {
-        let mut data = Vec::with_capacity(block_size_usize);
-        let mut take_handle = handle.take(block_size);
-        let read_data = take_handle
-            .read_to_end(&mut data)
-            .await
-            .map_err(|e| io_err("reading block", e))?;
-        if read_data == 0 {
+    let mut content_type = None;
+    let mut offset = 0;
+    if let Some(state) = &state {
+        offset = state.offset();
+        if offset > 0 {
+            debug!("resuming upload after {offset} bytes");
+        }
+        block_list.extend(
+            state
+                .blocks
+                .iter()
+                .map(|block| Bytes::from(block.id.clone())),
+        );
+        content_type = state.content_type.as_deref().map(known_content_type);
+        sizer.reserve(offset, block_list.len().try_into()?);
+    }
+    let resumed_blocks = block_list.len();
+    let mut hasher = (offset == 0).then(Sha256::new);
+
+    let default_progress = ProgressBarHandler::new();
+    let progress = options
+        .progress
+        .as_deref()
+        .unwrap_or(&default_progress as &dyn ProgressHandler);
+    progress.on_start(size, offset);
+
+    let mut in_flight = FuturesOrdered::new();
+    let mut next_index = block_list.len();
+    let mut reading = true;
+    let mut stalls = 0;
+    let mut uploaded = 0;
+    loop {
+        while reading && in_flight.len() < concurrency {
+            let block_size = sizer.next_block_size();
+            let mut data = Vec::with_capacity(block_size.try_into()?);
+            let read_data = (&mut handle)
+                .take(block_size)
+                .read_to_end(&mut data)
+                .await
+                .map_err(|e| io_err("reading block", e))?;
+            if read_data == 0 {
+                reading = false;
+                break;
+            }
+            sizer.reserve(read_data as u64, 1);
+            if let Some(hasher) = &mut hasher {
+                hasher.update(&data);
+            }
+            if content_type.is_none() {
+                content_type = Some(detect_content_type(&data));
+            }
+            let id = Bytes::from(format!("{next_index:032x}"));
+            sas.refresh_if_expiring(&refresh).await?;
+            in_flight.push_back(put_block(
+                blobs,
+                sas.clone(),
+                &refresh,
+                next_index,
+                id,
+                Bytes::from(data),
+                options.stall_timeout,
+                progress,
+            ));
+            next_index += 1;
+        }
+
+        let Some(block) = in_flight.next().await else {
             break;
+        };
+        let block = block?;
+        stalls += block.stalls;
+        uploaded += block.size;
+        if let Some(state) = &mut state {
+            state.content_type = content_type.map(ToString::to_string);
+            state.blocks.push(UploadedBlock {
+                id: String::from_utf8_lossy(&block.id).into_owned(),
+                size: block.size,
+            });
+            if let Err(err) = state.save().await {
+                warn!("unable to save upload progress: {err}");
+            }
         }
-        handle = take_handle.into_inner();
-        let id = Bytes::from(format!("{i:032x}"));
-        blob_client
-            .put_block(id.clone(), data)
-            .into_future()
-            .await?;
-        block_list.push(id);
-        status.inc(read_data as u64);
+        block_list.push(block.id);
+        sizer.record(block.elapsed);
+        progress.on_progress(block.size);
     }
 
+    let block_count = block_list.len().saturating_sub(resumed_blocks);
     let blocks = block_list
         .into_iter()
         .map(|x| BlobBlockType::Uncommitted(BlockId::new(x)))
         .collect::<Vec<_>>();
-    blob_client
+    sas.refresh_if_expiring(&refresh).await?;
+    blobs
+        .blob_client(sas.url())?
         .put_block_list(BlockList { blocks })
+        .content_type(content_type.unwrap_or(OCTET_STREAM))
         .into_future()
         .await?;
+    progress.on_finish();
 
-    Ok(())
+    let mut stats = TransferStats::new(uploaded, start.elapsed(), stalls, block_count);
+    if let Some(hasher) = hasher {
+        stats = stats.with_sha256(to_hex(&hasher.finalize()));
+    }
+    #[cfg(feature = "metrics")]
+    metrics::record_transfer(Direction::Upload, &stats);
+    Ok(stats)
 }
 
-/// Convert a SAS URL to an Azure Blob Storage `ContainerClient`
-pub(crate) fn container_client(container_sas: &Url) -> Result<ContainerClient> {
-    let container_client = ContainerClient::from_sas_url(container_sas)?;
-    Ok(container_client)
+/// Creates Azure Blob Storage clients from SAS URLs
+///
+/// Unless the configuration customizes connections, clients use the default
+/// transport of the Azure SDK.  Otherwise, clients share an HTTP client that
+/// uses the configured connection preferences.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BlobConnector {
+    /// HTTP client used as the transport, if connections are customized
+    http_client: Option<reqwest::Client>,
+    /// Retry policy for injected faults, which bypass the retries of the
+    /// Azure SDK
+    #[cfg(feature = "chaos")]
+    retry: RetryConfig,
 }
 
-/// Convert a container SAS URL to an Azure Blob Storage `BlobClient`
-fn blob_client<N>(container_sas: &Url, name: N) -> Result<BlobClient>
-where
-    N: Into<String>,
-{
-    let container_client = container_client(container_sas)?;
-    let blob_client = container_client.blob_client(name);
-    Ok(blob_client)
+impl BlobConnector {
+    /// Create a connector using the connection preferences of the
+    /// configuration
+    pub(crate) fn new(config: &Config) -> Result<Self> {
+        let http_client = if config.customizes_connections() {
+            Some(config.storage_http_client_builder()?.build()?)
+        } else {
+            None
+        };
+        Ok(Self {
+            http_client,
+            #[cfg(feature = "chaos")]
+            retry: config.retry,
+        })
+    }
+
+    /// Wait before retrying an operation that failed due to an injected
+    /// fault, or return the fault once the retry policy is exhausted
+    ///
+    /// The Azure SDK retries busy and unavailable responses itself, which
+    /// faults injected ahead of an operation bypass.
+    #[cfg(feature = "chaos")]
+    async fn retry_injected(&self, attempt: &mut u32, fault: Fault, context: &str) -> Result<()> {
+        if *attempt >= self.retry.max_attempts {
+            return Err(Error::InjectedFault(fault));
+        }
+        let delay = self.retry.jittered_backoff(*attempt);
+        warn!(
+            "{context} failed: {fault}.  retrying in {}ms (attempt {attempt}/{})",
+            delay.as_millis(),
+            self.retry.max_attempts
+        );
+        sleep(delay).await;
+        *attempt += 1;
+        Ok(())
+    }
+
+    /// Convert a SAS URL to an Azure Blob Storage `ContainerClient`
+    pub(crate) fn container_client(&self, container_sas: &Url) -> Result<ContainerClient> {
+        let Some(http_client) = &self.http_client else {
+            return Ok(ContainerClient::from_sas_url(container_sas)?);
+        };
+        let account = container_sas
+            .host_str()
+            .and_then(|host| host.split('.').next())
+            .filter(|x| !x.is_empty())
+            .ok_or(Error::InvalidSas("missing storage account"))?;
+        let container = container_sas
+            .path_segments()
+            .and_then(|mut segments| segments.next())
+            .filter(|x| !x.is_empty())
+            .ok_or(Error::InvalidSas("missing container"))?;
+        let token = container_sas
+            .query()
+            .ok_or(Error::InvalidSas("missing sas token"))?;
+
+        let credentials = StorageCredentials::sas_token(token)?;
+        let transport = TransportOptions::new(Arc::new(http_client.clone()));
+        let container_client = ClientBuilder::new(account, credentials)
+            .transport(transport)
+            .container_client(container);
+        Ok(container_client)
+    }
+
+    /// Convert a blob SAS URL to an Azure Blob Storage `BlobClient`
+    pub(crate) fn blob_client(&self, blob_sas: &Url) -> Result<BlobClient> {
+        if self.http_client.is_none() {
+            return Ok(BlobClient::from_sas_url(blob_sas)?);
+        }
+        let (container_sas, _) = blob_container_sas(blob_sas)?;
+        let name = blob_sas
+            .path_segments()
+            .map(|segments| segments.skip(1).collect::<Vec<_>>().join("/"))
+            .ok_or(Error::InvalidSas("missing path"))?;
+        self.container_blob_client(&container_sas, name)
+    }
+
+    /// Convert a container SAS URL to an Azure Blob Storage `BlobClient`
+    fn container_blob_client<N>(&self, container_sas: &Url, name: N) -> Result<BlobClient>
+    where
+        N: Into<String>,
+    {
+        let container_client = self.container_client(container_sas)?;
+        let blob_client = container_client.blob_client(name);
+        Ok(blob_client)
+    }
+}
+
+/// Split a blob SAS URL into a SAS URL for the container of the blob and the
+/// prefix of the blob within the container
+///
+/// The prefix is the path of the blob without its name, such that listing the
+/// container using the prefix enumerates the blob and its siblings.
+pub(crate) fn blob_container_sas(blob_sas: &Url) -> Result<(Url, String)> {
+    let mut segments = blob_sas
+        .path_segments()
+        .ok_or(Error::InvalidSas("missing path"))?;
+    let container = segments
+        .next()
+        .filter(|x| !x.is_empty())
+        .ok_or(Error::InvalidSas("missing container"))?;
+    let mut directories = segments.collect::<Vec<_>>();
+    if directories.pop().is_none_or(str::is_empty) {
+        return Err(Error::InvalidSas("missing blob name"));
+    }
+    let prefix = directories
+        .into_iter()
+        .map(|x| format!("{x}/"))
+        .collect::<String>();
+
+    let mut container_sas = blob_sas.clone();
+    container_sas.set_path(container);
+    Ok((container_sas, prefix))
+}
+
+/// Get a SAS URL for the blob `name` within the container of `container_sas`
+pub(crate) fn container_blob_sas(container_sas: &Url, name: &str) -> Result<Url> {
+    let mut blob_sas = container_sas.clone();
+    blob_sas
+        .path_segments_mut()
+        .map_err(|()| Error::InvalidSas("missing path"))?
+        .pop_if_empty()
+        .extend(name.split('/'));
+    Ok(blob_sas)
+}
+
+/// Does the SAS URL permit writing blobs, as specified by the `sp` query
+/// parameter
+pub(crate) fn sas_permits_write(sas: &Url) -> bool {
+    sas.query_pairs()
+        .any(|(key, value)| key == "sp" && value.contains('w'))
+}
+
+/// Does the SAS URL permit listing the blobs of its container, which requires
+/// the SAS to be scoped to the container (`sr=c`) and grant the list
+/// permission (`sp` containing `l`)
+pub(crate) fn sas_permits_list(sas: &Url) -> bool {
+    let mut container = false;
+    let mut list = false;
+    for (key, value) in sas.query_pairs() {
+        match key.as_ref() {
+            "sr" => container = value == "c",
+            "sp" => list = value.contains('l'),
+            _ => {}
+        }
+    }
+    container && list
 }
 
 /// Return the contents of a blob
-pub(crate) async fn blob_get<N>(container_sas: &Url, name: N) -> Result<Vec<u8>>
+pub(crate) async fn blob_get<N>(
+    blobs: &BlobConnector,
+    container_sas: &Url,
+    name: N,
+) -> Result<Vec<u8>>
 where
     N: Into<String>,
 {
-    let blob_client = blob_client(container_sas, name)?;
+    let blob_client = blobs.container_blob_client(container_sas, name)?;
+    #[cfg(feature = "chaos")]
+    {
+        let mut attempt = 1;
+        while let Some(fault) = chaos::inject(
+            "getting blob",
+            &[
+                Fault::TooManyRequests,
+                Fault::ServiceUnavailable,
+                Fault::Timeout,
+            ],
+        ) {
+            blobs
+                .retry_injected(&mut attempt, fault, "getting blob")
+                .await?;
+        }
+    }
     let blob = blob_client.get_content().await?;
     Ok(blob)
 }
 
 /// Download the contents of the specified blob to a file with a blob sas URL
-pub(crate) async fn blob_download<P>(blob_url: &Url, filename: P) -> Result<()>
+///
+/// If no data is received within `stall_timeout`, the download is resumed
+/// from the last received byte using a new request.  If the SAS URL is close
+/// to expiring when a request is issued, a fresh SAS URL is obtained using
+/// `refresh` first.  The progress of the download is reported to `progress`.
+///
+/// The SHA-256 digest of the downloaded data is calculated as it is written.
+pub(crate) async fn blob_download<P, R, F>(
+    blobs: &BlobConnector,
+    mut blob_url: SasUrl,
+    refresh: R,
+    filename: P,
+    stall_timeout: Option<Duration>,
+    progress: &dyn ProgressHandler,
+) -> Result<TransferStats>
 where
     P: AsRef<Path>,
+    R: Fn() -> F,
+    F: Future<Output = Result<Url>>,
 {
+    let start = Instant::now();
     let filename = filename.as_ref();
-    let blob_client = BlobClient::from_sas_url(blob_url)?;
+    blob_url.refresh_if_expiring(&refresh).await?;
+    let mut blob_client = blobs.blob_client(blob_url.url())?;
     let size = blob_client
         .get_properties()
         .await?
@@ -105,53 +836,127 @@ where
         .properties
         .content_length;
 
-    let style = ProgressStyle::with_template(
-        "[{elapsed_precise}] [eta:{eta}] [{wide_bar}] {bytes}/{total_bytes} ({bytes_per_sec})",
-    )?;
-    let status = ProgressBar::with_draw_target(Some(size), ProgressDrawTarget::stderr_with_hz(1))
-        .with_style(style)
-        .with_finish(ProgressFinish::AndLeave);
-
-    let mut stream = blob_client.get().into_stream();
-
     let mut file = File::create(filename)
         .await
         .map_err(|e| io_err(format!("creating file: {filename:?}"), e))?;
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        let mut body = chunk.data;
 
-        while let Some(value) = body.next().await {
-            let value = value?;
-            file.write_all(&value)
-                .await
-                .map_err(|e| io_err(format!("writing blob: {filename:?}"), e))?;
-            status.inc(value.len() as u64);
+    progress.on_start(Some(size), 0);
+    let mut hasher = Sha256::new();
+    let mut offset = 0;
+    let mut stalls = 0;
+    let mut chunks = 0;
+    'request: while offset < size {
+        if blob_url.refresh_if_expiring(&refresh).await? {
+            blob_client = blobs.blob_client(blob_url.url())?;
+        }
+        let mut stream = blob_client.get().range(offset..size).into_stream();
+        loop {
+            let Some(chunk) = with_stall_timeout(stall_timeout, stream.next()).await else {
+                record_stall(
+                    &mut stalls,
+                    format!("downloading {filename:?} at offset {offset}"),
+                    progress,
+                )?;
+                continue 'request;
+            };
+            let Some(chunk) = chunk else {
+                break 'request;
+            };
+            let mut body = chunk?.data;
+            chunks += 1;
+
+            loop {
+                let Some(value) = with_stall_timeout(stall_timeout, body.next()).await else {
+                    record_stall(
+                        &mut stalls,
+                        format!("downloading {filename:?} at offset {offset}"),
+                        progress,
+                    )?;
+                    continue 'request;
+                };
+                let Some(value) = value else {
+                    break;
+                };
+                let value = value?;
+
+                // a truncated body writes part of the data, then resumes from
+                // the last received byte using a new request
+                #[cfg(feature = "chaos")]
+                let (value, truncated) = match chaos::inject(
+                    "downloading blob",
+                    &[
+                        Fault::TooManyRequests,
+                        Fault::ServiceUnavailable,
+                        Fault::Timeout,
+                        Fault::TruncatedBody,
+                    ],
+                ) {
+                    Some(Fault::Timeout) => {
+                        record_stall(
+                            &mut stalls,
+                            format!("downloading {filename:?} at offset {offset}"),
+                            progress,
+                        )?;
+                        continue 'request;
+                    }
+                    Some(Fault::TruncatedBody) => (value.slice(..value.len() / 2), true),
+                    Some(fault) => return Err(Error::InjectedFault(fault)),
+                    None => (value, false),
+                };
+
+                file.write_all(&value)
+                    .await
+                    .map_err(|e| io_err(format!("writing blob: {filename:?}"), e))?;
+                hasher.update(&value);
+                offset += value.len() as u64;
+                progress.on_progress(value.len() as u64);
+
+                #[cfg(feature = "chaos")]
+                if truncated {
+                    continue 'request;
+                }
+            }
         }
     }
+    progress.on_finish();
 
-    Ok(())
+    let stats = TransferStats::new(offset, start.elapsed(), stalls, chunks)
+        .with_sha256(to_hex(&hasher.finalize()));
+    #[cfg(feature = "metrics")]
+    metrics::record_transfer(Direction::Download, &stats);
+    Ok(stats)
 }
 
 /// Download the contents of the specified blob to a file
+///
+/// Returns the statistics of the transfer and the hex encoded SHA-256 digest
+/// of the contents.  The progress of the download is reported to `progress`.
 pub(crate) async fn container_blob_download<P, N>(
+    blobs: &BlobConnector,
     container_sas: &Url,
     name: N,
     filename: P,
-) -> Result<()>
+    progress: &dyn ProgressHandler,
+) -> Result<(TransferStats, String)>
 where
     P: AsRef<Path>,
     N: Into<String>,
 {
+    let start = Instant::now();
     let filename = filename.as_ref();
-    let blob_client = blob_client(container_sas, name)?;
+    let blob_client = blobs.container_blob_client(container_sas, name)?;
     let mut stream = blob_client.get().into_stream();
 
     let mut file = File::create(filename)
         .await
         .map_err(|e| io_err(format!("creating file: {filename:?}"), e))?;
+    let mut hasher = Sha256::new();
+    let mut bytes = 0;
+    let mut chunks = 0;
+    progress.on_start(None, 0);
     while let Some(chunk) = stream.next().await {
         let chunk = chunk?;
+        chunks += 1;
         let mut body = chunk.data;
 
         while let Some(value) = body.next().await {
@@ -159,8 +964,212 @@ where
             file.write_all(&value)
                 .await
                 .map_err(|e| io_err(format!("writing blob: {filename:?}"), e))?;
+            hasher.update(&value);
+            bytes += value.len() as u64;
+            progress.on_progress(value.len() as u64);
         }
     }
+    progress.on_finish();
 
-    Ok(())
+    let stats = TransferStats::new(bytes, start.elapsed(), 0, chunks);
+    #[cfg(feature = "metrics")]
+    metrics::record_transfer(Direction::Download, &stats);
+    Ok((stats, to_hex(&hasher.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_sizer() {
+        let fast = Duration::from_secs(1);
+        let slow = Duration::from_secs(60);
+
+        let mut fixed = BlockSizer::new(1024, false);
+        assert_eq!(fixed.next_block_size(), FIXED_BLOCK_SIZE);
+        fixed.reserve(1024, 1);
+        fixed.record(fast);
+        assert_eq!(fixed.next_block_size(), FIXED_BLOCK_SIZE);
+
+        let mut adaptive = BlockSizer::new(1024 * 1024 * 1024, true);
+        assert_eq!(adaptive.next_block_size(), ADAPTIVE_INITIAL_BLOCK_SIZE);
+        adaptive.record(fast);
+        assert_eq!(adaptive.next_block_size(), ADAPTIVE_INITIAL_BLOCK_SIZE * 2);
+        for _ in 0..10 {
+            adaptive.record(fast);
+        }
+        assert_eq!(adaptive.next_block_size(), ADAPTIVE_MAX_BLOCK_SIZE);
+        for _ in 0..10 {
+            adaptive.record(slow);
+        }
+        assert_eq!(adaptive.next_block_size(), ADAPTIVE_MIN_BLOCK_SIZE);
+
+        // the block size grows such that the file fits within the maximum
+        // number of blocks
+        let size = ADAPTIVE_MIN_BLOCK_SIZE * MAX_BLOCKS * 2;
+        let huge = BlockSizer::new(size, true);
+        assert_eq!(huge.next_block_size(), size / MAX_BLOCKS);
+
+        // blocks that were read, or already uploaded when resuming, are
+        // accounted for
+        let mut resumed = BlockSizer::new(size, true);
+        resumed.reserve(size / 2, MAX_BLOCKS - 10);
+        assert_eq!(resumed.next_block_size(), (size / 2).div_ceil(10));
+    }
+
+    #[tokio::test]
+    async fn test_sas_url_refresh() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let sas_url =
+            |expiry: OffsetDateTime| -> std::result::Result<SasUrl, Box<dyn std::error::Error>> {
+                let mut url = Url::parse("https://x.blob.core.windows.net/images/image.lime")?;
+                url.query_pairs_mut()
+                    .append_pair("se", &expiry.format(&Rfc3339)?);
+                Ok(SasUrl::new(url))
+            };
+        let now = OffsetDateTime::now_utc();
+        let fresh_url = Url::parse("https://x.blob.core.windows.net/images/fresh")?;
+        let fresh = || {
+            let url = fresh_url.clone();
+            async move { Ok::<_, Error>(url) }
+        };
+        let unsupported =
+            || async { Err::<Url, _>(Error::Other("refresh not supported", String::new())) };
+
+        // SAS URLs far from expiring are not refreshed
+        let mut valid = sas_url(now + Duration::from_secs(60 * 60))?;
+        assert!(!valid.refresh_if_expiring(&fresh).await?);
+
+        let mut expiring = sas_url(now + Duration::from_secs(60))?;
+        assert!(expiring.refresh_if_expiring(&fresh).await?);
+        assert_eq!(expiring.url().path(), "/images/fresh");
+        assert!(!expiring.expires_within(SAS_REFRESH_MARGIN));
+
+        // SAS URLs that cannot be refreshed are used until they expire
+        let mut expiring = sas_url(now + Duration::from_secs(60))?;
+        assert!(!expiring.refresh_if_expiring(&unsupported).await?);
+        assert_eq!(expiring.url().path(), "/images/image.lime");
+
+        let mut expired = sas_url(now - Duration::from_secs(60))?;
+        assert!(expired.refresh_if_expiring(&unsupported).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_with_idle_timeout() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let stall_timeout = Some(Duration::from_millis(200));
+
+        // a transfer taking longer than the stall timeout does not stall
+        // while it keeps making progress
+        let activity = Activity::new();
+        let mut body = BlockBody {
+            data: Bytes::from_static(b"abcdef"),
+            position: 0,
+            activity: activity.clone(),
+        };
+        let reading = async {
+            let mut data = vec![];
+            let mut buf = [0; 1];
+            for _ in 0..6 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let count = futures::io::AsyncReadExt::read(&mut body, &mut buf).await?;
+                data.extend(buf.get(..count).unwrap_or_default());
+            }
+            Ok::<_, std::io::Error>(data)
+        };
+        let data = with_idle_timeout(stall_timeout, &activity, reading).await;
+        assert_eq!(data.transpose()?.as_deref(), Some(&b"abcdef"[..]));
+
+        SeekableStream::reset(&mut body).await?;
+        assert_eq!(body.position, 0);
+
+        // a transfer making no progress stalls
+        let activity = Activity::new();
+        let idle = tokio::time::sleep(Duration::from_secs(5));
+        assert!(with_idle_timeout(stall_timeout, &activity, idle)
+            .await
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_blob_container_sas() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let nested =
+            Url::parse("https://x.blob.core.windows.net/images/a/b/image.lime?se=1&sig=2")?;
+        let (container, prefix) = blob_container_sas(&nested)?;
+        assert_eq!(
+            container.as_str(),
+            "https://x.blob.core.windows.net/images?se=1&sig=2"
+        );
+        assert_eq!(prefix, "a/b/");
+
+        let top_level = Url::parse("https://x.blob.core.windows.net/images/image.lime")?;
+        assert_eq!(blob_container_sas(&top_level)?.1, "");
+
+        let missing_blob = Url::parse("https://x.blob.core.windows.net/images")?;
+        assert!(blob_container_sas(&missing_blob).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_container_blob_sas() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let container = Url::parse("https://x.blob.core.windows.net/artifacts?sp=rwl&sig=2")?;
+        assert_eq!(
+            container_blob_sas(&container, "symbols/a.pdb/1/a.pdb")?.as_str(),
+            "https://x.blob.core.windows.net/artifacts/symbols/a.pdb/1/a.pdb?sp=rwl&sig=2"
+        );
+        assert!(sas_permits_write(&container));
+
+        let read_only = Url::parse("https://x.blob.core.windows.net/artifacts?sp=rl&sig=2")?;
+        assert!(!sas_permits_write(&read_only));
+
+        let listable = Url::parse("https://x.blob.core.windows.net/images?sr=c&sp=rl&sig=2")?;
+        assert!(sas_permits_list(&listable));
+        let blob = Url::parse("https://x.blob.core.windows.net/images/image.lime?sr=b&sp=rl")?;
+        assert!(!sas_permits_list(&blob));
+        Ok(())
+    }
+
+    #[test]
+    fn test_blob_connector() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let config = Config {
+            ip_family: crate::IpFamily::V4,
+            ..Config::default()
+        };
+        let blobs = BlobConnector::new(&config)?;
+        assert!(blobs.http_client.is_some());
+
+        let container = Url::parse("https://x.blob.core.windows.net/images?se=1&sig=2")?;
+        assert_eq!(
+            blobs.container_client(&container)?.container_name(),
+            "images"
+        );
+
+        let blob = Url::parse("https://x.blob.core.windows.net/images/a/image.lime?se=1&sig=2")?;
+        assert_eq!(blobs.blob_client(&blob)?.blob_name(), "a/image.lime");
+
+        let unsigned = Url::parse("https://x.blob.core.windows.net/images")?;
+        assert!(blobs.container_client(&unsigned).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_content_type() {
+        assert_eq!(detect_content_type(b" {\"a\": 1}"), "application/json");
+        assert_eq!(detect_content_type(b"[1, 2"), "application/json");
+        assert_eq!(detect_content_type(b"hello"), "text/plain; charset=utf-8");
+        assert_eq!(known_content_type(JSON), JSON);
+        assert_eq!(known_content_type("image/png"), OCTET_STREAM);
+        assert_eq!(detect_content_type(b""), "text/plain; charset=utf-8");
+        // truncated in the middle of a multi-byte character
+        assert_eq!(detect_content_type(b"caf\xc3"), "text/plain; charset=utf-8");
+        assert_eq!(
+            detect_content_type(b"EMiL\0\x01"),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            detect_content_type(&[0xff, 0xfe, 0x41]),
+            "application/octet-stream"
+        );
+    }
 }
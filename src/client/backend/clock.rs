@@ -0,0 +1,92 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use reqwest::header::{HeaderMap, DATE};
+use time::{format_description::well_known::Rfc2822, Duration, OffsetDateTime};
+use tracing::warn;
+
+/// Difference between the local clock and the clock of the service above
+/// which the user is warned, as tokens may be rejected as expired or not yet
+/// valid
+const SKEW_WARNING_THRESHOLD: Duration = Duration::minutes(5);
+
+/// Get the time reported by the `Date` header of a response
+pub(crate) fn server_time(headers: &HeaderMap) -> Option<OffsetDateTime> {
+    let value = headers.get(DATE)?.to_str().ok()?;
+    OffsetDateTime::parse(value, &Rfc2822).ok()
+}
+
+/// Tracks the difference between the local clock and the clock of the
+/// service, based on the `Date` header of responses
+#[derive(Debug, Default)]
+pub(crate) struct ClockSkew {
+    /// Time of the service minus the local time, as of the latest response
+    skew: Duration,
+    /// Whether the user has been warned about the skew
+    warned: bool,
+}
+
+impl ClockSkew {
+    /// Time of the service minus the local time
+    ///
+    /// Adding the skew to the local time estimates the time of the service.
+    pub(crate) const fn get(&self) -> Duration {
+        self.skew
+    }
+
+    /// Record the time reported by the service in a response received at
+    /// `local` time, warning the first time the skew exceeds the threshold
+    pub(crate) fn record(&mut self, server: OffsetDateTime, local: OffsetDateTime) {
+        // the `Date` header has a resolution of 1 second and is generated
+        // before the response is sent, so small differences are ignored
+        let skew = server - local;
+        self.skew = if skew.abs() < Duration::seconds(2) {
+            Duration::ZERO
+        } else {
+            skew
+        };
+
+        if self.skew.abs() > SKEW_WARNING_THRESHOLD && !self.warned {
+            self.warned = true;
+            let direction = if self.skew.is_negative() {
+                "ahead of"
+            } else {
+                "behind"
+            };
+            warn!(
+                "the local clock is {} seconds {direction} the Freta service.  synchronize the system clock to avoid authentication failures",
+                self.skew.whole_seconds().abs()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_clock_skew() -> Result<(), time::error::Parse> {
+        let mut headers = HeaderMap::new();
+        assert_eq!(server_time(&headers), None);
+        headers.insert(
+            DATE,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        let server = OffsetDateTime::parse("Wed, 21 Oct 2015 07:28:00 GMT", &Rfc2822)?;
+        assert_eq!(server_time(&headers), Some(server));
+
+        let mut skew = ClockSkew::default();
+        skew.record(server, server + Duration::seconds(1));
+        assert_eq!(skew.get(), Duration::ZERO);
+        assert!(!skew.warned);
+
+        skew.record(server, server + Duration::minutes(10));
+        assert_eq!(skew.get(), Duration::minutes(-10));
+        assert!(skew.warned);
+
+        skew.record(server, server - Duration::minutes(1));
+        assert_eq!(skew.get(), Duration::minutes(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::client::backend::retry::is_idempotent;
+use reqwest::Method;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Number of consecutive connection failures after which an endpoint is
+/// skipped
+const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an endpoint is skipped once it reaches `FAILURE_THRESHOLD`
+/// consecutive connection failures, after which it is tried again
+const OPEN_DURATION: Duration = Duration::from_secs(60);
+
+/// Returns true if the request failed at the connection level, such that it
+/// can be sent to another endpoint
+///
+/// Requests that failed to connect were never received by the service.
+/// Requests that timed out may have been processed, so only idempotent
+/// requests are sent to another endpoint.
+pub(crate) fn is_connection_failure(method: &Method, err: &reqwest::Error) -> bool {
+    err.is_connect() || (is_idempotent(method) && err.is_timeout())
+}
+
+/// Replace the scheme, host, and port of `url` with those of `endpoint`,
+/// retaining the path and query
+pub(crate) fn rebase(url: &Url, endpoint: &Url) -> Url {
+    let mut rebased = endpoint.clone();
+    rebased.set_path(url.path());
+    rebased.set_query(url.query());
+    rebased
+}
+
+/// Circuit breaker state of an endpoint
+#[derive(Debug)]
+struct Endpoint {
+    /// URL of the endpoint
+    url: Url,
+    /// Number of consecutive connection failures
+    failures: u32,
+    /// When the endpoint may be tried again, if it is being skipped
+    open_until: Option<Instant>,
+}
+
+impl Endpoint {
+    /// Returns true if requests may be sent to the endpoint
+    fn is_available(&self, now: Instant) -> bool {
+        self.open_until.is_none_or(|until| until <= now)
+    }
+}
+
+/// The endpoints of the Freta API, in order of preference, and the circuit
+/// breaker state of each
+#[derive(Debug)]
+pub(crate) struct Endpoints {
+    /// Primary endpoint followed by the fallback endpoints
+    endpoints: Vec<Endpoint>,
+}
+
+impl Endpoints {
+    /// Track the primary endpoint and its fallbacks
+    pub(crate) fn new(primary: &Url, fallbacks: &[Url]) -> Self {
+        let endpoints = std::iter::once(primary)
+            .chain(fallbacks)
+            .map(|url| Endpoint {
+                url: url.clone(),
+                failures: 0,
+                open_until: None,
+            })
+            .collect();
+        Self { endpoints }
+    }
+
+    /// Endpoints to try, in order
+    ///
+    /// Available endpoints are tried in order of preference, followed by the
+    /// endpoints that are being skipped, such that a request is attempted even
+    /// if every endpoint recently failed.
+    pub(crate) fn candidates(&self, now: Instant) -> Vec<Url> {
+        let (available, skipped): (Vec<_>, Vec<_>) = self
+            .endpoints
+            .iter()
+            .partition(|endpoint| endpoint.is_available(now));
+        available
+            .into_iter()
+            .chain(skipped)
+            .map(|endpoint| endpoint.url.clone())
+            .collect()
+    }
+
+    /// Get the endpoint a URL was sent to
+    fn endpoint_mut(&mut self, url: &Url) -> Option<&mut Endpoint> {
+        let origin = url.origin();
+        self.endpoints
+            .iter_mut()
+            .find(|endpoint| endpoint.url.origin() == origin)
+    }
+
+    /// Record that a request sent to `url` connected, closing the circuit of
+    /// its endpoint
+    pub(crate) fn record_success(&mut self, url: &Url) {
+        if let Some(endpoint) = self.endpoint_mut(url) {
+            endpoint.failures = 0;
+            endpoint.open_until = None;
+        }
+    }
+
+    /// Record that a request sent to `url` failed to connect, skipping its
+    /// endpoint once it fails repeatedly
+    pub(crate) fn record_failure(&mut self, url: &Url, now: Instant) {
+        if let Some(endpoint) = self.endpoint_mut(url) {
+            endpoint.failures = endpoint.failures.saturating_add(1);
+            // endpoints that fail again after being skipped are skipped again
+            // immediately
+            if endpoint.failures >= FAILURE_THRESHOLD || endpoint.open_until.is_some() {
+                endpoint.open_until = now.checked_add(OPEN_DURATION);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebase() -> Result<(), url::ParseError> {
+        let url = Url::parse("https://primary.example.com/api/images?image_id=1")?;
+        let endpoint = Url::parse("https://secondary.example.com:8443/")?;
+        assert_eq!(
+            rebase(&url, &endpoint).as_str(),
+            "https://secondary.example.com:8443/api/images?image_id=1"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_endpoints() -> Result<(), url::ParseError> {
+        let primary = Url::parse("https://primary.example.com/")?;
+        let secondary = Url::parse("https://secondary.example.com/")?;
+        let mut endpoints = Endpoints::new(&primary, &[secondary.clone()]);
+
+        let now = Instant::now();
+        let preferred = vec![primary.clone(), secondary.clone()];
+        let skipping_primary = vec![secondary.clone(), primary.clone()];
+
+        // occasional failures do not skip the endpoint
+        let request = primary.join("/api/images")?;
+        for _ in 1..FAILURE_THRESHOLD {
+            endpoints.record_failure(&request, now);
+        }
+        assert_eq!(endpoints.candidates(now), preferred);
+        endpoints.record_failure(&primary, now);
+        assert_eq!(endpoints.candidates(now), skipping_primary);
+
+        // the endpoint is tried again once the circuit has been open long
+        // enough, and skipped again immediately if it fails
+        let later = now + OPEN_DURATION;
+        assert_eq!(endpoints.candidates(later), preferred);
+        endpoints.record_failure(&primary, later);
+        assert_eq!(endpoints.candidates(later), skipping_primary);
+
+        endpoints.record_success(&primary);
+        assert_eq!(endpoints.candidates(later), preferred);
+        Ok(())
+    }
+}
@@ -4,20 +4,89 @@
 mod auth;
 /// helpers for dealing with Azure Blob Storage
 pub(crate) mod azure_blobs;
+/// difference between the local clock and the clock of the service
+mod clock;
+/// failover between instances of the Freta API
+mod failover;
+/// retry policy for transient failures
+mod retry;
+/// tracing of HTTP requests for debugging
+mod trace;
 
-use crate::{
-    client::{
-        backend::auth::Auth,
-        config::Config,
-        error::{Error, Result},
+#[cfg(feature = "chaos")]
+use crate::client::chaos::{self, Fault};
+#[cfg(feature = "metrics")]
+use crate::client::metrics;
+use crate::client::{
+    backend::{
+        auth::Auth,
+        azure_blobs::BlobConnector,
+        clock::{server_time, ClockSkew},
+        failover::{is_connection_failure, rebase, Endpoints},
+        trace::HttpTrace,
     },
-    SDK_NAME, SDK_VERSION,
+    config::Config,
+    error::{service_error, Error, Result},
+    Deprecation,
 };
+use crate::{SDK_NAME, SDK_VERSION};
 use bytes::Bytes;
-use reqwest::ClientBuilder;
+#[cfg(feature = "chaos")]
+use reqwest::header::RETRY_AFTER;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, ETAG, IF_NONE_MATCH},
+    StatusCode,
+};
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::sync::Mutex;
-use tracing::trace;
+#[cfg(feature = "chaos")]
+use std::time::Duration;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Instant,
+};
+use time::{Duration as TimeDelta, OffsetDateTime};
+use tokio::{sync::Mutex, time::sleep};
+use tracing::{debug, trace, warn};
+
+/// maximum number of responses retained for conditional GET requests
+const MAX_CACHED_RESPONSES: usize = 256;
+
+/// header used to inform the service of the version of the SDK
+const SDK_VERSION_HEADER: &str = "x-freta-sdk-version";
+
+/// response header indicating the endpoint is deprecated
+const DEPRECATION_HEADER: &str = "deprecation";
+
+/// response header indicating when the endpoint will stop responding
+const SUNSET_HEADER: &str = "sunset";
+
+/// A GET response retained for use with conditional requests
+#[derive(Debug)]
+struct CachedResponse {
+    /// `ETag` returned by the service for the response
+    etag: String,
+    /// Body of the response
+    body: Bytes,
+}
+
+/// Build the response the service would send for an injected fault
+///
+/// Injected `429 Too Many Requests` responses request an immediate retry
+/// using `Retry-After`, while `503 Service Unavailable` responses use the
+/// configured backoff.
+#[cfg(feature = "chaos")]
+fn injected_response(fault: Fault) -> reqwest::Response {
+    let mut response = http::Response::new(format!("injected fault: {fault}"));
+    if fault == Fault::TooManyRequests {
+        *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from_static("0"));
+    } else {
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    response.into()
+}
 
 #[derive(Debug)]
 /// REST API client implementation
@@ -26,43 +95,165 @@ pub(crate) struct Backend {
     config: Config,
     /// http client
     http_client: reqwest::Client,
+    /// headers added to every request sent to the service
+    extra_headers: HeaderMap,
+    /// creates Azure Blob Storage clients
+    blobs: BlobConnector,
     /// backend authentication information
     auth: Mutex<Auth>,
+    /// GET responses that included an `ETag`, keyed by the request URL
+    cached_responses: Mutex<HashMap<String, CachedResponse>>,
+    /// records requests and responses, if enabled by the configuration
+    trace: Option<HttpTrace>,
+    /// deprecation notices received from the service, keyed by the method
+    /// and path of the request
+    deprecations: Mutex<BTreeMap<(String, String), Deprecation>>,
+    /// the primary and fallback endpoints of the service
+    endpoints: Mutex<Endpoints>,
+    /// difference between the local clock and the clock of the service
+    clock_skew: Mutex<ClockSkew>,
 }
 
 impl Backend {
     /// Create a new backend client
     pub(crate) async fn new(config: Config) -> Result<Self> {
-        let http_client = ClientBuilder::new()
-            .user_agent(format!("{SDK_NAME}/{SDK_VERSION}"))
-            .build()?;
-        let auth = Mutex::new(Auth::new(&config).await?);
+        let http_client = config.http_client_builder()?.build()?;
+        let mut extra_headers = config.extra_header_map()?;
+        extra_headers
+            .entry(HeaderName::from_static(SDK_VERSION_HEADER))
+            .or_insert(HeaderValue::from_static(SDK_VERSION));
+        let blobs = BlobConnector::new(&config)?;
+        let auth = Mutex::new(Auth::new(&config, TimeDelta::ZERO).await?);
+        let trace = config
+            .trace_http_path
+            .as_deref()
+            .map(HttpTrace::open)
+            .transpose()?;
+        let endpoints = Mutex::new(Endpoints::new(&config.api_url, &config.api_fallback_urls));
 
         Ok(Self {
             config,
             http_client,
+            extra_headers,
+            blobs,
             auth,
+            cached_responses: Mutex::new(HashMap::new()),
+            trace,
+            deprecations: Mutex::new(BTreeMap::new()),
+            endpoints,
+            clock_skew: Mutex::new(ClockSkew::default()),
         })
     }
 
+    /// Deprecation notices received from the service
+    pub(crate) async fn deprecations(&self) -> Vec<Deprecation> {
+        self.deprecations.lock().await.values().cloned().collect()
+    }
+
+    /// Record the deprecation notice included in a response, if any, logging
+    /// the notice the first time it is received
+    async fn record_deprecation(&self, method: &reqwest::Method, path: &str, headers: &HeaderMap) {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+        let deprecation = header(DEPRECATION_HEADER);
+        let sunset = header(SUNSET_HEADER);
+        if deprecation.is_none() && sunset.is_none() {
+            return;
+        }
+
+        let key = (method.to_string(), path.to_string());
+        let mut deprecations = self.deprecations.lock().await;
+        if !deprecations.contains_key(&key) {
+            warn!(
+                "{} {} is deprecated by the service (deprecation: {}, sunset: {}).  upgrade to the latest version of {}",
+                key.0,
+                key.1,
+                deprecation.as_deref().unwrap_or("unspecified"),
+                sunset.as_deref().unwrap_or("unspecified"),
+                SDK_NAME,
+            );
+        }
+        deprecations.insert(
+            key.clone(),
+            Deprecation {
+                method: key.0,
+                path: key.1,
+                deprecation,
+                sunset,
+            },
+        );
+    }
+
+    /// The configuration used by the backend
+    pub(crate) const fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Creates Azure Blob Storage clients using the connection preferences of
+    /// the configuration
+    pub(crate) const fn blobs(&self) -> &BlobConnector {
+        &self.blobs
+    }
+
     /// log out of the backend
     pub(crate) async fn logout() -> Result<()> {
         Auth::logout().await?;
         Ok(())
     }
 
+    /// Send an unauthenticated GET request to the backend, returning the HTTP
+    /// status of the response.
+    ///
+    /// This does not treat non-success HTTP status codes as errors, as this is
+    /// intended to measure connectivity to the service independent of
+    /// authentication.
+    pub(crate) async fn get_unauthenticated(&self, path: &str) -> Result<reqwest::StatusCode> {
+        let mut url = self.config.api_url.clone();
+        url.set_path(path);
+        let res = self
+            .http_client
+            .get(url)
+            .headers(self.extra_headers.clone())
+            .send()
+            .await?;
+        let status = res.status();
+        // read the body such that the full round trip is included
+        res.bytes().await?;
+        Ok(status)
+    }
+
     /// send the request to the backend and return the results in `Bytes`
-    async fn execute_raw<Q>(
+    ///
+    /// If `conditional` is set, GET requests for which a response with an
+    /// `ETag` was previously received are sent as conditional requests using
+    /// `If-None-Match`, such that unchanged responses are not resent by the
+    /// service.
+    ///
+    /// If `Config.trace_http_path` is set, the request and response are
+    /// recorded with credentials redacted.
+    ///
+    /// Transient failures are retried as specified by `Config.retry`.
+    /// Requests that fail to connect are first retried against each of
+    /// `Config.api_fallback_urls`.
+    async fn execute_raw<Q, B>(
         &self,
         method: reqwest::Method,
         path: &str,
         query: Option<Q>,
-        body: Option<Q>,
+        body: Option<B>,
+        conditional: bool,
     ) -> Result<Bytes>
     where
         Q: Serialize,
+        B: Serialize,
     {
-        let mut url = self.config.api_url.clone();
+        let endpoints = self.endpoints.lock().await.candidates(Instant::now());
+        let mut endpoint = 0;
+        let mut url = endpoints.first().unwrap_or(&self.config.api_url).clone();
         url.set_path(path);
 
         if let Some(query) = query {
@@ -73,12 +264,25 @@ impl Backend {
             }
         }
 
-        let mut builder = self.http_client.clone().request(method, url);
+        let cache_key = (method == reqwest::Method::GET).then(|| url.to_string());
+
+        let mut builder = self
+            .http_client
+            .clone()
+            .request(method.clone(), url)
+            .headers(self.extra_headers.clone());
+
+        if let (Some(cache_key), true) = (&cache_key, conditional) {
+            if let Some(cached) = self.cached_responses.lock().await.get(cache_key) {
+                builder = builder.header(IF_NONE_MATCH, cached.etag.as_str());
+            }
+        }
 
         // lock self.auth while getting an auth token
         let token = {
+            let clock_skew = self.clock_skew.lock().await.get();
             let mut auth = self.auth.lock().await;
-            auth.get_token(&self.config).await?
+            auth.get_token(&self.config, clock_skew).await?
         };
         if let Some(token) = token {
             builder = builder.bearer_auth(token.secret());
@@ -90,33 +294,195 @@ impl Backend {
             builder = builder.header("Content-Length", "0");
         }
 
-        let res = builder.send().await?;
+        let mut request = builder.build()?;
+        let first_attempt = Instant::now();
+        let mut attempt = 1;
+        let res = loop {
+            // requests with JSON bodies can always be cloned
+            let mut retry_request = request.try_clone();
+            if let Some(trace) = &self.trace {
+                trace.request(&request).await;
+            }
+            let start = Instant::now();
+            let sent_to = request.url().clone();
+            // injected faults are handled like the failures they stand in for,
+            // including retries and failover
+            #[cfg(feature = "chaos")]
+            let result = match chaos::inject(
+                path,
+                &[
+                    Fault::TooManyRequests,
+                    Fault::ServiceUnavailable,
+                    Fault::Timeout,
+                ],
+            ) {
+                Some(fault @ (Fault::TooManyRequests | Fault::ServiceUnavailable)) => {
+                    Ok(injected_response(fault))
+                }
+                // a deadline that already passed times out the request
+                Some(Fault::Timeout) => {
+                    *request.timeout_mut() = Some(Duration::ZERO);
+                    self.http_client.execute(request).await
+                }
+                _ => self.http_client.execute(request).await,
+            };
+            #[cfg(not(feature = "chaos"))]
+            let result = self.http_client.execute(request).await;
+            #[cfg(feature = "metrics")]
+            metrics::record_request(&method, &result, start.elapsed());
+            if let (Some(trace), Ok(res)) = (&self.trace, &result) {
+                trace.response(res, start.elapsed()).await;
+            }
+
+            match &result {
+                Err(err) if is_connection_failure(&method, err) => {
+                    self.endpoints
+                        .lock()
+                        .await
+                        .record_failure(&sent_to, Instant::now());
+                    if let Some(next) = endpoints.get(endpoint + 1) {
+                        if let Some(mut failover_request) = retry_request.take() {
+                            warn!(
+                                "{method} {path} failed to connect to {}: {err}.  failing over to {}",
+                                sent_to.origin().ascii_serialization(),
+                                next.origin().ascii_serialization()
+                            );
+                            *failover_request.url_mut() = rebase(&sent_to, next);
+                            request = failover_request;
+                            endpoint += 1;
+                            continue;
+                        }
+                    }
+                }
+                Err(_) => {}
+                Ok(_) => {
+                    self.endpoints.lock().await.record_success(&sent_to);
+                    if !self.config.api_fallback_urls.is_empty() {
+                        debug!(
+                            "{method} {path} served by {}",
+                            sent_to.origin().ascii_serialization()
+                        );
+                    }
+                }
+            }
+
+            let delay = self
+                .config
+                .retry
+                .delay(&method, &result, attempt, first_attempt.elapsed());
+            let (Some(delay), Some(retry_request)) = (delay, retry_request) else {
+                break result?;
+            };
+            let failure = match &result {
+                Ok(res) => res.status().to_string(),
+                Err(err) => err.to_string(),
+            };
+            warn!(
+                "{method} {path} failed: {failure}.  retrying in {}ms (attempt {attempt}/{})",
+                delay.as_millis(),
+                self.config.retry.max_attempts
+            );
+            sleep(delay).await;
+            request = retry_request;
+            attempt += 1;
+        };
+        self.record_deprecation(&method, path, res.headers()).await;
+        if let Some(server) = server_time(res.headers()) {
+            self.clock_skew
+                .lock()
+                .await
+                .record(server, OffsetDateTime::now_utc());
+        }
 
         if res.status() == reqwest::StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS {
             let response_body = res.bytes().await?;
+            if let Some(trace) = &self.trace {
+                trace.response_body(&response_body).await;
+            }
             let eula = String::from_utf8_lossy(&response_body).to_string();
             return Err(Error::Eula(eula));
         }
 
-        let res = res.error_for_status()?;
+        if let Some(cache_key) = &cache_key {
+            if res.status() == StatusCode::NOT_MODIFIED {
+                if let Some(cached) = self.cached_responses.lock().await.get(cache_key) {
+                    trace!("response not modified: {}", cache_key);
+                    return Ok(cached.body.clone());
+                }
+                return Err(Error::InvalidResponse(
+                    "service returned not modified for an uncached response",
+                ));
+            }
+        }
+
+        // include the error described by the service, rather than only the
+        // status of the response
+        if let Some(err) = res.error_for_status_ref().err() {
+            let status = res.status().as_u16();
+            let headers = res.headers().clone();
+            let response_body = res.bytes().await?;
+            if let Some(trace) = &self.trace {
+                trace.response_body(&response_body).await;
+            }
+            return Err(
+                service_error(status, &headers, &response_body).unwrap_or_else(|| err.into())
+            );
+        }
+
+        let etag = res
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(ToOwned::to_owned);
         let response_body = res.bytes().await?;
+        if let Some(trace) = &self.trace {
+            trace.response_body(&response_body).await;
+        }
+
         trace!("response body: {:?}", response_body);
+
+        if let (Some(cache_key), Some(etag)) = (cache_key, etag) {
+            let mut cached_responses = self.cached_responses.lock().await;
+            if cached_responses.len() >= MAX_CACHED_RESPONSES
+                && !cached_responses.contains_key(&cache_key)
+            {
+                cached_responses.clear();
+            }
+            cached_responses.insert(
+                cache_key,
+                CachedResponse {
+                    etag,
+                    body: response_body.clone(),
+                },
+            );
+        }
+
+        // truncate after caching, such that later conditional requests are
+        // not answered using the truncated body
+        #[cfg(feature = "chaos")]
+        let response_body = if chaos::inject(path, &[Fault::TruncatedBody]).is_some() {
+            response_body.slice(..response_body.len() / 2)
+        } else {
+            response_body
+        };
+
         Ok(response_body)
     }
 
     /// send the request to the backend and deserialize the response as JSON
-    async fn execute<Q, R>(
+    pub(crate) async fn execute<Q, B, R>(
         &self,
         method: reqwest::Method,
         path: &str,
         query: Option<Q>,
-        body: Option<Q>,
+        body: Option<B>,
     ) -> Result<R>
     where
         Q: Serialize,
+        B: Serialize,
         R: DeserializeOwned,
     {
-        let body = self.execute_raw(method, path, query, body).await?;
+        let body = self.execute_raw(method, path, query, body, true).await?;
         let as_json = serde_json::from_slice(&body)?;
         Ok(as_json)
     }
@@ -126,7 +492,7 @@ impl Backend {
     where
         Q: Serialize,
     {
-        self.execute_raw(reqwest::Method::GET, path, query, None)
+        self.execute_raw(reqwest::Method::GET, path, query, None::<bool>, true)
             .await
     }
 
@@ -136,7 +502,22 @@ impl Backend {
         Q: Serialize,
         R: DeserializeOwned,
     {
-        self.execute(reqwest::Method::GET, path, query, None).await
+        self.execute(reqwest::Method::GET, path, query, None::<bool>)
+            .await
+    }
+
+    /// Send a GET request to the backend that is never sent as a conditional
+    /// request, such that the service always generates a new response
+    pub(crate) async fn get_fresh<Q, R>(&self, path: &str, query: Option<Q>) -> Result<R>
+    where
+        Q: Serialize,
+        R: DeserializeOwned,
+    {
+        let body = self
+            .execute_raw(reqwest::Method::GET, path, query, None::<bool>, false)
+            .await?;
+        let as_json = serde_json::from_slice(&body)?;
+        Ok(as_json)
     }
 
     /// Send a PATCH request to the backend but do not deserialize the response.
@@ -144,7 +525,7 @@ impl Backend {
     where
         Q: Serialize,
     {
-        self.execute_raw(reqwest::Method::PATCH, path, None, Some(body))
+        self.execute_raw(reqwest::Method::PATCH, path, None::<bool>, Some(body), true)
             .await
     }
 
@@ -154,7 +535,7 @@ impl Backend {
         Q: Serialize,
         R: DeserializeOwned,
     {
-        self.execute(reqwest::Method::POST, path, None, Some(body))
+        self.execute(reqwest::Method::POST, path, None::<bool>, Some(body))
             .await
     }
 
@@ -173,7 +554,7 @@ impl Backend {
         Q: Serialize,
         R: DeserializeOwned,
     {
-        self.execute(reqwest::Method::PATCH, path, None, Some(body))
+        self.execute(reqwest::Method::PATCH, path, None::<bool>, Some(body))
             .await
     }
 }
@@ -0,0 +1,181 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::client::config::RetryConfig;
+use reqwest::{header::RETRY_AFTER, Method, Response, StatusCode};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+use time::{format_description::well_known::Rfc2822, OffsetDateTime};
+
+/// Status codes indicating the service did not process the request, such
+/// that any request may be retried
+const UNPROCESSED_STATUSES: &[StatusCode] = &[
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::SERVICE_UNAVAILABLE,
+];
+
+/// Status codes indicating a transient failure that may have occurred after
+/// the service processed the request, such that only idempotent requests are
+/// retried
+const TRANSIENT_STATUSES: &[StatusCode] = &[
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+/// Returns true if sending a request multiple times has the same effect as
+/// sending it once
+pub(crate) fn is_idempotent(method: &Method) -> bool {
+    [
+        Method::GET,
+        Method::HEAD,
+        Method::PUT,
+        Method::DELETE,
+        Method::OPTIONS,
+    ]
+    .contains(method)
+}
+
+/// Returns true if the outcome of an attempt is a transient failure that
+/// should be retried
+fn is_retryable(method: &Method, result: &reqwest::Result<Response>) -> bool {
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            UNPROCESSED_STATUSES.contains(&status)
+                || (is_idempotent(method) && TRANSIENT_STATUSES.contains(&status))
+        }
+        // requests that failed to connect were never received by the service
+        Err(err) => err.is_connect() || (is_idempotent(method) && err.is_timeout()),
+    }
+}
+
+/// Parse the delay requested by a `Retry-After` header, specified either in
+/// seconds or as an HTTP date
+fn retry_after(value: &str, now: OffsetDateTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let when = OffsetDateTime::parse(value, &Rfc2822).ok()?;
+    Some((when - now).try_into().unwrap_or(Duration::ZERO))
+}
+
+/// Get a random value in `0..max`, used to spread out retries from multiple
+/// clients
+fn random_below(max: u64) -> u64 {
+    if max == 0 {
+        return 0;
+    }
+    RandomState::new().build_hasher().finish() % max
+}
+
+impl RetryConfig {
+    /// Exponential backoff prior to retrying after `attempt` attempts, before
+    /// jitter is applied
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2_u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(
+            self.initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+
+    /// Backoff prior to retrying after `attempt` attempts, using equal jitter
+    /// such that the delay is between half and all of the backoff
+    pub(crate) fn jittered_backoff(&self, attempt: u32) -> Duration {
+        let backoff = u64::try_from(self.backoff(attempt).as_millis()).unwrap_or(u64::MAX);
+        let half = backoff / 2;
+        Duration::from_millis(half.saturating_add(random_below(backoff.saturating_sub(half))))
+    }
+
+    /// Determine how long to wait before retrying a request, or `None` if the
+    /// request should not be retried
+    ///
+    /// `attempt` is the number of attempts made so far, and `elapsed` is the
+    /// time spent on the request since the first attempt.
+    pub(crate) fn delay(
+        &self,
+        method: &Method,
+        result: &reqwest::Result<Response>,
+        attempt: u32,
+        elapsed: Duration,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts || !is_retryable(method, result) {
+            return None;
+        }
+
+        let requested = result
+            .as_ref()
+            .ok()
+            .and_then(|response| response.headers().get(RETRY_AFTER))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| retry_after(value, OffsetDateTime::now_utc()));
+
+        let delay = requested.unwrap_or_else(|| self.jittered_backoff(attempt));
+
+        if self.deadline_secs > 0
+            && elapsed.saturating_add(delay) > Duration::from_secs(self.deadline_secs)
+        {
+            return None;
+        }
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let config = RetryConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+            ..RetryConfig::default()
+        };
+        assert_eq!(config.backoff(1), Duration::from_millis(100));
+        assert_eq!(config.backoff(2), Duration::from_millis(200));
+        assert_eq!(config.backoff(4), Duration::from_millis(800));
+        assert_eq!(config.backoff(5), Duration::from_millis(1000));
+        assert_eq!(config.backoff(100), Duration::from_millis(1000));
+
+        let jittered = config.jittered_backoff(2);
+        assert!(jittered >= Duration::from_millis(100));
+        assert!(jittered <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_retry_after() -> Result<(), time::error::Parse> {
+        let now = OffsetDateTime::parse("Wed, 21 Oct 2015 07:28:00 GMT", &Rfc2822)?;
+        assert_eq!(retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        // dates in the past retry immediately
+        assert_eq!(
+            retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after("soon", now), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_idempotent() {
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::DELETE));
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+
+    #[test]
+    fn test_random_below() {
+        assert_eq!(random_below(0), 0);
+        assert!(random_below(10) < 10);
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::client::error::{io_err, Result};
+use reqwest::{
+    header::{HeaderMap, AUTHORIZATION, COOKIE, SET_COOKIE},
+    Request, Response,
+};
+use serde_json::Value;
+use std::{fmt::Write as _, fs::OpenOptions, path::Path, time::Duration};
+use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex};
+use tracing::warn;
+
+/// Maximum number of bytes of a request or response body written to the trace
+const MAX_BODY_BYTES: usize = 4096;
+
+/// Value written in place of secrets
+const REDACTED: &str = "[redacted]";
+
+/// Query parameters of Azure Storage SAS URLs that grant access
+const SECRET_PARAMETERS: &[&str] = &["sig="];
+
+/// Fields of JSON request and response bodies that contain secrets, such as
+/// the HMAC tokens of webhooks and the secrets of personal access tokens
+const SECRET_FIELDS: &[&str] = &[
+    "hmac_token",
+    "hmac_token_previous",
+    "secret",
+    "client_secret",
+];
+
+/// Writes sanitized HTTP requests and responses sent to the Freta API to a
+/// file, to diagnose issues integrating with the service
+///
+/// Tracing is best-effort, such that failing to write the trace does not
+/// cause requests to fail.
+#[derive(Debug)]
+pub(crate) struct HttpTrace {
+    /// File the trace is appended to
+    file: Mutex<File>,
+}
+
+impl HttpTrace {
+    /// Open the trace file, appending to it if it already exists
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| io_err(format!("opening http trace: {path:?}"), e))?;
+        Ok(Self {
+            file: Mutex::new(File::from_std(file)),
+        })
+    }
+
+    /// Record a request prior to it being sent
+    pub(crate) async fn request(&self, request: &Request) {
+        let mut entry = format!(
+            ">>> {} {}\n",
+            request.method(),
+            redact(request.url().as_str())
+        );
+        write_headers(&mut entry, request.headers());
+        if let Some(body) = request.body().and_then(reqwest::Body::as_bytes) {
+            write_body(&mut entry, body);
+        }
+        self.write(entry).await;
+    }
+
+    /// Record the status and headers of a response
+    pub(crate) async fn response(&self, response: &Response, elapsed: Duration) {
+        let mut entry = format!(
+            "<<< {} {} ({}ms)\n",
+            response.status(),
+            redact(response.url().as_str()),
+            elapsed.as_millis()
+        );
+        write_headers(&mut entry, response.headers());
+        self.write(entry).await;
+    }
+
+    /// Record the body of a response
+    pub(crate) async fn response_body(&self, body: &[u8]) {
+        let mut entry = String::new();
+        write_body(&mut entry, body);
+        self.write(entry).await;
+    }
+
+    /// Append an entry to the trace, logging any failures
+    async fn write(&self, entry: String) {
+        let mut file = self.file.lock().await;
+        if let Err(err) = file.write_all(entry.as_bytes()).await {
+            warn!("unable to write http trace: {err}");
+        }
+    }
+}
+
+/// Write headers to a trace entry, redacting those that include credentials
+fn write_headers(entry: &mut String, headers: &HeaderMap) {
+    for (name, value) in headers {
+        let value = if value.is_sensitive() || [AUTHORIZATION, COOKIE, SET_COOKIE].contains(name) {
+            REDACTED.to_string()
+        } else {
+            redact(&String::from_utf8_lossy(value.as_bytes()))
+        };
+        // writing to a `String` does not fail
+        let _ = writeln!(entry, "{name}: {value}");
+    }
+}
+
+/// Write a body to a trace entry, truncated to `MAX_BODY_BYTES`
+fn write_body(entry: &mut String, body: &[u8]) {
+    let redacted = redact_fields(body);
+    let body = redacted.as_deref().unwrap_or(body);
+    let truncated = body.get(..MAX_BODY_BYTES).unwrap_or(body);
+    let _ = writeln!(entry, "\n{}", redact(&String::from_utf8_lossy(truncated)));
+    if truncated.len() < body.len() {
+        let _ = writeln!(
+            entry,
+            "[truncated {} of {} bytes]",
+            truncated.len(),
+            body.len()
+        );
+    }
+    entry.push('\n');
+}
+
+/// Redact the string values of `SECRET_FIELDS` in a JSON body
+///
+/// Returns `None` if the body is not JSON or does not include any secrets.
+fn redact_fields(body: &[u8]) -> Option<Vec<u8>> {
+    /// Redact the secrets within a JSON value, returning true if any were
+    /// redacted
+    fn redact_value(value: &mut Value) -> bool {
+        let mut redacted = false;
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    if SECRET_FIELDS.contains(&key.as_str()) && value.is_string() {
+                        *value = Value::String(REDACTED.to_string());
+                        redacted = true;
+                    } else if redact_value(value) {
+                        redacted = true;
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    if redact_value(value) {
+                        redacted = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+        redacted
+    }
+
+    let mut value = serde_json::from_slice::<Value>(body).ok()?;
+    if !redact_value(&mut value) {
+        return None;
+    }
+    serde_json::to_vec(&value).ok()
+}
+
+/// Redact the signatures of SAS URLs included in a value
+fn redact(value: &str) -> String {
+    let mut result = value.to_string();
+    for parameter in SECRET_PARAMETERS {
+        let mut start = 0;
+        while let Some(offset) = result.get(start..).and_then(|rest| rest.find(parameter)) {
+            let value_start = start + offset + parameter.len();
+            let value_end = result
+                .get(value_start..)
+                .and_then(|rest| rest.find(['&', '"', '\'', ' ', '\n']))
+                .map_or(result.len(), |end| value_start + end);
+            result.replace_range(value_start..value_end, REDACTED);
+            start = value_start + REDACTED.len();
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact(r#"{"sas_url":"https://a.blob.core.windows.net/c/b?sv=1&sig=abc%3D&se=2"}"#),
+            r#"{"sas_url":"https://a.blob.core.windows.net/c/b?sv=1&sig=[redacted]&se=2"}"#
+        );
+        assert_eq!(redact("https://a/b?sig=abc"), "https://a/b?sig=[redacted]");
+        assert_eq!(redact("no secrets"), "no secrets");
+    }
+
+    #[test]
+    fn test_redact_fields() {
+        let mut entry = String::new();
+        write_body(
+            &mut entry,
+            br#"{"webhooks":[{"webhook_id":"1","hmac_token":"current-token","hmac_token_previous":"previous-token","event_types":[]}]}"#,
+        );
+        assert!(!entry.contains("current-token"), "{entry}");
+        assert!(!entry.contains("previous-token"), "{entry}");
+        assert!(entry.contains(r#""hmac_token":"[redacted]""#), "{entry}");
+        assert!(entry.contains(r#""webhook_id":"1""#), "{entry}");
+
+        assert!(redact_fields(br#"{"token_id":"1","secret":"abc"}"#)
+            .is_some_and(|body| !String::from_utf8_lossy(&body).contains("abc")));
+        assert!(redact_fields(br#"{"hmac_token":null}"#).is_none());
+        assert!(redact_fields(b"not json").is_none());
+    }
+
+    #[test]
+    fn test_write_body() {
+        let mut entry = String::new();
+        write_body(&mut entry, &[b'a'; MAX_BODY_BYTES + 10]);
+        assert!(entry.contains(&format!(
+            "[truncated {MAX_BODY_BYTES} of {} bytes]",
+            MAX_BODY_BYTES + 10
+        )));
+    }
+}
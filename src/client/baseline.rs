@@ -0,0 +1,125 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Detects drift in the analysis of images of the same system, such as a VM
+//! template that is analyzed on a schedule.
+//!
+//! The summary of the analysis of a known-good image is saved as a named
+//! baseline in `~/.config/freta/baselines/`.  Subsequent images are compared
+//! to the baseline, producing a `DriftReport` of the kernel version, kernel
+//! modules, hooked functions, and other findings that changed.
+//!
+//! ```rust,no_run
+//! # use freta::{Client, ImageId, Result};
+//! # async fn example(client: Client, known_good: ImageId, latest: ImageId) -> Result<()> {
+//! client.baseline_set("vm-template", known_good).await?;
+//! let drift = client.baseline_compare("vm-template", latest).await?;
+//! if drift.has_drift() {
+//!     println!("{drift:?}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::{
+        config::get_config_dir,
+        io::{create_dir_all, read_json, write_json},
+    },
+    models::baseline::{BaselineSummary, DriftReport},
+    Client, Error, ImageId, Result,
+};
+use std::path::PathBuf;
+
+/// Get the path of a named baseline
+///
+/// # Errors
+/// This will return an error if the name is not a valid file name or the
+/// config directory cannot be determined
+fn baseline_path(name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(Error::Other("invalid baseline name", name.to_string()));
+    }
+    Ok(get_config_dir()?
+        .join("baselines")
+        .join(format!("{name}.json")))
+}
+
+impl Client {
+    /// Summarize the analysis of an image for drift detection
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The analysis report for the image is unavailable or invalid
+    pub async fn baseline_summary(&self, image_id: ImageId) -> Result<BaselineSummary> {
+        let report = self.report(image_id).await?;
+        Ok(BaselineSummary::from_report(image_id, &report))
+    }
+
+    /// Save the summary of the analysis of an image as a named baseline,
+    /// replacing any existing baseline with the same name
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The analysis of the image cannot be summarized
+    /// 2. The baseline cannot be saved
+    pub async fn baseline_set(&self, name: &str, image_id: ImageId) -> Result<BaselineSummary> {
+        let path = baseline_path(name)?;
+        let summary = self.baseline_summary(image_id).await?;
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).await?;
+        }
+        write_json(path, &summary).await?;
+        Ok(summary)
+    }
+
+    /// Get a named baseline
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the baseline does not exist or
+    /// cannot be read
+    pub async fn baseline_get(name: &str) -> Result<BaselineSummary> {
+        let path = baseline_path(name)?;
+        if !path.exists() {
+            return Err(Error::Other("baseline not found", name.to_string()));
+        }
+        read_json(path).await
+    }
+
+    /// Compare the analysis of an image to a named baseline
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The baseline does not exist or cannot be read
+    /// 2. The analysis of the image cannot be summarized
+    pub async fn baseline_compare(&self, name: &str, image_id: ImageId) -> Result<DriftReport> {
+        let baseline = Self::baseline_get(name).await?;
+        let current = self.baseline_summary(image_id).await?;
+        Ok(DriftReport::new(&baseline, &current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_baseline_path() -> Result<()> {
+        assert!(
+            baseline_path("vm-template_2.weekly")?.ends_with("baselines/vm-template_2.weekly.json")
+        );
+        for name in ["", "..", ".hidden", "a/b", "with space"] {
+            assert!(baseline_path(name).is_err(), "{name}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::{
+    client::{
+        config::{get_config_dir, Config},
+        error::{io_err, Result},
+        io::{create_dir_all, read_json, remove_file, write_json},
+    },
+    models::{
+        base::{Image, ImageId, ImageState},
+        service::Info,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use time::OffsetDateTime;
+use tokio::fs;
+use url::Url;
+
+/// Statistics for the local artifact cache
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Location of the cache on disk
+    pub path: PathBuf,
+    /// Number of cached artifacts
+    pub entries: usize,
+    /// Total size of the cached artifacts in bytes
+    pub bytes: u64,
+    /// Maximum size of the cache in bytes.  A value of 0 disables caching
+    pub max_bytes: u64,
+}
+
+/// A single file stored in the cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CacheEntry {
+    /// Path of the cached file
+    path: PathBuf,
+    /// Size of the cached file in bytes
+    size: u64,
+    /// Last time the cached file was written or read
+    last_used: SystemTime,
+}
+
+/// Local on-disk cache of artifacts
+///
+/// Only artifacts of images whose analysis has completed are cached, as the
+/// artifacts of such images do not change until the image is reanalyzed.
+/// Artifacts are stored by the time the image was last updated, such that
+/// artifacts cached before the image was reanalyzed are not used.  The cache
+/// is kept within `Config.cache_max_bytes` by evicting the least recently
+/// used artifacts.
+pub(crate) struct Cache {
+    /// Root directory of the cache
+    root: PathBuf,
+    /// Maximum size of the cache in bytes
+    max_bytes: u64,
+}
+
+impl Cache {
+    /// Create a `Cache` using the limits from the client configuration
+    ///
+    /// # Errors
+    /// This will return an error if the user's home directory cannot be
+    /// determined
+    pub(crate) fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            root: get_config_dir()?.join("cache"),
+            max_bytes: config.cache_max_bytes,
+        })
+    }
+
+    /// Get the on-disk path for an artifact of `image`, rejecting names that
+    /// would escape the cache directory
+    ///
+    /// Returns `None` if the artifacts of the image cannot be cached, as its
+    /// analysis has not completed.
+    fn path_for(&self, image: &Image, name: &str) -> Option<PathBuf> {
+        if image.state != ImageState::Completed {
+            return None;
+        }
+        let analyzed = image.last_updated?.unix_timestamp_nanos();
+        let name = Path::new(name);
+        let is_normal = name
+            .components()
+            .all(|part| matches!(part, Component::Normal(_)));
+        (is_normal && name.components().next().is_some()).then(|| {
+            self.root
+                .join(image.image_id.to_string())
+                .join(analyzed.to_string())
+                .join(name)
+        })
+    }
+
+    /// Read an artifact of `image` from the cache, marking it as recently
+    /// used
+    ///
+    /// # Errors
+    /// This will return an error if the cached artifact cannot be read
+    pub(crate) async fn get(&self, image: &Image, name: &str) -> Result<Option<Vec<u8>>> {
+        let Some(path) = self.path_for(image, name) else {
+            return Ok(None);
+        };
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let data = fs::read(&path)
+            .await
+            .map_err(|e| io_err(format!("reading cached artifact: {path:?}"), e))?;
+
+        let handle = fs::File::options()
+            .write(true)
+            .open(&path)
+            .await
+            .map_err(|e| io_err(format!("opening cached artifact: {path:?}"), e))?;
+        handle
+            .into_std()
+            .await
+            .set_modified(SystemTime::now())
+            .map_err(|e| io_err(format!("updating cached artifact: {path:?}"), e))?;
+
+        Ok(Some(data))
+    }
+
+    /// Add an artifact of `image` to the cache, then evict artifacts as
+    /// needed to keep the cache within its configured size
+    ///
+    /// # Errors
+    /// This will return an error in the following cases:
+    /// 1. Writing the artifact to the cache fails
+    /// 2. Evicting artifacts from the cache fails
+    pub(crate) async fn put(&self, image: &Image, name: &str, data: &[u8]) -> Result<()> {
+        if data.len() as u64 > self.max_bytes {
+            return Ok(());
+        }
+        let Some(path) = self.path_for(image, name) else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).await?;
+        }
+        fs::write(&path, data)
+            .await
+            .map_err(|e| io_err(format!("writing cached artifact: {path:?}"), e))?;
+
+        self.evict().await?;
+        Ok(())
+    }
+
+    /// Remove all of the cached artifacts for an image
+    ///
+    /// # Errors
+    /// This will return an error if the cached artifacts cannot be removed
+    pub(crate) async fn remove_image(&self, image_id: ImageId) -> Result<()> {
+        let path = self.root.join(image_id.to_string());
+        if path.exists() {
+            fs::remove_dir_all(&path)
+                .await
+                .map_err(|e| io_err(format!("removing cached artifacts: {path:?}"), e))?;
+        }
+        Ok(())
+    }
+
+    /// Get the statistics for the cache
+    ///
+    /// # Errors
+    /// This will return an error if the cache directory cannot be read
+    pub(crate) async fn stats(&self) -> Result<CacheStats> {
+        let entries = self.entries().await?;
+        Ok(CacheStats {
+            path: self.root.clone(),
+            entries: entries.len(),
+            bytes: entries.iter().map(|entry| entry.size).sum(),
+            max_bytes: self.max_bytes,
+        })
+    }
+
+    /// Evict the least recently used artifacts until the cache is within its
+    /// configured size, returning the number of evicted artifacts
+    ///
+    /// # Errors
+    /// This will return an error in the following cases:
+    /// 1. The cache directory cannot be read
+    /// 2. Removing an evicted artifact fails
+    pub(crate) async fn evict(&self) -> Result<usize> {
+        let evictions = select_evictions(self.entries().await?, self.max_bytes);
+        for entry in &evictions {
+            remove_file(&entry.path).await?;
+        }
+        Ok(evictions.len())
+    }
+
+    /// List all of the files in the cache
+    async fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries = vec![];
+        if !self.root.exists() {
+            return Ok(entries);
+        }
+
+        let mut pending = vec![self.root.clone()];
+        while let Some(dir) = pending.pop() {
+            let mut read_dir = fs::read_dir(&dir)
+                .await
+                .map_err(|e| io_err(format!("reading cache directory: {dir:?}"), e))?;
+            while let Some(entry) = read_dir
+                .next_entry()
+                .await
+                .map_err(|e| io_err(format!("reading cache directory: {dir:?}"), e))?
+            {
+                let path = entry.path();
+                let metadata = entry
+                    .metadata()
+                    .await
+                    .map_err(|e| io_err(format!("reading cached artifact: {path:?}"), e))?;
+                if metadata.is_dir() {
+                    pending.push(path);
+                } else {
+                    entries.push(CacheEntry {
+                        path,
+                        size: metadata.len(),
+                        last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    });
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Service information retained to avoid repeatedly requesting `/api/info`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct CachedInfo {
+    /// URL of the service the information was retrieved from
+    api_url: Url,
+    /// When the information was retrieved
+    #[serde(with = "time::serde::rfc3339")]
+    retrieved_at: OffsetDateTime,
+    /// The service information
+    info: Info,
+}
+
+impl CachedInfo {
+    /// Record service information retrieved from `api_url`
+    pub(crate) fn new(api_url: Url, info: Info) -> Self {
+        Self {
+            api_url,
+            retrieved_at: OffsetDateTime::now_utc(),
+            info,
+        }
+    }
+
+    /// Get the service information if it was retrieved from `api_url` within
+    /// the last `ttl`
+    pub(crate) fn get(&self, api_url: &Url, ttl: Duration) -> Option<&Info> {
+        let age = OffsetDateTime::now_utc() - self.retrieved_at;
+        (self.api_url == *api_url && age >= time::Duration::ZERO && age < ttl).then_some(&self.info)
+    }
+
+    /// Get the path for the on-disk copy of the service information
+    fn path() -> Result<PathBuf> {
+        Ok(get_config_dir()?.join("info.json"))
+    }
+
+    /// Load the service information saved to disk, if any
+    ///
+    /// # Errors
+    /// This will return an error if the saved information cannot be read
+    pub(crate) async fn load() -> Result<Option<Self>> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        read_json(path).await
+    }
+
+    /// Save the service information to disk
+    ///
+    /// # Errors
+    /// This will return an error if the information cannot be written
+    pub(crate) async fn save(&self) -> Result<()> {
+        create_dir_all(get_config_dir()?).await?;
+        write_json(Self::path()?, self).await
+    }
+}
+
+/// Select the least recently used entries that must be removed for the
+/// remaining entries to fit within `max_bytes`
+fn select_evictions(mut entries: Vec<CacheEntry>, max_bytes: u64) -> Vec<CacheEntry> {
+    let mut total = entries.iter().map(|entry| entry.size).sum::<u64>();
+    entries.sort_by(|a, b| a.last_used.cmp(&b.last_used).then(a.path.cmp(&b.path)));
+    entries
+        .into_iter()
+        .take_while(|entry| {
+            let evict = total > max_bytes;
+            if evict {
+                total -= entry.size;
+            }
+            evict
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::base::{ImageFormat, OwnerId};
+    use std::collections::BTreeMap;
+
+    fn entry(name: &str, size: u64, age: u64) -> CacheEntry {
+        CacheEntry {
+            path: PathBuf::from(name),
+            size,
+            last_used: SystemTime::UNIX_EPOCH + Duration::from_secs(1000 - age),
+        }
+    }
+
+    #[test]
+    fn test_select_evictions() {
+        let entries = vec![
+            entry("new", 10, 1),
+            entry("old", 10, 100),
+            entry("mid", 10, 50),
+        ];
+
+        assert!(select_evictions(entries.clone(), 30).is_empty());
+
+        assert_eq!(
+            select_evictions(entries.clone(), 25),
+            vec![entry("old", 10, 100)]
+        );
+
+        assert_eq!(
+            select_evictions(entries.clone(), 10),
+            vec![entry("old", 10, 100), entry("mid", 10, 50)]
+        );
+
+        assert_eq!(select_evictions(entries, 0).len(), 3);
+    }
+
+    #[test]
+    fn test_path_for() {
+        let cache = Cache {
+            root: PathBuf::from("/cache"),
+            max_bytes: 1024,
+        };
+        let mut image = Image::new(OwnerId::samples(), ImageFormat::Lime, BTreeMap::new());
+        let image_id = image.image_id;
+
+        // artifacts of images that were not analyzed are not cached
+        assert!(cache.path_for(&image, "report.json").is_none());
+
+        image.state = ImageState::Completed;
+        image.last_updated = Some(OffsetDateTime::UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(
+            cache.path_for(&image, "report.json"),
+            Some(PathBuf::from(format!(
+                "/cache/{image_id}/1000000000/report.json"
+            )))
+        );
+        assert!(cache.path_for(&image, "../report.json").is_none());
+        assert!(cache.path_for(&image, "/etc/passwd").is_none());
+        assert!(cache.path_for(&image, "").is_none());
+
+        // reanalyzing the image changes where its artifacts are cached
+        let analyzed = cache.path_for(&image, "report.json");
+        image.last_updated = Some(OffsetDateTime::UNIX_EPOCH + Duration::from_secs(2));
+        assert_ne!(cache.path_for(&image, "report.json"), analyzed);
+    }
+
+    #[test]
+    fn test_cached_info() -> Result<()> {
+        let info = Info {
+            api_version: "1".into(),
+            models_version: "1".into(),
+            current_eula: "eula".into(),
+            formats: vec![],
+        };
+        let api_url = Url::parse("https://freta.microsoft.com/")
+            .map_err(|e| crate::Error::Other("invalid url", e.to_string()))?;
+        let other_url = Url::parse("https://freta.example.com/")
+            .map_err(|e| crate::Error::Other("invalid url", e.to_string()))?;
+
+        let cached = CachedInfo::new(api_url.clone(), info);
+        assert!(cached.get(&api_url, Duration::from_secs(60)).is_some());
+        assert!(cached.get(&other_url, Duration::from_secs(60)).is_none());
+        assert!(cached.get(&api_url, Duration::ZERO).is_none());
+
+        let expired = CachedInfo {
+            retrieved_at: cached.retrieved_at - Duration::from_secs(120),
+            ..cached
+        };
+        assert!(expired.get(&api_url, Duration::from_secs(60)).is_none());
+        Ok(())
+    }
+}
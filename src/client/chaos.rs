@@ -0,0 +1,288 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! When enabled, requests to the Freta service and Azure Blob Storage
+//! operations randomly fail at the configured rate, such that retry and
+//! resume handling can be exercised without a misbehaving service.  Faults
+//! are drawn from a pseudo-random sequence derived from the configured seed,
+//! such that a run with the same seed and the same sequence of operations
+//! injects the same faults.
+//!
+//! Fault injection is configured for the whole process, either by calling
+//! `ChaosConfig::install` or by setting the following environment variables:
+//!
+//! * `FRETA_CHAOS_RATE`: rate at which to inject faults, from 0.0 to 1.0
+//! * `FRETA_CHAOS_SEED`: seed for the fault sequence.  Defaults to 0
+//! * `FRETA_CHAOS_FAULTS`: comma separated list of the faults to inject, such
+//!   as `too-many-requests,timeout`.  Defaults to all faults
+//!
+//! ```rust,no_run
+//! use freta::chaos::{ChaosConfig, Fault};
+//! ChaosConfig::new(0.1)
+//!     .seed(1234)
+//!     .faults(vec![Fault::ServiceUnavailable, Fault::Timeout])
+//!     .install();
+//! ```
+
+use crate::{Error, Result};
+use std::{
+    env,
+    fmt::{Display, Error as FmtError, Formatter},
+    str::FromStr,
+    sync::{Mutex, Once},
+};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use tracing::warn;
+
+/// Environment variable for the rate at which faults are injected
+pub const CHAOS_RATE_ENV: &str = "FRETA_CHAOS_RATE";
+
+/// Environment variable for the seed of the fault sequence
+pub const CHAOS_SEED_ENV: &str = "FRETA_CHAOS_SEED";
+
+/// Environment variable for the faults to inject
+pub const CHAOS_FAULTS_ENV: &str = "FRETA_CHAOS_FAULTS";
+
+/// The fault injector for the process, if fault injection is enabled
+static CHAOS: Mutex<Option<Chaos>> = Mutex::new(None);
+
+/// Ensures the environment is only used to configure fault injection once
+static FROM_ENV: Once = Once::new();
+
+/// A fault that can be injected into an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum Fault {
+    /// The service responds with `429 Too Many Requests`
+    TooManyRequests,
+    /// The service responds with `503 Service Unavailable`
+    ServiceUnavailable,
+    /// The operation does not complete in time
+    Timeout,
+    /// The response body ends before all of the data is received
+    TruncatedBody,
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FmtError> {
+        match self {
+            Self::TooManyRequests => write!(f, "too-many-requests"),
+            Self::ServiceUnavailable => write!(f, "service-unavailable"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::TruncatedBody => write!(f, "truncated-body"),
+        }
+    }
+}
+
+impl FromStr for Fault {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        Self::iter()
+            .find(|fault| fault.to_string() == s)
+            .ok_or_else(|| Error::Other("invalid fault", s.to_string()))
+    }
+}
+
+/// Configuration for fault injection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChaosConfig {
+    /// Rate at which faults are injected, from 0.0 to 1.0
+    pub rate: f64,
+    /// Seed for the fault sequence
+    pub seed: u64,
+    /// Faults to inject
+    pub faults: Vec<Fault>,
+}
+
+impl ChaosConfig {
+    /// Create a configuration that injects all faults at the specified rate
+    #[must_use]
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            seed: 0,
+            faults: Fault::iter().collect(),
+        }
+    }
+
+    /// Set the seed for the fault sequence
+    #[must_use]
+    pub fn seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Set the faults to inject
+    #[must_use]
+    pub fn faults(self, faults: Vec<Fault>) -> Self {
+        Self { faults, ..self }
+    }
+
+    /// Load the configuration from the environment
+    ///
+    /// Returns `None` if `FRETA_CHAOS_RATE` is not set.
+    ///
+    /// # Errors
+    /// This will return an error if any of the environment variables are
+    /// invalid
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(rate) = env::var(CHAOS_RATE_ENV) else {
+            return Ok(None);
+        };
+        let rate = rate
+            .parse()
+            .map_err(|_| Error::Other("invalid chaos rate", rate))?;
+        let mut config = Self::new(rate);
+
+        if let Ok(seed) = env::var(CHAOS_SEED_ENV) {
+            config.seed = seed
+                .parse()
+                .map_err(|_| Error::Other("invalid chaos seed", seed))?;
+        }
+
+        if let Ok(faults) = env::var(CHAOS_FAULTS_ENV) {
+            config.faults = faults
+                .split(',')
+                .map(|fault| fault.trim().parse())
+                .collect::<Result<_>>()?;
+        }
+
+        Ok(Some(config))
+    }
+
+    /// Enable fault injection for the process using this configuration,
+    /// replacing any previous configuration
+    pub fn install(self) {
+        load_env();
+        if let Ok(mut chaos) = CHAOS.lock() {
+            *chaos = Some(Chaos::new(self));
+        }
+    }
+
+    /// Disable fault injection for the process
+    pub fn uninstall() {
+        load_env();
+        if let Ok(mut chaos) = CHAOS.lock() {
+            *chaos = None;
+        }
+    }
+}
+
+/// Enable fault injection from the environment, if configured
+fn load_env() {
+    FROM_ENV.call_once(|| match ChaosConfig::from_env() {
+        Ok(Some(config)) => {
+            if let Ok(mut chaos) = CHAOS.lock() {
+                *chaos = Some(Chaos::new(config));
+            }
+        }
+        Ok(None) => {}
+        Err(err) => warn!("ignoring chaos configuration: {err}"),
+    });
+}
+
+/// Fault injector state
+struct Chaos {
+    /// Configuration for fault injection
+    config: ChaosConfig,
+    /// State of the pseudo-random sequence
+    state: u64,
+}
+
+impl Chaos {
+    /// Create a fault injector from the configuration
+    const fn new(config: ChaosConfig) -> Self {
+        let state = config.seed;
+        Self { config, state }
+    }
+
+    /// Get the next value in the pseudo-random sequence, using `SplitMix64`
+    const fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Decide which fault, if any, to inject from the `applicable` faults
+    // the upper 53 bits of the sample are exactly representable as an f64
+    #[allow(clippy::cast_precision_loss)]
+    fn next_fault(&mut self, applicable: &[Fault]) -> Option<Fault> {
+        let faults = self
+            .config
+            .faults
+            .iter()
+            .filter(|fault| applicable.contains(fault))
+            .copied()
+            .collect::<Vec<_>>();
+        if faults.is_empty() {
+            return None;
+        }
+
+        // use the upper 53 bits to build a uniformly distributed f64 in [0, 1)
+        let sample = (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64;
+        if sample >= self.config.rate {
+            return None;
+        }
+
+        let index = usize::try_from(self.next_u64() % faults.len() as u64).ok()?;
+        faults.get(index).copied()
+    }
+}
+
+/// Decide which fault, if any, to inject into an operation
+///
+/// Only the `applicable` faults that are enabled in the configuration are
+/// injected.
+pub(crate) fn inject(operation: &str, applicable: &[Fault]) -> Option<Fault> {
+    load_env();
+    let fault = CHAOS.lock().ok()?.as_mut()?.next_fault(applicable)?;
+    warn!("injecting {fault} into {operation}");
+    Some(fault)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence(config: ChaosConfig, count: usize) -> Vec<Option<Fault>> {
+        let all = Fault::iter().collect::<Vec<_>>();
+        let mut chaos = Chaos::new(config);
+        (0..count).map(|_| chaos.next_fault(&all)).collect()
+    }
+
+    #[test]
+    fn test_fault_sequence() {
+        let config = ChaosConfig::new(0.5).seed(1234);
+        let faults = sequence(config.clone(), 1000);
+
+        // the same seed results in the same faults
+        assert_eq!(faults, sequence(config.clone(), 1000));
+        assert_ne!(faults, sequence(config.seed(4321), 1000));
+
+        let injected = faults.iter().flatten().count();
+        assert!((400..600).contains(&injected), "injected {injected}");
+
+        assert!(sequence(ChaosConfig::new(0.0), 100)
+            .iter()
+            .all(Option::is_none));
+        assert!(sequence(ChaosConfig::new(1.0), 100)
+            .iter()
+            .all(Option::is_some));
+    }
+
+    #[test]
+    fn test_applicable_faults() {
+        let mut chaos = Chaos::new(ChaosConfig::new(1.0).faults(vec![Fault::Timeout]));
+        assert_eq!(chaos.next_fault(&[Fault::Timeout]), Some(Fault::Timeout));
+        assert_eq!(chaos.next_fault(&[Fault::TruncatedBody]), None);
+    }
+
+    #[test]
+    fn test_fault_parse() -> Result<()> {
+        for fault in Fault::iter() {
+            assert_eq!(fault.to_string().parse::<Fault>()?, fault);
+        }
+        assert!("timeouts".parse::<Fault>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,541 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Reports the outcome of the analysis of an image to a CI system, such that
+//! a pipeline that builds and analyzes VM images can surface the results on
+//! the commit or pull request that triggered it.
+//!
+//! The outcome is posted as a commit status, using the GitHub commit status
+//! API or the Azure DevOps Git status API.  If a pull request is specified, a
+//! comment summarizing the findings is also added to the pull request.
+//!
+//! ```rust,no_run
+//! # use freta::{ci::{CiTarget, GitHubTarget}, Client, ImageId, Result, Secret};
+//! # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+//! let target = CiTarget::GitHub(GitHubTarget::new(
+//!     "contoso/images",
+//!     "8f7e2a1c9d0b3e4f5a6b7c8d9e0f1a2b3c4d5e6f",
+//!     Secret::new("github token"),
+//! ));
+//! let summary = client.ci_report(image_id, &target).await?;
+//! println!("{}", summary.description());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::config::Secret, models::analysis::report::Report, Client, Error, ImageId, Result,
+    SDK_NAME, SDK_VERSION,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt::{Display, Error as FmtError, Formatter};
+use tracing::info;
+use url::Url;
+
+/// Default GitHub API endpoint
+const GITHUB_API_URL: &str = "https://api.github.com/";
+
+/// Maximum length of the description of a GitHub commit status
+const GITHUB_DESCRIPTION_MAX: usize = 140;
+
+/// Azure DevOps REST API version used for posting statuses and comments
+const AZURE_DEVOPS_API_VERSION: &str = "7.1";
+
+/// Default name used to identify the status posted by Freta
+pub const DEFAULT_CONTEXT: &str = "freta/analysis";
+
+/// Maximum number of findings included in a pull request comment
+const SUMMARY_MAX_FINDINGS: usize = 20;
+
+/// State of a status posted to a CI system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiState {
+    /// The analysis has not completed
+    Pending,
+    /// The analysis completed without findings
+    Success,
+    /// The analysis completed with findings
+    Failure,
+    /// The analysis did not complete
+    Error,
+}
+
+impl CiState {
+    /// Name of the state used by the GitHub commit status API
+    const fn github(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::Error => "error",
+        }
+    }
+
+    /// Name of the state used by the Azure DevOps Git status API
+    const fn azure_devops(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Success => "succeeded",
+            Self::Failure => "failed",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl Display for CiState {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FmtError> {
+        write!(f, "{}", self.github())
+    }
+}
+
+/// Summary of the analysis of an image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AnalysisSummary {
+    /// Image that was analyzed
+    pub image_id: ImageId,
+    /// Outcome of the analysis
+    #[serde(serialize_with = "serialize_state")]
+    pub state: CiState,
+    /// Issues found in the analysis
+    pub findings: Vec<String>,
+    /// Reason the analysis did not complete
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Serialize a `CiState` using its display name
+fn serialize_state<S>(state: &CiState, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_str(state)
+}
+
+impl AnalysisSummary {
+    /// Summary for an image whose analysis has not completed
+    #[must_use]
+    pub const fn pending(image_id: ImageId) -> Self {
+        Self {
+            image_id,
+            state: CiState::Pending,
+            findings: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Summarize the findings in an analysis report
+    #[must_use]
+    pub fn from_report(image_id: ImageId, report: &Report) -> Self {
+        let findings = report
+            .checks
+            .iter()
+            .map(|check| check.issue.clone())
+            .collect::<Vec<_>>();
+        let state = if findings.is_empty() {
+            CiState::Success
+        } else {
+            CiState::Failure
+        };
+        Self {
+            image_id,
+            state,
+            findings,
+            error: None,
+        }
+    }
+
+    /// Summary for an image whose analysis failed
+    #[must_use]
+    pub fn from_error(image_id: ImageId, error: &Error) -> Self {
+        Self {
+            image_id,
+            state: CiState::Error,
+            findings: Vec::new(),
+            error: Some(error.to_string()),
+        }
+    }
+
+    /// One line description of the outcome of the analysis
+    #[must_use]
+    pub fn description(&self) -> String {
+        match self.state {
+            CiState::Pending => "analysis in progress".to_string(),
+            CiState::Success => "analysis completed: no findings".to_string(),
+            CiState::Failure => match self.findings.len() {
+                1 => "analysis completed: 1 finding".to_string(),
+                count => format!("analysis completed: {count} findings"),
+            },
+            CiState::Error => self
+                .error
+                .clone()
+                .unwrap_or_else(|| "analysis failed".to_string()),
+        }
+    }
+
+    /// Markdown summary of the findings, used for pull request comments
+    #[must_use]
+    pub fn markdown(&self) -> String {
+        let mut lines = vec![format!(
+            "**Freta analysis of {}**: {}",
+            self.image_id,
+            self.description()
+        )];
+        if !self.findings.is_empty() {
+            lines.push(String::new());
+            lines.extend(
+                self.findings
+                    .iter()
+                    .take(SUMMARY_MAX_FINDINGS)
+                    .map(|finding| format!("* {finding}")),
+            );
+            let remaining = self.findings.len().saturating_sub(SUMMARY_MAX_FINDINGS);
+            if remaining > 0 {
+                lines.push(format!("* ... and {remaining} more"));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// Truncate a description to the maximum length of a GitHub commit status
+fn truncate_description(description: String) -> String {
+    if description.chars().count() <= GITHUB_DESCRIPTION_MAX {
+        return description;
+    }
+    let mut result = description
+        .chars()
+        .take(GITHUB_DESCRIPTION_MAX - 3)
+        .collect::<String>();
+    result.push_str("...");
+    result
+}
+
+/// Commit or pull request on GitHub
+#[derive(Debug, Clone)]
+pub struct GitHubTarget {
+    /// Repository, in the form `owner/name`
+    pub repository: String,
+    /// Commit SHA to post the status to
+    pub sha: String,
+    /// Pull request to comment on with the findings summary
+    pub pull_request: Option<u64>,
+    /// Token used to authenticate to GitHub
+    pub token: Secret,
+    /// GitHub API endpoint
+    pub api_url: Url,
+    /// Name used to identify the status
+    pub context: String,
+    /// URL linked from the status
+    pub target_url: Option<Url>,
+}
+
+impl GitHubTarget {
+    /// Create a target for a commit in a repository on github.com
+    #[must_use]
+    pub fn new<R, S>(repository: R, sha: S, token: Secret) -> Self
+    where
+        R: Into<String>,
+        S: Into<String>,
+    {
+        Self {
+            repository: repository.into(),
+            sha: sha.into(),
+            pull_request: None,
+            token,
+            #[allow(clippy::expect_used)]
+            api_url: Url::parse(GITHUB_API_URL).expect("default GitHub URL failed"),
+            context: DEFAULT_CONTEXT.to_string(),
+            target_url: None,
+        }
+    }
+
+    /// Build a URL relative to the API endpoint
+    fn url(&self, path: &str) -> Result<Url> {
+        self.api_url
+            .join(path)
+            .map_err(|e| Error::Other("invalid GitHub URL", e.to_string()))
+    }
+
+    /// Body of the commit status request
+    fn status(&self, summary: &AnalysisSummary) -> Value {
+        json!({
+            "state": summary.state.github(),
+            "description": truncate_description(summary.description()),
+            "context": self.context,
+            "target_url": self.target_url,
+        })
+    }
+
+    /// Post the summary to GitHub
+    async fn post(&self, http: &reqwest::Client, summary: &AnalysisSummary) -> Result<()> {
+        let url = self.url(&format!("repos/{}/statuses/{}", self.repository, self.sha))?;
+        http.post(url)
+            .bearer_auth(self.token.get_secret())
+            .header("Accept", "application/vnd.github+json")
+            .json(&self.status(summary))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        // the findings are only known once the analysis completes
+        let pull_request = self
+            .pull_request
+            .filter(|_| summary.state != CiState::Pending);
+        if let Some(pull_request) = pull_request {
+            let url = self.url(&format!(
+                "repos/{}/issues/{pull_request}/comments",
+                self.repository
+            ))?;
+            http.post(url)
+                .bearer_auth(self.token.get_secret())
+                .header("Accept", "application/vnd.github+json")
+                .json(&json!({ "body": summary.markdown() }))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+}
+
+/// Commit or pull request in an Azure DevOps Git repository
+#[derive(Debug, Clone)]
+pub struct AzureDevOpsTarget {
+    /// Organization URL, such as `https://dev.azure.com/contoso/`
+    pub organization_url: Url,
+    /// Project containing the repository
+    pub project: String,
+    /// Repository name or ID
+    pub repository: String,
+    /// Commit ID to post the status to
+    pub commit_id: String,
+    /// Pull request to post the status to and comment on with the findings
+    /// summary
+    pub pull_request: Option<u64>,
+    /// Token used to authenticate to Azure DevOps, such as
+    /// `System.AccessToken` in a pipeline
+    pub token: Secret,
+    /// Name used to identify the status
+    pub context: String,
+    /// URL linked from the status
+    pub target_url: Option<Url>,
+}
+
+impl AzureDevOpsTarget {
+    /// Create a target for a commit in an Azure DevOps Git repository
+    #[must_use]
+    pub fn new<P, R, C>(
+        organization_url: Url,
+        project: P,
+        repository: R,
+        commit_id: C,
+        token: Secret,
+    ) -> Self
+    where
+        P: Into<String>,
+        R: Into<String>,
+        C: Into<String>,
+    {
+        Self {
+            organization_url,
+            project: project.into(),
+            repository: repository.into(),
+            commit_id: commit_id.into(),
+            pull_request: None,
+            token,
+            context: DEFAULT_CONTEXT.to_string(),
+            target_url: None,
+        }
+    }
+
+    /// Build a URL for a Git repository API relative to the organization
+    fn url(&self, path: &str) -> Result<Url> {
+        let mut base = self.organization_url.clone();
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        let mut url = base
+            .join(&format!(
+                "{}/_apis/git/repositories/{}/{path}",
+                self.project, self.repository
+            ))
+            .map_err(|e| Error::Other("invalid Azure DevOps URL", e.to_string()))?;
+        url.query_pairs_mut()
+            .append_pair("api-version", AZURE_DEVOPS_API_VERSION);
+        Ok(url)
+    }
+
+    /// Body of the status request
+    fn status(&self, summary: &AnalysisSummary) -> Value {
+        // Azure DevOps identifies a status by its genre and name
+        let (genre, name) = self
+            .context
+            .rsplit_once('/')
+            .unwrap_or(("freta", self.context.as_str()));
+        json!({
+            "state": summary.state.azure_devops(),
+            "description": summary.description(),
+            "context": { "genre": genre, "name": name },
+            "targetUrl": self.target_url,
+        })
+    }
+
+    /// Post the summary to Azure DevOps
+    async fn post(&self, http: &reqwest::Client, summary: &AnalysisSummary) -> Result<()> {
+        let path = if let Some(pull_request) = self.pull_request {
+            format!("pullRequests/{pull_request}/statuses")
+        } else {
+            format!("commits/{}/statuses", self.commit_id)
+        };
+        http.post(self.url(&path)?)
+            .bearer_auth(self.token.get_secret())
+            .json(&self.status(summary))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        // the findings are only known once the analysis completes
+        let pull_request = self
+            .pull_request
+            .filter(|_| summary.state != CiState::Pending);
+        if let Some(pull_request) = pull_request {
+            let url = self.url(&format!("pullRequests/{pull_request}/threads"))?;
+            let thread = json!({
+                "comments": [{
+                    "parentCommentId": 0,
+                    "content": summary.markdown(),
+                    "commentType": "text",
+                }],
+                "status": "closed",
+            });
+            http.post(url)
+                .bearer_auth(self.token.get_secret())
+                .json(&thread)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+}
+
+/// CI system to report the analysis outcome to
+#[derive(Debug, Clone)]
+pub enum CiTarget {
+    /// GitHub commit status and pull request comment
+    GitHub(GitHubTarget),
+    /// Azure DevOps Git status and pull request thread
+    AzureDevOps(AzureDevOpsTarget),
+}
+
+impl CiTarget {
+    /// Post the summary of an analysis to the CI system
+    ///
+    /// # Errors
+    /// This will return an error if the CI system rejects the request
+    pub async fn post(&self, summary: &AnalysisSummary) -> Result<()> {
+        let http = reqwest::Client::builder()
+            .user_agent(format!("{SDK_NAME}/{SDK_VERSION}"))
+            .build()?;
+        match self {
+            Self::GitHub(target) => target.post(&http, summary).await,
+            Self::AzureDevOps(target) => target.post(&http, summary).await,
+        }
+    }
+}
+
+impl Client {
+    /// Monitor the analysis of an image and report the outcome to a CI
+    /// system
+    ///
+    /// A pending status is posted while the analysis is in progress.  Once
+    /// the analysis completes, the status is updated with the outcome, and
+    /// the findings are summarized on the pull request, if one is specified.
+    /// An analysis that fails is reported to the CI system rather than
+    /// returned as an error.
+    ///
+    /// # Errors
+    /// This will return an error in the following cases:
+    /// 1. Getting the analysis report fails
+    /// 2. The CI system rejects the status
+    pub async fn ci_report(&self, image_id: ImageId, target: &CiTarget) -> Result<AnalysisSummary> {
+        target.post(&AnalysisSummary::pending(image_id)).await?;
+
+        let summary = match self.images_monitor(image_id).await {
+            Ok(_) => {
+                let report = self.report(image_id).await?;
+                AnalysisSummary::from_report(image_id, &report)
+            }
+            Err(err @ Error::AnalysisFailed(_)) => AnalysisSummary::from_error(image_id, &err),
+            Err(err) => return Err(err),
+        };
+
+        info!("reporting {image_id}: {}", summary.description());
+        target.post(&summary).await?;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image_id() -> ImageId {
+        ImageId::from(uuid::Uuid::nil())
+    }
+
+    #[test]
+    fn test_summary_from_report() -> Result<()> {
+        let report: Report = serde_json::from_str(
+            r#"{"checks": [{"issue": "hooked function"}, {"issue": "hidden process"}]}"#,
+        )?;
+        let summary = AnalysisSummary::from_report(image_id(), &report);
+        assert_eq!(summary.state, CiState::Failure);
+        assert_eq!(summary.findings, vec!["hooked function", "hidden process"]);
+        assert_eq!(summary.description(), "analysis completed: 2 findings");
+        assert!(summary.markdown().ends_with("* hidden process"));
+
+        let summary = AnalysisSummary::from_report(image_id(), &Report::default());
+        assert_eq!(summary.state, CiState::Success);
+        assert_eq!(summary.description(), "analysis completed: no findings");
+        Ok(())
+    }
+
+    #[test]
+    fn test_github_status() {
+        let target = GitHubTarget::new("contoso/images", "abc123", Secret::new("token"));
+        let summary =
+            AnalysisSummary::from_error(image_id(), &Error::AnalysisFailed("x".repeat(200).into()));
+        let status = target.status(&summary);
+        assert_eq!(status["state"], "error");
+        assert_eq!(status["context"], DEFAULT_CONTEXT);
+        assert_eq!(
+            status["description"].as_str().map(|x| x.chars().count()),
+            Some(GITHUB_DESCRIPTION_MAX)
+        );
+    }
+
+    #[test]
+    fn test_azure_devops_status() -> Result<()> {
+        let organization_url = Url::parse("https://dev.azure.com/contoso")
+            .map_err(|e| Error::Other("invalid url", e.to_string()))?;
+        let mut target = AzureDevOpsTarget::new(
+            organization_url,
+            "images",
+            "builder",
+            "abc123",
+            Secret::new("token"),
+        );
+        assert_eq!(
+            target.url("commits/abc123/statuses")?.as_str(),
+            "https://dev.azure.com/contoso/images/_apis/git/repositories/builder/commits/abc123/statuses?api-version=7.1"
+        );
+
+        target.pull_request = Some(7);
+        let status = target.status(&AnalysisSummary::pending(image_id()));
+        assert_eq!(status["state"], "pending");
+        assert_eq!(status["context"]["genre"], "freta");
+        assert_eq!(status["context"]["name"], "analysis");
+        Ok(())
+    }
+}
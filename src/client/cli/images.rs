@@ -0,0 +1,1268 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::{
+    argparse::{merge_tags, parse_days, parse_key_val, parse_tags_file},
+    cli::{
+        confirm, print_data, print_field, serialize_stream, to_cell, write_stdout, BatchArgs,
+        OutputFormat, OutputOptions, Shutdown, TimeFormat,
+    },
+    export::{ExportFormat, ImageExporter},
+    models::{
+        artifacts::REPORT_JSON,
+        cost::{CostFilter, CostGroup, CostRates},
+        formats::validate_image,
+    },
+    Client, Error, Image, ImageFormat, ImageId, ImageState, MonitorOptions, NoProgress, OwnerId,
+    PreflightReport, Result, UploadOptions,
+};
+use clap::Subcommand;
+use cli_table::{print_stdout, Cell, CellStruct, Style, Table};
+use futures::{
+    future::{ready, try_join_all},
+    Stream, StreamExt, TryStreamExt,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    io::{stderr, IsTerminal},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+use url::Url;
+
+/// The default fields for image list output used in `CSV` and `Table` format
+const IMAGE_LIST_FIELDS: &[&str] = &["image_id", "owner_id", "state", "format"];
+
+/// Image specific subcommands
+#[derive(Debug, Subcommand)]
+pub enum ImagesCommands {
+    /// list the blobs stored as part of an image.  only the image blob is listed unless the service grants access to its container
+    Blobs {
+        /// image id
+        image_id: ImageId,
+
+        #[arg(long, default_value_t=OutputFormat::Json)]
+        /// print in table mode
+        output: OutputFormat,
+    },
+    /// estimate the cost of storing and analyzing images, broken down by owner or tag
+    Cost {
+        #[arg(long, value_name = "DAYS", default_value = "30d", value_parser = parse_days)]
+        /// length of the reporting period, such as `30d`
+        since: u32,
+
+        #[arg(long)]
+        /// owner id
+        owner_id: Option<OwnerId>,
+
+        #[arg(long)]
+        /// include sample images
+        include_samples: bool,
+
+        #[arg(long, value_name = "KEY")]
+        /// break down costs by the value of this tag rather than by owner
+        group_by_tag: Option<String>,
+
+        #[arg(long, value_name = "COST", default_value_t = CostRates::default().storage_per_gib_month)]
+        /// cost of storing 1 GiB for 30 days
+        storage_rate: f64,
+
+        #[arg(long, value_name = "COST", default_value_t = CostRates::default().analysis_per_gib)]
+        /// cost of analyzing 1 GiB of image
+        analysis_rate: f64,
+
+        #[arg(long, default_value_t=OutputFormat::Json)]
+        /// print in table mode
+        output: OutputFormat,
+    },
+    /// export the metadata of images to a file for analytics.  images are written as they are listed
+    Export {
+        /// output path
+        output: PathBuf,
+
+        #[arg(long, default_value_t = ExportFormat::Csv)]
+        /// file format
+        format: ExportFormat,
+
+        #[arg(long)]
+        /// owner id
+        owner_id: Option<OwnerId>,
+
+        #[arg(long)]
+        /// state
+        state: Option<ImageState>,
+
+        #[arg(long)]
+        /// include sample images
+        include_samples: bool,
+
+        #[arg(long)]
+        /// include tombstone records of deleted images
+        include_deleted: bool,
+
+        #[arg(long, value_name = "KEY", action = clap::ArgAction::Append)]
+        /// export the value of this tag as a separate `tag.<KEY>` column.  specify multiple times to include multiple tags
+        tag_column: Option<Vec<String>>,
+    },
+    /// get information about an image
+    Get {
+        /// image id
+        image_id: ImageId,
+
+        #[arg(long, alias = "query", value_name = "PATH")]
+        /// extract a single field of the image, such as `state` or `tags.name`, and print it without quoting
+        field: Option<String>,
+    },
+    /// get a new SAS URL for an image, printing only the URL
+    Sas {
+        /// image id
+        image_id: ImageId,
+
+        #[arg(long, conflicts_with = "image")]
+        /// get the SAS URL for the artifacts of the image
+        artifacts: bool,
+
+        #[arg(long)]
+        /// get the SAS URL for the image itself.  this is the default
+        image: bool,
+    },
+    /// monitor the analysis of specific images.  when monitoring a single image from a terminal, the progress of the analysis is shown for versions of the service that report it
+    Monitor {
+        /// image ids
+        #[arg(required = true)]
+        image_ids: Vec<ImageId>,
+
+        #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+        /// how often to log that an image remains in the same state.  use 0 to only log state changes
+        heartbeat: u64,
+
+        #[arg(long, value_name = "SECONDS", default_value_t = 1)]
+        /// how often to check the state of the images
+        interval: u64,
+
+        #[arg(long, value_name = "SECONDS", default_value_t = 0)]
+        /// double the interval each time an image remains in the same state, up to this many seconds.  use 0 to always use --interval
+        max_interval: u64,
+
+        #[arg(long, value_name = "SECONDS", default_value_t = 0)]
+        /// fail if the images do not reach the awaited states within this many seconds.  use 0 to wait indefinitely
+        timeout: u64,
+
+        #[arg(long, value_name = "STATE", action = clap::ArgAction::Append)]
+        /// wait until the images reach one of these states rather than until the analysis completes.  specify multiple times to include multiple states
+        until: Option<Vec<ImageState>>,
+
+        #[arg(long)]
+        /// apply the policies in `~/.config/freta/policies.yaml` to each image once its analysis completes
+        apply_policies: bool,
+    },
+    /// delete specific images, or the images matching the filters.  for example, `freta images delete --state failed --older-than 30d` deletes the images that failed more than 30 days ago
+    Delete {
+        /// image ids
+        #[arg(
+            required_unless_present_any = ["state", "older_than", "tags"],
+            conflicts_with_all = ["state", "older_than", "tags"]
+        )]
+        image_ids: Vec<ImageId>,
+
+        #[arg(long)]
+        /// delete the images in this state
+        state: Option<ImageState>,
+
+        #[arg(long, value_name = "DAYS", value_parser = parse_days)]
+        /// delete the images that were last updated more than this many days ago, such as `30d`
+        older_than: Option<u32>,
+
+        #[clap(long = "tag", value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// delete the images with this tag.  specify multiple times to require multiple key/value pairs
+        tags: Option<Vec<(String, String)>>,
+
+        #[command(flatten)]
+        /// batch processing options
+        batch: BatchArgs,
+    },
+    /// reanalyze specific images
+    Reanalyze {
+        /// image ids
+        #[arg(required = true)]
+        image_ids: Vec<ImageId>,
+
+        #[arg(long, value_name = "VERSION")]
+        /// analyze the image using this version of the analysis engine, rather than the current version
+        engine_version: Option<String>,
+
+        #[command(flatten)]
+        /// batch processing options
+        batch: BatchArgs,
+    },
+    /// list available images
+    List {
+        #[arg(long)]
+        /// image id
+        image_id: Option<ImageId>,
+
+        #[arg(long)]
+        /// owner id
+        owner_id: Option<OwnerId>,
+
+        #[arg(long)]
+        /// state
+        state: Option<ImageState>,
+
+        #[arg(long)]
+        /// include sample images
+        include_samples: bool,
+
+        #[arg(long)]
+        /// include tombstone records of deleted images
+        include_deleted: bool,
+
+        #[clap(long = "tag", value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// only include images with this tag.  specify multiple times to require multiple key/value pairs
+        tags: Option<Vec<(String, String)>>,
+
+        #[arg(long, default_value_t=OutputFormat::Json)]
+        /// print in table mode
+        output: OutputFormat,
+
+        #[arg(long, action = clap::ArgAction::Append)]
+        /// fields to include when using csv and table output format.  specify multiple times to include multiple fields
+        fields: Option<Vec<String>>,
+
+        #[arg(long)]
+        /// keep listing images, showing only the images whose state, analysis stage, or last update changed.  state transitions are highlighted in table output
+        watch: bool,
+
+        #[arg(long, value_name = "SECONDS", default_value_t = 5, requires = "watch")]
+        /// how often to list images when using --watch
+        interval: u64,
+    },
+    /// create a new image record.  note: the image must be uploaded using other tools such as azcopy.
+    Create {
+        /// image format
+        format: ImageFormat,
+
+        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// specify multiple times to include multiple key/value pairs
+        tags: Option<Vec<(String, String)>>,
+
+        #[clap(long, value_name = "PATH", value_parser = parse_tags_file)]
+        /// read tags from a `.json` or `.env` file.  tags specified with `--tags` take precedence
+        tags_file: Option<BTreeMap<String, String>>,
+
+        #[arg(long)]
+        /// URL notified of the events for this image, without creating a webhook.  may include `{event_type}`
+        callback_url: Option<Url>,
+
+        #[arg(long, value_name = "VERSION")]
+        /// analyze the image using this version of the analysis engine, rather than the current version
+        engine_version: Option<String>,
+    },
+    /// signal that an image created with `create` has been uploaded using other tools
+    MarkUploaded {
+        /// image id
+        image_id: ImageId,
+    },
+    /// create an upload an image
+    Upload {
+        /// image path
+        path: PathBuf,
+
+        #[clap(long)]
+        /// image format.  by default, the format is detected from the contents of the image, falling back to the file extension
+        format: Option<ImageFormat>,
+
+        #[clap(long)]
+        /// monitor
+        monitor: bool,
+
+        #[clap(long)]
+        /// monitor until completed and then emit the analysis result
+        show_result: bool,
+
+        #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+        /// how often to log that an image remains in the same state.  use 0 to only log state changes
+        heartbeat: u64,
+
+        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// specify multiple times to include multiple key/value pairs
+        tags: Option<Vec<(String, String)>>,
+
+        #[clap(long, value_name = "PATH", value_parser = parse_tags_file)]
+        /// read tags from a `.json` or `.env` file.  tags specified with `--tags` take precedence
+        tags_file: Option<BTreeMap<String, String>>,
+
+        #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+        /// retry an in-flight block if it sends no data for this long.  use 0 to disable
+        stall_timeout: u64,
+
+        #[arg(long)]
+        /// adjust the block size based on the upload throughput.  by default, this is enabled for files larger than 10 GiB
+        adaptive_chunking: Option<bool>,
+
+        #[arg(long)]
+        /// skip checking that the image can be uploaded before starting the upload
+        skip_preflight: bool,
+
+        #[arg(long)]
+        /// skip checking the headers of the image for truncation or a mismatched format before starting the upload
+        skip_validation: bool,
+
+        #[arg(long)]
+        /// URL notified of the events for this image, without creating a webhook.  may include `{event_type}`
+        callback_url: Option<Url>,
+
+        #[arg(long)]
+        /// continue an interrupted upload of the same file, rather than creating a new image.  uploads can only be continued until the SAS URL used to upload the image expires
+        resume: bool,
+
+        #[arg(long)]
+        /// number of blocks uploaded concurrently.  defaults to the `upload_concurrency` config setting
+        concurrency: Option<usize>,
+
+        #[arg(long, value_name = "VERSION")]
+        /// analyze the image using this version of the analysis engine, rather than the current version.  with `--monitor`, fails if the image is analyzed using a different version
+        engine_version: Option<String>,
+
+        #[arg(long)]
+        /// compress lime images while uploading them, as written by avml with `--compress`.  compressed uploads cannot be resumed
+        compress: bool,
+    },
+    /// upload the images listed in a manifest, or the files in a directory.  a manifest is a JSON file such as `{"tags": {...}, "images": [{"path": "host1.lime", "format": "lime", "tags": {...}}]}`, where relative paths are relative to the manifest.  interrupted uploads are resumed when the batch is retried, until the SAS URL used to upload the image expires
+    UploadBatch {
+        /// manifest or directory of images
+        source: PathBuf,
+
+        #[arg(long, value_name = "PATH")]
+        /// write a JSON file mapping the path of each uploaded image to its image id.  results recorded in the file by earlier runs are retained
+        results: Option<PathBuf>,
+
+        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// tags applied to every image.  specify multiple times to include multiple key/value pairs.  tags in the manifest take precedence
+        tags: Option<Vec<(String, String)>>,
+
+        #[clap(long, value_name = "PATH", value_parser = parse_tags_file)]
+        /// read tags applied to every image from a `.json` or `.env` file.  tags specified with `--tags` take precedence
+        tags_file: Option<BTreeMap<String, String>>,
+
+        #[arg(long)]
+        /// skip checking the headers of the images for truncation or a mismatched format before uploading them
+        skip_validation: bool,
+
+        #[arg(long, value_name = "VERSION")]
+        /// analyze the images using this version of the analysis engine, rather than the current version
+        engine_version: Option<String>,
+
+        #[command(flatten)]
+        /// batch processing options.  `--concurrency` is the number of images uploaded concurrently
+        batch: BatchArgs,
+    },
+    /// check the headers of an image for truncation or a mismatched format, without connecting to the service
+    Validate {
+        /// image path
+        path: PathBuf,
+
+        #[clap(long)]
+        /// image format.  by default, the format is detected from the contents of the image, falling back to the file extension
+        format: Option<ImageFormat>,
+    },
+    /// check that an image can be uploaded, without uploading it
+    Preflight {
+        /// image path
+        path: PathBuf,
+
+        #[clap(long)]
+        /// image format.  by default, the format is detected from the contents of the image, falling back to the file extension
+        format: Option<ImageFormat>,
+    },
+    /// update the configuration for an image
+    Update {
+        /// image id
+        image_id: ImageId,
+
+        #[clap(long)]
+        /// images that are shared are readable to any authenticated user
+        shareable: Option<bool>,
+
+        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        /// specify multiple times to include multiple key/value pairs
+        tags: Option<Vec<(String, String)>>,
+
+        #[clap(long, value_name = "PATH", value_parser = parse_tags_file)]
+        /// read tags from a `.json` or `.env` file.  tags specified with `--tags` take precedence
+        tags_file: Option<BTreeMap<String, String>>,
+    },
+    /// Download an image to a local file.  NOTE: This is only available for successfully analyzed images.
+    ///
+    /// If the SHA-256 digest of the image was recorded when it was uploaded, the downloaded file is verified against it.
+    Download {
+        /// image id
+        image_id: ImageId,
+
+        /// output path
+        path: PathBuf,
+    },
+    /// Verify a local copy of an image matches the SHA-256 digest recorded when the image was uploaded
+    Verify {
+        /// image id
+        image_id: ImageId,
+
+        /// path of the local copy
+        path: PathBuf,
+    },
+}
+
+/// Run an image specific subcommand
+///
+/// Long-running subcommands such as `monitor` and `list --watch` stop once a
+/// shutdown is requested, and batch subcommands finish their in-flight
+/// images.  Shutdown signals are only handled by these subcommands, such that
+/// other subcommands, such as `download`, exit immediately when interrupted.
+///
+/// # Errors
+///
+/// Returns an error if the subcommand fails
+#[allow(clippy::print_stdout)]
+pub async fn run_images(subcommands: ImagesCommands, options: &OutputOptions) -> Result<()> {
+    let OutputOptions {
+        yes, time_format, ..
+    } = *options;
+    // validating images does not require connecting to the service
+    if let ImagesCommands::Validate { path, format } = subcommands {
+        let format = image_format(&path, format)?;
+        return print_data(validate_image(format, &path)?);
+    }
+
+    let client = options.client().await?;
+    match subcommands {
+        // handled before connecting to the service
+        ImagesCommands::Validate { .. } => Ok(()),
+        ImagesCommands::Blobs { image_id, output } => {
+            let stream = client.image_blobs_list(image_id);
+            serialize_stream(output, time_format, None, None, stream).await
+        }
+        ImagesCommands::Cost {
+            since,
+            owner_id,
+            include_samples,
+            group_by_tag,
+            storage_rate,
+            analysis_rate,
+            output,
+        } => {
+            let filter = CostFilter {
+                owner_id,
+                include_samples,
+                group: group_by_tag.map_or(CostGroup::Owner, CostGroup::Tag),
+                rates: CostRates {
+                    storage_per_gib_month: storage_rate,
+                    analysis_per_gib: analysis_rate,
+                },
+                ..CostFilter::new(OffsetDateTime::now_utc() - time::Duration::days(since.into()))
+            };
+            let entries = client.cost_report(&filter).await?;
+            let stream = Box::pin(futures::stream::iter(entries.into_iter().map(Ok)));
+            serialize_stream(output, time_format, None, None, stream).await
+        }
+        ImagesCommands::Export {
+            output,
+            format,
+            owner_id,
+            state,
+            include_samples,
+            include_deleted,
+            tag_column,
+        } => {
+            let file = std::fs::File::create(&output)
+                .map_err(|e| Error::Other("unable to create export file", e.to_string()))?;
+            let mut exporter = ImageExporter::new(
+                std::io::BufWriter::new(file),
+                format,
+                tag_column.unwrap_or_default(),
+            )?;
+            let mut stream = client.images_list(
+                None,
+                owner_id,
+                state,
+                include_samples,
+                include_deleted,
+                None,
+            );
+            while let Some(image) = stream.next().await {
+                exporter.write(&image?)?;
+            }
+            let count = exporter.finish()?;
+            info!("exported {count} images to {}", output.display());
+            Ok(())
+        }
+        ImagesCommands::Sas {
+            image_id,
+            artifacts,
+            image: _,
+        } => {
+            let (image_url, artifacts_url) = client.images_refresh_urls(image_id).await?;
+            let url = if artifacts { artifacts_url } else { image_url };
+            let url = url.ok_or_else(|| {
+                Error::Other("service did not provide a SAS URL", image_id.to_string())
+            })?;
+            println!("{url}");
+            Ok(())
+        }
+        ImagesCommands::Get { image_id, field } => {
+            let image = client.images_get(image_id).await?;
+            print_field(image, field.as_deref())
+        }
+        ImagesCommands::List {
+            image_id,
+            owner_id,
+            state,
+            include_samples,
+            include_deleted,
+            tags,
+            output,
+            fields,
+            watch,
+            interval,
+        } => {
+            let tags: Option<BTreeMap<String, String>> =
+                tags.map(|tags| tags.into_iter().collect());
+            let fields = fields.unwrap_or(
+                IMAGE_LIST_FIELDS
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            );
+            if watch {
+                let shutdown = Shutdown::listen()?;
+                let mut watcher = ImageWatcher::new(output, fields, time_format);
+                loop {
+                    let stream = client.images_list(
+                        image_id,
+                        owner_id,
+                        state.clone(),
+                        include_samples,
+                        include_deleted,
+                        tags.clone(),
+                    );
+                    watcher.update(stream).await?;
+                    tokio::select! {
+                        () = sleep(Duration::from_secs(interval)) => {}
+                        () = shutdown.wait() => {
+                            info!("stopped watching images");
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            let stream = client.images_list(
+                image_id,
+                owner_id,
+                state,
+                include_samples,
+                include_deleted,
+                tags,
+            );
+            serialize_stream(
+                output,
+                time_format,
+                Some(fields),
+                Some(("{\"images\":", "}")),
+                stream,
+            )
+            .await
+        }
+        ImagesCommands::Delete {
+            image_ids,
+            state,
+            older_than,
+            tags,
+            batch,
+        } => {
+            let images: Vec<Image> = if image_ids.is_empty() {
+                // images without a timestamp are never considered old enough
+                let cutoff = older_than
+                    .map(|days| OffsetDateTime::now_utc() - time::Duration::days(days.into()));
+                client
+                    .images_list(
+                        None,
+                        None,
+                        state,
+                        false,
+                        false,
+                        tags.map(|tags| tags.into_iter().collect()),
+                    )
+                    .try_filter(|image| {
+                        ready(cutoff.is_none_or(|cutoff| {
+                            image.last_updated.is_some_and(|updated| updated < cutoff)
+                        }))
+                    })
+                    .try_collect()
+                    .await?
+            } else {
+                // images deleted by a previous run can no longer be retrieved,
+                // so only the remaining images are confirmed
+                let image_ids = batch.pending(image_ids).await?;
+                try_join_all(image_ids.iter().map(|x| client.images_get(*x))).await?
+            };
+            if images.is_empty() {
+                info!("no images to delete");
+                return Ok(());
+            }
+            confirm("delete the images", &images, yes, client.config())?;
+
+            let shutdown = Shutdown::listen()?;
+            let image_ids = images.iter().map(|image| image.image_id).collect();
+            batch
+                .run(image_ids, &shutdown, |image_id| {
+                    client.images_delete(image_id)
+                })
+                .await?
+                .finish()
+        }
+        ImagesCommands::Reanalyze {
+            image_ids,
+            engine_version,
+            batch,
+        } => {
+            let image_ids = batch.pending(image_ids).await?;
+            let shutdown = Shutdown::listen()?;
+            batch
+                .run(image_ids, &shutdown, |image_id| {
+                    client.images_reanalyze_with_version(image_id, engine_version.clone())
+                })
+                .await?
+                .finish()
+        }
+        ImagesCommands::Create {
+            format,
+            tags,
+            tags_file,
+            callback_url,
+            engine_version,
+        } => client
+            .images_create_with_options(
+                format,
+                merge_tags(tags_file, tags).unwrap_or_default(),
+                &UploadOptions {
+                    callback_url,
+                    analysis_version: engine_version,
+                    ..UploadOptions::default()
+                },
+            )
+            .await
+            .map(print_data)?,
+        ImagesCommands::MarkUploaded { image_id } => client
+            .images_upload_complete(image_id)
+            .await
+            .map(print_data)?,
+        ImagesCommands::Update {
+            image_id,
+            tags,
+            tags_file,
+            shareable,
+        } => client
+            .images_update(image_id, merge_tags(tags_file, tags), shareable)
+            .await
+            .map(print_data)?,
+        ImagesCommands::Upload {
+            path,
+            format,
+            tags,
+            tags_file,
+            monitor,
+            show_result,
+            heartbeat,
+            stall_timeout,
+            adaptive_chunking,
+            skip_preflight,
+            skip_validation,
+            callback_url,
+            resume,
+            concurrency,
+            engine_version,
+            compress,
+        } => {
+            let format = image_format(&path, format)?;
+            if !skip_validation {
+                let validation = validate_image(format, &path)?;
+                info!("validated {format} image of {} bytes", validation.size);
+            }
+            if !skip_preflight {
+                preflight(&client, &path, format).await?.into_result()?;
+            }
+
+            let upload_options = UploadOptions {
+                stall_timeout: (stall_timeout > 0).then(|| Duration::from_secs(stall_timeout)),
+                adaptive_chunking,
+                callback_url,
+                resume,
+                concurrency,
+                analysis_version: engine_version.clone(),
+                progress: None,
+                compress,
+            };
+            let (image, stats) = client
+                .images_upload_with_options(
+                    format,
+                    merge_tags(tags_file, tags).unwrap_or_default(),
+                    &path,
+                    &upload_options,
+                )
+                .await?;
+            info!("upload complete: {stats}");
+            if monitor || show_result {
+                let options = MonitorOptions {
+                    image_size: std::fs::metadata(&path).ok().map(|m| m.len()),
+                    ..monitor_options(heartbeat)
+                };
+                let analyzed = client
+                    .images_monitor_with_options(image.image_id, &options)
+                    .await?;
+                if let Some(engine_version) = &engine_version {
+                    Client::verify_analysis_version(&analyzed, engine_version)?;
+                }
+            }
+            if show_result {
+                let result = client.artifacts_get(image.image_id, REPORT_JSON).await?;
+                write_stdout(&result).await?;
+            }
+            Ok(())
+        }
+        ImagesCommands::UploadBatch {
+            source,
+            results,
+            tags,
+            tags_file,
+            skip_validation,
+            engine_version,
+            batch,
+        } => {
+            let common_tags = merge_tags(tags_file, tags).unwrap_or_default();
+            let images = batch
+                .pending(load_upload_batch(&source, &common_tags)?)
+                .await?;
+            // retried uploads continue where the failed attempt stopped,
+            // rather than creating another image, as long as the SAS URL
+            // used to upload the image has not expired
+            let upload_options = UploadOptions {
+                resume: true,
+                analysis_version: engine_version,
+                progress: Some(Arc::new(NoProgress)),
+                ..UploadOptions::default()
+            };
+            let client = &client;
+            let upload_options = &upload_options;
+            let shutdown = Shutdown::listen()?;
+            let outcome = batch
+                .run(images, &shutdown, |image| async move {
+                    let format = image_format(&image.path, image.format)?;
+                    if !skip_validation {
+                        validate_image(format, &image.path)?;
+                    }
+                    let (uploaded, stats) = client
+                        .images_upload_with_options(format, image.tags, &image.path, upload_options)
+                        .await?;
+                    info!(
+                        "uploaded {} as {}: {stats}",
+                        image.path.display(),
+                        uploaded.image_id
+                    );
+                    Ok(UploadBatchResult {
+                        path: image.path,
+                        image_id: uploaded.image_id,
+                    })
+                })
+                .await?;
+            if let Some(results) = &results {
+                write_upload_results(results, &outcome.completed)?;
+            }
+            outcome.finish()
+        }
+        ImagesCommands::Preflight { path, format } => {
+            let format = image_format(&path, format)?;
+            let report = preflight(&client, &path, format).await?;
+            print_data(&report)?;
+            report.into_result().map(|_| ())
+        }
+        ImagesCommands::Download { image_id, path } => {
+            let stats = client.images_download(image_id, path).await?;
+            info!("download complete: {stats}");
+            Ok(())
+        }
+        ImagesCommands::Verify { image_id, path } => {
+            client.images_verify(image_id, path).await.map(print_data)?
+        }
+        ImagesCommands::Monitor {
+            image_ids,
+            heartbeat,
+            interval,
+            max_interval,
+            timeout,
+            until,
+            apply_policies,
+        } => {
+            if interval == 0 {
+                return Err(Error::Other(
+                    "invalid interval",
+                    "must be at least 1".into(),
+                ));
+            }
+            // progress bars of concurrently monitored images would overwrite
+            // each other, so they are only shown for a single image
+            let options = MonitorOptions {
+                interval: Duration::from_secs(interval),
+                max_interval: (max_interval > 0).then(|| Duration::from_secs(max_interval)),
+                timeout: (timeout > 0).then(|| Duration::from_secs(timeout)),
+                show_progress: image_ids.len() == 1 && stderr().is_terminal(),
+                ..monitor_options(heartbeat)
+            };
+            let until = until.unwrap_or_else(|| vec![ImageState::Completed]);
+            // load the policies prior to monitoring such that invalid
+            // policies are reported immediately
+            let policies = if apply_policies {
+                Some(Client::policies_load(None).await?)
+            } else {
+                None
+            };
+            // in the previous methods processing a list of `ImageId`, the
+            // implementing function was called sequentially.  For `monitor`,
+            // however, we want to check the status of each of the provided
+            // images concurrently as these can be a long running operation.
+            // This operation should fail as soon as any of the images fail.
+            let shutdown = Shutdown::listen()?;
+            let monitor = try_join_all(image_ids.into_iter().map(|image_id| async {
+                let image = client
+                    .images_wait_for(image_id, |image| until.contains(&image.state), &options)
+                    .await?;
+                if let Some(policies) = &policies {
+                    client.policies_apply(policies, image.image_id).await?;
+                }
+                Ok::<_, Error>(image)
+            }));
+            tokio::select! {
+                result = monitor => {
+                    result?;
+                }
+                () = shutdown.wait() => {
+                    info!("stopped monitoring images");
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Determine the format of an image, unless the format is specified
+///
+/// The format is detected from the contents of the image, such that misnamed
+/// images are uploaded as the correct format.  Formats that cannot be detected
+/// use the extension of the path.
+fn image_format(path: &Path, format: Option<ImageFormat>) -> Result<ImageFormat> {
+    if let Some(format) = format {
+        return Ok(format);
+    }
+    let ext_str = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    let ignore_case = true;
+    let from_ext = ext_str
+        .as_deref()
+        .and_then(|ext| ImageFormat::from_str(ext, ignore_case).ok());
+
+    if let Some(detected) = ImageFormat::detect(path)? {
+        if from_ext.is_some_and(|ext_format| ext_format != detected) {
+            warn!(
+                "{} appears to be {detected}, despite its extension",
+                path.display()
+            );
+        }
+        return Ok(detected);
+    }
+
+    match (from_ext, ext_str) {
+        (Some(ext_format), _) => Ok(ext_format),
+        (None, Some(ext)) => Err(Error::Extension(ext.into())),
+        (None, None) => Err(Error::Extension("missing file extension".into())),
+    }
+}
+
+/// Check that an image can be uploaded, logging the outcome of each check
+async fn preflight(client: &Client, path: &Path, format: ImageFormat) -> Result<PreflightReport> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| Error::Io {
+            message: format!("reading image size: {path:?}").into(),
+            source: e,
+        })?
+        .len();
+    let report = client.preflight_upload(format, size).await?;
+    for check in &report.checks {
+        if check.passed {
+            info!("preflight {}: {}", check.name, check.message);
+        } else {
+            error!("preflight {}: {}", check.name, check.message);
+        }
+    }
+    Ok(report)
+}
+
+/// An image listed in the manifest used by `images upload-batch`
+#[derive(Debug, Clone, Deserialize)]
+struct UploadBatchImage {
+    /// path of the image
+    path: PathBuf,
+    /// format of the image.  by default, the format is detected
+    #[serde(default)]
+    format: Option<ImageFormat>,
+    /// tags of the image
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+impl Display for UploadBatchImage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())
+    }
+}
+
+/// The manifest used by `images upload-batch`
+#[derive(Debug, Deserialize)]
+struct UploadBatchManifest {
+    /// tags applied to every image in the manifest
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    /// images to upload
+    images: Vec<UploadBatchImage>,
+}
+
+/// The image id of an image uploaded by `images upload-batch`
+#[derive(Debug, Serialize, Deserialize)]
+struct UploadBatchResult {
+    /// path of the image
+    path: PathBuf,
+    /// image id of the uploaded image
+    image_id: ImageId,
+}
+
+/// Load the images to upload from a manifest or a directory
+///
+/// The tags of each image are merged with the tags of the manifest and
+/// `common_tags`, with the tags of the image taking precedence.  Files in a
+/// directory are uploaded in order of their names, excluding hidden files
+/// and subdirectories.
+fn load_upload_batch(
+    source: &Path,
+    common_tags: &BTreeMap<String, String>,
+) -> Result<Vec<UploadBatchImage>> {
+    let read_err = |e| Error::Io {
+        message: format!("reading upload batch: {}", source.display()).into(),
+        source: e,
+    };
+
+    let (tags, images) = if source.is_dir() {
+        let mut paths = vec![];
+        for entry in std::fs::read_dir(source).map_err(read_err)? {
+            let entry = entry.map_err(read_err)?;
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if !hidden && entry.file_type().map_err(read_err)?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+        let images = paths
+            .into_iter()
+            .map(|path| UploadBatchImage {
+                path,
+                format: None,
+                tags: BTreeMap::new(),
+            })
+            .collect();
+        (BTreeMap::new(), images)
+    } else {
+        let contents = std::fs::read(source).map_err(read_err)?;
+        let manifest: UploadBatchManifest = serde_json::from_slice(&contents)?;
+        let base = source.parent().unwrap_or_else(|| Path::new(""));
+        let images = manifest
+            .images
+            .into_iter()
+            .map(|image| UploadBatchImage {
+                path: base.join(&image.path),
+                ..image
+            })
+            .collect();
+        (manifest.tags, images)
+    };
+
+    Ok(images
+        .into_iter()
+        .map(|image: UploadBatchImage| {
+            let mut merged = common_tags.clone();
+            merged.extend(tags.clone());
+            merged.extend(image.tags);
+            UploadBatchImage {
+                tags: merged,
+                ..image
+            }
+        })
+        .collect())
+}
+
+/// Write the image ids of the uploaded images to `path`, retaining the
+/// results recorded by earlier runs
+fn write_upload_results(path: &Path, completed: &[Value]) -> Result<()> {
+    let mut results = BTreeMap::new();
+    if path.exists() {
+        let contents = std::fs::read(path).map_err(|e| Error::Io {
+            message: format!("reading upload results: {}", path.display()).into(),
+            source: e,
+        })?;
+        let earlier: Vec<UploadBatchResult> = serde_json::from_slice(&contents)?;
+        for result in earlier {
+            results.insert(result.path.clone(), result);
+        }
+    }
+    for value in completed {
+        let result: UploadBatchResult = serde_json::from_value(value.clone())?;
+        results.insert(result.path.clone(), result);
+    }
+
+    let contents = serde_json::to_vec_pretty(&results.into_values().collect::<Vec<_>>())?;
+    std::fs::write(path, contents).map_err(|e| Error::Io {
+        message: format!("writing upload results: {}", path.display()).into(),
+        source: e,
+    })
+}
+
+/// build the `MonitorOptions` for a heartbeat interval in seconds, where 0
+/// disables the heartbeat
+fn monitor_options(heartbeat: u64) -> MonitorOptions {
+    MonitorOptions {
+        heartbeat: (heartbeat > 0).then(|| Duration::from_secs(heartbeat)),
+        ..MonitorOptions::default()
+    }
+}
+
+/// Convert a JSON value into a string for display
+fn value_str(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// Fields compared by `images list --watch` to determine if an image changed
+///
+/// Other fields, such as the SAS URLs, change with every listing.
+const WATCHED_FIELDS: &[&str] = &["/state", "/progress/stage", "/last_updated"];
+
+/// Has the image changed between listings, based on `WATCHED_FIELDS`
+fn has_changed(before: &Value, after: &Value) -> bool {
+    WATCHED_FIELDS
+        .iter()
+        .any(|field| before.pointer(field) != after.pointer(field))
+}
+
+/// Tracks the images shown by `images list --watch` across listings
+struct ImageWatcher {
+    /// format the images are displayed in
+    output: OutputFormat,
+    /// fields of the images to display
+    fields: Vec<String>,
+    /// rendering of timestamps
+    time_format: TimeFormat,
+    /// images from the previous listing, keyed by image id
+    previous: Option<BTreeMap<String, Value>>,
+}
+
+impl ImageWatcher {
+    /// Create an `ImageWatcher` that displays the specified fields
+    const fn new(output: OutputFormat, fields: Vec<String>, time_format: TimeFormat) -> Self {
+        Self {
+            output,
+            fields,
+            time_format,
+            previous: None,
+        }
+    }
+
+    /// Read a listing of images, displaying the images that were added,
+    /// changed, or removed since the previous listing
+    ///
+    /// All images are displayed for the first listing.  Removed images are
+    /// only displayed in `table` output, and logged otherwise.
+    async fn update(
+        &mut self,
+        mut stream: Pin<Box<impl Stream<Item = Result<Image>>>>,
+    ) -> Result<()> {
+        let mut current = BTreeMap::new();
+        while let Some(image) = stream.next().await {
+            let image = image?;
+            current.insert(image.image_id.to_string(), serde_json::to_value(image)?);
+        }
+
+        let mut changed = vec![];
+        for (image_id, image) in &current {
+            let before = self
+                .previous
+                .as_ref()
+                .and_then(|previous| previous.get(image_id));
+            if before.is_some_and(|before| !has_changed(before, image)) {
+                continue;
+            }
+            changed.push((before, image));
+        }
+        let removed = self
+            .previous
+            .iter()
+            .flatten()
+            .filter(|(image_id, _)| !current.contains_key(*image_id))
+            .collect::<Vec<_>>();
+
+        if self.output == OutputFormat::Table {
+            let mut rows = vec![];
+            for (before, image) in changed {
+                rows.push(self.row(before, Some(image))?);
+            }
+            for (_, image) in removed {
+                rows.push(self.row(Some(image), None)?);
+            }
+            if !rows.is_empty() {
+                let mut table = rows.table();
+                if self.previous.is_none() {
+                    let title = self.fields.iter().map(|field| field.cell().bold(true));
+                    table = table.title(title).bold(true);
+                }
+                print_stdout(table).map_err(|e| Error::Io {
+                    message: "writing result table".into(),
+                    source: e,
+                })?;
+            }
+        } else {
+            for (image_id, _) in removed {
+                info!("image removed: {image_id}");
+            }
+            if !changed.is_empty() {
+                let images = changed
+                    .into_iter()
+                    .map(|(_, image)| Ok(image.clone()))
+                    .collect::<Vec<_>>();
+                serialize_stream(
+                    self.output,
+                    self.time_format,
+                    Some(self.fields.clone()),
+                    Some(("{\"images\":", "}")),
+                    Box::pin(futures::stream::iter(images)),
+                )
+                .await?;
+            }
+        }
+
+        self.previous = Some(current);
+        Ok(())
+    }
+
+    /// Build the table row for an image, highlighting state transitions
+    ///
+    /// `before` is the image from the previous listing, and `after` is the
+    /// image from the current listing.
+    fn row(&self, before: Option<&Value>, after: Option<&Value>) -> Result<Vec<CellStruct>> {
+        let mut row = vec![];
+        for field in &self.fields {
+            let old = before.and_then(|image| image.get(field));
+            let new = after.and_then(|image| image.get(field));
+            let cell = match (field.as_str(), old, new) {
+                ("state", Some(old), Some(new)) if old != new => {
+                    format!("{} -> {}", value_str(old), value_str(new))
+                        .cell()
+                        .bold(true)
+                }
+                ("state", Some(old), None) => {
+                    format!("{} -> removed", value_str(old)).cell().bold(true)
+                }
+                (_, _, Some(value)) | (_, Some(value), None) => {
+                    match self.time_format.render(value) {
+                        Some(rendered) => rendered.cell(),
+                        None => to_cell(value)?,
+                    }
+                }
+                (_, None, None) => "".cell(),
+            };
+            row.push(cell);
+        }
+        Ok(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_changed() {
+        let before = serde_json::json!({
+            "state": "Running",
+            "progress": {"stage": "symbols"},
+            "image_url": "https://x.blob.core.windows.net/images/a?sig=1",
+        });
+        let resigned = serde_json::json!({
+            "state": "Running",
+            "progress": {"stage": "symbols"},
+            "image_url": "https://x.blob.core.windows.net/images/a?sig=2",
+        });
+        assert!(!has_changed(&before, &resigned));
+
+        let next_stage = serde_json::json!({"state": "Running", "progress": {"stage": "report"}});
+        assert!(has_changed(&before, &next_stage));
+
+        let completed = serde_json::json!({"state": "Completed"});
+        assert!(has_changed(&before, &completed));
+    }
+
+    #[test]
+    fn test_load_upload_batch() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("freta-upload-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let manifest = dir.join("manifest.json");
+        std::fs::write(
+            &manifest,
+            r#"{
+                "tags": {"case": "1234", "host": "unknown"},
+                "images": [
+                    {"path": "host1.lime", "format": "lime", "tags": {"host": "host1"}},
+                    {"path": "/images/host2.vmrs"}
+                ]
+            }"#,
+        )?;
+
+        let common = BTreeMap::from([
+            ("case".to_string(), "0".to_string()),
+            ("owner".to_string(), "ir".to_string()),
+        ]);
+        let images = load_upload_batch(&manifest, &common)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        let [first, second] = images.as_slice() else {
+            return Err("expected two images".into());
+        };
+        assert_eq!(first.path, dir.join("host1.lime"));
+        assert_eq!(first.format, Some(ImageFormat::Lime));
+        assert_eq!(
+            first.tags,
+            BTreeMap::from([
+                ("case".to_string(), "1234".to_string()),
+                ("host".to_string(), "host1".to_string()),
+                ("owner".to_string(), "ir".to_string()),
+            ])
+        );
+        assert_eq!(second.path, PathBuf::from("/images/host2.vmrs"));
+        assert_eq!(second.format, None);
+        assert_eq!(second.tags.get("host").map(String::as_str), Some("unknown"));
+        Ok(())
+    }
+}
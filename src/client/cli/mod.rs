@@ -0,0 +1,896 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Building blocks for command line interfaces to the service
+//!
+//! The subcommands of the `freta` binary are available as `clap`
+//! subcommands, such that other command line tools can mount them under
+//! their own commands without reimplementing them.
+//!
+//! ```rust,no_run
+//! use clap::{Parser, Subcommand};
+//! use freta::{
+//!     cli::{run_images, run_webhooks, ImagesCommands, OutputOptions, WebhooksCommands},
+//!     Result,
+//! };
+//!
+//! #[derive(Parser)]
+//! struct Args {
+//!     #[command(subcommand)]
+//!     command: Commands,
+//! }
+//!
+//! #[derive(Subcommand)]
+//! enum Commands {
+//!     /// memory forensics
+//!     Images {
+//!         #[command(subcommand)]
+//!         subcommands: ImagesCommands,
+//!     },
+//!     /// memory forensics notifications
+//!     Webhooks {
+//!         #[command(subcommand)]
+//!         subcommands: WebhooksCommands,
+//!     },
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let options = OutputOptions::default();
+//! match Args::parse().command {
+//!     Commands::Images { subcommands } => run_images(subcommands, &options).await,
+//!     Commands::Webhooks { subcommands } => run_webhooks(subcommands, &options).await,
+//! }
+//! # }
+//! ```
+//!
+//! Results are written to stdout, while progress is logged using `tracing`.
+
+/// Image specific subcommands
+mod images;
+/// Webhook specific subcommands
+mod webhooks;
+
+pub use self::{
+    images::{run_images, ImagesCommands},
+    webhooks::{run_webhooks, WebhooksCommands},
+};
+
+use crate::{Client, Config, Error, Result};
+use clap::{Args, ValueEnum};
+use cli_table::{print_stdout, Cell, CellStruct, Style, Table};
+use futures::{Stream, StreamExt};
+use serde::{
+    ser::{SerializeSeq, Serializer},
+    Deserialize, Serialize,
+};
+use serde_json::{ser::PrettyFormatter, Value};
+use std::{
+    collections::BTreeSet,
+    fmt::{Display, Formatter},
+    future::Future,
+    io::{stderr, stdin, stdout, IsTerminal, Write},
+    path::PathBuf,
+    pin::Pin,
+    time::Duration,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::{
+    io::{self, AsyncWriteExt},
+    signal::ctrl_c,
+    sync::watch,
+    time::sleep,
+};
+use tracing::{error, info, warn};
+
+/// Exit code used when a second shutdown signal is received
+pub const SHUTDOWN_EXIT_CODE: i32 = 130;
+
+/// Options shared by the subcommands, typically set using global flags
+#[derive(Debug, Clone, Default)]
+pub struct OutputOptions {
+    /// skip confirmation prompts for destructive commands, unless the
+    /// configuration requires confirmation for all destructive actions
+    pub yes: bool,
+
+    /// rendering of timestamps in `table` and `csv` output
+    pub time_format: TimeFormat,
+
+    /// append requests sent to the service and their responses to this file,
+    /// overriding `Config::trace_http_path`
+    pub trace_http_path: Option<PathBuf>,
+}
+
+impl OutputOptions {
+    /// Create a client using the saved configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration cannot be loaded or the client
+    /// cannot authenticate
+    pub async fn client(&self) -> Result<Client> {
+        let mut config = Config::load().await?;
+        if let Some(path) = &self.trace_http_path {
+            config.trace_http_path = Some(path.clone());
+        }
+        Client::with_config(config).await
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+/// Output formats for `list` commands
+pub enum OutputFormat {
+    /// Output in JSON format
+    Json,
+    /// Output in table format
+    Table,
+    /// Output in CSV format
+    Csv,
+    /// Output in newline-delimited JSON format, with one record per line
+    Ndjson,
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Table => write!(f, "table"),
+            OutputFormat::Csv => write!(f, "csv"),
+            OutputFormat::Ndjson => write!(f, "ndjson"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+/// Rendering of timestamps in `table` and `csv` output
+pub enum TimeFormat {
+    /// RFC 3339 timestamps in UTC
+    #[default]
+    Rfc3339,
+    /// Time relative to now, such as `3h ago`
+    Relative,
+}
+
+impl TimeFormat {
+    /// Select the rendering of timestamps from the `--relative-time` flag
+    #[must_use]
+    pub const fn new(relative: bool) -> Self {
+        if relative {
+            Self::Relative
+        } else {
+            Self::Rfc3339
+        }
+    }
+
+    /// Render a value if it is an RFC 3339 timestamp
+    ///
+    /// Timestamps from the service may use any offset, so they are converted
+    /// to UTC to provide consistent output.
+    #[must_use]
+    pub fn render(self, value: &Value) -> Option<String> {
+        let timestamp = OffsetDateTime::parse(value.as_str()?, &Rfc3339).ok()?;
+        match self {
+            Self::Rfc3339 => timestamp.to_offset(UtcOffset::UTC).format(&Rfc3339).ok(),
+            Self::Relative => Some(relative_time(timestamp, OffsetDateTime::now_utc())),
+        }
+    }
+
+    /// Render the timestamps in the top-level fields of a record, leaving
+    /// other values unchanged
+    pub fn normalize(self, value: &mut Value) {
+        let Some(obj) = value.as_object_mut() else {
+            return;
+        };
+        for value in obj.values_mut() {
+            if let Some(rendered) = self.render(value) {
+                *value = Value::String(rendered);
+            }
+        }
+    }
+}
+
+/// Describe a timestamp relative to `now` using its largest whole unit, such
+/// as `3h ago` or `in 5m`
+fn relative_time(timestamp: OffsetDateTime, now: OffsetDateTime) -> String {
+    let seconds = (now - timestamp).whole_seconds();
+    let elapsed = seconds.unsigned_abs();
+    let amount = match elapsed {
+        0..=59 => format!("{elapsed}s"),
+        60..=3599 => format!("{}m", elapsed / 60),
+        3600..=86_399 => format!("{}h", elapsed / 3600),
+        _ => format!("{}d", elapsed / 86_400),
+    };
+    if seconds < 0 {
+        format!("in {amount}")
+    } else {
+        format!("{amount} ago")
+    }
+}
+
+/// Print a `Serialize`-able object as JSON to stdout
+///
+/// # Errors
+///
+/// Returns an error if the object cannot be serialized or written
+pub fn print_data<D>(data: D) -> Result<()>
+where
+    D: serde::Serialize,
+{
+    serde_json::to_writer_pretty(stdout(), &data)?;
+    Ok(())
+}
+
+/// Confirm a destructive action with the user, showing a summary of the
+/// affected records
+///
+/// Confirmation is skipped if `yes` is set, unless `config` requires
+/// confirmation for all destructive actions.  If confirmation is required and
+/// stdin is not a terminal, the prompt defaults to not confirming the action.
+///
+/// # Errors
+///
+/// Returns `Error::NotConfirmed` if the action is not confirmed, or an error
+/// if the prompt cannot be displayed
+pub fn confirm<D>(action: &'static str, records: D, yes: bool, config: &Config) -> Result<()>
+where
+    D: Serialize,
+{
+    if yes && !config.always_confirm_destructive {
+        return Ok(());
+    }
+
+    if !stdin().is_terminal() {
+        warn!("unable to prompt to {action}.  use --yes to skip confirmation");
+        return Err(Error::NotConfirmed(action.into()));
+    }
+
+    let mut stderr = stderr();
+    serde_json::to_writer_pretty(&mut stderr, &records)?;
+    write!(stderr, "\n{action}? [y/N] ")
+        .map_err(|e| Error::Other("writing prompt", e.to_string()))?;
+    stderr
+        .flush()
+        .map_err(|e| Error::Other("writing prompt", e.to_string()))?;
+
+    let mut response = String::new();
+    stdin()
+        .read_line(&mut response)
+        .map_err(|e| Error::Other("reading confirmation", e.to_string()))?;
+
+    if matches!(response.trim().to_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        Err(Error::NotConfirmed(action.into()))
+    }
+}
+
+/// Extract a field from a `serde_json::Value` using a path such as
+/// `tags.name` or `$.event_types[0]`
+fn extract_field<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut current = value;
+    for part in path.split(['.', '[']).filter(|part| !part.is_empty()) {
+        current = if let Some(index) = part.strip_suffix(']') {
+            current.get(index.parse::<usize>().ok()?)?
+        } else {
+            current.get(part)?
+        };
+    }
+    Some(current)
+}
+
+/// Print a `Serialize`-able object as JSON to stdout, or if `field` is
+/// provided, print only the specified field
+///
+/// Strings are printed without quoting, which enables using the output
+/// directly in shell scripts.
+///
+/// # Errors
+///
+/// Returns an error if the object cannot be serialized or written, or if the
+/// field does not exist
+#[allow(clippy::print_stdout)]
+pub fn print_field<D>(data: D, field: Option<&str>) -> Result<()>
+where
+    D: serde::Serialize,
+{
+    let Some(field) = field else {
+        return print_data(data);
+    };
+
+    let value = serde_json::to_value(data)?;
+    let extracted = extract_field(&value, field)
+        .ok_or_else(|| Error::Other("field not found", field.to_string()))?;
+    match extracted {
+        Value::String(s) => println!("{s}"),
+        other => println!("{}", serde_json::to_string(other)?),
+    }
+    Ok(())
+}
+
+/// Convert a `serde_json::Value` into a `CellStruct`
+///
+/// This handles converting records into a `CellStruct` for use in the table
+/// creation.
+pub(crate) fn to_cell(value: &Value) -> Result<CellStruct> {
+    let as_cell = match value {
+        Value::String(s) => s.cell(),
+        Value::Number(n) => n.to_string().cell(),
+        Value::Bool(b) => b.to_string().cell(),
+        Value::Null => "null".cell(),
+        Value::Array(_) | Value::Object(_) => serde_json::to_string(value)?.cell(),
+    };
+    Ok(as_cell)
+}
+
+/// Coordinates the graceful shutdown of long-running commands
+///
+/// The first SIGINT or SIGTERM requests a shutdown, such that commands can
+/// finish their in-flight work, flush their journals, and exit with a
+/// summary.  A second signal exits immediately.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    /// set to true once a shutdown is requested
+    requested: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Start listening for shutdown signals
+    ///
+    /// This must be called from within a tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signal handlers cannot be installed
+    pub fn listen() -> Result<Self> {
+        let (sender, requested) = watch::channel(false);
+        #[cfg(unix)]
+        let mut terminate = signal(SignalKind::terminate()).map_err(|e| Error::Io {
+            message: "listening for SIGTERM".into(),
+            source: e,
+        })?;
+
+        tokio::spawn(async move {
+            for first in [true, false] {
+                #[cfg(unix)]
+                let result = next_signal(&mut terminate).await;
+                #[cfg(not(unix))]
+                let result = ctrl_c().await;
+
+                if result.is_err() {
+                    return;
+                }
+                if first {
+                    warn!(
+                        "shutting down once in-flight work completes.  repeat to exit immediately"
+                    );
+                    sender.send_replace(true);
+                } else {
+                    warn!("exiting immediately");
+                    std::process::exit(SHUTDOWN_EXIT_CODE);
+                }
+            }
+        });
+
+        Ok(Self { requested })
+    }
+
+    /// Returns true if a shutdown has been requested
+    #[must_use]
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Wait until a shutdown is requested
+    pub async fn wait(&self) {
+        let mut requested = self.requested.clone();
+        if requested.wait_for(|requested| *requested).await.is_err() {
+            // the listener stopped, so a shutdown will never be requested
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Wait for the next SIGINT or SIGTERM
+#[cfg(unix)]
+async fn next_signal(terminate: &mut Signal) -> std::io::Result<()> {
+    tokio::select! {
+        result = ctrl_c() => result,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+/// Options for commands that process many items
+///
+/// Items are processed by a bounded pool of concurrent workers, and items
+/// failing with transient errors are retried with exponential backoff.  Completed items can be
+/// recorded to a JSONL journal, such that an interrupted or partially failed
+/// command can be resumed without repeating the completed items.
+#[derive(Debug, Clone, Args)]
+pub struct BatchArgs {
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    /// number of items to process concurrently
+    concurrency: u16,
+
+    #[arg(long, default_value_t = 3)]
+    /// number of times to retry an item that fails with a transient error, such as a connection failure or throttling
+    retries: u32,
+
+    #[arg(long, value_name = "PATH")]
+    /// record each completed item to this JSONL journal
+    journal: Option<PathBuf>,
+
+    #[arg(long, value_name = "PATH")]
+    /// skip the items recorded as completed in this journal.  unless --journal is specified, newly completed items are recorded to this journal
+    resume_from: Option<PathBuf>,
+}
+
+/// A completed item recorded in a batch journal
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    /// the item that was processed
+    item: String,
+    /// the result of processing the item
+    result: Value,
+}
+
+/// The outcome of running a batch
+#[derive(Debug, Default)]
+pub struct BatchOutcome {
+    /// results of the items that completed during this run
+    completed: Vec<Value>,
+    /// items that failed after exhausting their retries
+    failed: Vec<String>,
+    /// items that were not started due to a shutdown
+    skipped: Vec<String>,
+}
+
+impl BatchOutcome {
+    /// Print the results of the completed items, then fail if any of the
+    /// items failed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the items failed or were not started
+    pub fn finish(self) -> Result<()> {
+        print_data(self.completed)?;
+        if !self.failed.is_empty() {
+            return Err(Error::Other(
+                "batch failed",
+                format!(
+                    "{} items failed: {}",
+                    self.failed.len(),
+                    self.failed.join(", ")
+                ),
+            ));
+        }
+        if !self.skipped.is_empty() {
+            return Err(Error::Other(
+                "batch interrupted",
+                format!(
+                    "{} items not started: {}",
+                    self.skipped.len(),
+                    self.skipped.join(", ")
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl BatchArgs {
+    /// Filter out the items recorded as completed in the journal specified by
+    /// `--resume-from`
+    ///
+    /// A journal that does not exist yet is treated as empty, such that the
+    /// same command line can be used for the first run and for resuming it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal cannot be read
+    pub async fn pending<T>(&self, items: Vec<T>) -> Result<Vec<T>>
+    where
+        T: Display,
+    {
+        let Some(path) = &self.resume_from else {
+            return Ok(items);
+        };
+        if !path.exists() {
+            return Ok(items);
+        }
+
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| Error::Io {
+                message: format!("reading journal: {}", path.display()).into(),
+                source: e,
+            })?;
+
+        let mut completed = BTreeSet::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            // the final entry may be truncated if the previous run was
+            // interrupted while writing it
+            match serde_json::from_str::<JournalEntry>(line) {
+                Ok(entry) => {
+                    completed.insert(entry.item);
+                }
+                Err(err) => warn!("ignoring invalid journal entry: {err}"),
+            }
+        }
+
+        Ok(items
+            .into_iter()
+            .filter(|item| {
+                let done = completed.contains(&item.to_string());
+                if done {
+                    info!("skipping {item}: already completed");
+                }
+                !done
+            })
+            .collect())
+    }
+
+    /// Process the items using `op`, recording each completed item to the
+    /// journal
+    ///
+    /// Results are returned in the same order as the items, regardless of the
+    /// order in which the items complete.  Once a shutdown is requested, no
+    /// new items are started, while the in-flight items are completed and
+    /// recorded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal cannot be written.  Failures of
+    /// individual items are reported by `BatchOutcome::finish`.
+    pub async fn run<T, R, F, Fut>(
+        &self,
+        items: Vec<T>,
+        shutdown: &Shutdown,
+        op: F,
+    ) -> Result<BatchOutcome>
+    where
+        T: Display + Clone,
+        R: Serialize,
+        F: Fn(T) -> Fut,
+        Fut: Future<Output = Result<R>>,
+    {
+        let mut journal = match self.journal.as_ref().or(self.resume_from.as_ref()) {
+            Some(path) => Some(
+                tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .map_err(|e| Error::Io {
+                        message: format!("opening journal: {}", path.display()).into(),
+                        source: e,
+                    })?,
+            ),
+            None => None,
+        };
+
+        let op = &op;
+        // items are only taken from `pending` as they are started, such that
+        // the items remaining after a shutdown were not started
+        let mut pending = items.into_iter();
+        let started = std::iter::from_fn(|| {
+            if shutdown.is_requested() {
+                None
+            } else {
+                pending.next()
+            }
+        });
+        let mut results = futures::stream::iter(started)
+            .map(|item| async move {
+                let result = self.retry(|| op(item.clone())).await;
+                (item, result)
+            })
+            .buffered(usize::from(self.concurrency));
+
+        let mut outcome = BatchOutcome::default();
+        while let Some((item, result)) = results.next().await {
+            let value = match result.and_then(|value| Ok(serde_json::to_value(value)?)) {
+                Ok(value) => value,
+                Err(err) => {
+                    error!("{item} failed: {err}");
+                    outcome.failed.push(item.to_string());
+                    continue;
+                }
+            };
+
+            let entry = JournalEntry {
+                item: item.to_string(),
+                result: value,
+            };
+            if let Some(journal) = &mut journal {
+                let mut line = serde_json::to_vec(&entry)?;
+                line.push(b'\n');
+                journal.write_all(&line).await.map_err(|e| Error::Io {
+                    message: "writing journal".into(),
+                    source: e,
+                })?;
+                journal.flush().await.map_err(|e| Error::Io {
+                    message: "writing journal".into(),
+                    source: e,
+                })?;
+            }
+            outcome.completed.push(entry.result);
+        }
+        drop(results);
+
+        outcome.skipped = pending.map(|item| item.to_string()).collect();
+        if !outcome.skipped.is_empty() {
+            let completed = outcome.completed.len();
+            let failed = outcome.failed.len();
+            let skipped = outcome.skipped.len();
+            info!("shut down after {completed} completed and {failed} failed items, skipping {skipped}");
+        }
+
+        Ok(outcome)
+    }
+
+    /// Run `op`, retrying transient failures with exponential backoff
+    ///
+    /// Other failures, such as an item that does not exist, fail
+    /// immediately as retrying them would fail the same way.
+    async fn retry<R, F, Fut>(&self, op: F) -> Result<R>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<R>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match op().await {
+                Ok(result) => return Ok(result),
+                Err(err) if attempt < self.retries && err.is_transient() => {
+                    attempt += 1;
+                    let delay = 2_u64.saturating_pow(attempt);
+                    warn!("attempt {attempt} failed, retrying in {delay}s: {err}");
+                    sleep(Duration::from_secs(delay)).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Write raw data, such as a report, to stdout
+///
+/// # Errors
+///
+/// Returns an error if writing to stdout fails
+pub async fn write_stdout(data: &[u8]) -> Result<()> {
+    io::stdout().write_all(data).await.map_err(|e| Error::Io {
+        message: "writing to stdout".into(),
+        source: e,
+    })
+}
+
+/// Build and display a table from a stream of `Serialize`-trait objects
+///
+/// # Errors
+///
+/// 1. If the stream errors, the error is returned
+/// 2. If the record cannot be serialized, the error is returned
+async fn table_serialize_stream<V>(
+    time_format: TimeFormat,
+    fields: Option<Vec<String>>,
+    mut stream: Pin<Box<impl Stream<Item = Result<V>>>>,
+) -> Result<()>
+where
+    V: serde::Serialize,
+{
+    let mut table: Vec<Vec<CellStruct>> = Vec::new();
+    let mut title = vec![];
+    let mut have_title = false;
+
+    while let Some(entry) = stream.next().await {
+        let entry = entry?;
+        let mut entry = serde_json::to_value(entry)?;
+        time_format.normalize(&mut entry);
+
+        if let Some(obj) = entry.as_object() {
+            let mut row = vec![];
+            for (key, value) in obj {
+                if !fields.as_ref().is_none_or(|y| y.contains(key)) {
+                    continue;
+                }
+                if !have_title {
+                    title.push(key.cell().bold(true));
+                }
+                row.push(to_cell(value)?);
+            }
+            have_title = true;
+            table.push(row);
+        } else {
+            table.push(vec![to_cell(&entry)?]);
+        }
+    }
+
+    let table = table.table().title(title).bold(true);
+
+    print_stdout(table).map_err(|e| Error::Io {
+        message: "writing result table".into(),
+        source: e,
+    })?;
+
+    Ok(())
+}
+
+/// Display CSV from a stream of `Serialize`-trait objects
+///
+/// This will write the CSV to stdout, with nested types (like Array or Object)
+/// rendered as JSON strings and timestamps rendered as per `time_format`.
+///
+/// # Errors
+///
+/// 1. If the stream errors, the error is returned
+/// 2. If the record cannot be serialized, the error is returned
+async fn csv_serialize_stream<V>(
+    time_format: TimeFormat,
+    fields: Option<Vec<String>>,
+    mut stream: Pin<Box<impl Stream<Item = Result<V>>>>,
+) -> Result<()>
+where
+    V: serde::Serialize,
+{
+    let mut ser = csv::Writer::from_writer(std::io::stdout());
+
+    let mut wrote_headers = false;
+    while let Some(entry) = stream.next().await {
+        let entry = entry?;
+        let mut entry = serde_json::to_value(entry)?;
+        time_format.normalize(&mut entry);
+        if let Some(obj) = entry.as_object_mut() {
+            obj.retain(|key, _| fields.as_ref().is_none_or(|y| y.contains(key)));
+
+            if !wrote_headers {
+                let headers = obj.keys().collect::<Vec<_>>();
+                ser.write_record(headers)?;
+                wrote_headers = true;
+            }
+
+            let mut values = vec![];
+            for (_, value) in &mut *obj {
+                if value.is_object() || value.is_array() {
+                    *value = serde_json::Value::String(serde_json::to_string(value)?);
+                }
+                values.push(value);
+            }
+            ser.serialize(values)?;
+        } else {
+            ser.serialize(&entry)?;
+        }
+    }
+    Ok(())
+}
+
+/// Display JSON from a stream of `Serialize`-trait objects
+///
+/// This allows iterating over results rather than buffering everything in
+/// memory prior to writing the results.
+///
+/// # Errors
+///
+/// 1. If the stream errors, the error is returned
+/// 2. If the record cannot be serialized, the error is returned
+#[allow(clippy::print_stdout)]
+async fn json_serialize_stream<V>(
+    wrapper: Option<(&str, &str)>,
+    mut stream: Pin<Box<impl Stream<Item = Result<V>>>>,
+) -> Result<()>
+where
+    V: serde::Serialize,
+{
+    if let Some((prefix, _)) = &wrapper {
+        print!("{prefix}");
+    }
+    let mut ser = serde_json::Serializer::with_formatter(std::io::stdout(), PrettyFormatter::new());
+    let mut serializer = ser.serialize_seq(None)?;
+    while let Some(entry) = stream.next().await {
+        let entry = entry?;
+        serializer.serialize_element(&entry)?;
+    }
+    serializer.end()?;
+    if let Some((_, suffix)) = &wrapper {
+        print!("{suffix}");
+    }
+    Ok(())
+}
+
+/// Display newline-delimited JSON from a stream of `Serialize`-trait objects
+///
+/// Each record is written on its own line as soon as the stream yields it,
+/// such that the output can be processed incrementally by tools such as `jq`.
+///
+/// # Errors
+///
+/// 1. If the stream errors, the error is returned
+/// 2. If the record cannot be serialized, the error is returned
+async fn ndjson_serialize_stream<V>(
+    mut stream: Pin<Box<impl Stream<Item = Result<V>>>>,
+) -> Result<()>
+where
+    V: serde::Serialize,
+{
+    while let Some(entry) = stream.next().await {
+        let entry = entry?;
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        stdout().write_all(&line).map_err(|e| Error::Io {
+            message: "writing result".into(),
+            source: e,
+        })?;
+    }
+    Ok(())
+}
+
+/// Display values from a stream of `Serialize`-trait objects
+///
+/// `fields` limits the fields included in `table` and `csv` output, and
+/// `wrapper` is written before and after the records in `json` output, such
+/// as `("{\"images\":", "}")`.
+///
+/// # Errors
+///
+/// 1. If the stream errors, the error is returned
+/// 2. If the record cannot be serialized, the error is returned
+pub async fn serialize_stream<V>(
+    output: OutputFormat,
+    time_format: TimeFormat,
+    fields: Option<Vec<String>>,
+    wrapper: Option<(&str, &str)>,
+    stream: Pin<Box<impl Stream<Item = Result<V>>>>,
+) -> Result<()>
+where
+    V: serde::Serialize,
+{
+    match output {
+        OutputFormat::Table => table_serialize_stream(time_format, fields, stream).await,
+        OutputFormat::Csv => csv_serialize_stream(time_format, fields, stream).await,
+        OutputFormat::Json => json_serialize_stream(wrapper, stream).await,
+        OutputFormat::Ndjson => ndjson_serialize_stream(stream).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_time() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000)?;
+        assert_eq!(relative_time(now, now), "0s ago");
+        assert_eq!(
+            relative_time(now - time::Duration::minutes(5), now),
+            "5m ago"
+        );
+        assert_eq!(relative_time(now - time::Duration::hours(3), now), "3h ago");
+        assert_eq!(relative_time(now + time::Duration::days(2), now), "in 2d");
+
+        let rendered =
+            TimeFormat::Rfc3339.render(&Value::String("2023-11-14T23:13:20+01:00".into()));
+        assert_eq!(rendered.as_deref(), Some("2023-11-14T22:13:20Z"));
+        assert_eq!(
+            TimeFormat::Rfc3339.render(&Value::String("lime".into())),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_field() -> Result<()> {
+        let value = serde_json::json!({"tags": {"name": "x"}, "event_types": ["a", "b"]});
+        assert_eq!(
+            extract_field(&value, "tags.name"),
+            Some(&Value::String("x".into()))
+        );
+        assert_eq!(
+            extract_field(&value, "$.event_types[1]"),
+            Some(&Value::String("b".into()))
+        );
+        assert_eq!(extract_field(&value, "tags.missing"), None);
+        Ok(())
+    }
+}
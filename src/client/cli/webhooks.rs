@@ -0,0 +1,309 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::{
+    argparse::parse_timestamp,
+    cli::{
+        confirm, print_data, print_field, serialize_stream, write_stdout, OutputFormat,
+        OutputOptions,
+    },
+    models::webhooks::{
+        service::WebhookLogListRequest, WebhookEventId, WebhookEventState, WebhookEventType,
+        WebhookId, WebhookTarget,
+    },
+    Error, Result, Secret,
+};
+use clap::Subcommand;
+use time::OffsetDateTime;
+
+#[derive(Debug, Subcommand)]
+/// webhook specific subcommands
+pub enum WebhooksCommands {
+    /// Create a new webhook
+    Create {
+        /// webhook target.  either an http url, `servicebus://<namespace>/<queue>`, or `storagequeue://<account>/<queue>`.  `{event_type}` in an http url is replaced with the type of the event
+        target: WebhookTarget,
+
+        /// webhook event types to monitor
+        #[clap(required = true)]
+        event_types: Vec<WebhookEventType>,
+
+        #[clap(long)]
+        /// webhook hmsecret
+        hmac_token: Option<Secret>,
+
+        #[clap(long)]
+        /// include the tags of the image in image related events
+        include_tags: bool,
+    },
+    /// Create a webhook, or update the existing webhook with the same target.  use for idempotent provisioning scripts
+    Ensure {
+        /// webhook target.  either an http url, `servicebus://<namespace>/<queue>`, or `storagequeue://<account>/<queue>`.  `{event_type}` in an http url is replaced with the type of the event
+        target: WebhookTarget,
+
+        /// webhook event types to monitor
+        #[clap(required = true)]
+        event_types: Vec<WebhookEventType>,
+
+        #[clap(long)]
+        /// webhook hmsecret
+        hmac_token: Option<Secret>,
+
+        #[clap(long)]
+        /// include the tags of the image in image related events
+        include_tags: bool,
+    },
+    /// Delete an existing webhook
+    Delete {
+        /// unique identifier for the webhook
+        webhook_id: WebhookId,
+    },
+    /// Get an existing webhook
+    Get {
+        /// unique identifier for the webhook
+        webhook_id: WebhookId,
+
+        #[arg(long, alias = "query", value_name = "PATH")]
+        /// extract a single field of the webhook, such as `url` or `event_types[0]`, and print it without quoting
+        field: Option<String>,
+    },
+    /// Update an existing webhook
+    Update {
+        /// webhook id
+        webhook_id: WebhookId,
+
+        /// webhook target.  either an http url, `servicebus://<namespace>/<queue>`, or `storagequeue://<account>/<queue>`.  `{event_type}` in an http url is replaced with the type of the event
+        target: WebhookTarget,
+
+        /// webhook event types to monitor
+        #[clap(required = true)]
+        event_types: Vec<WebhookEventType>,
+
+        #[clap(long)]
+        /// webhook hmsecret
+        hmac_token: Option<Secret>,
+
+        #[clap(long)]
+        /// webhook hmsecret being replaced by `--hmac-token`.  if not provided, an in-progress rotation of the hmsecret is kept
+        hmac_token_previous: Option<Secret>,
+
+        #[clap(long)]
+        /// include the tags of the image in image related events
+        include_tags: bool,
+    },
+    /// List existing webhooks
+    List {
+        #[arg(long, default_value_t=OutputFormat::Json)]
+        /// print in table mode
+        output: OutputFormat,
+
+        #[arg(long)]
+        /// only include webhooks whose url contains the specified value
+        url_contains: Option<String>,
+
+        #[arg(long)]
+        /// only include webhooks that monitor the specified event type
+        event_type: Option<WebhookEventType>,
+    },
+    /// List webhook logs
+    Logs {
+        /// unique identifier for the webhook
+        webhook_id: WebhookId,
+
+        #[arg(long)]
+        /// only include events of this type
+        event_type: Option<WebhookEventType>,
+
+        #[arg(long)]
+        /// only include events in this delivery state
+        state: Option<WebhookEventState>,
+
+        #[arg(long, value_name = "TIMESTAMP", value_parser = parse_timestamp)]
+        /// only include events that occurred at or after this time, as RFC 3339 or days ago such as `7d`
+        since: Option<OffsetDateTime>,
+
+        #[arg(long, value_name = "TIMESTAMP", value_parser = parse_timestamp)]
+        /// only include events that occurred before this time, as RFC 3339 or days ago such as `7d`
+        until: Option<OffsetDateTime>,
+
+        #[arg(long, default_value_t=OutputFormat::Json)]
+        /// print in table mode
+        output: OutputFormat,
+    },
+    /// Test an existing webhook
+    Ping {
+        /// unique identifier for the webhook
+        webhook_id: WebhookId,
+    },
+    /// Send synthetic events to every webhook, reporting the results for each webhook and event type.  an event only passes if the service sent an event of the requested type
+    PingAll {
+        #[clap(long = "event-type")]
+        /// event types to send.  specify multiple times to include multiple event types.  each webhook is only sent the event types it monitors.  if not specified, each webhook is sent all of the event types it monitors
+        event_types: Vec<WebhookEventType>,
+    },
+    /// Start rotating the HMAC token of a webhook.  events are signed using the new token, while receivers should accept either token until the rotation is completed
+    RotateHmacToken {
+        /// unique identifier for the webhook
+        webhook_id: WebhookId,
+
+        /// the new HMAC token
+        hmac_token: Secret,
+    },
+    /// Complete the rotation of the HMAC token of a webhook, such that the previous token is no longer used
+    CompleteHmacRotation {
+        /// unique identifier for the webhook
+        webhook_id: WebhookId,
+    },
+    /// Resend an event to a webhook
+    Resend {
+        /// unique identifier for the webhook
+        webhook_id: WebhookId,
+
+        /// unique identifier for the webhook event
+        webhook_event_id: WebhookEventId,
+    },
+}
+
+/// Run a webhook specific subcommand
+///
+/// # Errors
+///
+/// Returns an error if the subcommand fails, or if `ping-all` finds webhooks
+/// that did not receive all of the events
+pub async fn run_webhooks(subcommands: WebhooksCommands, options: &OutputOptions) -> Result<()> {
+    let OutputOptions {
+        yes, time_format, ..
+    } = *options;
+    let client = options.client().await?;
+    match subcommands {
+        WebhooksCommands::Create {
+            target,
+            event_types,
+            hmac_token,
+            include_tags,
+        } => client
+            .webhook_create(
+                target,
+                event_types.into_iter().collect(),
+                hmac_token,
+                include_tags,
+            )
+            .await
+            .map(print_data)?,
+        WebhooksCommands::Ensure {
+            target,
+            event_types,
+            hmac_token,
+            include_tags,
+        } => client
+            .webhook_ensure(
+                target,
+                event_types.into_iter().collect(),
+                hmac_token,
+                include_tags,
+            )
+            .await
+            .map(print_data)?,
+        WebhooksCommands::Delete { webhook_id } => {
+            let webhook = client.webhook_get(webhook_id).await?;
+            confirm("delete the webhook", &webhook, yes, client.config())?;
+            client.webhook_delete(webhook_id).await.map(print_data)?
+        }
+        WebhooksCommands::Get { webhook_id, field } => {
+            let webhook = client.webhook_get(webhook_id).await?;
+            print_field(webhook, field.as_deref())
+        }
+        WebhooksCommands::Ping { webhook_id } => {
+            let result = client.webhook_ping(webhook_id).await?;
+            write_stdout(&result).await?;
+            Ok(())
+        }
+        WebhooksCommands::PingAll { event_types } => {
+            let results = client.webhooks_ping_all(&event_types).await?;
+            let failed = results.iter().filter(|x| !x.passed()).count();
+            print_data(&results)?;
+            if failed > 0 {
+                return Err(Error::Other(
+                    "webhook ping failed",
+                    format!("{failed} webhook(s) did not receive all events"),
+                ));
+            }
+            Ok(())
+        }
+        WebhooksCommands::Update {
+            webhook_id,
+            target,
+            event_types,
+            hmac_token,
+            hmac_token_previous,
+            include_tags,
+        } => client
+            .webhook_update(
+                webhook_id,
+                target,
+                event_types.into_iter().collect(),
+                hmac_token,
+                hmac_token_previous,
+                include_tags,
+            )
+            .await
+            .map(print_data)?,
+        WebhooksCommands::List {
+            output,
+            url_contains,
+            event_type,
+        } => {
+            let stream = client.webhooks_list(url_contains, event_type);
+            serialize_stream(
+                output,
+                time_format,
+                None,
+                Some(("{\"webhooks\":", "}")),
+                stream,
+            )
+            .await
+        }
+        WebhooksCommands::Logs {
+            webhook_id,
+            event_type,
+            state,
+            since,
+            until,
+            output,
+        } => {
+            let request = WebhookLogListRequest {
+                event_type,
+                state,
+                since,
+                until,
+                ..WebhookLogListRequest::default()
+            };
+            let stream = client.webhooks_logs_filtered(webhook_id, request);
+            serialize_stream(
+                output,
+                time_format,
+                None,
+                Some(("{\"webhook_events\":", "}")),
+                stream,
+            )
+            .await
+        }
+        WebhooksCommands::RotateHmacToken {
+            webhook_id,
+            hmac_token,
+        } => client
+            .webhook_rotate_hmac_token(webhook_id, hmac_token)
+            .await
+            .map(print_data)?,
+        WebhooksCommands::CompleteHmacRotation { webhook_id } => client
+            .webhook_complete_hmac_rotation(webhook_id)
+            .await
+            .map(print_data)?,
+        WebhooksCommands::Resend {
+            webhook_id,
+            webhook_event_id,
+        } => client
+            .webhook_resend(webhook_id, webhook_event_id)
+            .await
+            .map(print_data)?,
+    }
+}
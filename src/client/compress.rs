@@ -0,0 +1,241 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use bytes::Bytes;
+use snap::write::FrameEncoder;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    sync::mpsc::{self, Receiver},
+    task::spawn_blocking,
+};
+
+/// Magic value at the start of each uncompressed `LiME` range header
+const LIME_MAGIC: u32 = 0x4C69_4D45;
+
+/// Magic value at the start of each range header of `LiME` images compressed
+/// by AVML
+const AVML_MAGIC: u32 = 0x4C4D_5641;
+
+/// Version of the range headers of `LiME` images compressed by AVML
+const AVML_VERSION: u32 = 2;
+
+/// Size of a range header
+const HEADER_SIZE: usize = 32;
+
+/// Maximum number of bytes compressed at once.  Larger memory ranges are
+/// split into adjacent ranges, which bounds the memory used to compress them.
+const MAX_RANGE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Read a little-endian `u32` at `offset` within `header`
+fn read_u32(header: &[u8], offset: usize) -> Option<u32> {
+    header
+        .get(offset..offset + 4)?
+        .try_into()
+        .ok()
+        .map(u32::from_le_bytes)
+}
+
+/// Read a little-endian `u64` at `offset` within `header`
+fn read_u64(header: &[u8], offset: usize) -> Option<u64> {
+    header
+        .get(offset..offset + 8)?
+        .try_into()
+        .ok()
+        .map(u64::from_le_bytes)
+}
+
+/// Compress an uncompressed `LiME` image read from `reader` into the format
+/// written by AVML with `--compress`, passing each compressed range to `emit`
+/// in order
+///
+/// Each range is written as a range header using the AVML magic value,
+/// followed by the contents of the range compressed using the snappy framing
+/// format, followed by the size of the compressed contents as a little-endian
+/// `u64`.
+pub(crate) fn compress_lime<R, F>(mut reader: R, mut emit: F) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(Vec<u8>) -> io::Result<()>,
+{
+    let invalid = |reason| io::Error::new(io::ErrorKind::InvalidData, reason);
+    let truncated = |reason| io::Error::new(io::ErrorKind::UnexpectedEof, reason);
+
+    loop {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        (&mut reader)
+            .take(HEADER_SIZE as u64)
+            .read_to_end(&mut header)?;
+        match header.len() {
+            0 => return Ok(()),
+            HEADER_SIZE => {}
+            _ => return Err(truncated("LiME range header is truncated")),
+        }
+
+        if read_u32(&header, 0) != Some(LIME_MAGIC) || read_u32(&header, 4) != Some(1) {
+            return Err(invalid("not an uncompressed LiME range header"));
+        }
+        let (Some(start), Some(end)) = (read_u64(&header, 8), read_u64(&header, 16)) else {
+            return Err(invalid("LiME range header is too short"));
+        };
+        if end < start {
+            return Err(invalid("LiME range ends before it starts"));
+        }
+
+        let mut range_start = start;
+        loop {
+            let range_end = end.min(range_start.saturating_add(MAX_RANGE_SIZE - 1));
+            let len = range_end - range_start + 1;
+            let mut data = Vec::with_capacity(usize::try_from(len).unwrap_or_default());
+            (&mut reader).take(len).read_to_end(&mut data)?;
+            if (data.len() as u64) < len {
+                return Err(truncated("LiME range is truncated"));
+            }
+
+            let mut compressed = vec![];
+            let mut encoder = FrameEncoder::new(&mut compressed);
+            encoder.write_all(&data)?;
+            encoder.flush()?;
+            drop(encoder);
+
+            let mut block = Vec::with_capacity(HEADER_SIZE + compressed.len() + 8);
+            block.extend(AVML_MAGIC.to_le_bytes());
+            block.extend(AVML_VERSION.to_le_bytes());
+            block.extend(range_start.to_le_bytes());
+            block.extend(range_end.to_le_bytes());
+            block.extend([0; 8]);
+            block.extend(&compressed);
+            block.extend((compressed.len() as u64).to_le_bytes());
+            emit(block)?;
+
+            if range_end == end {
+                break;
+            }
+            range_start = range_end + 1;
+        }
+    }
+}
+
+/// Reads the chunks of data sent through a channel, such that data produced
+/// by a blocking task can be uploaded
+#[derive(Debug)]
+pub(crate) struct ChannelReader {
+    /// Receives chunks of data, or the error that stopped the producer
+    receiver: Receiver<io::Result<Bytes>>,
+    /// Remainder of the chunk currently being read
+    chunk: Bytes,
+}
+
+impl AsyncRead for ChannelReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.chunk.is_empty() {
+            match ready!(self.receiver.poll_recv(cx)) {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(err)) => return Poll::Ready(Err(err)),
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+        let len = self.chunk.len().min(buf.remaining());
+        let data = self.chunk.split_to(len);
+        buf.put_slice(&data);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Compress an uncompressed `LiME` image read from `file` using a blocking
+/// task, returning a reader of the compressed image
+///
+/// At most `capacity` compressed ranges are held in memory waiting to be
+/// read.  Errors reading or compressing the image are returned by the
+/// reader, such that an incomplete image is not uploaded.
+pub(crate) fn compressed_lime_reader(file: File, capacity: usize) -> ChannelReader {
+    let (sender, receiver) = mpsc::channel(capacity.max(1));
+    spawn_blocking(move || {
+        let result = compress_lime(BufReader::new(file), |block| {
+            sender
+                .blocking_send(Ok(Bytes::from(block)))
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "upload stopped"))
+        });
+        if let Err(err) = result {
+            // the upload may have already stopped, such that there is no one
+            // to report the error to
+            let _ = sender.blocking_send(Err(err));
+        }
+    });
+    ChannelReader {
+        receiver,
+        chunk: Bytes::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snap::read::FrameDecoder;
+
+    fn lime_range(start: u64, end: u64, fill: u8) -> Vec<u8> {
+        let mut range = vec![];
+        range.extend(LIME_MAGIC.to_le_bytes());
+        range.extend(1_u32.to_le_bytes());
+        range.extend(start.to_le_bytes());
+        range.extend(end.to_le_bytes());
+        range.extend([0; 8]);
+        range.resize(
+            range.len() + usize::try_from(end - start + 1).unwrap_or_default(),
+            fill,
+        );
+        range
+    }
+
+    #[test]
+    fn test_compress_lime() -> io::Result<()> {
+        let mut image = lime_range(0, 0xfff, 1);
+        image.extend(lime_range(0x10_0000, 0x10_0000 + MAX_RANGE_SIZE, 2));
+
+        let mut blocks = vec![];
+        compress_lime(image.as_slice(), |block| {
+            blocks.push(block);
+            Ok(())
+        })?;
+
+        // the second range is split, as it is larger than `MAX_RANGE_SIZE`
+        let expected = [
+            (0, 0xfff, 1),
+            (0x10_0000, 0x10_0000 + MAX_RANGE_SIZE - 1, 2),
+            (0x10_0000 + MAX_RANGE_SIZE, 0x10_0000 + MAX_RANGE_SIZE, 2),
+        ];
+        assert_eq!(blocks.len(), expected.len());
+        for (block, (start, end, fill)) in blocks.iter().zip(expected) {
+            assert_eq!(read_u32(block, 0), Some(AVML_MAGIC));
+            assert_eq!(read_u32(block, 4), Some(AVML_VERSION));
+            assert_eq!(read_u64(block, 8), Some(start));
+            assert_eq!(read_u64(block, 16), Some(end));
+
+            let compressed = block.get(HEADER_SIZE..block.len() - 8).unwrap_or_default();
+            assert_eq!(
+                read_u64(block, block.len() - 8),
+                Some(compressed.len() as u64)
+            );
+            let mut data = vec![];
+            FrameDecoder::new(compressed).read_to_end(&mut data)?;
+            assert_eq!(data.len() as u64, end - start + 1);
+            assert!(data.iter().all(|x| *x == fill));
+        }
+
+        let truncated = image.get(..image.len() - 1).unwrap_or_default();
+        let result = compress_lime(truncated, |_| Ok(()));
+        assert!(matches!(result, Err(err) if err.kind() == io::ErrorKind::UnexpectedEof));
+
+        let result = compress_lime([0; HEADER_SIZE].as_slice(), |_| Ok(()));
+        assert!(matches!(result, Err(err) if err.kind() == io::ErrorKind::InvalidData));
+        Ok(())
+    }
+}
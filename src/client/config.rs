@@ -2,17 +2,27 @@
 
 use crate::{
     client::{
-        backend::Backend,
-        io::{create_dir_all, read_json, write_json},
+        backend::{azure_blobs::DEFAULT_UPLOAD_CONCURRENCY, Backend},
+        error::io_err,
+        io::create_dir_all,
+        keychain,
     },
-    Error, Result,
+    Error, Result, SDK_NAME, SDK_VERSION,
 };
+use clap::ValueEnum;
 use home::home_dir;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    ClientBuilder, Proxy,
+};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display},
-    path::PathBuf,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
 };
+use tokio::fs;
 use url::Url;
 
 /// Value that is printed upon trying to show a debug version of a `Secret`
@@ -59,6 +69,169 @@ impl From<String> for Secret {
     }
 }
 
+/// IP address family used to connect to the service and Azure Storage
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    /// Connect using either IPv4 or IPv6, preferring whichever connects first
+    #[default]
+    Any,
+    /// Only connect using IPv4
+    V4,
+    /// Only connect using IPv6
+    V6,
+}
+
+impl IpFamily {
+    /// Returns true if this is the default value
+    fn is_any(&self) -> bool {
+        *self == Self::Any
+    }
+}
+
+impl Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::V4 => write!(f, "v4"),
+            Self::V6 => write!(f, "v6"),
+        }
+    }
+}
+
+/// Authentication to a proxy
+///
+/// Proxies that use NTLM or Kerberos authenticate each connection with a
+/// challenge-response exchange, which is not supported.  For those proxies,
+/// either provide a `Proxy-Authorization` value obtained ahead of time, such
+/// as a `Negotiate` token, or connect through a local proxy that
+/// authenticates to the upstream proxy, such as `cntlm` or `px`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProxyAuth {
+    /// Authenticate using HTTP Basic authentication
+    Basic {
+        /// User name
+        username: String,
+        /// Password
+        password: Secret,
+    },
+    /// Send the value as the `Proxy-Authorization` header, such as
+    /// `Negotiate <token>`
+    Header {
+        /// Value of the `Proxy-Authorization` header
+        value: Secret,
+    },
+}
+
+impl std::str::FromStr for ProxyAuth {
+    type Err = Error;
+
+    /// Parse `basic:USERNAME:PASSWORD` or `header:VALUE`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            Some(("basic", credentials)) => {
+                let (username, password) = credentials.split_once(':').ok_or(Error::Other(
+                    "invalid proxy auth",
+                    "expected basic:USERNAME:PASSWORD".into(),
+                ))?;
+                Ok(Self::Basic {
+                    username: username.to_string(),
+                    password: Secret::new(password),
+                })
+            }
+            Some(("header", value)) => Ok(Self::Header {
+                value: Secret::new(value),
+            }),
+            _ => Err(Error::Other(
+                "invalid proxy auth",
+                "expected basic:USERNAME:PASSWORD or header:VALUE".into(),
+            )),
+        }
+    }
+}
+
+/// HTTP proxy used to connect to the service or Azure Storage
+///
+/// Connections to HTTPS endpoints are tunneled through the proxy using HTTP
+/// `CONNECT`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProxyConfig {
+    /// URL of the proxy, such as `http://proxy.contoso.com:8080`
+    pub url: Url,
+
+    /// Authentication to the proxy, if it is required
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<ProxyAuth>,
+}
+
+impl ProxyConfig {
+    /// Create a `reqwest` proxy used for all requests
+    ///
+    /// # Errors
+    /// This will return an error if the proxy URL or authentication is invalid
+    fn to_proxy(&self) -> Result<Proxy> {
+        let proxy = Proxy::all(self.url.clone())?;
+        let proxy = match &self.auth {
+            None => proxy,
+            Some(ProxyAuth::Basic { username, password }) => {
+                proxy.basic_auth(username, password.get_secret())
+            }
+            Some(ProxyAuth::Header { value }) => {
+                let mut header_value = HeaderValue::from_str(value.get_secret())
+                    .map_err(|_| Error::Other("invalid proxy auth header", self.url.to_string()))?;
+                header_value.set_sensitive(true);
+                proxy.custom_http_auth(header_value)
+            }
+        };
+        Ok(proxy)
+    }
+}
+
+/// Retry policy for transient failures of requests to the Freta API
+///
+/// Requests that are rate limited (HTTP 429) or that the service is
+/// temporarily unable to handle are retried with exponential backoff and
+/// jitter.  A `Retry-After` header sent by the service takes precedence over
+/// the backoff.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Maximum number of attempts for each request, including the first.  A
+    /// value of 1 disables retries.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, in milliseconds.  The delay doubles with
+    /// each subsequent retry.
+    pub initial_backoff_ms: u64,
+
+    /// Maximum delay between retries, in milliseconds
+    pub max_backoff_ms: u64,
+
+    /// Total time allowed for a request, including retries, in seconds.
+    /// Retries that would exceed this are not attempted.  A value of 0 does
+    /// not limit the total time.
+    pub deadline_secs: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            deadline_secs: 120,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Returns true if this is the default value
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
 /// AAD App client id
 pub struct ClientId(String);
@@ -76,7 +249,7 @@ impl ClientId {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 /// Freta client Config
 pub struct Config {
     /// URL for the Freta API.
@@ -84,6 +257,15 @@ pub struct Config {
     /// NOTE: For the public Freta service, this should always be `https://freta.microsoft.com`
     pub api_url: Url,
 
+    /// URLs of additional instances of the Freta API, such as the paired
+    /// instance of a private deployment used for disaster recovery.
+    ///
+    /// Requests that fail to connect to `api_url` are retried against these
+    /// in order.  Endpoints that repeatedly fail to connect are skipped for a
+    /// period of time.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_fallback_urls: Vec<Url>,
+
     /// AAD app registration client id
     pub client_id: ClientId,
 
@@ -93,12 +275,111 @@ pub struct Config {
     /// Client Secrt for custom app registrations to connect to Freta
     pub client_secret: Option<Secret>,
 
+    /// Personal access token issued by the Freta service.
+    ///
+    /// When set, this is used instead of an AAD login, which allows headless
+    /// machines to use the service.  See `freta tokens create`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pat: Option<Secret>,
+
     /// AAD App registration scope
     pub scope: Option<String>,
 
     /// Do not load or save cached login tokens
     #[serde(default)]
     pub ignore_login_cache: bool,
+
+    /// Acquire tokens using an existing Azure CLI login (`az login`) rather
+    /// than prompting with the device code flow.
+    ///
+    /// If the Azure CLI is not installed or not logged in, the device code
+    /// flow is used instead.
+    #[serde(default)]
+    pub use_azure_cli: bool,
+
+    /// Tags automatically added to images created by the client.
+    ///
+    /// Tags explicitly provided when creating an image take precedence over
+    /// these defaults.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub default_tags: BTreeMap<String, String>,
+
+    /// Maximum size of the local artifact cache in bytes.
+    ///
+    /// The least recently used artifacts are evicted once the cache exceeds
+    /// this size.  A value of 0, the default, disables the cache.
+    #[serde(default)]
+    pub cache_max_bytes: u64,
+
+    /// Always interactively confirm destructive operations, even if
+    /// confirmation was skipped via the command line.
+    #[serde(default)]
+    pub always_confirm_destructive: bool,
+
+    /// Save the service information to disk, such that it is reused across
+    /// invocations of the client until it expires.
+    #[serde(default)]
+    pub persist_service_info: bool,
+
+    /// IP address family used to connect to the service and Azure Storage
+    #[serde(default, skip_serializing_if = "IpFamily::is_any")]
+    pub ip_family: IpFamily,
+
+    /// Host names that are resolved to the specified addresses rather than
+    /// using DNS.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dns_overrides: BTreeMap<String, IpAddr>,
+
+    /// Proxy used to connect to the Freta API.
+    ///
+    /// When unset, the proxy specified by the `HTTPS_PROXY` environment
+    /// variable is used, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_proxy: Option<ProxyConfig>,
+
+    /// Proxy used to upload and download blobs from Azure Storage.
+    ///
+    /// When unset, the proxy specified by the `HTTPS_PROXY` environment
+    /// variable is used, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_proxy: Option<ProxyConfig>,
+
+    /// Headers added to every request sent to the Freta API, such as those
+    /// required by an API gateway in front of the service.
+    ///
+    /// These are not sent to Azure Storage.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra_headers: BTreeMap<String, Secret>,
+
+    /// Scopes requested in addition to `scope` when acquiring tokens.
+    ///
+    /// Microsoft Entra ID issues tokens for a single resource, such that
+    /// these must be scopes of the same resource as `scope`, or scopes that
+    /// are not specific to a resource, such as `offline_access`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_scopes: Vec<String>,
+
+    /// File that requests sent to the Freta API and their responses are
+    /// appended to, for debugging integration issues.
+    ///
+    /// Credentials are redacted and bodies are truncated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_http_path: Option<PathBuf>,
+
+    /// Number of blocks uploaded concurrently when uploading images.
+    ///
+    /// Each in-flight block is held in memory.
+    #[serde(default = "default_upload_concurrency")]
+    pub upload_concurrency: usize,
+
+    /// Retry policy for transient failures of requests to the Freta API
+    #[serde(default, skip_serializing_if = "RetryConfig::is_default")]
+    pub retry: RetryConfig,
+}
+
+/// Default value for `Config.upload_concurrency` when deserializing
+const fn default_upload_concurrency() -> usize {
+    DEFAULT_UPLOAD_CONCURRENCY
 }
 
 impl Default for Config {
@@ -106,11 +387,27 @@ impl Default for Config {
         Self {
             #[allow(clippy::expect_used)]
             api_url: Url::parse(DEFAULT_ENDPOINT).expect("default URL failed"),
+            api_fallback_urls: Vec::new(),
             client_id: ClientId::new("574efb07-14a8-4232-a200-89714a0324c9".into()),
             tenant_id: "common".into(),
             client_secret: None,
+            pat: None,
             scope: Some("api://a934fc14-92d7-4127-aecd-bddab35935da/.default".into()),
             ignore_login_cache: false,
+            use_azure_cli: false,
+            default_tags: BTreeMap::new(),
+            cache_max_bytes: 0,
+            always_confirm_destructive: false,
+            persist_service_info: false,
+            ip_family: IpFamily::Any,
+            dns_overrides: BTreeMap::new(),
+            api_proxy: None,
+            storage_proxy: None,
+            extra_headers: BTreeMap::new(),
+            additional_scopes: Vec::new(),
+            trace_http_path: None,
+            upload_concurrency: DEFAULT_UPLOAD_CONCURRENCY,
+            retry: RetryConfig::default(),
         }
     }
 }
@@ -122,11 +419,69 @@ impl fmt::Debug for Config {
         d.field("client id", &self.client_id.as_str());
         d.field("tenant id", &self.tenant_id.as_str());
         d.field("ignore login cache", &self.ignore_login_cache);
+        d.field("use azure cli", &self.use_azure_cli);
+        d.field("cache max bytes", &self.cache_max_bytes);
+        d.field(
+            "always confirm destructive",
+            &self.always_confirm_destructive,
+        );
+        d.field("persist service info", &self.persist_service_info);
+        d.field("ip family", &self.ip_family);
+        d.field("upload concurrency", &self.upload_concurrency);
+        d.field("retry", &self.retry);
+
+        if !self.api_fallback_urls.is_empty() {
+            d.field(
+                "api fallback urls",
+                &self
+                    .api_fallback_urls
+                    .iter()
+                    .map(Url::as_str)
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        if !self.dns_overrides.is_empty() {
+            d.field("dns overrides", &self.dns_overrides);
+        }
+
+        // the proxy URL may include credentials, so only the host is shown
+        if let Some(api_proxy) = &self.api_proxy {
+            d.field("api proxy", &api_proxy.url.host_str());
+        }
+
+        if let Some(storage_proxy) = &self.storage_proxy {
+            d.field("storage proxy", &storage_proxy.url.host_str());
+        }
+
+        if !self.default_tags.is_empty() {
+            d.field("default tags", &self.default_tags);
+        }
+
+        // header values may include gateway keys, so only the names are shown
+        if !self.extra_headers.is_empty() {
+            d.field(
+                "extra headers",
+                &self.extra_headers.keys().collect::<Vec<_>>(),
+            );
+        }
+
+        if !self.additional_scopes.is_empty() {
+            d.field("additional scopes", &self.additional_scopes);
+        }
+
+        if let Some(trace_http_path) = &self.trace_http_path {
+            d.field("trace http path", trace_http_path);
+        }
 
         if self.client_secret.is_some() {
             d.field("client secret", &REDACTED);
         }
 
+        if self.pat.is_some() {
+            d.field("pat", &REDACTED);
+        }
+
         if let Some(scope) = &self.scope {
             d.field("scope", &scope);
         }
@@ -143,25 +498,136 @@ impl Display for Config {
     }
 }
 
+/// Formats the configuration file can be written in, detected by the name of
+/// the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFormat {
+    /// `cli.config`
+    Json,
+    /// `cli.config.toml`
+    Toml,
+    /// `cli.config.yaml`
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Formats in the order the configuration files are looked for
+    const ALL: [Self; 3] = [Self::Json, Self::Toml, Self::Yaml];
+
+    /// Name of the configuration file in this format
+    const fn file_name(self) -> &'static str {
+        match self {
+            Self::Json => "cli.config",
+            Self::Toml => "cli.config.toml",
+            Self::Yaml => "cli.config.yaml",
+        }
+    }
+
+    /// Name of the format, as used in errors
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Toml => "TOML",
+            Self::Yaml => "YAML",
+        }
+    }
+
+    /// Parse a configuration file, reporting the location of invalid
+    /// settings as described by the parser for the format
+    fn parse(self, path: &Path, contents: &str) -> Result<Config> {
+        let parsed = match self {
+            Self::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            Self::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string()),
+        };
+        parsed.map_err(|message| Error::InvalidConfig {
+            path: path.display().to_string(),
+            format: self.name(),
+            message,
+        })
+    }
+
+    /// Serialize a configuration file
+    fn serialize(self, config: &Config) -> Result<String> {
+        match self {
+            Self::Json => Ok(serde_json::to_string_pretty(config)?),
+            Self::Toml => toml::to_string_pretty(config)
+                .map_err(|e| Error::Other("serializing TOML config", e.to_string())),
+            Self::Yaml => serde_yaml::to_string(config)
+                .map_err(|e| Error::Other("serializing YAML config", e.to_string())),
+        }
+    }
+}
+
 impl Config {
-    /// Get the path for the config file
-    fn get_path() -> Result<PathBuf> {
-        Ok(get_config_dir()?.join("cli.config"))
+    /// Find the existing config file and its format
+    ///
+    /// # Errors
+    /// This will return an error if the config directory cannot be determined
+    /// or config files exist in more than one format, as it would be unclear
+    /// which to use
+    fn find_path() -> Result<Option<(PathBuf, ConfigFormat)>> {
+        let dir = get_config_dir()?;
+        let mut found = ConfigFormat::ALL
+            .into_iter()
+            .map(|format| (dir.join(format.file_name()), format))
+            .filter(|(path, _)| path.exists());
+        let first = found.next();
+        if let (Some((path, _)), Some((other, _))) = (&first, found.next()) {
+            return Err(Error::Other(
+                "config files exist in multiple formats.  remove all but one",
+                format!("{} and {}", path.display(), other.display()),
+            ));
+        }
+        Ok(first)
     }
 
-    /// Load the user's current configuration from `~/.config/freta/cli.config`
-    /// or use the default if that does not exist
+    /// Load the user's current configuration from `~/.config/freta/`, or use
+    /// the default if no configuration file exists
+    ///
+    /// The configuration is read from `cli.config` as JSON,
+    /// `cli.config.toml` as TOML, or `cli.config.yaml` as YAML.  Secrets that
+    /// are not in the configuration file are read from the keychain of the
+    /// operating system when using the `keyring` feature.
     ///
     /// # Errors
     /// This will return an error in the following cases:
     /// 1. The path loading the configuration file cannot be determined
-    /// 2. Loading the configuration file fails
+    /// 2. Configuration files exist in more than one format
+    /// 3. Reading or parsing the configuration file fails
     pub async fn load() -> Result<Self> {
-        let path = Self::get_path()?;
-        if path.exists() {
-            read_json(path).await
+        let Some((path, format)) = Self::find_path()? else {
+            return Ok(Self::default());
+        };
+        let contents = fs::read_to_string(&path)
+            .await
+            .map_err(|e| io_err(format!("reading config: {path:?}"), e))?;
+        let mut config = format.parse(&path, &contents)?;
+        if config.client_secret.is_none() {
+            config.client_secret = keychain::get(keychain::CLIENT_SECRET)
+                .await?
+                .map(Secret::new);
+        }
+        if config.pat.is_none() {
+            config.pat = keychain::get(keychain::PAT).await?.map(Secret::new);
+        }
+        Ok(config)
+    }
+
+    /// Store a secret in the keychain, returning the secret if it must be
+    /// saved to the configuration file instead
+    ///
+    /// Removing a secret from the configuration also removes it from the
+    /// keychain.
+    async fn store_secret(name: &'static str, secret: Option<&Secret>) -> Result<Option<Secret>> {
+        let Some(secret) = secret else {
+            keychain::delete(name).await?;
+            return Ok(None);
+        };
+        if keychain::set(name, secret.get_secret().to_string()).await? {
+            Ok(None)
         } else {
-            Ok(Self::default())
+            Ok(Some(secret.clone()))
         }
     }
 
@@ -176,22 +642,148 @@ impl Config {
         create_dir_all(path).await
     }
 
-    /// Save the user's configuration to `~/.config/freta/cli.config`
+    /// Save the user's configuration to `~/.config/freta/`
+    ///
+    /// The configuration is written in the format of the existing
+    /// configuration file, or to `cli.config` as JSON if there is none.  When
+    /// using the `keyring` feature, `client_secret` and `pat` are stored in
+    /// the keychain of the operating system rather than in the file, unless
+    /// no keychain is available.
     ///
     /// At the moment, client configuration only includes login configuration
     /// information.  Therefore, on any change, log the user out and log them
     /// back in.
     ///
     /// # Errors
-    /// This will return an error if the configuration file cannot be saved
+    /// This will return an error in the following cases:
+    /// 1. An additional scope is for a different resource than the primary
+    ///    scope
+    /// 2. The configuration file cannot be saved
     pub async fn save(&self) -> Result<()> {
+        self.get_scopes()?;
         Self::create_config_dir().await?;
-        let path = Self::get_path()?;
-        write_json(path, self).await?;
+        let (path, format) = match Self::find_path()? {
+            Some(found) => found,
+            None => (
+                get_config_dir()?.join(ConfigFormat::Json.file_name()),
+                ConfigFormat::Json,
+            ),
+        };
+        let stored = Self {
+            client_secret: Self::store_secret(keychain::CLIENT_SECRET, self.client_secret.as_ref())
+                .await?,
+            pat: Self::store_secret(keychain::PAT, self.pat.as_ref()).await?,
+            ..self.clone()
+        };
+        let contents = format.serialize(&stored)?;
+        fs::write(&path, contents)
+            .await
+            .map_err(|e| io_err(format!("writing config: {path:?}"), e))?;
         Backend::logout().await?;
         Ok(())
     }
 
+    /// Returns true if the configuration changes how connections to Azure
+    /// Storage are made from the defaults
+    pub(crate) fn customizes_connections(&self) -> bool {
+        !self.ip_family.is_any() || !self.dns_overrides.is_empty() || self.storage_proxy.is_some()
+    }
+
+    /// Create an HTTP client builder for requests to the Freta API that uses
+    /// the configured connection preferences
+    ///
+    /// # Errors
+    /// This will return an error if the API proxy is invalid
+    pub(crate) fn http_client_builder(&self) -> Result<ClientBuilder> {
+        self.connection_builder(self.api_proxy.as_ref())
+    }
+
+    /// Create an HTTP client builder for transfers to and from Azure Storage
+    /// that uses the configured connection preferences
+    ///
+    /// # Errors
+    /// This will return an error if the storage proxy is invalid
+    pub(crate) fn storage_http_client_builder(&self) -> Result<ClientBuilder> {
+        self.connection_builder(self.storage_proxy.as_ref())
+    }
+
+    /// Create an HTTP client builder that uses the configured connection
+    /// preferences and `proxy`
+    fn connection_builder(&self, proxy: Option<&ProxyConfig>) -> Result<ClientBuilder> {
+        let mut builder = ClientBuilder::new().user_agent(format!("{SDK_NAME}/{SDK_VERSION}"));
+
+        // binding to the unspecified address of a family restricts outgoing
+        // connections to that family
+        builder = match self.ip_family {
+            IpFamily::Any => builder,
+            IpFamily::V4 => builder.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpFamily::V6 => builder.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        };
+
+        // the port of an override is ignored in favor of the port of the URL
+        for (host, addr) in &self.dns_overrides {
+            builder = builder.resolve(host, SocketAddr::new(*addr, 0));
+        }
+
+        // adding a proxy disables the use of the proxy environment variables
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(proxy.to_proxy()?);
+        }
+
+        Ok(builder)
+    }
+
+    /// Build the headers added to every request sent to the Freta API
+    ///
+    /// # Errors
+    /// This will return an error if a header name or value is invalid
+    pub(crate) fn extra_header_map(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.extra_headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| Error::Other("invalid extra header name", name.clone()))?;
+            let mut header_value = HeaderValue::from_str(value.get_secret())
+                .map_err(|_| Error::Other("invalid extra header value", name.clone()))?;
+            header_value.set_sensitive(true);
+            headers.insert(header_name, header_value);
+        }
+        Ok(headers)
+    }
+
+    /// Get the resource for which the Azure CLI acquires tokens, which is the
+    /// scope without the `/.default` suffix
+    pub(crate) fn get_resource(&self) -> String {
+        let scope = self.get_scope();
+        scope
+            .strip_suffix("/.default")
+            .map_or_else(|| scope.clone(), ToString::to_string)
+    }
+
+    /// Get the JWT token scopes for the current configuration, including any
+    /// additional scopes
+    ///
+    /// # Errors
+    /// This will return an error if an additional scope is for a different
+    /// resource than the primary scope, as tokens are only issued for a
+    /// single resource
+    pub(crate) fn get_scopes(&self) -> Result<Vec<String>> {
+        let primary = self.get_scope();
+        let resource = scope_resource(&primary);
+        let mut scopes = vec![primary.clone()];
+        for scope in &self.additional_scopes {
+            if scope_resource(scope).is_some_and(|x| Some(x) != resource) {
+                return Err(Error::Other(
+                    "additional scopes must be for the same resource as the primary scope",
+                    format!("{scope} (primary scope: {primary})"),
+                ));
+            }
+            if !scopes.contains(scope) {
+                scopes.push(scope.clone());
+            }
+        }
+        Ok(scopes)
+    }
+
     /// Get the JWT token scope for the current configuration
     pub(crate) fn get_scope(&self) -> String {
         self.scope.as_ref().map_or_else(
@@ -205,6 +797,15 @@ impl Config {
     }
 }
 
+/// Get the resource a scope grants access to, such as `api://freta` for
+/// `api://freta/.default`
+///
+/// Returns `None` for scopes that are not specific to a resource, such as
+/// `offline_access`.
+fn scope_resource(scope: &str) -> Option<&str> {
+    scope.rsplit_once('/').map(|(resource, _)| resource)
+}
+
 /// return expaneded version of `$HOME/.config/freta/`
 ///
 /// # Errors
@@ -214,3 +815,114 @@ pub(crate) fn get_config_dir() -> Result<PathBuf> {
         .ok_or(Error::MissingHome)
         .map(|x| x.join(".config/freta/"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy_url() -> Result<Url> {
+        Url::parse("http://proxy.contoso.com:8080")
+            .map_err(|e| Error::Other("invalid url", e.to_string()))
+    }
+
+    #[test]
+    fn test_config_formats() -> Result<()> {
+        let config = Config {
+            upload_concurrency: 2,
+            default_tags: BTreeMap::from([("team".to_string(), "red".to_string())]),
+            storage_proxy: Some(ProxyConfig {
+                url: proxy_url()?,
+                auth: Some(ProxyAuth::Basic {
+                    username: "user".into(),
+                    password: Secret::new("password"),
+                }),
+            }),
+            ..Config::default()
+        };
+        let expected = serde_json::to_value(&config)?;
+        let path = Path::new("config");
+        for format in ConfigFormat::ALL {
+            let contents = format.serialize(&config)?;
+            let parsed = format.parse(path, &contents)?;
+            assert_eq!(
+                serde_json::to_value(&parsed)?,
+                expected,
+                "{}",
+                format.name()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_proxy_auth() -> Result<()> {
+        assert!(matches!(
+            "basic:user:pass:word".parse::<ProxyAuth>()?,
+            ProxyAuth::Basic { username, password }
+                if username == "user" && password.get_secret() == "pass:word"
+        ));
+        assert!(matches!(
+            "header:Negotiate abc".parse::<ProxyAuth>()?,
+            ProxyAuth::Header { value } if value.get_secret() == "Negotiate abc"
+        ));
+        assert!("basic:user".parse::<ProxyAuth>().is_err());
+        assert!("ntlm:user:password".parse::<ProxyAuth>().is_err());
+
+        let config = Config {
+            api_proxy: Some(ProxyConfig {
+                url: proxy_url()?,
+                auth: Some("header:Negotiate abc".parse()?),
+            }),
+            ..Config::default()
+        };
+        assert!(!config.customizes_connections());
+        config.http_client_builder()?.build()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_resource() {
+        let mut config = Config::default();
+        assert_eq!(
+            config.get_resource(),
+            "api://a934fc14-92d7-4127-aecd-bddab35935da"
+        );
+        config.scope = Some("api://example".into());
+        assert_eq!(config.get_resource(), "api://example");
+    }
+
+    #[test]
+    fn test_get_scopes() -> Result<()> {
+        let mut config = Config {
+            scope: Some("api://gateway/.default".into()),
+            additional_scopes: vec!["api://gateway/freta.read".into(), "offline_access".into()],
+            ..Config::default()
+        };
+        assert_eq!(
+            config.get_scopes()?,
+            [
+                "api://gateway/.default",
+                "api://gateway/freta.read",
+                "offline_access"
+            ]
+        );
+
+        // tokens are only issued for a single resource
+        config
+            .additional_scopes
+            .push("https://graph.microsoft.com/User.Read".into());
+        assert!(config.get_scopes().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let path = Path::new("cli.config.toml");
+        let result = ConfigFormat::Toml.parse(path, "upload_concurrency = \"eight\"\n");
+        assert!(matches!(
+            result,
+            Err(Error::InvalidConfig { format: "TOML", message, .. })
+                if message.contains("upload_concurrency")
+        ));
+    }
+}
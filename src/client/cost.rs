@@ -0,0 +1,88 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Estimates the cost of images, broken down by owner or tag.
+//!
+//! The service does not report usage, so usage is aggregated client-side
+//! from the blobs stored as part of each image, the retention period
+//! recorded on the image, and its analysis state.
+//!
+//! ```rust,no_run
+//! # use freta::{models::cost::CostFilter, Client, Result};
+//! # use time::{Duration, OffsetDateTime};
+//! # async fn example(client: Client) -> Result<()> {
+//! let filter = CostFilter::new(OffsetDateTime::now_utc() - Duration::days(30));
+//! for entry in client.cost_report(&filter).await? {
+//!     println!("{}: {:.2}", entry.group, entry.total_cost);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    models::{
+        base::Image,
+        cost::{cost_breakdown, CostEntry, CostFilter, ImageUsage},
+    },
+    Client, Result,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use time::OffsetDateTime;
+use tracing::debug;
+
+/// Number of images whose blobs are listed concurrently
+const COST_CONCURRENCY: usize = 8;
+
+impl Client {
+    /// Get the total size of the blobs of an image and when the image was
+    /// uploaded, based on the oldest blob
+    ///
+    /// Images whose blobs cannot be listed, such as images that were never
+    /// uploaded, are considered empty.
+    async fn image_usage_size(&self, image: &Image) -> (u64, Option<OffsetDateTime>) {
+        let result = self
+            .image_blobs_list(image.image_id)
+            .try_fold((0, None), |(bytes, uploaded), blob| async move {
+                let uploaded = uploaded.map_or(blob.last_modified, |time: OffsetDateTime| {
+                    time.min(blob.last_modified)
+                });
+                Ok((bytes + blob.size, Some(uploaded)))
+            })
+            .await;
+        result.unwrap_or_else(|err| {
+            debug!("unable to list blobs of {}: {err}", image.image_id);
+            (0, None)
+        })
+    }
+
+    /// Estimate the cost of images within a reporting period, broken down by
+    /// owner or tag
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if listing the images fails
+    pub async fn cost_report(&self, filter: &CostFilter) -> Result<Vec<CostEntry>> {
+        let now = OffsetDateTime::now_utc();
+        let images: Vec<Image> = self
+            .images_list(
+                None,
+                filter.owner_id,
+                None,
+                filter.include_samples,
+                false,
+                None,
+            )
+            .try_collect()
+            .await?;
+
+        let usage: Vec<ImageUsage> = stream::iter(images)
+            .map(|image| async move {
+                let (bytes, uploaded) = self.image_usage_size(&image).await;
+                ImageUsage::new(&image, bytes, uploaded, filter.since, now)
+            })
+            .buffer_unordered(COST_CONCURRENCY)
+            .collect()
+            .await;
+
+        Ok(cost_breakdown(&usage, &filter.group, &filter.rates))
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::client::{
+    error::{Error, Result},
+    io::{read_chunks, DEFAULT_CHUNK_SIZE},
+};
+use sha2::{Digest, Sha256};
+use std::{fmt::Write as _, path::Path};
+
+/// Hex encode a digest
+pub(crate) fn to_hex(digest: &[u8]) -> String {
+    digest.iter().fold(String::new(), |mut hex, byte| {
+        // writing to a `String` does not fail
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Get the hex encoded SHA-256 digest of `data`
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Get the hex encoded SHA-256 digest of the contents of a file
+///
+/// This performs blocking IO.
+pub(crate) fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    read_chunks(path, DEFAULT_CHUNK_SIZE, |chunk| {
+        hasher.update(chunk);
+        Ok(())
+    })?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Ensure the hex encoded SHA-256 digest of an artifact matches the expected
+/// digest, ignoring case
+pub(crate) fn verify_sha256(name: &str, expected: &str, actual: &str) -> Result<()> {
+    if expected.trim().eq_ignore_ascii_case(actual) {
+        Ok(())
+    } else {
+        Err(Error::DigestMismatch {
+            name: name.to_string(),
+            expected: expected.trim().to_lowercase(),
+            actual: actual.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::error::io_err;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(sha256_hex(b"hello"), HELLO_SHA256);
+    }
+
+    #[test]
+    fn test_sha256_file() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("freta-sha256-{}", std::process::id()));
+        std::fs::write(&path, b"hello").map_err(|e| io_err("writing test file", e))?;
+        let result = sha256_file(&path);
+        std::fs::remove_file(&path).map_err(|e| io_err("removing test file", e))?;
+        assert_eq!(result?, HELLO_SHA256);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_sha256() {
+        let actual = sha256_hex(b"hello");
+        assert!(verify_sha256("a", HELLO_SHA256, &actual).is_ok());
+        assert!(verify_sha256("a", &HELLO_SHA256.to_uppercase(), &actual).is_ok());
+        assert!(matches!(
+            verify_sha256("a", &sha256_hex(b"other"), &actual),
+            Err(Error::DigestMismatch { .. })
+        ));
+    }
+}
@@ -1,7 +1,36 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
+use azure_core::error::ErrorKind;
+use reqwest::header::{HeaderMap, HeaderName};
+use serde::Deserialize;
 use std::borrow::Cow;
 
+/// Azure Storage error codes returned when a request is blocked by the
+/// network rules of the storage account
+const FIREWALL_ERROR_CODES: &[&str] = &["AuthorizationFailure", "AuthorizationSourceIPMismatch"];
+
+/// HTTP status codes of responses indicating a failure that may not recur if
+/// the request is retried
+const TRANSIENT_STATUSES: &[u16] = &[408, 429, 500, 502, 503, 504];
+
+/// Azure Storage error code returned when the credentials of a request are
+/// rejected
+const AUTHENTICATION_FAILED: &str = "AuthenticationFailed";
+
+/// Messages included with `AuthenticationFailed` when the SAS is no longer
+/// valid
+const SAS_EXPIRED_MESSAGES: &[&str] = &[
+    "signed expiry time",
+    "signature not valid in the specified time frame",
+];
+
+/// Messages included with `AuthenticationFailed` when the clock of the
+/// client differs from that of Azure Storage
+const CLOCK_SKEW_MESSAGES: &[&str] = &["request date header too old", "clock skew"];
+
+/// Headers that identify a request to the service, in order of preference
+const REQUEST_ID_HEADERS: &[&str] = &["x-ms-request-id", "x-request-id"];
+
 /// Freta errors
 #[derive(thiserror::Error)]
 pub enum Error {
@@ -35,7 +64,12 @@ pub enum Error {
     #[error("analysis failed: {0}")]
     AnalysisFailed(Cow<'static, str>),
 
-    /// Invalid OAuth2 authentication token
+    /// The analysis of an image completed without the image matching the
+    /// awaited condition
+    #[error("analysis completed without the image matching the awaited condition")]
+    CompletedBeforeMatch,
+
+    /// Invalid `OAuth2` authentication token
     #[error("invalid token: {0}")]
     InvalidToken(&'static str),
 
@@ -49,7 +83,21 @@ pub enum Error {
 
     /// There was an error interacting with an Azure service
     #[error("azure error")]
-    Azure(#[from] azure_core::Error),
+    Azure(#[source] azure_core::Error),
+
+    /// The SAS URL used to access Azure Storage expired
+    #[error("the SAS URL used to access Azure Storage expired.  rerun the command to use a new SAS URL, or split large transfers into smaller ones")]
+    SasExpired(#[source] azure_core::Error),
+
+    /// Azure Storage rejected the request due to the network rules of the
+    /// storage account
+    #[error("Azure Storage blocked the request from this network.  check that proxies, VPNs, or firewalls allow connections to the storage account, or retry from another network")]
+    StorageFirewall(#[source] azure_core::Error),
+
+    /// Azure Storage rejected the request as the clock of the system differs
+    /// from that of Azure Storage
+    #[error("Azure Storage rejected the request due to clock skew.  synchronize the system clock, such as by enabling NTP, and retry")]
+    ClockSkew(#[source] azure_core::Error),
 
     /// There was an error serializing to CSV
     #[error("csv serialization error")]
@@ -59,6 +107,21 @@ pub enum Error {
     #[error(transparent)]
     Request(#[from] reqwest::Error),
 
+    /// The service rejected the request, as described by the error included
+    /// in the response
+    #[error("the freta service responded with {status} {code}: {message}")]
+    Service {
+        /// HTTP status code of the response
+        status: u16,
+        /// Machine-readable code of the error, such as `ImageNotFound`
+        code: String,
+        /// Description of the error
+        message: String,
+        /// Identifier of the request, which can be provided when reporting
+        /// issues with the service
+        request_id: Option<String>,
+    },
+
     /// Error serializing URL parameters
     #[error(transparent)]
     UrlSerialization(#[from] serde_urlencoded::ser::Error),
@@ -71,10 +134,79 @@ pub enum Error {
     #[error(transparent)]
     TryFromIntError(#[from] std::num::TryFromIntError),
 
+    /// Invalid webhook URL template
+    #[error(transparent)]
+    WebhookUrl(#[from] crate::models::webhooks::WebhookUrlError),
+
+    /// Invalid webhook target
+    #[error(transparent)]
+    WebhookTarget(#[from] crate::models::webhooks::WebhookTargetError),
+
+    /// Invalid cron expression
+    #[error(transparent)]
+    Cron(#[from] crate::models::schedules::CronError),
+
+    /// The image is not valid for its format
+    #[error(transparent)]
+    Format(#[from] crate::models::formats::FormatError),
+
     /// Error converting a value into a known file extension
     #[error("file extension error: {0}")]
     Extension(Cow<'static, str>),
 
+    /// A transfer made no progress within the configured stall timeout
+    #[error("transfer stalled: {0}")]
+    Stalled(Cow<'static, str>),
+
+    /// Waiting for an image did not finish within the configured timeout
+    #[error("timed out: {0}")]
+    TimedOut(Cow<'static, str>),
+
+    /// The checks performed before uploading an image failed
+    #[error("upload preflight failed: {0}")]
+    PreflightFailed(Cow<'static, str>),
+
+    /// The digest of downloaded data did not match the expected digest
+    #[error("sha256 mismatch for {name}: expected {expected}, got {actual}")]
+    DigestMismatch {
+        /// Name of the downloaded data
+        name: String,
+        /// Expected hex encoded digest
+        expected: String,
+        /// Hex encoded digest of the downloaded data
+        actual: String,
+    },
+
+    /// The configuration file could not be parsed
+    #[error("invalid {format} config {path}: {message}")]
+    InvalidConfig {
+        /// Path of the configuration file
+        path: String,
+        /// Format of the configuration file
+        format: &'static str,
+        /// Description of the problem, including its location when known
+        message: String,
+    },
+
+    /// A destructive operation was not confirmed
+    #[error("operation not confirmed: {0}")]
+    NotConfirmed(Cow<'static, str>),
+
+    /// A fault was injected for testing
+    #[cfg(feature = "chaos")]
+    #[error("injected fault: {0}")]
+    InjectedFault(crate::client::chaos::Fault),
+
+    /// Error writing an Arrow export
+    #[cfg(feature = "arrow")]
+    #[error(transparent)]
+    Arrow(#[from] arrow::error::ArrowError),
+
+    /// Error writing a Parquet export
+    #[cfg(feature = "arrow")]
+    #[error(transparent)]
+    Parquet(#[from] parquet::errors::ParquetError),
+
     /// Otherwise unspecified error
     #[error("{0}: {1}")]
     Other(&'static str, String),
@@ -83,6 +215,150 @@ pub enum Error {
 /// Freta Result wrapper
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl From<azure_core::Error> for Error {
+    /// Map the errors commonly encountered when transferring images to more
+    /// specific variants that include remediation hints
+    fn from(error: azure_core::Error) -> Self {
+        let error_code = match error.kind() {
+            ErrorKind::HttpResponse {
+                error_code: Some(error_code),
+                ..
+            } => error_code.clone(),
+            _ => return Self::Azure(error),
+        };
+
+        if FIREWALL_ERROR_CODES.contains(&error_code.as_str()) {
+            return Self::StorageFirewall(error);
+        }
+
+        if error_code == AUTHENTICATION_FAILED {
+            let message = error_messages(&error).to_lowercase();
+            let contains_any = |markers: &[&str]| markers.iter().any(|x| message.contains(x));
+            if contains_any(CLOCK_SKEW_MESSAGES) {
+                return Self::ClockSkew(error);
+            }
+            if contains_any(SAS_EXPIRED_MESSAGES) {
+                return Self::SasExpired(error);
+            }
+        }
+
+        Self::Azure(error)
+    }
+}
+
+/// Concatenate the messages of an error and its sources
+fn error_messages(e: &(dyn std::error::Error + 'static)) -> String {
+    let mut messages = vec![e.to_string()];
+    let mut source = e.source();
+    while let Some(inner) = source {
+        messages.push(inner.to_string());
+        source = inner.source();
+    }
+    messages.join("\n")
+}
+
+impl Error {
+    /// Returns true if the service responded that the resource was not found
+    pub(crate) fn is_not_found(&self) -> bool {
+        self.status() == Some(reqwest::StatusCode::NOT_FOUND.as_u16())
+    }
+
+    /// Returns true if the service does not support the request, such as
+    /// routes added in later versions of the service
+    pub(crate) fn is_unsupported(&self) -> bool {
+        self.status().is_some_and(|status| {
+            status == reqwest::StatusCode::NOT_FOUND.as_u16()
+                || status == reqwest::StatusCode::METHOD_NOT_ALLOWED.as_u16()
+        })
+    }
+
+    /// Returns true if the error is likely transient, such that retrying the
+    /// operation may succeed
+    ///
+    /// This includes connection failures, stalled transfers, and responses
+    /// indicating that the service or Azure Storage is overloaded or
+    /// unavailable.
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            Self::Request(e) if e.is_connect() || e.is_timeout() => true,
+            Self::Request(_) | Self::Service { .. } => self
+                .status()
+                .is_some_and(|status| TRANSIENT_STATUSES.contains(&status)),
+            Self::Azure(e) => match e.kind() {
+                ErrorKind::Io => true,
+                ErrorKind::HttpResponse { status, .. } => {
+                    TRANSIENT_STATUSES.contains(&u16::from(*status))
+                }
+                _ => false,
+            },
+            Self::Stalled(_) => true,
+            #[cfg(feature = "chaos")]
+            Self::InjectedFault(_) => true,
+            _ => false,
+        }
+    }
+
+    /// HTTP status code of the response, if the error was caused by the
+    /// service rejecting the request
+    #[must_use]
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Service { status, .. } => Some(*status),
+            Self::Request(e) => e.status().map(|status| status.as_u16()),
+            _ => None,
+        }
+    }
+}
+
+/// Error included in the body of failed responses from the service
+///
+/// Errors are either nested, as in `{"error": {"code": ..., "message": ...}}`,
+/// or at the top level of the response.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ServiceErrorBody {
+    /// An error nested within the `error` field
+    Nested {
+        /// The error
+        error: ServiceErrorDetails,
+    },
+    /// An error at the top level of the response
+    Flat(ServiceErrorDetails),
+}
+
+/// Details of an error included in a failed response
+#[derive(Deserialize)]
+struct ServiceErrorDetails {
+    /// Machine-readable code of the error
+    code: String,
+    /// Description of the error
+    message: String,
+    /// Identifier of the request
+    #[serde(default)]
+    request_id: Option<String>,
+}
+
+/// Build an `Error::Service` from the body of a failed response
+///
+/// Returns `None` if the body does not include an error in a known format,
+/// such as responses generated by proxies.
+pub(crate) fn service_error(status: u16, headers: &HeaderMap, body: &[u8]) -> Option<Error> {
+    let details = match serde_json::from_slice(body).ok()? {
+        ServiceErrorBody::Nested { error } | ServiceErrorBody::Flat(error) => error,
+    };
+    let request_id = REQUEST_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(HeaderName::from_static(*name))?.to_str().ok())
+        .map(ToString::to_string)
+        .or(details.request_id);
+    Some(Error::Service {
+        status,
+        code: details.code,
+        message: details.message,
+        request_id,
+    })
+}
+
 /// Format an error and its sources
 fn format_error(e: &impl std::error::Error, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     write!(f, "error: {e}")?;
@@ -118,3 +394,106 @@ where
         source,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azure_core::StatusCode;
+
+    fn storage_error(status: StatusCode, error_code: &str, message: &'static str) -> Error {
+        azure_core::Error::with_message(
+            ErrorKind::HttpResponse {
+                status,
+                error_code: Some(error_code.to_string()),
+            },
+            || message,
+        )
+        .into()
+    }
+
+    #[test]
+    fn test_is_unsupported() {
+        let error = |status| Error::Service {
+            status,
+            code: String::new(),
+            message: String::new(),
+            request_id: None,
+        };
+        assert!(error(404).is_unsupported());
+        assert!(!error(404).is_transient());
+        assert!(error(503).is_transient());
+        assert!(error(405).is_unsupported());
+        assert!(!error(403).is_unsupported());
+        assert!(!Error::NotConfirmed("".into()).is_unsupported());
+    }
+
+    #[test]
+    fn test_service_error() {
+        let mut headers = HeaderMap::new();
+        let body = br#"{"error": {"code": "ImageNotFound", "message": "image not found"}}"#;
+        assert!(matches!(
+            service_error(404, &headers, body),
+            Some(Error::Service { status: 404, code, message, request_id: None })
+                if code == "ImageNotFound" && message == "image not found"
+        ));
+
+        let body = br#"{"code": "QuotaExceeded", "message": "quota exceeded", "request_id": "1"}"#;
+        let error = service_error(429, &headers, body);
+        assert!(matches!(
+            &error,
+            Some(Error::Service { code, request_id: Some(request_id), .. })
+                if code == "QuotaExceeded" && request_id == "1"
+        ));
+        assert_eq!(error.and_then(|e| e.status()), Some(429));
+
+        headers.insert(
+            "x-ms-request-id",
+            reqwest::header::HeaderValue::from_static("2"),
+        );
+        assert!(matches!(
+            service_error(429, &headers, body),
+            Some(Error::Service { request_id: Some(request_id), .. }) if request_id == "2"
+        ));
+
+        assert!(service_error(502, &headers, b"<html>bad gateway</html>").is_none());
+        assert!(service_error(500, &headers, b"").is_none());
+    }
+
+    #[test]
+    fn test_azure_error_hints() {
+        assert!(matches!(
+            storage_error(
+                StatusCode::Forbidden,
+                "AuthenticationFailed",
+                "Signed expiry time [Mon, 01 Jan 2024 00:00:00 GMT] must be after signed start time"
+            ),
+            Error::SasExpired(_)
+        ));
+        assert!(matches!(
+            storage_error(
+                StatusCode::Forbidden,
+                "AuthenticationFailed",
+                "Request date header too old"
+            ),
+            Error::ClockSkew(_)
+        ));
+        assert!(matches!(
+            storage_error(
+                StatusCode::Forbidden,
+                "AuthorizationFailure",
+                "This request is not authorized to perform this operation."
+            ),
+            Error::StorageFirewall(_)
+        ));
+        assert!(matches!(
+            storage_error(StatusCode::NotFound, "BlobNotFound", "missing"),
+            Error::Azure(_)
+        ));
+        assert!(matches!(
+            Error::from(azure_core::Error::with_message(ErrorKind::Other, || {
+                "other"
+            })),
+            Error::Azure(_)
+        ));
+    }
+}
@@ -0,0 +1,463 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Streaming export of image metadata for analytics.
+//!
+//! Images are written as rows as they are received, such that large
+//! listings are exported without holding every image in memory.  Arrow and
+//! Parquet exports buffer up to `BATCH_SIZE` rows at a time, and require the
+//! `arrow` feature.
+//!
+//! Each row includes the image's tags as a JSON object.  Tags used for
+//! analysis can also be exported as individual columns named `tag.<key>`.
+//!
+//! ```rust,no_run
+//! # use freta::{export::{ExportFormat, ImageExporter}, Client};
+//! # use futures::StreamExt;
+//! # async fn example(client: Client) -> Result<(), Box<dyn std::error::Error>> {
+//! let file = std::fs::File::create("images.csv")?;
+//! let mut exporter = ImageExporter::new(file, ExportFormat::Csv, vec!["team".into()])?;
+//! let mut images = client.images_list(None, None, None, false, false, None);
+//! while let Some(image) = images.next().await {
+//!     exporter.write(&image?)?;
+//! }
+//! exporter.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    models::base::{Image, ImageState},
+    Result,
+};
+use clap::ValueEnum;
+use serde_json::Value;
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::Write,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+#[cfg(feature = "arrow")]
+use arrow::{
+    array::{ArrayRef, BooleanArray, StringArray, TimestampMicrosecondArray},
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    ipc::writer::FileWriter,
+    record_batch::RecordBatch,
+};
+#[cfg(feature = "arrow")]
+use parquet::arrow::ArrowWriter;
+#[cfg(feature = "arrow")]
+use std::sync::Arc;
+
+/// Number of rows written to each Arrow record batch
+#[cfg(feature = "arrow")]
+const BATCH_SIZE: usize = 8192;
+
+/// Prefix of the columns containing individual tags
+const TAG_COLUMN_PREFIX: &str = "tag.";
+
+/// Columns included for every image, prior to the tag columns
+const COLUMNS: &[&str] = &[
+    "image_id",
+    "owner_id",
+    "state",
+    "format",
+    "shareable",
+    "analysis_version",
+    "error",
+    "last_updated",
+    "deleted_at",
+    "tags",
+];
+
+/// Format of exported image metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Comma separated values, with a header row
+    Csv,
+    /// Arrow IPC file
+    #[cfg(feature = "arrow")]
+    Arrow,
+    /// Parquet file
+    #[cfg(feature = "arrow")]
+    Parquet,
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let as_str = match self {
+            Self::Csv => "csv",
+            #[cfg(feature = "arrow")]
+            Self::Arrow => "arrow",
+            #[cfg(feature = "arrow")]
+            Self::Parquet => "parquet",
+        };
+        write!(f, "{as_str}")
+    }
+}
+
+/// The exported values of an image
+#[derive(Debug, Clone, PartialEq)]
+struct ImageRow {
+    /// Unique identifier of the image
+    image_id: String,
+    /// Owner of the image
+    owner_id: String,
+    /// State of the image
+    state: String,
+    /// Format of the image
+    format: String,
+    /// Is the image shareable
+    shareable: bool,
+    /// Version of the analysis engine
+    analysis_version: Option<String>,
+    /// Error of the last analysis
+    error: Option<String>,
+    /// Last time the image was updated
+    last_updated: Option<OffsetDateTime>,
+    /// Time the image was deleted
+    deleted_at: Option<OffsetDateTime>,
+    /// Tags of the image, as a JSON object
+    tags: String,
+    /// Values of the exported tags, in the order of the tag columns
+    tag_values: Vec<Option<String>>,
+}
+
+/// Name of a state as used by the service, such as `waiting_for_upload`
+fn state_name(state: &ImageState) -> Result<String> {
+    Ok(match serde_json::to_value(state)? {
+        Value::String(name) => name,
+        other => other.to_string(),
+    })
+}
+
+impl ImageRow {
+    /// Extract the exported values of an image
+    fn new(image: &Image, tag_columns: &[String]) -> Result<Self> {
+        Ok(Self {
+            image_id: image.image_id.to_string(),
+            owner_id: image.owner_id.to_string(),
+            state: state_name(&image.state)?,
+            format: image.format.to_string(),
+            shareable: image.shareable,
+            analysis_version: image.analysis_version.clone(),
+            error: image.error.clone(),
+            last_updated: image.last_updated,
+            deleted_at: image.deleted_at,
+            tags: serde_json::to_string(&image.tags)?,
+            tag_values: tag_columns
+                .iter()
+                .map(|key| image.tags.get(key).cloned())
+                .collect(),
+        })
+    }
+
+    /// The values as CSV fields, where missing values are empty
+    fn csv_record(&self) -> Result<Vec<String>> {
+        let time = |value: Option<OffsetDateTime>| {
+            value
+                .map(|x| x.format(&Rfc3339))
+                .transpose()
+                .map(Option::unwrap_or_default)
+        };
+        let mut record = vec![
+            self.image_id.clone(),
+            self.owner_id.clone(),
+            self.state.clone(),
+            self.format.clone(),
+            self.shareable.to_string(),
+            self.analysis_version.clone().unwrap_or_default(),
+            self.error.clone().unwrap_or_default(),
+            time(self.last_updated)?,
+            time(self.deleted_at)?,
+            self.tags.clone(),
+        ];
+        record.extend(
+            self.tag_values
+                .iter()
+                .cloned()
+                .map(Option::unwrap_or_default),
+        );
+        Ok(record)
+    }
+}
+
+/// Names of the exported columns, including the columns of `tag_columns`
+fn column_names(tag_columns: &[String]) -> Vec<String> {
+    COLUMNS
+        .iter()
+        .map(ToString::to_string)
+        .chain(
+            tag_columns
+                .iter()
+                .map(|key| format!("{TAG_COLUMN_PREFIX}{key}")),
+        )
+        .collect()
+}
+
+/// Arrow schema of the exported columns
+#[cfg(feature = "arrow")]
+fn arrow_schema(tag_columns: &[String]) -> SchemaRef {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    let mut fields = vec![
+        Field::new("image_id", DataType::Utf8, false),
+        Field::new("owner_id", DataType::Utf8, false),
+        Field::new("state", DataType::Utf8, false),
+        Field::new("format", DataType::Utf8, false),
+        Field::new("shareable", DataType::Boolean, false),
+        Field::new("analysis_version", DataType::Utf8, true),
+        Field::new("error", DataType::Utf8, true),
+        Field::new("last_updated", timestamp.clone(), true),
+        Field::new("deleted_at", timestamp, true),
+        Field::new("tags", DataType::Utf8, false),
+    ];
+    fields.extend(
+        tag_columns
+            .iter()
+            .map(|key| Field::new(format!("{TAG_COLUMN_PREFIX}{key}"), DataType::Utf8, true)),
+    );
+    Arc::new(Schema::new(fields))
+}
+
+/// Convert buffered rows into an Arrow record batch
+#[cfg(feature = "arrow")]
+fn record_batch(schema: &SchemaRef, rows: &[ImageRow]) -> Result<RecordBatch> {
+    let strings = |value: fn(&ImageRow) -> Option<&str>| -> ArrayRef {
+        Arc::new(rows.iter().map(value).collect::<StringArray>())
+    };
+    let timestamps = |value: fn(&ImageRow) -> Option<OffsetDateTime>| -> ArrayRef {
+        let micros = rows
+            .iter()
+            .map(|row| value(row).and_then(|x| i64::try_from(x.unix_timestamp_nanos() / 1000).ok()))
+            .collect::<TimestampMicrosecondArray>();
+        Arc::new(micros.with_timezone("UTC"))
+    };
+
+    let mut columns = vec![
+        strings(|row| Some(row.image_id.as_str())),
+        strings(|row| Some(row.owner_id.as_str())),
+        strings(|row| Some(row.state.as_str())),
+        strings(|row| Some(row.format.as_str())),
+        Arc::new(
+            rows.iter()
+                .map(|row| Some(row.shareable))
+                .collect::<BooleanArray>(),
+        ) as ArrayRef,
+        strings(|row| row.analysis_version.as_deref()),
+        strings(|row| row.error.as_deref()),
+        timestamps(|row| row.last_updated),
+        timestamps(|row| row.deleted_at),
+        strings(|row| Some(row.tags.as_str())),
+    ];
+    let tag_count = schema.fields().len().saturating_sub(COLUMNS.len());
+    for index in 0..tag_count {
+        let values = rows
+            .iter()
+            .map(|row| row.tag_values.get(index).and_then(Option::as_deref))
+            .collect::<StringArray>();
+        columns.push(Arc::new(values));
+    }
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Destination of the exported rows
+enum Sink<W: Write + Send> {
+    /// CSV rows are written as they are received
+    Csv(csv::Writer<W>),
+    /// Arrow IPC file, written in batches
+    #[cfg(feature = "arrow")]
+    Arrow(FileWriter<W>),
+    /// Parquet file, written in batches
+    #[cfg(feature = "arrow")]
+    Parquet(ArrowWriter<W>),
+}
+
+/// Writes image metadata as rows of a CSV, Arrow, or Parquet file
+pub struct ImageExporter<W: Write + Send> {
+    /// Keys of the tags exported as individual columns
+    tag_columns: Vec<String>,
+    /// Destination of the rows
+    sink: Sink<W>,
+    /// Rows not yet written as an Arrow record batch
+    #[cfg(feature = "arrow")]
+    pending: Vec<ImageRow>,
+    /// Schema of the Arrow record batches
+    #[cfg(feature = "arrow")]
+    schema: SchemaRef,
+    /// Number of images written
+    count: u64,
+}
+
+impl<W: Write + Send> std::fmt::Debug for ImageExporter<W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ImageExporter")
+            .field("tag_columns", &self.tag_columns)
+            .field("count", &self.count)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write + Send> ImageExporter<W> {
+    /// Create an exporter that writes to `writer`
+    ///
+    /// The tags with the keys in `tag_columns` are also exported as
+    /// individual columns.
+    ///
+    /// # Errors
+    /// This will return an error if the header of the file cannot be written
+    pub fn new(writer: W, format: ExportFormat, tag_columns: Vec<String>) -> Result<Self> {
+        #[cfg(feature = "arrow")]
+        let schema = arrow_schema(&tag_columns);
+        let sink = match format {
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(writer);
+                writer.write_record(column_names(&tag_columns))?;
+                Sink::Csv(writer)
+            }
+            #[cfg(feature = "arrow")]
+            ExportFormat::Arrow => Sink::Arrow(FileWriter::try_new(writer, &schema)?),
+            #[cfg(feature = "arrow")]
+            ExportFormat::Parquet => {
+                Sink::Parquet(ArrowWriter::try_new(writer, schema.clone(), None)?)
+            }
+        };
+        Ok(Self {
+            tag_columns,
+            sink,
+            #[cfg(feature = "arrow")]
+            pending: Vec::new(),
+            #[cfg(feature = "arrow")]
+            schema,
+            count: 0,
+        })
+    }
+
+    /// Number of images written
+    #[must_use]
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Write the metadata of an image
+    ///
+    /// # Errors
+    /// This will return an error if writing the row fails
+    pub fn write(&mut self, image: &Image) -> Result<()> {
+        let row = ImageRow::new(image, &self.tag_columns)?;
+        match &mut self.sink {
+            Sink::Csv(writer) => writer.write_record(row.csv_record()?)?,
+            #[cfg(feature = "arrow")]
+            Sink::Arrow(_) | Sink::Parquet(_) => {
+                self.pending.push(row);
+                if self.pending.len() >= BATCH_SIZE {
+                    self.flush_batch()?;
+                }
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Write the pending rows as an Arrow record batch
+    #[cfg(feature = "arrow")]
+    fn flush_batch(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let batch = record_batch(&self.schema, &self.pending)?;
+        self.pending.clear();
+        match &mut self.sink {
+            Sink::Csv(_) => {}
+            Sink::Arrow(writer) => writer.write(&batch)?,
+            Sink::Parquet(writer) => writer.write(&batch)?,
+        }
+        Ok(())
+    }
+
+    /// Write any pending rows and complete the file
+    ///
+    /// # Errors
+    /// This will return an error if writing the remaining rows or the footer
+    /// of the file fails
+    pub fn finish(mut self) -> Result<u64> {
+        #[cfg(feature = "arrow")]
+        self.flush_batch()?;
+        match &mut self.sink {
+            Sink::Csv(writer) => writer
+                .flush()
+                .map_err(|e| crate::client::error::io_err("writing csv", e))?,
+            #[cfg(feature = "arrow")]
+            Sink::Arrow(writer) => writer.finish()?,
+            #[cfg(feature = "arrow")]
+            Sink::Parquet(writer) => {
+                writer.finish()?;
+            }
+        }
+        Ok(self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::base::{ImageFormat, OwnerId};
+    use std::collections::BTreeMap;
+
+    fn image(team: Option<&str>) -> Image {
+        let tags = team
+            .map(|value| BTreeMap::from([("team".to_string(), value.to_string())]))
+            .unwrap_or_default();
+        Image::new(OwnerId::samples(), ImageFormat::Lime, tags)
+    }
+
+    #[test]
+    fn test_csv_export() -> Result<()> {
+        let mut data = vec![];
+        let mut exporter = ImageExporter::new(&mut data, ExportFormat::Csv, vec!["team".into()])?;
+        exporter.write(&image(Some("red")))?;
+        exporter.write(&image(None))?;
+        assert_eq!(exporter.finish()?, 2);
+        let mut reader = csv::Reader::from_reader(data.as_slice());
+
+        let headers = reader.headers()?.clone();
+        assert_eq!(headers.get(2), Some("state"));
+        assert_eq!(headers.get(COLUMNS.len()), Some("tag.team"));
+
+        let records = reader
+            .records()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let values = |record: &csv::StringRecord| {
+            (
+                record.get(2).map(ToString::to_string),
+                record.get(COLUMNS.len()).map(ToString::to_string),
+            )
+        };
+        assert_eq!(
+            records.iter().map(values).collect::<Vec<_>>(),
+            vec![
+                (Some("waiting_for_upload".into()), Some("red".into())),
+                (Some("waiting_for_upload".into()), Some(String::new())),
+            ]
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_arrow_export() -> Result<()> {
+        let tag_columns = vec!["team".to_string()];
+        let schema = arrow_schema(&tag_columns);
+        let rows = [image(Some("red")), image(None)]
+            .iter()
+            .map(|x| ImageRow::new(x, &tag_columns))
+            .collect::<Result<Vec<_>>>()?;
+        let batch = record_batch(&schema, &rows)?;
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.num_columns(), COLUMNS.len() + 1);
+
+        let mut exporter = ImageExporter::new(vec![], ExportFormat::Parquet, tag_columns)?;
+        exporter.write(&image(Some("red")))?;
+        assert_eq!(exporter.finish()?, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Capture memory images from sources such as hypervisors and cloud
+//! providers, and upload them to Freta.
+//!
+//! Sources implement `CaptureSource`, and are driven by
+//! `Client::capture`.  Sources that are not built into a program are
+//! provided as plugins: executables named `freta-capture-<name>` found in
+//! `PATH`, which `ExternalSource` runs using the following commands:
+//!
+//! * `list` prints the targets as a JSON array of `CaptureTarget`
+//! * `capture <TARGET>` captures the target and prints the resulting
+//!   `Capture` as JSON
+//!
+//! The SAS URL the image may be uploaded to is provided to `capture` using the
+//! `FRETA_UPLOAD_URL` environment variable, rather than as an argument, such
+//! that it is not visible to other users of the system in the list of
+//! processes.
+//!
+//! Lines a plugin writes to stderr are reported as progress.  Plugins written
+//! in Rust can implement these commands using `PluginCommand` and
+//! `run_plugin`.
+//!
+//! ```rust,no_run
+//! # use freta::{integrations::ExternalSource, Client, ImageFormat, Result};
+//! # async fn example(client: Client) -> Result<()> {
+//! let source = ExternalSource::find("vmware", ImageFormat::Raw)?;
+//! let progress = |message: &str| eprintln!("{message}");
+//! let image = client
+//!     .capture(&source, "web-01", [("name", "web-01")], &Default::default(), &progress)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::{
+        error::io_err,
+        io::{open_file, remove_file},
+    },
+    Client, Error, Image, ImageFormat, Result, UploadOptions,
+};
+use clap::Subcommand;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::{env, path::PathBuf, process::Stdio};
+use tokio::{
+    io::{stdout, AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+};
+use tracing::{debug, info, warn};
+use url::Url;
+
+/// Prefix of the names of executables providing capture sources
+const PLUGIN_PREFIX: &str = "freta-capture-";
+
+/// Environment variable providing the upload SAS URL to `capture` commands
+const UPLOAD_URL_ENV: &str = "FRETA_UPLOAD_URL";
+
+/// Reports the progress of a capture, such as the steps taken by the source
+pub type Progress<'a> = &'a (dyn Fn(&str) + Send + Sync);
+
+/// A system that a `CaptureSource` can capture memory from, such as a VM
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CaptureTarget {
+    /// Unique identifier of the target, as used by the source
+    pub id: String,
+    /// Name of the target, which is used to specify the target to capture
+    pub name: String,
+}
+
+/// The result of capturing a target
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Capture {
+    /// The memory image was written to a local file, which is uploaded to
+    /// the image
+    File {
+        /// Path of the memory image
+        path: PathBuf,
+        /// Remove the file once it has been uploaded
+        #[serde(default)]
+        remove_after_upload: bool,
+    },
+    /// The source uploaded the memory image to the upload URL itself, such as
+    /// when the capture runs on a remote system
+    Uploaded,
+}
+
+/// A source of memory images
+///
+/// Methods return boxed futures such that sources can be selected at runtime
+/// as `&dyn CaptureSource`.
+pub trait CaptureSource: Send + Sync {
+    /// Name of the source, such as `hyperv`
+    fn name(&self) -> &str;
+
+    /// Format of the images captured by the source
+    fn format(&self) -> ImageFormat;
+
+    /// List the targets that can be captured
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<CaptureTarget>>>;
+
+    /// Capture the memory of `target`
+    ///
+    /// `upload_url` is a SAS URL that sources capturing on remote systems can
+    /// upload the image to directly.
+    fn capture<'a>(
+        &'a self,
+        target: &'a str,
+        upload_url: &'a Url,
+        progress: Progress<'a>,
+    ) -> BoxFuture<'a, Result<Capture>>;
+
+    /// Release any resources held for a capture once it has been uploaded,
+    /// such as a VM snapshot
+    ///
+    /// This is called even if the upload fails.
+    fn cleanup<'a>(&'a self, target: &'a str, capture: &'a Capture) -> BoxFuture<'a, Result<()>> {
+        let _ = (target, capture);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A capture source provided by a `freta-capture-<name>` executable
+#[derive(Debug, Clone)]
+pub struct ExternalSource {
+    /// Name of the source
+    name: String,
+    /// Path of the executable
+    program: PathBuf,
+    /// Format of the images captured by the source
+    format: ImageFormat,
+}
+
+impl ExternalSource {
+    /// Create a source using the specified executable
+    #[must_use]
+    pub const fn new(name: String, program: PathBuf, format: ImageFormat) -> Self {
+        Self {
+            name,
+            program,
+            format,
+        }
+    }
+
+    /// Find the `freta-capture-<name>` executable in `PATH`
+    ///
+    /// # Errors
+    /// This will return an error if the executable is not found
+    pub fn find(name: &str, format: ImageFormat) -> Result<Self> {
+        let filename = format!("{PLUGIN_PREFIX}{name}{}", env::consts::EXE_SUFFIX);
+        env::var_os("PATH")
+            .iter()
+            .flat_map(env::split_paths)
+            .map(|dir| dir.join(&filename))
+            .find(|path| path.is_file())
+            .map(|program| Self::new(name.to_string(), program, format))
+            .ok_or_else(|| Error::Other("capture source not found in PATH", filename))
+    }
+
+    /// Run the executable with the additional environment variables `envs`,
+    /// reporting its stderr as progress, and returning its stdout
+    ///
+    /// Only the command name is logged, as the environment variables may
+    /// include SAS URLs.
+    async fn run(
+        &self,
+        args: &[&str],
+        envs: &[(&str, &str)],
+        progress: Progress<'_>,
+    ) -> Result<Vec<u8>> {
+        let command = args.first().copied().unwrap_or_default();
+        debug!("running {} {command}", self.program.display());
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .envs(envs.iter().copied())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| io_err(format!("launching {}", self.program.display()), e))?;
+
+        let stderr = child.stderr.take();
+        let report = async {
+            if let Some(stderr) = stderr {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    progress(&line);
+                }
+            }
+        };
+        let (output, ()) = tokio::join!(child.wait_with_output(), report);
+        let output =
+            output.map_err(|e| io_err(format!("running {}", self.program.display()), e))?;
+        if !output.status.success() {
+            return Err(Error::Other(
+                "capture source failed",
+                format!("{} {command}: {}", self.name, output.status),
+            ));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl CaptureSource for ExternalSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<CaptureTarget>>> {
+        Box::pin(async {
+            let progress = |line: &str| debug!("{}: {line}", self.name);
+            let output = self.run(&["list"], &[], &progress).await?;
+            Ok(serde_json::from_slice(&output)?)
+        })
+    }
+
+    fn capture<'a>(
+        &'a self,
+        target: &'a str,
+        upload_url: &'a Url,
+        progress: Progress<'a>,
+    ) -> BoxFuture<'a, Result<Capture>> {
+        Box::pin(async move {
+            let envs = [(UPLOAD_URL_ENV, upload_url.as_str())];
+            let output = self.run(&["capture", target], &envs, progress).await?;
+            Ok(serde_json::from_slice(&output)?)
+        })
+    }
+}
+
+/// Commands implemented by `freta-capture-<name>` executables
+#[derive(Subcommand, Debug, Clone)]
+pub enum PluginCommand {
+    /// List the targets that can be captured, as JSON
+    List,
+    /// Capture a target, printing the result as JSON
+    Capture {
+        /// name of the target
+        target: String,
+
+        #[arg(long, env = UPLOAD_URL_ENV, hide_env_values = true)]
+        /// SAS URL the image may be uploaded to directly.  provided by `freta` using the environment, such that it is not visible in the list of processes
+        upload_url: Url,
+    },
+}
+
+/// Run a plugin command using `source`, such that a program can be used as a
+/// `freta-capture-<name>` executable
+///
+/// Progress is written to stderr, and results are written to stdout as JSON.
+///
+/// # Errors
+/// This will return an error if the command fails or the result cannot be
+/// written to stdout
+pub async fn run_plugin(source: &dyn CaptureSource, command: PluginCommand) -> Result<()> {
+    let output = match command {
+        PluginCommand::List => serde_json::to_vec(&source.list_targets().await?)?,
+        PluginCommand::Capture { target, upload_url } => {
+            let progress = |message: &str| info!("{message}");
+            serde_json::to_vec(&source.capture(&target, &upload_url, &progress).await?)?
+        }
+    };
+    let mut stdout = stdout();
+    stdout
+        .write_all(&output)
+        .await
+        .map_err(|e| io_err("writing to stdout", e))?;
+    stdout
+        .flush()
+        .await
+        .map_err(|e| io_err("writing to stdout", e))
+}
+
+impl Client {
+    /// Capture the memory of a target using `source`, and upload it as a new
+    /// image
+    ///
+    /// The image is created prior to the capture, such that sources can
+    /// upload the image directly.  Returns the image once it has been
+    /// uploaded.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. Creating the image fails
+    /// 2. The capture fails
+    /// 3. Uploading the captured image fails
+    /// 4. Cleaning up after the capture fails
+    pub async fn capture<T, K, V>(
+        &self,
+        source: &dyn CaptureSource,
+        target: &str,
+        tags: T,
+        options: &UploadOptions,
+        progress: Progress<'_>,
+    ) -> Result<Image>
+    where
+        T: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let image = self
+            .images_create_with_options(source.format(), tags, options)
+            .await?;
+        info!(
+            "capturing {target} from {} as image id: {}",
+            source.name(),
+            image.image_id
+        );
+        let upload_url = image.image_url.clone().ok_or(Error::InvalidResponse(
+            "missing image_url from the response",
+        ))?;
+
+        let capture = source.capture(target, &upload_url, progress).await?;
+        let uploaded = self.capture_upload(&image, &capture, options).await;
+
+        if let Capture::File {
+            path,
+            remove_after_upload: true,
+        } = &capture
+        {
+            if let Err(err) = remove_file(path).await {
+                warn!("unable to remove {}: {err}", path.display());
+            }
+        }
+        let cleanup = source.cleanup(target, &capture).await;
+
+        uploaded?;
+        cleanup?;
+        Ok(image)
+    }
+
+    /// Upload the result of a capture to the image, if the source did not
+    /// upload it directly
+    async fn capture_upload(
+        &self,
+        image: &Image,
+        capture: &Capture,
+        options: &UploadOptions,
+    ) -> Result<()> {
+        match capture {
+            Capture::File { path, .. } => {
+                let handle = open_file(path).await?;
+                let stats = self
+                    .images_upload_file(image, handle, options, None)
+                    .await?;
+                info!("uploaded {}: {stats}", path.display());
+            }
+            Capture::Uploaded => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_serialization() -> Result<()> {
+        let file: Capture = serde_json::from_str(r#"{"type": "file", "path": "/tmp/vm.raw"}"#)?;
+        assert_eq!(
+            file,
+            Capture::File {
+                path: PathBuf::from("/tmp/vm.raw"),
+                remove_after_upload: false,
+            }
+        );
+        let uploaded: Capture = serde_json::from_str(r#"{"type": "uploaded"}"#)?;
+        assert_eq!(uploaded, Capture::Uploaded);
+        Ok(())
+    }
+}
@@ -1,9 +1,98 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
+//! Helpers for reading and writing local files
+//!
+//! `read_chunks` visits the contents of large files, such as memory
+//! snapshots, without reading the entire file into memory.  When the `mmap`
+//! feature is enabled, files are memory mapped rather than read using
+//! buffered reads, which avoids copying the contents of the file.
+
 use crate::{client::error::io_err, Result};
 use serde::{de::DeserializeOwned, Serialize};
-use std::path::Path;
+use std::{
+    io::{BufReader, Read},
+    path::Path,
+};
 use tokio::fs;
+#[cfg(feature = "mmap")]
+use tracing::debug;
+
+/// Default size of the chunks provided by `read_chunks`
+pub const DEFAULT_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Visit the contents of a file in order, in chunks of at most `chunk_size`
+/// bytes
+///
+/// When the `mmap` feature is enabled, the file is memory mapped.  If the
+/// file cannot be mapped, such as pipes or some network filesystems, the file
+/// is read using buffered reads instead.
+///
+/// This performs blocking IO.  From async code, call this using
+/// `tokio::task::spawn_blocking`.
+///
+/// ```rust,no_run
+/// # use freta::{io::{read_chunks, DEFAULT_CHUNK_SIZE}, Result};
+/// # fn example() -> Result<()> {
+/// let mut zeros = 0;
+/// read_chunks("image.lime", DEFAULT_CHUNK_SIZE, |chunk| {
+///     zeros += chunk.iter().filter(|x| **x == 0).count();
+///     Ok(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+/// This will return an error if the file cannot be read or if `visit`
+/// returns an error
+pub fn read_chunks<P, F>(path: P, chunk_size: usize, mut visit: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(&[u8]) -> Result<()>,
+{
+    let path = path.as_ref();
+    let chunk_size = chunk_size.max(1);
+    let file =
+        std::fs::File::open(path).map_err(|e| io_err(format!("opening file: {path:?}"), e))?;
+
+    #[cfg(feature = "mmap")]
+    match map_file(&file) {
+        Ok(mapped) => {
+            for chunk in mapped.chunks(chunk_size) {
+                visit(chunk)?;
+            }
+            return Ok(());
+        }
+        Err(e) => debug!("unable to memory map {path:?}, using buffered reads: {e}"),
+    }
+
+    let mut reader = BufReader::new(file);
+    let mut chunk = Vec::with_capacity(chunk_size);
+    loop {
+        chunk.clear();
+        let read = reader
+            .by_ref()
+            .take(chunk_size as u64)
+            .read_to_end(&mut chunk)
+            .map_err(|e| io_err(format!("reading file: {path:?}"), e))?;
+        if read == 0 {
+            return Ok(());
+        }
+        visit(&chunk)?;
+    }
+}
+
+/// Memory map a file for reading
+#[cfg(feature = "mmap")]
+#[allow(unsafe_code)]
+fn map_file(file: &std::fs::File) -> std::io::Result<memmap2::Mmap> {
+    // SAFETY: the mapping is read-only and does not outlive the call to
+    // `read_chunks`.  If another process modifies the file while it is
+    // mapped, the changes may be visible in the chunks, as they would be
+    // when using buffered reads.  Truncating the file while it is mapped
+    // terminates the process, which is why this is opt-in.
+    unsafe { memmap2::Mmap::map(file) }
+}
 
 /// Read and deserialize a JSON file
 pub(crate) async fn read_json<P, S>(path: P) -> Result<S>
@@ -65,3 +154,33 @@ where
         .await
         .map_err(|e| io_err(format!("opening file: {path:?}"), e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_read_chunks() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("freta-read-chunks-{}", std::process::id()));
+        let contents = (0..=255).cycle().take(1000).collect::<Vec<u8>>();
+        std::fs::File::create(&path)
+            .and_then(|mut file| file.write_all(&contents))
+            .map_err(|e| io_err("writing test file", e))?;
+
+        let mut chunks = vec![];
+        let result = read_chunks(&path, 300, |chunk| {
+            chunks.push(chunk.to_vec());
+            Ok(())
+        });
+        std::fs::remove_file(&path).map_err(|e| io_err("removing test file", e))?;
+        result?;
+
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![300, 300, 300, 100]
+        );
+        assert_eq!(chunks.concat(), contents);
+        Ok(())
+    }
+}
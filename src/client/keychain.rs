@@ -0,0 +1,112 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Storage of secrets in the keychain of the operating system
+//!
+//! With the `keyring` feature, secrets are stored using the Windows
+//! Credential Manager, the macOS Keychain, or the Secret Service (such as
+//! `gnome-keyring`) on Linux.  If the feature is disabled or no keychain is
+//! available, nothing is stored, and callers fall back to storing secrets in
+//! files in `~/.config/freta/`.
+
+#[cfg(feature = "keyring")]
+use crate::client::error::Error;
+use crate::client::error::Result;
+#[cfg(feature = "keyring")]
+use tracing::{debug, warn};
+
+/// Name of the entry used for the login cache
+pub(crate) const LOGIN_CACHE: &str = "login.cache";
+
+/// Name of the entry used for `Config::client_secret`
+pub(crate) const CLIENT_SECRET: &str = "client_secret";
+
+/// Name of the entry used for `Config::pat`
+pub(crate) const PAT: &str = "pat";
+
+/// Service under which the entries are stored
+#[cfg(feature = "keyring")]
+const SERVICE: &str = "freta";
+
+/// Run a blocking keychain operation, as the keychains of some platforms are
+/// accessed using blocking IPC
+#[cfg(feature = "keyring")]
+async fn run<F, T>(name: &'static str, op: F) -> Result<T>
+where
+    F: FnOnce(&keyring::Entry) -> T + Send + 'static,
+    T: Default + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || match keyring::Entry::new(SERVICE, name) {
+        Ok(entry) => op(&entry),
+        Err(err) => {
+            debug!("keychain unavailable for {name}: {err}");
+            T::default()
+        }
+    })
+    .await
+    .map_err(|e| Error::Other("accessing the keychain", e.to_string()))
+}
+
+/// Read a secret from the keychain
+///
+/// Returns `None` if the secret is not stored in the keychain or the keychain
+/// is unavailable.
+#[cfg(feature = "keyring")]
+pub(crate) async fn get(name: &'static str) -> Result<Option<String>> {
+    run(name, move |entry| match entry.get_password() {
+        Ok(secret) => Some(secret),
+        Err(keyring::Error::NoEntry) => None,
+        Err(err) => {
+            debug!("unable to read {name} from the keychain: {err}");
+            None
+        }
+    })
+    .await
+}
+
+/// Store a secret in the keychain
+///
+/// Returns false if the keychain is unavailable, in which case the caller is
+/// expected to store the secret elsewhere.
+#[cfg(feature = "keyring")]
+pub(crate) async fn set(name: &'static str, secret: String) -> Result<bool> {
+    run(name, move |entry| match entry.set_password(&secret) {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("unable to store {name} in the keychain, storing it in a file: {err}");
+            false
+        }
+    })
+    .await
+}
+
+/// Remove a secret from the keychain, if it is stored there
+#[cfg(feature = "keyring")]
+pub(crate) async fn delete(name: &'static str) -> Result<()> {
+    run(name, move |entry| match entry.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(err) => warn!("unable to remove {name} from the keychain: {err}"),
+    })
+    .await
+}
+
+/// Read a secret from the keychain
+///
+/// Without the `keyring` feature, secrets are never stored in the keychain.
+#[cfg(not(feature = "keyring"))]
+pub(crate) async fn get(_name: &'static str) -> Result<Option<String>> {
+    Ok(None)
+}
+
+/// Store a secret in the keychain
+///
+/// Without the `keyring` feature, the keychain is always unavailable.
+#[cfg(not(feature = "keyring"))]
+pub(crate) async fn set(_name: &'static str, _secret: String) -> Result<bool> {
+    Ok(false)
+}
+
+/// Remove a secret from the keychain, if it is stored there
+#[cfg(not(feature = "keyring"))]
+pub(crate) async fn delete(_name: &'static str) -> Result<()> {
+    Ok(())
+}
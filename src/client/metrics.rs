@@ -0,0 +1,98 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! When enabled, the client records the number of requests to the Freta
+//! service, their outcome and latency, and the volume of data transferred to
+//! and from Azure Blob Storage using the `metrics` crate facade.
+//!
+//! Metrics are only aggregated by the recorder installed by the application,
+//! such as `metrics-exporter-prometheus`.  Nothing is sent anywhere by the
+//! client itself.  Labels only include the HTTP method, the outcome of
+//! requests, and the direction of transfers, such that metrics do not
+//! identify users, images, or endpoints.
+//!
+//! ```rust,no_run
+//! # fn install_recorder() {}
+//! install_recorder();
+//! freta::metrics::describe();
+//! ```
+
+use crate::TransferStats;
+use ::metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
+use std::time::Duration;
+
+/// Requests sent to the Freta service, labeled by `method` and `outcome`
+pub const REQUESTS: &str = "freta_client_requests_total";
+
+/// Duration of requests sent to the Freta service, labeled by `method`
+pub const REQUEST_DURATION: &str = "freta_client_request_duration_seconds";
+
+/// Completed uploads and downloads, labeled by `direction`
+pub const TRANSFERS: &str = "freta_client_transfers_total";
+
+/// Bytes uploaded or downloaded, labeled by `direction`
+pub const TRANSFER_BYTES: &str = "freta_client_transfer_bytes_total";
+
+/// Direction of a transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    /// Data sent to Azure Blob Storage
+    Upload,
+    /// Data received from Azure Blob Storage
+    Download,
+}
+
+impl Direction {
+    /// The direction as used in the `direction` label
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Upload => "upload",
+            Self::Download => "download",
+        }
+    }
+}
+
+/// Register the descriptions and units of the metrics with the installed
+/// recorder
+///
+/// This is optional, and should be called after installing the recorder.
+pub fn describe() {
+    describe_counter!(REQUESTS, Unit::Count, "requests sent to the Freta service");
+    describe_histogram!(
+        REQUEST_DURATION,
+        Unit::Seconds,
+        "duration of requests sent to the Freta service"
+    );
+    describe_counter!(TRANSFERS, Unit::Count, "completed uploads and downloads");
+    describe_counter!(
+        TRANSFER_BYTES,
+        Unit::Bytes,
+        "bytes uploaded to or downloaded from Azure Blob Storage"
+    );
+}
+
+/// Outcome of a request, as used in the `outcome` label
+fn outcome(result: &Result<reqwest::Response, reqwest::Error>) -> &'static str {
+    match result {
+        Ok(res) if res.status().is_server_error() => "server_error",
+        Ok(res) if res.status().is_client_error() => "client_error",
+        Ok(_) => "success",
+        Err(err) if err.is_timeout() => "timeout",
+        Err(_) => "connection_error",
+    }
+}
+
+/// Record an attempt to send a request to the Freta service
+pub(crate) fn record_request(
+    method: &reqwest::Method,
+    result: &Result<reqwest::Response, reqwest::Error>,
+    elapsed: Duration,
+) {
+    counter!(REQUESTS, 1, "method" => method.to_string(), "outcome" => outcome(result));
+    histogram!(REQUEST_DURATION, elapsed, "method" => method.to_string());
+}
+
+/// Record a completed upload or download
+pub(crate) fn record_transfer(direction: Direction, stats: &TransferStats) {
+    counter!(TRANSFERS, 1, "direction" => direction.as_str());
+    counter!(TRANSFER_BYTES, stats.bytes, "direction" => direction.as_str());
+}
@@ -4,51 +4,123 @@
 pub mod argparse;
 /// HTTP client used by the client
 pub(crate) mod backend;
+/// Detect drift in the analysis of images relative to a baseline
+mod baseline;
+/// local artifact cache
+pub(crate) mod cache;
+/// Failure injection for testing error handling
+#[cfg(feature = "chaos")]
+pub mod chaos;
+/// Report analysis outcomes to CI systems
+#[cfg(feature = "ci")]
+pub mod ci;
+/// Building blocks for command line interfaces to the service
+pub mod cli;
+/// Compress images while they are uploaded
+pub(crate) mod compress;
 /// client config
 pub(crate) mod config;
+/// Estimate the cost of images
+mod cost;
+/// Digests used to verify downloaded data
+pub(crate) mod digest;
 /// client error types
 pub(crate) mod error;
-/// internal IO wrappers
-pub(crate) mod io;
+/// Export image metadata for analytics
+pub mod export;
+/// Capture memory images from hypervisors and cloud providers
+pub mod integrations;
+/// local file IO helpers
+pub mod io;
+/// Storage of secrets in the keychain of the operating system
+mod keychain;
+/// Record metrics of requests and transfers using the `metrics` crate
+#[cfg(feature = "metrics")]
+pub mod metrics;
+/// Stream records from endpoints that return results in pages
+pub mod paginate;
+/// Automatically act on images once their analysis completes
+mod policies;
+/// Reporting the progress of uploads and downloads
+pub(crate) mod progress;
+/// Periodically act on images
+mod schedules;
+/// Manage personal access tokens
+mod tokens;
+/// Progress of resumable uploads
+pub(crate) mod uploads;
 
 use crate::{
     client::{
         backend::{
             azure_blobs::{
-                blob_download, blob_get, blob_upload, container_blob_download, container_client,
+                blob_container_sas, blob_download, blob_get, blob_upload, container_blob_download,
+                container_blob_sas, sas_permits_list, sas_permits_write, SasUrl,
             },
             Backend,
         },
+        cache::{Cache, CacheStats, CachedInfo},
+        compress::compressed_lime_reader,
         config::Config,
-        error::{Error, Result},
-        io::open_file,
+        digest::{sha256_file, sha256_hex, verify_sha256},
+        error::{io_err, Error, Result},
+        io::{create_dir_all, open_file, remove_file},
+        paginate::paginate,
+        progress::{AnalysisProgressBar, NoProgress, ProgressBarHandler, ProgressHandler},
+        uploads::UploadState,
     },
     models::{
-        base::{Image, ImageFormat, ImageId, ImageState, OwnerId},
+        analysis::{
+            report::{KernelModule, Report, Task},
+            symbols::{SymbolLayout, SYMBOLS_PREFIX},
+        },
+        artifacts::{join_relative, ArtifactFilter, REPORT_JSON},
+        base::{Image, ImageFormat, ImageId, ImageState, OwnerId, IMAGE_SHA256_TAG},
+        collections::{
+            Collection, CollectionAddImage, CollectionCreate, CollectionName,
+            CollectionsListRequest, CollectionsListResponse,
+        },
         service::{
-            ImageCreate, ImageDeleteResponse, ImageList, ImageReanalyzeResponse, ImageUpdate,
-            ImagesListResponse, Info, UserConfig, UserConfigUpdateResponse,
+            ImageCreate, ImageDeleteResponse, ImageList, ImageReanalyze, ImageReanalyzeResponse,
+            ImageUpdate, ImageUploadCompleteResponse, ImagesListResponse, Info, UserConfig,
+            UserConfigUpdateResponse,
         },
         webhooks::{
             service::{
                 WebhookBoolResponse, WebhookEventReplayRequest, WebhookLogListRequest,
-                WebhookLogListResponse, WebhookSubmit, WebhooksListRequest, WebhooksListResponse,
+                WebhookLogListResponse, WebhookPingRequest, WebhookSubmit, WebhooksListRequest,
+                WebhooksListResponse,
             },
-            Webhook, WebhookEvent, WebhookEventId, WebhookEventType, WebhookId, WebhookLog,
+            validate_url_template, Webhook, WebhookEvent, WebhookEventId, WebhookEventType,
+            WebhookId, WebhookLog, WebhookTarget,
         },
     },
     Secret,
 };
 use bytes::Bytes;
-use futures::{Stream, StreamExt};
+use futures::{
+    future::{join_all, ready},
+    stream, Stream, StreamExt, TryStreamExt,
+};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
     collections::{BTreeMap, BTreeSet},
-    path::Path,
+    fmt::{Display, Formatter},
+    io::SeekFrom,
+    path::{Path, PathBuf},
     pin::Pin,
+    sync::Arc,
     time::Duration,
 };
-use tokio::time::sleep;
-use tracing::{debug, info};
+use time::OffsetDateTime;
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncSeekExt},
+    sync::Mutex,
+    time::{sleep, Instant},
+};
+use tracing::{debug, info, warn};
 use url::Url;
 
 /// convert an `Iterator` of key/value pairs into a `BTreeMap`
@@ -65,14 +137,526 @@ where
         .collect()
 }
 
-/// interval for polling image status
+/// default interval for polling image status
 const IMAGE_MONITOR_INTERVAL: Duration = Duration::from_secs(1);
 
+/// default interval for logging that an image remains in the same state
+const IMAGE_MONITOR_HEARTBEAT: Duration = Duration::from_secs(60);
+
+/// rough estimate of the analysis throughput, in bytes per second, used to
+/// estimate the remaining time for an analysis
+const ESTIMATED_ANALYSIS_BYTES_PER_SEC: u64 = 50 * 1024 * 1024;
+
+/// default maximum time a transfer may go without progress before the
+/// in-flight request is retried
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// how long service information is reused by `Client::info_cached`
+const INFO_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// number of artifacts downloaded concurrently by
+/// `Client::artifacts_download_all`
+const ARTIFACT_DOWNLOAD_CONCURRENCY: usize = 8;
+
+/// files larger than this use adaptive chunking unless otherwise specified
+const ADAPTIVE_CHUNKING_THRESHOLD: u64 = 1024 * 1024 * 1024 * 10;
+
+/// Options for uploading images
+#[derive(Debug, Clone)]
+pub struct UploadOptions {
+    /// Maximum time an in-flight block may go without sending data, or
+    /// without a response once sent, before considering the connection
+    /// stalled and retrying the block.
+    ///
+    /// If `None`, stalled connections are not detected.
+    pub stall_timeout: Option<Duration>,
+
+    /// Adjust the size of uploaded blocks based on the observed throughput.
+    ///
+    /// If `None`, adaptive chunking is used for files larger than 10 GiB.
+    pub adaptive_chunking: Option<bool>,
+
+    /// URL notified of the events for the uploaded image.  See
+    /// `Client::images_create_with_callback`.
+    pub callback_url: Option<Url>,
+
+    /// Save the progress of the upload in `~/.config/freta/uploads/`, and
+    /// continue an earlier upload of the same file if one was interrupted.
+    ///
+    /// When an upload is continued, the format, tags, and callback URL of the
+    /// earlier upload are used.  The service does not issue new SAS URLs to
+    /// upload existing images, such that the SAS URL of the earlier upload is
+    /// saved with its progress, and continuing the upload fails once it
+    /// expired.
+    pub resume: bool,
+
+    /// Number of blocks uploaded concurrently.  Each in-flight block is held
+    /// in memory.
+    ///
+    /// If `None`, `Config.upload_concurrency` is used.
+    pub concurrency: Option<usize>,
+
+    /// Version of the analysis engine to analyze the uploaded image with.
+    /// See `Client::images_create_with_options`.
+    pub analysis_version: Option<String>,
+
+    /// Receives the progress of the upload.
+    ///
+    /// If `None`, a progress bar is displayed on stderr.
+    pub progress: Option<Arc<dyn ProgressHandler>>,
+
+    /// Compress `LiME` images while uploading them, as written by AVML with
+    /// `--compress`, if the service supports the `lime` format.
+    ///
+    /// Compressed uploads cannot be resumed, such that `resume` is ignored.
+    /// Progress is reported in compressed bytes, relative to the size of the
+    /// uncompressed image.
+    pub compress: bool,
+}
+
+impl UploadOptions {
+    /// Should adaptive chunking be used for a file of `size` bytes
+    #[must_use]
+    pub fn adaptive_chunking(&self, size: u64) -> bool {
+        self.adaptive_chunking
+            .unwrap_or(size > ADAPTIVE_CHUNKING_THRESHOLD)
+    }
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            stall_timeout: Some(DEFAULT_STALL_TIMEOUT),
+            adaptive_chunking: None,
+            callback_url: None,
+            resume: false,
+            concurrency: None,
+            analysis_version: None,
+            progress: None,
+            compress: false,
+        }
+    }
+}
+
+/// Statistics for a completed upload or download
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransferStats {
+    /// Number of bytes transferred
+    pub bytes: u64,
+    /// Total time spent transferring the data
+    pub duration: Duration,
+    /// Average throughput, in bytes per second
+    pub avg_throughput: f64,
+    /// Number of times an in-flight request was retried
+    pub retries: usize,
+    /// Number of blocks (for uploads) or chunks (for downloads) transferred
+    pub blocks: usize,
+    /// Hex encoded SHA-256 digest of the transferred data, if all of the
+    /// data was transferred.  Resumed uploads do not include the digest, as
+    /// the data uploaded before resuming is not read again.
+    pub sha256: Option<String>,
+}
+
+impl TransferStats {
+    /// Create `TransferStats`, calculating the average throughput
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(bytes: u64, duration: Duration, retries: usize, blocks: usize) -> Self {
+        let seconds = duration.as_secs_f64();
+        let avg_throughput = if seconds > 0.0 {
+            bytes as f64 / seconds
+        } else {
+            0.0
+        };
+        Self {
+            bytes,
+            duration,
+            avg_throughput,
+            retries,
+            blocks,
+            sha256: None,
+        }
+    }
+
+    /// Set the digest of the transferred data
+    #[must_use]
+    pub fn with_sha256(self, sha256: String) -> Self {
+        Self {
+            sha256: Some(sha256),
+            ..self
+        }
+    }
+
+    /// Combine the statistics of multiple transfers that occurred
+    /// concurrently over the specified `duration`
+    #[must_use]
+    pub fn combine<'a, I>(stats: I, duration: Duration) -> Self
+    where
+        I: IntoIterator<Item = &'a Self>,
+    {
+        let (bytes, retries, blocks) = stats
+            .into_iter()
+            .fold((0, 0, 0), |(bytes, retries, blocks), x| {
+                (bytes + x.bytes, retries + x.retries, blocks + x.blocks)
+            });
+        Self::new(bytes, duration, retries, blocks)
+    }
+}
+
+impl Display for TransferStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "transferred {} bytes in {:.1}s ({:.0} bytes/sec, {} blocks, {} retries)",
+            self.bytes,
+            self.duration.as_secs_f64(),
+            self.avg_throughput,
+            self.blocks,
+            self.retries
+        )
+    }
+}
+
+/// An artifact extracted from an image
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ArtifactEntry {
+    /// Name of the artifact
+    pub name: String,
+    /// Size of the artifact in bytes
+    pub size: u64,
+    /// Content type of the artifact
+    pub content_type: String,
+    /// When the artifact was last modified
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_modified: OffsetDateTime,
+}
+
+/// An artifact downloaded to a file, along with its digest such that the
+/// exact content that was downloaded can be recorded
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct DownloadedArtifact {
+    /// Name of the artifact
+    pub name: String,
+    /// Path of the downloaded file
+    pub path: PathBuf,
+    /// Size of the artifact in bytes
+    pub size: u64,
+    /// Hex encoded SHA-256 digest of the artifact
+    pub sha256: String,
+}
+
+/// A local copy of an image that matches the digest recorded when the image
+/// was uploaded
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct VerifiedImage {
+    /// Unique identifier of the image
+    pub image_id: ImageId,
+    /// Path of the verified file
+    pub path: PathBuf,
+    /// Hex encoded SHA-256 digest of the image
+    pub sha256: String,
+}
+
+impl ArtifactEntry {
+    /// Is the artifact textual content, such as JSON or plain text
+    #[must_use]
+    pub fn is_text(&self) -> bool {
+        let content_type = self
+            .content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim();
+        content_type.starts_with("text/")
+            || content_type == "application/json"
+            || content_type.ends_with("+json")
+    }
+}
+
+/// A blob stored as part of an image
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ImageBlob {
+    /// Name of the blob
+    pub name: String,
+    /// Size of the blob in bytes
+    pub size: u64,
+    /// `ETag` of the blob
+    pub etag: String,
+    /// When the blob was last modified
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_modified: OffsetDateTime,
+}
+
+/// Options for monitoring the analysis of an image
+#[derive(Debug, Clone)]
+pub struct MonitorOptions {
+    /// How often to log that the image remains in the same state.
+    ///
+    /// If `None`, only state changes are logged.
+    pub heartbeat: Option<Duration>,
+
+    /// Size of the image in bytes, if known.
+    ///
+    /// This is used to estimate the remaining time while the image is being
+    /// analyzed.
+    pub image_size: Option<u64>,
+
+    /// How often to poll the state of the image
+    pub interval: Duration,
+
+    /// Maximum interval between polls.
+    ///
+    /// If set, the interval doubles each time the image remains in the same
+    /// state, up to this maximum, and is reset when the state changes.
+    pub max_interval: Option<Duration>,
+
+    /// How long to wait before giving up, if at all
+    pub timeout: Option<Duration>,
+
+    /// Display a progress bar on stderr while the service reports the
+    /// progress of the analysis
+    pub show_progress: bool,
+}
+
+impl Default for MonitorOptions {
+    fn default() -> Self {
+        Self {
+            heartbeat: Some(IMAGE_MONITOR_HEARTBEAT),
+            image_size: None,
+            interval: IMAGE_MONITOR_INTERVAL,
+            max_interval: None,
+            timeout: None,
+            show_progress: false,
+        }
+    }
+}
+
+impl MonitorOptions {
+    /// Interval before the next poll of an image that remained in the same
+    /// state since the previous poll
+    fn backoff(&self, interval: Duration) -> Duration {
+        match self.max_interval {
+            Some(max_interval) if max_interval > interval => {
+                interval.saturating_mul(2).min(max_interval)
+            }
+            _ => interval,
+        }
+    }
+
+    /// Estimate the remaining time for an image that has been in the
+    /// `Running` state for `elapsed`
+    fn estimate_remaining(&self, state: &ImageState, elapsed: Duration) -> Option<Duration> {
+        if *state != ImageState::Running {
+            return None;
+        }
+        let estimate = Duration::from_secs(self.image_size? / ESTIMATED_ANALYSIS_BYTES_PER_SEC);
+        Some(estimate.saturating_sub(elapsed))
+    }
+}
+
+/// Result of a single check performed before uploading an image
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightCheck {
+    /// Name of the check
+    pub name: &'static str,
+    /// Whether the check passed
+    pub passed: bool,
+    /// Description of the outcome of the check
+    pub message: String,
+}
+
+impl PreflightCheck {
+    /// Record the outcome of a check
+    fn new<M>(name: &'static str, passed: bool, message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self {
+            name,
+            passed,
+            message: message.into(),
+        }
+    }
+}
+
+/// Results of the checks performed before uploading an image
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightReport {
+    /// Results of the individual checks
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// Returns true if all of the checks passed
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    /// Convert the report into an error if any of the checks failed
+    ///
+    /// # Errors
+    /// This will return an error describing each of the failed checks
+    pub fn into_result(self) -> Result<Self> {
+        if self.passed() {
+            return Ok(self);
+        }
+        let failures = self
+            .checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| format!("{}: {}", check.name, check.message))
+            .collect::<Vec<_>>();
+        Err(Error::PreflightFailed(failures.join("; ").into()))
+    }
+}
+
+/// Whether the current EULA of the service has been accepted
+#[derive(Debug, Clone, Serialize)]
+pub struct EulaStatus {
+    /// Checksum of the current EULA
+    pub current_checksum: String,
+    /// Checksum of the EULA accepted by the user, if any
+    pub accepted_checksum: Option<String>,
+    /// Whether the accepted EULA is the current EULA
+    pub accepted: bool,
+}
+
+impl EulaStatus {
+    /// Convert the status into an error if the current EULA is not accepted
+    ///
+    /// # Errors
+    /// This will return `Error::Eula` if the current EULA is not accepted
+    pub fn into_result(self) -> Result<Self> {
+        if self.accepted {
+            Ok(self)
+        } else {
+            Err(Error::Eula(
+                "current EULA not accepted.  run `freta eula accept`".to_string(),
+            ))
+        }
+    }
+}
+
+/// Result of sending a synthetic event of a single type to a webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPingResult {
+    /// Type of the synthetic event
+    pub event_type: WebhookEventType,
+    /// Whether the service sent an event of the requested type to the webhook
+    pub delivered: bool,
+    /// Response from the service, or the reason the event was not delivered
+    pub message: String,
+}
+
+impl WebhookPingResult {
+    /// Check that the event returned by the service for a ping is of the
+    /// requested type
+    ///
+    /// Versions of the service that do not support sending events of other
+    /// types send a `ping` event instead.
+    fn new(event_type: WebhookEventType, response: Result<Bytes>) -> Self {
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                return Self {
+                    event_type,
+                    delivered: false,
+                    message: err.to_string(),
+                }
+            }
+        };
+        let message = String::from_utf8_lossy(&response).to_string();
+        match serde_json::from_slice::<WebhookEvent>(&response) {
+            Ok(event) if event.event_type == event_type => Self {
+                event_type,
+                delivered: true,
+                message,
+            },
+            Ok(event) => Self {
+                message: format!(
+                    "the service sent a {} event rather than {}",
+                    event.event_type.as_str(),
+                    event_type.as_str()
+                ),
+                event_type,
+                delivered: false,
+            },
+            Err(err) => Self {
+                event_type,
+                delivered: false,
+                message: format!("unable to parse the event sent by the service: {err}"),
+            },
+        }
+    }
+}
+
+/// Results of sending synthetic events to a single webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPingResults {
+    /// Unique identifier for the webhook
+    pub webhook_id: WebhookId,
+    /// Results for each of the event types sent to the webhook
+    pub results: Vec<WebhookPingResult>,
+}
+
+impl WebhookPingResults {
+    /// Returns true if all of the events were delivered
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.delivered)
+    }
+}
+
+/// Notice from the service that an endpoint is deprecated
+///
+/// These are parsed from the `Deprecation` and `Sunset` headers of responses.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Deprecation {
+    /// HTTP method of the deprecated request
+    pub method: String,
+    /// Path of the deprecated endpoint
+    pub path: String,
+    /// Value of the `Deprecation` header, describing when the endpoint was or
+    /// will be deprecated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecation: Option<String>,
+    /// Value of the `Sunset` header, describing when the endpoint will stop
+    /// responding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sunset: Option<String>,
+}
+
+/// Round-trip latency to the Freta service
+#[derive(Debug, Clone, Copy)]
+pub struct Latency {
+    /// Round-trip time of an authenticated request, including the time spent
+    /// acquiring an authentication token
+    pub authenticated: Duration,
+    /// Round-trip time of an unauthenticated request
+    pub unauthenticated: Duration,
+}
+
+/// Get the canonical path and size of a file, which identify the file when
+/// resuming an upload
+fn upload_identity(path: &Path) -> Result<(PathBuf, u64)> {
+    let path = path
+        .canonicalize()
+        .map_err(|e| io_err(format!("resolving path: {}", path.display()), e))?;
+    let size = path
+        .metadata()
+        .map_err(|e| io_err(format!("reading file size: {}", path.display()), e))?
+        .len();
+    Ok((path, size))
+}
+
 #[derive(Debug)]
 /// Freta Client
 pub struct Client {
     /// Backend client
     backend: Backend,
+    /// Service information retained by `info_cached`
+    info_cache: Mutex<Option<CachedInfo>>,
 }
 
 impl Client {
@@ -94,7 +678,16 @@ impl Client {
     /// client fails
     pub async fn with_config(config: Config) -> Result<Self> {
         let backend = Backend::new(config).await?;
-        Ok(Self { backend })
+        Ok(Self {
+            backend,
+            info_cache: Mutex::new(None),
+        })
+    }
+
+    /// The configuration used by the client
+    #[must_use]
+    pub const fn config(&self) -> &Config {
+        self.backend.config()
     }
 
     /// logout of the service
@@ -107,6 +700,55 @@ impl Client {
         Ok(())
     }
 
+    /// Deprecation notices the service has sent in response to requests made
+    /// by this client
+    ///
+    /// Each notice is also logged as a warning when it is first received.
+    pub async fn deprecations(&self) -> Vec<Deprecation> {
+        self.backend.deprecations().await
+    }
+
+    /// Get the statistics for the local artifact cache
+    ///
+    /// # Errors
+    /// This function will return an error in the following cases:
+    /// 1. Loading the client configuration fails
+    /// 2. Reading the cache directory fails
+    pub async fn cache_stats() -> Result<CacheStats> {
+        let config = Config::load().await?;
+        Cache::new(&config)?.stats().await
+    }
+
+    /// Get the local artifact cache, if it is enabled
+    ///
+    /// The cache is best-effort, such that failing to locate it is logged and
+    /// treated as if the cache was disabled.
+    fn artifact_cache(&self) -> Option<Cache> {
+        if self.backend.config().cache_max_bytes == 0 {
+            return None;
+        }
+        match Cache::new(self.backend.config()) {
+            Ok(cache) => Some(cache),
+            Err(err) => {
+                warn!("unable to use the artifact cache: {err}");
+                None
+            }
+        }
+    }
+
+    /// Remove the cached artifacts for an image, logging any failures
+    ///
+    /// The cache is best-effort, such that failing to update it does not
+    /// cause the requested operation to fail.
+    async fn cache_invalidate(&self, image_id: ImageId) {
+        let Some(cache) = self.artifact_cache() else {
+            return;
+        };
+        if let Err(err) = cache.remove_image(image_id).await {
+            warn!("unable to remove cached artifacts for {image_id}: {err}");
+        }
+    }
+
     /// Retrieve user configuration settings
     ///
     /// # Errors
@@ -153,6 +795,29 @@ impl Client {
         Ok(res)
     }
 
+    /// Determine whether the current EULA has been accepted
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to get the service information
+    ///    or their configuration
+    pub async fn eula_status(&self) -> Result<EulaStatus> {
+        let info = self.info().await?;
+        let accepted_checksum = match self.user_config_get().await {
+            Ok(config) => config.eula_accepted,
+            Err(Error::Eula(_)) => None,
+            Err(err) => return Err(err),
+        };
+        let accepted = accepted_checksum.as_deref() == Some(info.current_eula.as_str());
+        Ok(EulaStatus {
+            current_checksum: info.current_eula,
+            accepted_checksum,
+            accepted,
+        })
+    }
+
     /// Retrieve information about the service
     ///
     /// # Errors
@@ -165,15 +830,148 @@ impl Client {
         Ok(res)
     }
 
+    /// Retrieve information about the service, reusing information retrieved
+    /// within the last minute
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to get the service information
+    pub async fn info_cached(&self) -> Result<Info> {
+        self.info_cached_with_ttl(INFO_CACHE_TTL).await
+    }
+
+    /// Retrieve information about the service, reusing information retrieved
+    /// within `ttl`
+    ///
+    /// Retrieved information is retained in memory and, if
+    /// `Config.persist_service_info` is set, on disk.  Information retrieved
+    /// from a different `api_url` is not reused.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to get the service information
+    pub async fn info_cached_with_ttl(&self, ttl: Duration) -> Result<Info> {
+        let config = self.backend.config();
+        let mut cached = self.info_cache.lock().await;
+
+        if cached.is_none() && config.persist_service_info {
+            match CachedInfo::load().await {
+                Ok(loaded) => *cached = loaded,
+                Err(err) => warn!("unable to load saved service information: {err}"),
+            }
+        }
+
+        if let Some(info) = cached
+            .as_ref()
+            .and_then(|cached| cached.get(&config.api_url, ttl))
+        {
+            return Ok(info.clone());
+        }
+
+        let info = self.info().await?;
+        let entry = CachedInfo::new(config.api_url.clone(), info.clone());
+        if config.persist_service_info {
+            if let Err(err) = entry.save().await {
+                warn!("unable to save service information: {err}");
+            }
+        }
+        *cached = Some(entry);
+        Ok(info)
+    }
+
+    /// Measure the round-trip latency to the service
+    ///
+    /// This measures both an authenticated and an unauthenticated request to
+    /// `/api/info`, which enables distinguishing authentication overhead from
+    /// network issues.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to get the service information
+    pub async fn ping(&self) -> Result<Latency> {
+        let unauthenticated_start = Instant::now();
+        self.backend.get_unauthenticated("/api/info").await?;
+        let unauthenticated = unauthenticated_start.elapsed();
+
+        let authenticated_start = Instant::now();
+        self.info().await?;
+        let authenticated = authenticated_start.elapsed();
+
+        Ok(Latency {
+            authenticated,
+            unauthenticated,
+        })
+    }
+
+    /// Send a request to an arbitrary route of the service
+    ///
+    /// This enables calling routes that are not otherwise supported by the
+    /// client, such as routes added by private deployments of the service.
+    /// The request uses the same authentication as the rest of the client.
+    /// `query` is serialized as the query string and `body` as the JSON body
+    /// of the request.  The response is deserialized from JSON.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Method, Result};
+    /// # use serde_json::Value;
+    /// # async fn example(client: Client) -> Result<()> {
+    /// let status: Value = client
+    ///     .request(Method::GET, "/api/custom/status", None::<()>, None::<()>)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The service responds with an error status
+    /// 3. The response cannot be deserialized
+    pub async fn request<Q, B, R>(
+        &self,
+        method: Method,
+        path: &str,
+        query: Option<Q>,
+        body: Option<B>,
+    ) -> Result<R>
+    where
+        Q: Serialize,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        self.backend.execute(method, path, query, body).await
+    }
+
     /// List available images
     ///
+    /// If `include_deleted` is true, tombstone records of deleted images are
+    /// included in the results.  Tombstone records have the state `Deleted`
+    /// and include the `deleted_at` timestamp of the image.  Like other
+    /// images, they include the `format` of the image, while the SAS URLs are
+    /// not provided.
+    ///
+    /// If `tags` is provided, only images with all of the specified tags are
+    /// included.  Tags are filtered as the images are listed, as the service
+    /// does not support filtering by tags.
+    ///
     /// # Example
     ///
     /// ```rust,no_run
     /// use futures::StreamExt;
     /// # use freta::{Client, Result};
+    /// # use std::collections::BTreeMap;
     /// # async fn example(client: Client) -> Result<()> {
-    /// let mut stream = client.images_list(None, None, None, true);
+    /// let tags = BTreeMap::from([("host".to_string(), "web-01".to_string())]);
+    /// let mut stream = client.images_list(None, None, None, true, false, Some(tags));
     /// while let Some(image) = stream.next().await {
     ///     let image = image?;
     ///     println!("{image:?}");
@@ -193,26 +991,25 @@ impl Client {
         owner_id: Option<OwnerId>,
         state: Option<ImageState>,
         include_samples: bool,
+        include_deleted: bool,
+        tags: Option<BTreeMap<String, String>>,
     ) -> Pin<Box<impl Stream<Item = std::result::Result<Image, crate::Error>> + Send + '_>> {
-        let mut image_list = ImageList {
+        let image_list = ImageList {
             image_id,
             owner_id,
             state,
             include_samples,
+            include_deleted,
+            tags,
             continuation: None,
         };
-        Box::pin(async_stream::try_stream! {
-            loop {
-                let result: ImagesListResponse = self.backend.get("/api/images", Some(&image_list)).await?;
-                for image in result.images {
-                    yield image;
-                }
-                image_list.continuation = result.continuation;
-                if image_list.continuation.is_none() {
-                    break;
-                }
-            }
-        })
+        let filter = image_list.clone();
+        let stream = paginate(image_list, move |image_list| async move {
+            let result: ImagesListResponse =
+                self.backend.get("/api/images", Some(&image_list)).await?;
+            Ok(result)
+        });
+        Box::pin(stream.try_filter(move |image| ready(filter.matches(image))))
     }
 
     /// Create a new image entry
@@ -222,6 +1019,9 @@ impl Client {
     /// that can be used to upload a memory snapshot to Freta via tools such as
     /// [azcopy](https://learn.microsoft.com/en-us/azure/storage/common/storage-ref-azcopy)
     ///
+    /// The `default_tags` from the client configuration are added to the
+    /// image, with the provided `tags` taking precedence.
+    ///
     /// # Errors
     ///
     /// This function will return an error in the following conditions:
@@ -233,53 +1033,577 @@ impl Client {
         K: Into<String>,
         V: Into<String>,
     {
-        let tags = as_tags(tags);
-        let create = ImageCreate { format, tags };
-        let res = self.backend.post("/api/images", create).await?;
-        Ok(res)
+        self.images_create_with_callback(format, tags, None).await
     }
 
-    /// Create and upload an image to Freta
+    /// Create a new image entry, notifying `callback_url` of the events for
+    /// the image
+    ///
+    /// This avoids managing a webhook for one-off integrations.  The callback
+    /// receives the same events as a webhook monitoring all event types, and
+    /// may include the `{event_type}` placeholder.  Older versions of the
+    /// service ignore the callback.
     ///
     /// # Errors
     ///
-    /// This function will return an error in the following cases:
-    /// 1. Creating the image in Freta fails
-    /// 2. Uploading the blob to Azure Storage fails
-    pub async fn images_upload<P, T, K, V>(
+    /// This function will return an error in the following conditions:
+    /// 1. The callback URL is not a valid URL template
+    /// 2. The connection to the Service fails
+    /// 3. The user does not have permission to create images.
+    pub async fn images_create_with_callback<T, K, V>(
         &self,
         format: ImageFormat,
         tags: T,
-        path: P,
+        callback_url: Option<Url>,
     ) -> Result<Image>
     where
-        P: AsRef<Path>,
         T: IntoIterator<Item = (K, V)>,
         K: Into<String>,
         V: Into<String>,
     {
-        debug!("uploading {}", path.as_ref().display());
-        let handle = open_file(path).await?;
-
-        let image = self.images_create(format, tags).await?;
-
-        info!("uploading as image id: {}", image.image_id);
-
-        let image_url = image.image_url.clone().ok_or(Error::InvalidResponse(
-            "missing image_url from the response",
-        ))?;
-        blob_upload(handle, image_url).await?;
-
-        Ok(image)
+        let options = UploadOptions {
+            callback_url,
+            ..UploadOptions::default()
+        };
+        self.images_create_with_options(format, tags, &options)
+            .await
     }
 
-    /// Get information on an image
+    /// Create a new image entry using the `callback_url` and
+    /// `analysis_version` from `options`
+    ///
+    /// If `analysis_version` is provided, the image is analyzed using the
+    /// specified version of the analysis engine rather than the current
+    /// version, such that results can be reproduced.  The version used is
+    /// reported by `Image.analysis_version` once the analysis completes.
     ///
     /// # Errors
     ///
     /// This function will return an error in the following conditions:
-    /// 1. The connection to the Service fails
-    /// 2. The user does not have permission to read the specified image
+    /// 1. The callback URL is not a valid URL template
+    /// 2. The connection to the Service fails
+    /// 3. The user does not have permission to create images.
+    /// 4. The service does not support the requested analysis version
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, ImageFormat, Result, UploadOptions};
+    /// # async fn example(client: Client) -> Result<()> {
+    /// let options = UploadOptions {
+    ///     analysis_version: Some("1.2.3".to_string()),
+    ///     ..UploadOptions::default()
+    /// };
+    /// let image = client
+    ///     .images_create_with_options(ImageFormat::Lime, [("name", "pinned")], &options)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn images_create_with_options<T, K, V>(
+        &self,
+        format: ImageFormat,
+        tags: T,
+        options: &UploadOptions,
+    ) -> Result<Image>
+    where
+        T: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let callback_url = options.callback_url.clone();
+        if let Some(callback_url) = &callback_url {
+            validate_url_template(callback_url)?;
+        }
+        let mut merged = self.backend.config().default_tags.clone();
+        merged.extend(as_tags(tags));
+        let create = ImageCreate {
+            format,
+            tags: merged,
+            callback_url,
+            requested_analysis_version: options.analysis_version.clone(),
+        };
+        let res = self.backend.post("/api/images", create).await?;
+        Ok(res)
+    }
+
+    /// Check that an image of the specified format and size can be uploaded
+    ///
+    /// This verifies the following, such that problems are found before
+    /// starting a long running upload:
+    /// 1. The user can authenticate to the service
+    /// 2. The user has accepted the current EULA
+    /// 3. The service supports the image format
+    /// 4. The image is not empty
+    ///
+    /// The service does not publish quotas, such that they are only enforced
+    /// when the image is created.
+    ///
+    /// Failed checks are included in the report rather than returned as
+    /// errors.  Use `PreflightReport::into_result` to treat any failed check
+    /// as an error.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the connection to the Service
+    /// fails
+    pub async fn preflight_upload(
+        &self,
+        format: ImageFormat,
+        size: u64,
+    ) -> Result<PreflightReport> {
+        let mut checks = vec![];
+
+        let eula_accepted = match self.user_config_get().await {
+            Ok(config) => {
+                checks.push(PreflightCheck::new("auth", true, "authenticated"));
+                config.eula_accepted
+            }
+            Err(Error::Eula(_)) => {
+                checks.push(PreflightCheck::new("auth", true, "authenticated"));
+                None
+            }
+            Err(err @ (Error::Auth(_) | Error::InvalidToken(_) | Error::Azure(_))) => {
+                checks.push(PreflightCheck::new("auth", false, err.to_string()));
+                return Ok(PreflightReport { checks });
+            }
+            Err(err) => return Err(err),
+        };
+
+        let info = self.info_cached().await?;
+
+        checks.push(
+            if eula_accepted.as_deref() == Some(info.current_eula.as_str()) {
+                PreflightCheck::new("eula", true, "current EULA accepted")
+            } else {
+                PreflightCheck::new(
+                    "eula",
+                    false,
+                    "current EULA not accepted.  run `freta eula accept`",
+                )
+            },
+        );
+
+        checks.push(if info.formats.contains(&format) {
+            PreflightCheck::new("format", true, format!("{format} is supported"))
+        } else {
+            PreflightCheck::new("format", false, format!("{format} is not supported"))
+        });
+
+        checks.push(if size == 0 {
+            PreflightCheck::new("size", false, "the image is empty")
+        } else {
+            PreflightCheck::new("size", true, format!("{size} bytes"))
+        });
+
+        Ok(PreflightReport { checks })
+    }
+
+    /// Create and upload an image to Freta
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following cases:
+    /// 1. Creating the image in Freta fails
+    /// 2. Uploading the blob to Azure Storage fails
+    /// 3. Signaling the service that the upload completed fails
+    pub async fn images_upload<P, T, K, V>(
+        &self,
+        format: ImageFormat,
+        tags: T,
+        path: P,
+    ) -> Result<Image>
+    where
+        P: AsRef<Path>,
+        T: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let (image, _) = self
+            .images_upload_with_options(format, tags, path, &UploadOptions::default())
+            .await?;
+        Ok(image)
+    }
+
+    /// Create and upload an image to Freta using the specified options
+    ///
+    /// This returns the created image along with statistics for the upload.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following cases:
+    /// 1. Creating the image in Freta fails
+    /// 2. Refreshing an expiring SAS URL for the image fails
+    /// 3. Uploading the blob to Azure Storage fails
+    /// 4. Signaling the service that the upload completed fails
+    pub async fn images_upload_with_options<P, T, K, V>(
+        &self,
+        format: ImageFormat,
+        tags: T,
+        path: P,
+        options: &UploadOptions,
+    ) -> Result<(Image, TransferStats)>
+    where
+        P: AsRef<Path>,
+        T: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let path = path.as_ref();
+        debug!("uploading {}", path.display());
+        let handle = open_file(path).await?;
+
+        let compress = options.compress && self.accepts_compressed(format).await?;
+        if compress && options.resume {
+            warn!("compressed uploads cannot be resumed");
+        }
+
+        // the canonical path and size identify the file when resuming
+        let file = if options.resume && !compress {
+            Some(upload_identity(path)?)
+        } else {
+            None
+        };
+        let resumed = match &file {
+            Some((path, size)) => self.images_upload_resumable(path, *size).await?,
+            None => None,
+        };
+
+        let (image, mut state) = match (resumed, file) {
+            (Some((state, image)), _) => (image, Some(state)),
+            (None, file) => {
+                let image = self
+                    .images_create_with_options(format, tags, options)
+                    .await?;
+                let state = file.map(|(path, size)| {
+                    UploadState::new(image.image_id, image.image_url.clone(), path, size)
+                });
+                (image, state)
+            }
+        };
+
+        info!("uploading as image id: {}", image.image_id);
+        let stats = if compress {
+            self.images_upload_compressed(&image, handle, options)
+                .await?
+        } else {
+            self.images_upload_file(&image, handle, options, state.as_mut())
+                .await?
+        };
+
+        if let Some(state) = state {
+            if let Err(err) = state.remove().await {
+                warn!("unable to remove upload progress: {err}");
+            }
+        }
+
+        Ok((image, stats))
+    }
+
+    /// Upload a file to an image that is waiting for its upload, then mark the
+    /// upload as complete
+    ///
+    /// If `state` records blocks from an earlier upload, the upload continues
+    /// after them.
+    pub(crate) async fn images_upload_file(
+        &self,
+        image: &Image,
+        mut handle: File,
+        options: &UploadOptions,
+        state: Option<&mut UploadState>,
+    ) -> Result<TransferStats> {
+        let size = handle
+            .metadata()
+            .await
+            .map_err(|e| io_err("reading file size", e))?
+            .len();
+        let offset = state.as_ref().map_or(0, |state| state.offset());
+        if offset > 0 {
+            handle
+                .seek(SeekFrom::Start(offset))
+                .await
+                .map_err(|e| io_err("seeking to resume upload", e))?;
+        }
+        self.images_upload_reader(image, handle, Some(size), options, state)
+            .await
+    }
+
+    /// Returns true if images of the format can be compressed while they are
+    /// uploaded, which requires the service to support the `lime` format
+    async fn accepts_compressed(&self, format: ImageFormat) -> Result<bool> {
+        if format != ImageFormat::Lime {
+            warn!("only lime images can be compressed.  uploading uncompressed");
+            return Ok(false);
+        }
+        let info = self.info_cached().await?;
+        if !info.formats.contains(&format) {
+            warn!("the service does not support {format} images.  uploading uncompressed");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Compress a `LiME` image while uploading it to an image that is waiting
+    /// for its upload, then mark the upload as complete
+    async fn images_upload_compressed(
+        &self,
+        image: &Image,
+        handle: File,
+        options: &UploadOptions,
+    ) -> Result<TransferStats> {
+        let size = handle
+            .metadata()
+            .await
+            .map_err(|e| io_err("reading file size", e))?
+            .len();
+        let capacity = options
+            .concurrency
+            .unwrap_or(self.backend.config().upload_concurrency);
+        let reader = compressed_lime_reader(handle.into_std().await, capacity);
+        // the uncompressed size is used as the upper bound of the size of the
+        // compressed image
+        self.images_upload_reader(image, reader, Some(size), options, None)
+            .await
+    }
+
+    /// Upload the contents of a reader to an image that is waiting for its
+    /// upload, then mark the upload as complete
+    async fn images_upload_reader<H>(
+        &self,
+        image: &Image,
+        handle: H,
+        size: Option<u64>,
+        options: &UploadOptions,
+        state: Option<&mut UploadState>,
+    ) -> Result<TransferStats>
+    where
+        H: AsyncRead + Unpin,
+    {
+        let image_url = image.image_url.clone().ok_or(Error::InvalidResponse(
+            "missing image_url from the response",
+        ))?;
+        // the service only issues SAS URLs that permit uploading an image when
+        // the image is created, such that the SAS URL is used until it expires
+        let refresh = || async {
+            Err::<Url, _>(Error::Other(
+                "the service does not support refreshing the SAS URL used to upload an image",
+                image.image_id.to_string(),
+            ))
+        };
+        let options = UploadOptions {
+            concurrency: options
+                .concurrency
+                .or(Some(self.backend.config().upload_concurrency)),
+            ..options.clone()
+        };
+        let stats = blob_upload(
+            self.backend.blobs(),
+            handle,
+            size,
+            SasUrl::new(image_url),
+            refresh,
+            &options,
+            state,
+        )
+        .await?;
+        match &stats.sha256 {
+            Some(sha256) => self.images_record_sha256(image, sha256).await,
+            None => warn!("the digest of resumed uploads is not recorded"),
+        }
+        self.images_upload_complete(image.image_id).await?;
+        Ok(stats)
+    }
+
+    /// Record the digest of an uploaded image using `IMAGE_SHA256_TAG`
+    ///
+    /// The upload already succeeded, so failing to record the digest is not
+    /// treated as an error.
+    async fn images_record_sha256(&self, image: &Image, sha256: &str) {
+        let mut tags = image.tags.clone();
+        tags.insert(IMAGE_SHA256_TAG.to_string(), sha256.to_string());
+        if let Err(err) = self.images_update(image.image_id, Some(tags), None).await {
+            warn!("unable to record the digest of {}: {err}", image.image_id);
+        }
+    }
+
+    /// Create and upload an image to Freta from a stream, such as the output
+    /// of a memory capture tool, without writing it to disk
+    ///
+    /// `size_hint` is the size of the image in bytes, if known.  It is used to
+    /// choose the size of the uploaded blocks and to report progress.  Images
+    /// of unknown size may be up to 1 TiB.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following cases:
+    /// 1. Creating the image in Freta fails
+    /// 2. Reading from the stream fails
+    /// 3. Uploading the blob to Azure Storage fails
+    /// 4. Signaling the service that the upload completed fails
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, ImageFormat, Result};
+    /// # async fn example(client: Client) -> Result<()> {
+    /// let stdin = tokio::io::stdin();
+    /// let image = client
+    ///     .images_upload_stream(ImageFormat::Lime, [("name", "piped")], stdin, None)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn images_upload_stream<S, T, K, V>(
+        &self,
+        format: ImageFormat,
+        tags: T,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<Image>
+    where
+        S: AsyncRead + Send + Unpin,
+        T: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let (image, _) = self
+            .images_upload_stream_with_options(
+                format,
+                tags,
+                stream,
+                size_hint,
+                &UploadOptions::default(),
+            )
+            .await?;
+        Ok(image)
+    }
+
+    /// Create and upload an image to Freta from a stream using the specified
+    /// options
+    ///
+    /// This returns the created image along with statistics for the upload.
+    /// Streams cannot be resumed, such that `UploadOptions.resume` is
+    /// ignored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following cases:
+    /// 1. Creating the image in Freta fails
+    /// 2. Reading from the stream fails
+    /// 3. Uploading the blob to Azure Storage fails
+    /// 4. Signaling the service that the upload completed fails
+    pub async fn images_upload_stream_with_options<S, T, K, V>(
+        &self,
+        format: ImageFormat,
+        tags: T,
+        stream: S,
+        size_hint: Option<u64>,
+        options: &UploadOptions,
+    ) -> Result<(Image, TransferStats)>
+    where
+        S: AsyncRead + Send + Unpin,
+        T: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let image = self
+            .images_create_with_options(format, tags, options)
+            .await?;
+        info!("uploading stream as image id: {}", image.image_id);
+        let stats = self
+            .images_upload_reader(&image, stream, size_hint, options, None)
+            .await?;
+        Ok((image, stats))
+    }
+
+    /// Get the saved progress of an interrupted upload of a file, along with
+    /// the image being uploaded
+    ///
+    /// Progress is discarded if the image is no longer waiting for the
+    /// upload, such as if it was deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, after discarding the progress, if the SAS URL used to
+    /// upload the image expired, as the service does not issue new ones for
+    /// existing images.
+    async fn images_upload_resumable(
+        &self,
+        path: &Path,
+        size: u64,
+    ) -> Result<Option<(UploadState, Image)>> {
+        let Some(state) = UploadState::find(path, size).await? else {
+            return Ok(None);
+        };
+        let mut image = self.images_get(state.image_id).await?;
+        if image.state == ImageState::WaitingForUpload {
+            let image_url = state
+                .image_url
+                .clone()
+                .filter(|url| !SasUrl::new(url.clone()).expires_within(Duration::ZERO));
+            let Some(image_url) = image_url else {
+                state.remove().await?;
+                return Err(Error::Other(
+                    "unable to resume the upload, as the SAS URL used to upload the image expired and the service does not support refreshing it.  the saved progress was removed, such that retrying starts a new upload",
+                    state.image_id.to_string(),
+                ));
+            };
+            info!(
+                "resuming upload of {} after {} bytes",
+                state.image_id,
+                state.offset()
+            );
+            image.image_url = Some(image_url);
+            return Ok(Some((state, image)));
+        }
+        warn!(
+            "unable to resume upload of {} in state {:?}.  starting a new upload",
+            state.image_id, image.state
+        );
+        state.remove().await?;
+        Ok(None)
+    }
+
+    /// Signal to the service that the upload of an image completed
+    ///
+    /// This moves the image from `WaitingForUpload` to `ToQueue` without
+    /// waiting for the service to detect the upload.  This is done
+    /// automatically by `images_upload`, but is useful when the image is
+    /// uploaded using other tools, such as `azcopy`.
+    ///
+    /// This is best-effort.  If the service does not support signaling that
+    /// uploads completed, a warning is logged and `false` is returned, as the
+    /// service detects the upload on its own.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to update the specified image
+    pub async fn images_upload_complete(
+        &self,
+        image_id: ImageId,
+    ) -> Result<ImageUploadCompleteResponse> {
+        let res = self
+            .backend
+            .post(&format!("/api/images/{image_id}/uploaded"), None::<bool>)
+            .await;
+        match res {
+            Err(err) if err.is_unsupported() => {
+                warn!("the service does not support signaling that uploads completed.  waiting for the service to detect the upload");
+                Ok(ImageUploadCompleteResponse(false))
+            }
+            res => res,
+        }
+    }
+
+    /// Get information on an image
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to read the specified image
     pub async fn images_get(&self, image_id: ImageId) -> Result<Image> {
         let res = self
             .backend
@@ -288,6 +1612,42 @@ impl Client {
         Ok(res)
     }
 
+    /// Get new SAS URLs for an image and its artifacts, returned as
+    /// `(image_url, artifacts_url)`
+    ///
+    /// The SAS URLs included with `images_get` expire.  Unlike `images_get`,
+    /// this never reuses a previously received response, such that the
+    /// service always generates new SAS URLs.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to read the specified image
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Result, ImageId};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let (_, artifacts_url) = client.images_refresh_urls(image_id).await?;
+    /// if let Some(artifacts_url) = artifacts_url {
+    ///     println!("{artifacts_url}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn images_refresh_urls(
+        &self,
+        image_id: ImageId,
+    ) -> Result<(Option<Url>, Option<Url>)> {
+        let image: Image = self
+            .backend
+            .get_fresh(&format!("/api/images/{image_id}"), None::<bool>)
+            .await?;
+        Ok((image.image_url, image.artifacts_url))
+    }
+
     /// Delete an image
     ///
     /// # Errors
@@ -300,209 +1660,1043 @@ impl Client {
             .backend
             .delete(&format!("/api/images/{image_id}"))
             .await?;
+        self.cache_invalidate(image_id).await;
         Ok(res)
     }
 
-    /// Update metadata for an image
+    /// Delete multiple images, deleting up to `concurrency` images at a time
+    ///
+    /// Failing to delete an image does not stop the deletion of the remaining
+    /// images.  The result of deleting each image is returned in the order of
+    /// `image_ids`.
+    ///
+    /// # Example
+    ///
+    /// Delete the failed images of the user:
+    ///
+    /// ```rust,no_run
+    /// use futures::TryStreamExt;
+    /// # use freta::{Client, ImageState, Result};
+    /// # async fn example(client: Client) -> Result<()> {
+    /// let image_ids: Vec<_> = client
+    ///     .images_list(None, None, Some(ImageState::Failed), false, false, None)
+    ///     .map_ok(|image| image.image_id)
+    ///     .try_collect()
+    ///     .await?;
+    /// for (image_id, result) in client.images_delete_bulk(image_ids, 8).await {
+    ///     if let Err(err) = result {
+    ///         eprintln!("unable to delete {image_id}: {err}");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn images_delete_bulk<I>(
+        &self,
+        image_ids: I,
+        concurrency: usize,
+    ) -> Vec<(ImageId, Result<ImageDeleteResponse>)>
+    where
+        I: IntoIterator<Item = ImageId>,
+    {
+        stream::iter(image_ids)
+            .map(|image_id| async move { (image_id, self.images_delete(image_id).await) })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Update metadata for an image
+    ///
+    /// If `tags` is not None, then the tags are overwritten.
+    /// If `shareable` is not None, then the shareable value is overwritten.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to update metadata for the specified image
+    pub async fn images_update<T, K, V>(
+        &self,
+        image_id: ImageId,
+        tags: Option<T>,
+        shareable: Option<bool>,
+    ) -> Result<Image>
+    where
+        T: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let tags = tags.map(as_tags);
+        let update = ImageUpdate { tags, shareable };
+        let res = self
+            .backend
+            .post(&format!("/api/images/{image_id}"), update)
+            .await?;
+        Ok(res)
+    }
+
+    /// Reanalyze an image
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to reanalyze the specified image
+    pub async fn images_reanalyze(&self, image_id: ImageId) -> Result<ImageReanalyzeResponse> {
+        self.images_reanalyze_with_version(image_id, None).await
+    }
+
+    /// Reanalyze an image using the specified version of the analysis engine
+    ///
+    /// If `analysis_version` is `None`, the current version is used.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to reanalyze the specified image
+    /// 3. The service does not support the requested analysis version
+    pub async fn images_reanalyze_with_version(
+        &self,
+        image_id: ImageId,
+        analysis_version: Option<String>,
+    ) -> Result<ImageReanalyzeResponse> {
+        // requests without a version are sent without a body, as expected by
+        // older versions of the service
+        let request = analysis_version.map(|version| ImageReanalyze {
+            requested_analysis_version: Some(version),
+        });
+        let res = self
+            .backend
+            .patch(&format!("/api/images/{image_id}"), request)
+            .await?;
+        self.cache_invalidate(image_id).await;
+        Ok(res)
+    }
+
+    /// Download an image to a file
+    ///
+    /// This returns statistics for the download.
+    ///
+    /// NOTE: The service only allows downloading images that have been analyzed
+    /// successfully.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. The user does not have permission to access the specified image
+    /// 2. The image was not successfully analyzed
+    /// 3. Refreshing an expiring SAS URL for the image fails
+    /// 4. Downloading the image fails
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Result, ImageId};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// client.images_download(image_id, "/tmp/image.lime").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn images_download<P>(&self, image_id: ImageId, output: P) -> Result<TransferStats>
+    where
+        P: AsRef<Path>,
+    {
+        self.images_download_with_progress(image_id, output, &ProgressBarHandler::new())
+            .await
+    }
+
+    /// Download an image to a file, reporting the progress of the download
+    /// to `progress` rather than displaying a progress bar
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the same cases as
+    /// `images_download`.
+    pub async fn images_download_with_progress<P>(
+        &self,
+        image_id: ImageId,
+        output: P,
+        progress: &dyn ProgressHandler,
+    ) -> Result<TransferStats>
+    where
+        P: AsRef<Path>,
+    {
+        let image = self.images_monitor(image_id).await?;
+        let Some(image_url) = image.image_url else {
+            return Err(Error::InvalidResponse(
+                "service did not provide image_url in the response",
+            ));
+        };
+        let refresh = || self.images_get_sas(image_id);
+        let output = output.as_ref();
+        let stats = blob_download(
+            self.backend.blobs(),
+            SasUrl::new(image_url),
+            refresh,
+            output,
+            Some(DEFAULT_STALL_TIMEOUT),
+            progress,
+        )
+        .await?;
+
+        if let (Some(expected), Some(actual)) = (image.tags.get(IMAGE_SHA256_TAG), &stats.sha256) {
+            if let Err(err) = verify_sha256(&image_id.to_string(), expected, actual) {
+                if let Err(remove_err) = remove_file(output).await {
+                    warn!("unable to remove {}: {remove_err}", output.display());
+                }
+                return Err(err);
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Verify a local copy of an image, such as one kept as evidence, matches
+    /// the digest recorded when the image was uploaded
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Result, ImageId};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let verified = client.images_verify(image_id, "/tmp/image.lime").await?;
+    /// println!("sha256: {}", verified.sha256);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The digest of the image was not recorded when it was uploaded
+    /// 3. Reading the file fails
+    /// 4. The digest of the file does not match the recorded digest
+    pub async fn images_verify<P>(&self, image_id: ImageId, path: P) -> Result<VerifiedImage>
+    where
+        P: AsRef<Path>,
+    {
+        let image = self.images_get(image_id).await?;
+        let Some(expected) = image.sha256() else {
+            return Err(Error::Other(
+                "the digest of the image was not recorded",
+                image_id.to_string(),
+            ));
+        };
+
+        let path = path.as_ref().to_path_buf();
+        let hashed = path.clone();
+        let sha256 = tokio::task::spawn_blocking(move || sha256_file(&hashed))
+            .await
+            .map_err(|e| Error::Other("hashing file", e.to_string()))??;
+        verify_sha256(&image_id.to_string(), expected, &sha256)?;
+        Ok(VerifiedImage {
+            image_id,
+            path,
+            sha256,
+        })
+    }
+
+    /// Get a fresh SAS URL for the image itself
+    ///
+    /// This is used to replace SAS URLs that are close to expiring during
+    /// long-running downloads.  The service only includes `image_url` once
+    /// the image was uploaded, and the SAS URL only permits reading the image,
+    /// such that it cannot be used to refresh uploads.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the image metadata from the service fails
+    /// 2. The image metadata in the service is missing `image_url`
+    async fn images_get_sas(&self, image_id: ImageId) -> Result<Url> {
+        let (image_url, _) = self.images_refresh_urls(image_id).await?;
+        image_url.ok_or(Error::InvalidResponse(
+            "service did not provide image_url in the response",
+        ))
+    }
+
+    /// List the blobs stored as part of an image
+    ///
+    /// This enumerates the blobs alongside the image's blob in Azure Storage,
+    /// which is useful to verify that uploads of images stored as multiple
+    /// blobs completed.
+    ///
+    /// Listing the container requires the service to provide a SAS URL scoped
+    /// to the container.  If the SAS URL is scoped to the image's blob, only
+    /// the image's blob is returned.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the image metadata from the service fails
+    /// 2. The image metadata in the service is missing `image_url`
+    /// 3. Listing the blobs from the Azure Storage fails
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use futures::StreamExt;
+    /// # use freta::{Client, ImageId, Result};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let mut stream = client.image_blobs_list(image_id);
+    /// while let Some(blob) = stream.next().await {
+    ///     let blob = blob?;
+    ///     println!("{} {}", blob.name, blob.size);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn image_blobs_list(
+        &self,
+        image_id: ImageId,
+    ) -> Pin<Box<impl Stream<Item = std::result::Result<ImageBlob, crate::Error>> + Send + '_>>
+    {
+        Box::pin(async_stream::try_stream! {
+            let image_url = self.images_get_sas(image_id).await?;
+            if sas_permits_list(&image_url) {
+                let (container_sas, prefix) = blob_container_sas(&image_url)?;
+                let container_client = self.backend.blobs().container_client(&container_sas)?;
+                let mut stream = container_client.list_blobs().prefix(prefix).into_stream();
+
+                while let Some(entries) = stream.next().await {
+                    let entries = entries?;
+                    let blobs: Vec<_> = entries
+                        .blobs
+                        .blobs()
+                        .map(|b| ImageBlob {
+                            name: b.name.clone(),
+                            size: b.properties.content_length,
+                            etag: b.properties.etag.to_string(),
+                            last_modified: b.properties.last_modified,
+                        })
+                        .collect();
+                    for blob in blobs {
+                        yield blob;
+                    }
+                }
+            } else {
+                debug!("the SAS URL of {image_id} does not permit listing its container");
+                let blob = self
+                    .backend
+                    .blobs()
+                    .blob_client(&image_url)?
+                    .get_properties()
+                    .await?
+                    .blob;
+                yield ImageBlob {
+                    name: blob.name,
+                    size: blob.properties.content_length,
+                    etag: blob.properties.etag.to_string(),
+                    last_modified: blob.properties.last_modified,
+                };
+            }
+        })
+    }
+
+    /// Get the SAS URL for the Azure Storage container for artifacts extracted
+    /// from the image
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the image metadata from the service fails
+    /// 2. The image metadata in the service is missing `artifacts_url` which
+    ///    should always be returned when getting the metadata for a single
+    ///    image.
+    async fn artifacts_get_sas(&self, image_id: ImageId) -> Result<Url> {
+        let image = self.images_monitor(image_id).await?;
+        let Some(image_url) = image.artifacts_url else {
+            return Err(Error::InvalidResponse(
+                "missing artifacts_url from the response",
+            ));
+        };
+
+        Ok(image_url)
+    }
+
+    /// List the artifacts extracted from the image
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the artifacts SAS URL for the image fails
+    /// 2. Listing the blobs from the Azure Storage fails
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use futures::StreamExt;
+    /// # use freta::{Client, ImageFormat::Lime, ImageId, Result};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let mut stream = client.artifacts_list(image_id);
+    /// while let Some(entry) = stream.next().await {
+    ///     let entry = entry?;
+    ///     println!("{entry}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn artifacts_list(
+        &self,
+        image_id: ImageId,
+    ) -> Pin<Box<impl Stream<Item = std::result::Result<String, crate::Error>> + Send + '_>> {
+        self.artifacts_list_filtered(image_id, ArtifactFilter::default())
+    }
+
+    /// List the artifacts extracted from the image that match the filter
+    ///
+    /// Artifacts are returned as each page is received from Azure Storage.
+    /// Once `filter.limit` artifacts have been returned, no further pages are
+    /// requested.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the artifacts SAS URL for the image fails
+    /// 2. Listing the blobs from the Azure Storage fails
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use futures::StreamExt;
+    /// # use freta::{models::artifacts::ArtifactFilter, Client, ImageId, Result};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let filter = ArtifactFilter::default()
+    ///     .with_glob("processes/*/memory.dmp")
+    ///     .with_limit(100);
+    /// let mut stream = client.artifacts_list_filtered(image_id, filter);
+    /// while let Some(entry) = stream.next().await {
+    ///     let entry = entry?;
+    ///     println!("{entry}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn artifacts_list_filtered(
+        &self,
+        image_id: ImageId,
+        filter: ArtifactFilter,
+    ) -> Pin<Box<impl Stream<Item = std::result::Result<String, crate::Error>> + Send + '_>> {
+        Box::pin(
+            self.artifacts_list_entries_filtered(image_id, filter)
+                .map_ok(|entry| entry.name),
+        )
+    }
+
+    /// List the artifacts extracted from the image, including the size and
+    /// content type of each artifact
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the artifacts SAS URL for the image fails
+    /// 2. Listing the blobs from the Azure Storage fails
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use futures::StreamExt;
+    /// # use freta::{Client, ImageId, Result};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let mut stream = client.artifacts_list_entries(image_id);
+    /// while let Some(entry) = stream.next().await {
+    ///     let entry = entry?;
+    ///     if entry.is_text() {
+    ///         println!("{} ({})", entry.name, entry.content_type);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn artifacts_list_entries(
+        &self,
+        image_id: ImageId,
+    ) -> Pin<Box<impl Stream<Item = std::result::Result<ArtifactEntry, crate::Error>> + Send + '_>>
+    {
+        self.artifacts_list_entries_filtered(image_id, ArtifactFilter::default())
+    }
+
+    /// List the artifacts extracted from the image that match the filter,
+    /// including the size and content type of each artifact
+    ///
+    /// See [`Client::artifacts_list_filtered`] for how the filter is applied.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the artifacts SAS URL for the image fails
+    /// 2. Listing the blobs from the Azure Storage fails
+    pub fn artifacts_list_entries_filtered(
+        &self,
+        image_id: ImageId,
+        filter: ArtifactFilter,
+    ) -> Pin<Box<impl Stream<Item = std::result::Result<ArtifactEntry, crate::Error>> + Send + '_>>
+    {
+        Box::pin(async_stream::try_stream! {
+            let container_sas = self.artifacts_get_sas(image_id).await?;
+            let container_client = self.backend.blobs().container_client(&container_sas)?;
+            let mut builder = container_client.list_blobs();
+            if let Some(prefix) = filter.list_prefix() {
+                builder = builder.prefix(prefix);
+            }
+            if let Some(max_results) = filter.max_results {
+                builder = builder.max_results(max_results);
+            }
+            let mut stream = builder.into_stream();
+            let mut remaining = filter.limit;
+
+            while remaining != Some(0) {
+                let Some(entries) = stream.next().await else {
+                    break;
+                };
+                let entries = entries?;
+                let artifacts: Vec<_> = entries
+                    .blobs
+                    .blobs()
+                    .filter(|b| filter.matches(&b.name))
+                    .map(|b| ArtifactEntry {
+                        name: b.name.clone(),
+                        size: b.properties.content_length,
+                        content_type: b.properties.content_type.clone(),
+                        last_modified: b.properties.last_modified,
+                    })
+                    .collect();
+                for artifact in artifacts {
+                    if remaining == Some(0) {
+                        break;
+                    }
+                    remaining = remaining.map(|count| count.saturating_sub(1));
+                    yield artifact;
+                }
+            }
+        })
+    }
+
+    /// Get an artifact extracted from the image
+    ///
+    /// If `Config.cache_max_bytes` is set, artifacts of images whose analysis
+    /// has completed are stored in a local cache limited to that size.  The
+    /// cache is best-effort, such that failing to use it is logged rather than
+    /// causing the request to fail.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the artifacts SAS URL for the image fails
+    /// 2. Getting the artifact fails
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Result, ImageId};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let report = client.artifacts_get(image_id, "report.json").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn artifacts_get<N>(&self, image_id: ImageId, name: N) -> Result<Vec<u8>>
+    where
+        N: Into<String>,
+    {
+        let name = name.into();
+        let cache = self.artifact_cache();
+        let image = self.images_monitor(image_id).await?;
+        if let Some(cache) = &cache {
+            match cache.get(&image, &name).await {
+                Ok(Some(blob)) => {
+                    debug!("using cached artifact {name} for {image_id}");
+                    return Ok(blob);
+                }
+                Ok(None) => {}
+                Err(err) => warn!("unable to read cached artifact {name} for {image_id}: {err}"),
+            }
+        }
+
+        let Some(url) = &image.artifacts_url else {
+            return Err(Error::InvalidResponse(
+                "missing artifacts_url from the response",
+            ));
+        };
+        let blob = blob_get(self.backend.blobs(), url, name.as_str()).await?;
+
+        if let Some(cache) = &cache {
+            if let Err(err) = cache.put(&image, &name, &blob).await {
+                warn!("unable to cache artifact {name} for {image_id}: {err}");
+            }
+        }
+
+        Ok(blob)
+    }
+
+    /// Get an artifact extracted from the image, ensuring its SHA-256 digest
+    /// matches `expected_sha256`
+    ///
+    /// This is used to prove the content of an artifact is exactly the
+    /// content recorded earlier, such as by `Client::artifacts_download`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the artifact fails
+    /// 2. The digest of the artifact does not match `expected_sha256`
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Result, ImageId};
+    /// # async fn example(client: Client, image_id: ImageId, sha256: &str) -> Result<()> {
+    /// let report = client
+    ///     .artifacts_get_verified(image_id, "report.json", sha256)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn artifacts_get_verified<N>(
+        &self,
+        image_id: ImageId,
+        name: N,
+        expected_sha256: &str,
+    ) -> Result<Vec<u8>>
+    where
+        N: Into<String>,
+    {
+        let name = name.into();
+        let blob = self.artifacts_get(image_id, name.as_str()).await?;
+        verify_sha256(&name, expected_sha256, &sha256_hex(&blob))?;
+        Ok(blob)
+    }
+
+    /// Get the analysis report of the image, `report.json`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the report artifact fails
+    /// 2. The report cannot be parsed
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Result, ImageId};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let report = client.report(image_id).await?;
+    /// for check in report.checks {
+    ///     println!("{}", check.issue);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn report(&self, image_id: ImageId) -> Result<Report> {
+        let report = self.artifacts_get(image_id, REPORT_JSON).await?;
+        Ok(serde_json::from_slice(&report)?)
+    }
+
+    /// List the processes found in the analysis of an image, ordered by
+    /// process id
     ///
-    /// If `tags` is not None, then the tags are overwritten.
-    /// If `shareable` is not None, then the shareable value is overwritten.
+    /// These are the `tasks` of the analysis report.
     ///
     /// # Errors
     ///
-    /// This function will return an error in the following conditions:
-    /// 1. The connection to the Service fails
-    /// 2. The user does not have permission to update metadata for the specified image
-    pub async fn images_update<T, K, V>(
-        &self,
-        image_id: ImageId,
-        tags: Option<T>,
-        shareable: Option<bool>,
-    ) -> Result<Image>
-    where
-        T: IntoIterator<Item = (K, V)>,
-        K: Into<String>,
-        V: Into<String>,
-    {
-        let tags = tags.map(as_tags);
-        let update = ImageUpdate { tags, shareable };
-        let res = self
-            .backend
-            .post(&format!("/api/images/{image_id}"), update)
-            .await?;
-        Ok(res)
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the report artifact fails
+    /// 2. The report cannot be parsed
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use std::collections::BTreeSet;
+    /// # use freta::{Client, Result, ImageId};
+    /// # async fn example(client: Client, before: ImageId, after: ImageId) -> Result<()> {
+    /// let names = |processes: Vec<freta::models::analysis::report::Task>| {
+    ///     processes.into_iter().map(|p| p.comm).collect::<BTreeSet<_>>()
+    /// };
+    /// let old = names(client.processes_list(before).await?);
+    /// let new = names(client.processes_list(after).await?);
+    /// for name in new.difference(&old) {
+    ///     println!("new process: {name}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn processes_list(&self, image_id: ImageId) -> Result<Vec<Task>> {
+        let mut processes = self.report(image_id).await?.tasks;
+        processes.sort_by_key(|process| process.pid);
+        Ok(processes)
     }
 
-    /// Reanalyze an image
+    /// List the kernel modules found in the analysis of an image, including
+    /// the hooks whose destination is in each module
+    ///
+    /// These are the `kernel_modules.modules` of the analysis report.
     ///
     /// # Errors
     ///
-    /// This function will return an error in the following conditions:
-    /// 1. The connection to the Service fails
-    /// 2. The user does not have permission to reanalyze the specified image
-    pub async fn images_reanalyze(&self, image_id: ImageId) -> Result<ImageReanalyzeResponse> {
-        let res = self
-            .backend
-            .patch(&format!("/api/images/{image_id}"), None::<bool>)
-            .await?;
-        Ok(res)
+    /// This function will return an error in the follow cases:
+    /// 1. Getting the report artifact fails
+    /// 2. The report cannot be parsed
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Result, ImageId};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// for module in client.kernel_modules_list(image_id).await? {
+    ///     if !module.hooks.is_empty() {
+    ///         println!("hooked by module: {}", module.module.name);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn kernel_modules_list(&self, image_id: ImageId) -> Result<Vec<KernelModule>> {
+        let report = self.report(image_id).await?;
+        Ok(report.kernel_modules())
     }
 
-    /// Download an image to a file
+    /// Download an artifact extracted from the image to a file
     ///
-    /// NOTE: The service only allows downloading images that have been analyzed
-    /// successfully.
+    /// The SHA-256 digest of the artifact is calculated as it is downloaded.
     ///
     /// # Errors
     ///
     /// This function will return an error in the follow cases:
-    /// 1. The user does not have permission to access the specified image
-    /// 2. The image was not successfully analyzed
-    /// 3. Downloading the image fails
+    /// 1. Getting the artifacts SAS URL for the image fails
+    /// 2. Downloading the artifact fails
     ///
     /// # Example
     ///
     /// ```rust,no_run
-    /// # use freta::{Client, Result, ImageId};
+    /// # use freta::{Client, ImageFormat::Lime, Result, ImageId};
     /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
-    /// client.images_download(image_id, "/tmp/image.lime").await?;
+    /// let downloaded = client
+    ///     .artifacts_download(image_id, "report.json", "/tmp/report.json")
+    ///     .await?;
+    /// println!("sha256: {}", downloaded.sha256);
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn images_download<P>(&self, image_id: ImageId, output: P) -> Result<()>
+    pub async fn artifacts_download<P, N>(
+        &self,
+        image_id: ImageId,
+        name: N,
+        output: P,
+    ) -> Result<DownloadedArtifact>
     where
         P: AsRef<Path>,
+        N: Into<String>,
     {
-        let image = self.images_monitor(image_id).await?;
-        let Some(image_url) = image.image_url else {
-            return Err(Error::InvalidResponse(
-                "service did not provide image_url in the response",
-            ));
-        };
-        blob_download(&image_url, output).await?;
-        Ok(())
+        self.artifacts_download_with_progress(image_id, name, output, &NoProgress)
+            .await
     }
 
-    /// Get the SAS URL for the Azure Storage container for artifacts extracted
-    /// from the image
+    /// Download an artifact extracted from the image to a file, reporting the
+    /// progress of the download to `progress`
     ///
     /// # Errors
     ///
-    /// This function will return an error in the follow cases:
-    /// 1. Getting the image metadata from the service fails
-    /// 2. The image metadata in the service is missing `artifacts_url` which
-    ///    should always be returned when getting the metadata for a single
-    ///    image.
-    async fn artifacts_get_sas(&self, image_id: ImageId) -> Result<Url> {
-        let image = self.images_monitor(image_id).await?;
-        let Some(image_url) = image.artifacts_url else {
-            return Err(Error::InvalidResponse(
-                "missing artifacts_url from the response",
-            ));
-        };
+    /// This function will return an error in the same cases as
+    /// `artifacts_download`.
+    pub async fn artifacts_download_with_progress<P, N>(
+        &self,
+        image_id: ImageId,
+        name: N,
+        output: P,
+        progress: &dyn ProgressHandler,
+    ) -> Result<DownloadedArtifact>
+    where
+        P: AsRef<Path>,
+        N: Into<String>,
+    {
+        let name = name.into();
+        let path = output.as_ref().to_path_buf();
+        let url = self.artifacts_get_sas(image_id).await?;
+        let (stats, sha256) =
+            container_blob_download(self.backend.blobs(), &url, name.as_str(), &path, progress)
+                .await?;
+        Ok(DownloadedArtifact {
+            name,
+            path,
+            size: stats.bytes,
+            sha256,
+        })
+    }
 
-        Ok(image_url)
+    /// Download an artifact extracted from the image to a file, ensuring its
+    /// SHA-256 digest matches `expected_sha256`
+    ///
+    /// If the digest does not match, the downloaded file is removed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. Downloading the artifact fails
+    /// 2. The digest of the artifact does not match `expected_sha256`
+    pub async fn artifacts_download_verified<P, N>(
+        &self,
+        image_id: ImageId,
+        name: N,
+        output: P,
+        expected_sha256: &str,
+    ) -> Result<DownloadedArtifact>
+    where
+        P: AsRef<Path>,
+        N: Into<String>,
+    {
+        let downloaded = self.artifacts_download(image_id, name, output).await?;
+        if let Err(err) = verify_sha256(&downloaded.name, expected_sha256, &downloaded.sha256) {
+            if let Err(remove_err) = remove_file(&downloaded.path).await {
+                warn!(
+                    "unable to remove {}: {remove_err}",
+                    downloaded.path.display()
+                );
+            }
+            return Err(err);
+        }
+        Ok(downloaded)
     }
 
-    /// List the artifacts extracted from the image
+    /// Download every artifact extracted from the image to `output`
+    ///
+    /// The artifacts are downloaded concurrently, preserving the hierarchy of
+    /// the artifact names, such that `symbols/a.pdb/<id>/a.pdb` is written to
+    /// `<output>/symbols/a.pdb/<id>/a.pdb`.  Artifacts with names that would
+    /// be written outside of `output` are skipped.
+    ///
+    /// This returns the downloaded artifacts, ordered by name.
     ///
     /// # Errors
     ///
     /// This function will return an error in the follow cases:
     /// 1. Getting the artifacts SAS URL for the image fails
     /// 2. Listing the blobs from the Azure Storage fails
+    /// 3. Creating the directories for the artifacts fails
+    /// 4. Downloading any of the artifacts fails
     ///
     /// # Example
     ///
     /// ```rust,no_run
-    /// use futures::StreamExt;
-    /// # use freta::{Client, ImageFormat::Lime, ImageId, Result};
+    /// # use freta::{Client, Result, ImageId};
     /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
-    /// let mut stream = client.artifacts_list(image_id);
-    /// while let Some(entry) = stream.next().await {
-    ///     let entry = entry?;
-    ///     println!("{entry}");
+    /// let downloaded = client
+    ///     .artifacts_download_all(image_id, "/tmp/artifacts")
+    ///     .await?;
+    /// for artifact in downloaded {
+    ///     println!("{} {}", artifact.sha256, artifact.path.display());
     /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn artifacts_list(
+    pub async fn artifacts_download_all<P>(
         &self,
         image_id: ImageId,
-    ) -> Pin<Box<impl Stream<Item = std::result::Result<String, crate::Error>> + Send + '_>> {
-        Box::pin(async_stream::try_stream! {
-            let container_sas = self.artifacts_get_sas(image_id).await?;
-            let container_client = container_client(&container_sas)?;
-            let mut stream = container_client.list_blobs().into_stream();
+        output: P,
+    ) -> Result<Vec<DownloadedArtifact>>
+    where
+        P: AsRef<Path>,
+    {
+        let output = output.as_ref();
+        let entries: Vec<ArtifactEntry> =
+            self.artifacts_list_entries(image_id).try_collect().await?;
 
-            while let Some(entries) = stream.next().await {
-                let entries = entries?;
-                let blob_names: Vec<_> = entries.blobs.blobs().map(|b| b.name.clone()).collect();
-                for name in blob_names {
-                    yield name;
+        let mut downloads = vec![];
+        for entry in entries {
+            let Some(path) = join_relative(output, &entry.name) else {
+                warn!("skipping artifact with an unsupported name: {}", entry.name);
+                continue;
+            };
+            downloads.push((entry, path));
+        }
+
+        let total = downloads.iter().map(|(entry, _)| entry.size).sum();
+        let status = ProgressBarHandler::new();
+        status.on_start(Some(total), 0);
+
+        let url = self.artifacts_get_sas(image_id).await?;
+        let blobs = self.backend.blobs();
+        let (url, status) = (&url, &status);
+        let mut downloaded: Vec<DownloadedArtifact> = stream::iter(downloads)
+            .map(|(entry, path)| async move {
+                if let Some(parent) = path.parent() {
+                    create_dir_all(parent).await?;
                 }
-            }
-        })
+                debug!("downloading {} to {}", entry.name, path.display());
+                let (stats, sha256) =
+                    container_blob_download(blobs, url, entry.name.as_str(), &path, &NoProgress)
+                        .await?;
+                status.on_progress(entry.size);
+                Ok::<_, Error>(DownloadedArtifact {
+                    name: entry.name,
+                    path,
+                    size: stats.bytes,
+                    sha256,
+                })
+            })
+            .buffer_unordered(ARTIFACT_DOWNLOAD_CONCURRENCY)
+            .try_collect()
+            .await?;
+        status.on_finish();
+
+        downloaded.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(downloaded)
     }
 
-    /// Get an artifact extracted from the image
+    /// Upload a file as an artifact of the image, such as supplemental debug
+    /// symbols to use when the image is reanalyzed
+    ///
+    /// The artifact is stored as `name` in the artifacts container of the
+    /// image, replacing any existing artifact of the same name.  This requires
+    /// the service to provide an artifacts SAS URL that permits writes.
     ///
     /// # Errors
     ///
     /// This function will return an error in the follow cases:
-    /// 1. Getting the artifacts SAS URL for the image fails
-    /// 2. Getting the artifact fails
+    /// 1. `name` is empty or includes `.` or `..` components
+    /// 2. Getting the artifacts SAS URL for the image fails
+    /// 3. The artifacts SAS URL does not permit writes
+    /// 4. Reading the file fails
+    /// 5. Uploading the artifact fails
     ///
     /// # Example
     ///
     /// ```rust,no_run
     /// # use freta::{Client, Result, ImageId};
     /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
-    /// let report = client.artifacts_get(image_id, "report.json").await?;
+    /// client
+    ///     .artifacts_upload(image_id, "symbols/vmlinux/custom/vmlinux", "/tmp/vmlinux")
+    ///     .await?;
+    /// client.images_reanalyze(image_id).await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn artifacts_get<N>(&self, image_id: ImageId, name: N) -> Result<Vec<u8>>
+    pub async fn artifacts_upload<P, N>(
+        &self,
+        image_id: ImageId,
+        name: N,
+        path: P,
+    ) -> Result<TransferStats>
     where
+        P: AsRef<Path>,
         N: Into<String>,
     {
-        let url = self.artifacts_get_sas(image_id).await?;
-        let blob = blob_get(&url, name).await?;
-        Ok(blob)
+        let name = name.into();
+        if join_relative(Path::new(""), &name).is_none() {
+            return Err(Error::Other("invalid artifact name", name));
+        }
+
+        let container_sas = self.artifacts_get_sas(image_id).await?;
+        if !sas_permits_write(&container_sas) {
+            return Err(Error::Other(
+                "the service does not permit uploading artifacts for the image",
+                image_id.to_string(),
+            ));
+        }
+        let blob_sas = SasUrl::new(container_blob_sas(&container_sas, &name)?);
+
+        let handle = open_file(path).await?;
+        let size = handle
+            .metadata()
+            .await
+            .map_err(|e| io_err("reading file size", e))?
+            .len();
+
+        let blob_name = name.as_str();
+        let refresh = || async move {
+            let container_sas = self.artifacts_get_sas(image_id).await?;
+            container_blob_sas(&container_sas, blob_name)
+        };
+        let options = UploadOptions {
+            concurrency: Some(self.backend.config().upload_concurrency),
+            ..UploadOptions::default()
+        };
+        info!("uploading artifact {name} for {image_id}");
+        let stats = blob_upload(
+            self.backend.blobs(),
+            handle,
+            Some(size),
+            blob_sas,
+            refresh,
+            &options,
+            None,
+        )
+        .await?;
+        self.cache_invalidate(image_id).await;
+        Ok(stats)
     }
 
-    /// Download an artifact extracted from the image to a file
+    /// Download the debug symbols resolved during the analysis of the image
+    ///
+    /// The symbols are written to `output` using the specified directory
+    /// `layout`, such that the directory can be used directly as a Windows
+    /// symbol store or a debuginfod cache.
+    ///
+    /// This returns the paths of the downloaded symbol files.  If the analysis
+    /// did not produce any symbol artifacts, no files are downloaded.
     ///
     /// # Errors
     ///
     /// This function will return an error in the follow cases:
     /// 1. Getting the artifacts SAS URL for the image fails
-    /// 2. Downloading the artifact fails
+    /// 2. Listing the blobs from the Azure Storage fails
+    /// 3. Creating the directories for the symbols fails
+    /// 4. Downloading the symbols fails
     ///
     /// # Example
     ///
     /// ```rust,no_run
-    /// # use freta::{Client, ImageFormat::Lime, Result, ImageId};
+    /// # use freta::{Client, models::analysis::symbols::SymbolLayout, Result, ImageId};
     /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
     /// client
-    ///     .artifacts_download(image_id, "report.json", "/tmp/report.json")
+    ///     .symbols_download(image_id, "/tmp/symbols", SymbolLayout::Symsrv)
     ///     .await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn artifacts_download<P, N>(
+    pub async fn symbols_download<P>(
         &self,
         image_id: ImageId,
-        name: N,
         output: P,
-    ) -> Result<()>
+        layout: SymbolLayout,
+    ) -> Result<Vec<PathBuf>>
     where
         P: AsRef<Path>,
-        N: Into<String>,
     {
+        let output = output.as_ref();
         let url = self.artifacts_get_sas(image_id).await?;
-        container_blob_download(&url, name, output).await?;
-        Ok(())
+        let container_client = self.backend.blobs().container_client(&url)?;
+        let mut stream = container_client
+            .list_blobs()
+            .prefix(SYMBOLS_PREFIX)
+            .into_stream();
+
+        let mut downloaded = vec![];
+        while let Some(entries) = stream.next().await {
+            let entries = entries?;
+            for blob in entries.blobs.blobs() {
+                let Some(relative) = layout.path_for(&blob.name) else {
+                    debug!("skipping {}", blob.name);
+                    continue;
+                };
+                let path = output.join(relative);
+                if let Some(parent) = path.parent() {
+                    create_dir_all(parent).await?;
+                }
+                info!("downloading {} to {}", blob.name, path.display());
+                container_blob_download(
+                    self.backend.blobs(),
+                    &url,
+                    blob.name.clone(),
+                    &path,
+                    &NoProgress,
+                )
+                .await?;
+                downloaded.push(path);
+            }
+        }
+
+        Ok(downloaded)
     }
 
     /// Monitor the ongoing state of an image until the analysis has completed.
@@ -523,19 +2717,176 @@ impl Client {
     /// # }
     /// ```
     pub async fn images_monitor(&self, image_id: ImageId) -> Result<Image> {
+        self.images_monitor_with_options(image_id, &MonitorOptions::default())
+            .await
+    }
+
+    /// Monitor the ongoing state of an image until the analysis has completed,
+    /// using the specified options.
+    ///
+    /// State changes are logged as they occur, as are the stages of the
+    /// analysis for versions of the service that report its progress.  If the
+    /// image remains in the same state, a heartbeat including the time spent
+    /// in the state is logged periodically per `options.heartbeat`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following cases:
+    /// 1. Getting the image fails
+    /// 2. The image analysis state gets to `Failed` or is not recognized
+    /// 3. The analysis does not complete within `options.timeout`
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, MonitorOptions, Result, ImageId};
+    /// # use std::time::Duration;
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let options = MonitorOptions {
+    ///     heartbeat: Some(Duration::from_secs(300)),
+    ///     interval: Duration::from_secs(5),
+    ///     max_interval: Some(Duration::from_secs(60)),
+    ///     timeout: Some(Duration::from_secs(3600)),
+    ///     ..MonitorOptions::default()
+    /// };
+    /// client.images_monitor_with_options(image_id, &options).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn images_monitor_with_options(
+        &self,
+        image_id: ImageId,
+        options: &MonitorOptions,
+    ) -> Result<Image> {
+        self.images_wait_for(
+            image_id,
+            |image| image.state == ImageState::Completed,
+            options,
+        )
+        .await
+    }
+
+    /// Check that an analyzed image was analyzed using the requested version
+    /// of the analysis engine
+    ///
+    /// Versions of the service that do not report `Image.analysis_version`
+    /// cannot be verified, which is logged as a warning.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the image was analyzed using a
+    /// different version of the analysis engine
+    pub fn verify_analysis_version(image: &Image, requested: &str) -> Result<()> {
+        match image.analysis_version.as_deref() {
+            Some(version) if version == requested => Ok(()),
+            Some(version) => Err(Error::Other(
+                "analysis version mismatch",
+                format!(
+                    "{} was analyzed using {version} rather than the requested {requested}",
+                    image.image_id
+                ),
+            )),
+            None => {
+                warn!(
+                    "unable to verify {} was analyzed using {requested}.  the service did not report the analysis version",
+                    image.image_id
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Monitor the ongoing state of an image until it matches `predicate`,
+    /// returning the first version of the image that matches.
+    ///
+    /// This allows waiting for states other than the completion of the
+    /// analysis, such as confirming an image was queued.  State changes and
+    /// heartbeats are logged as with `images_monitor_with_options`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following cases:
+    /// 1. Getting the image fails
+    /// 2. The image analysis state gets to `Failed` or `Deleted` without
+    ///    matching `predicate`
+    /// 3. The analysis completes without the image matching `predicate`
+    /// 4. The image does not match `predicate` within `options.timeout`
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, ImageId, ImageState, MonitorOptions, Result};
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let states = [ImageState::Queued, ImageState::Running];
+    /// let image = client
+    ///     .images_wait_for(
+    ///         image_id,
+    ///         |image| states.contains(&image.state),
+    ///         &MonitorOptions::default(),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn images_wait_for<P>(
+        &self,
+        image_id: ImageId,
+        predicate: P,
+        options: &MonitorOptions,
+    ) -> Result<Image>
+    where
+        P: Fn(&Image) -> bool,
+    {
         let mut image = self.images_get(image_id).await?;
-        if image.state == ImageState::Completed {
+        if predicate(&image) {
             return Ok(image);
         }
 
-        // This will ensure we print the current state at the start of the loop
-        let mut prev_state = ImageState::Completed;
-        loop {
-            if image.state != prev_state {
+        // This will ensure we print the current state at the start of the loop
+        let mut prev_state = None;
+        let mut prev_stage = None;
+        let mut bar = options
+            .show_progress
+            .then(|| AnalysisProgressBar::new(image_id.to_string()));
+        let started = Instant::now();
+        let mut state_since = Instant::now();
+        let mut last_logged = Instant::now();
+        let mut interval = options.interval;
+        loop {
+            match &image.progress {
+                Some(progress) => {
+                    if prev_stage.as_ref() != Some(&progress.stage) {
+                        info!("{:?}: {progress}", image.state);
+                        prev_stage = Some(progress.stage.clone());
+                    }
+                    if let Some(bar) = &mut bar {
+                        bar.update(progress);
+                    }
+                }
+                None => {
+                    if let Some(bar) = &mut bar {
+                        bar.finish();
+                    }
+                }
+            }
+
+            if prev_state.as_ref() != Some(&image.state) {
+                state_since = Instant::now();
+                last_logged = Instant::now();
+                interval = options.interval;
+                if image.state == ImageState::Completed {
+                    info!("analysis completed");
+                } else {
+                    info!("{:?}", image.state);
+                }
+
+                if predicate(&image) {
+                    break;
+                }
+
                 match image.state {
                     ImageState::Completed => {
-                        info!("analysis completed");
-                        break;
+                        return Err(Error::CompletedBeforeMatch);
                     }
                     ImageState::Failed => {
                         if let Some(error) = image.error {
@@ -543,26 +2894,159 @@ impl Client {
                         }
                         return Err(Error::AnalysisFailed("unknown error".into()));
                     }
+                    ImageState::Deleted => {
+                        return Err(Error::AnalysisFailed("image has been deleted".into()));
+                    }
                     ImageState::WaitingForUpload
                     | ImageState::ToQueue
                     | ImageState::Queued
                     | ImageState::Running
                     | ImageState::Finalizing
-                    | ImageState::Deleting => {
-                        info!("{:?}", image.state);
-                    }
+                    | ImageState::Deleting => {}
+                }
+            } else if predicate(&image) {
+                break;
+            } else if options
+                .heartbeat
+                .is_some_and(|heartbeat| last_logged.elapsed() >= heartbeat)
+            {
+                last_logged = Instant::now();
+                let elapsed = state_since.elapsed();
+                let progress = image
+                    .progress
+                    .as_ref()
+                    .map(|x| format!(": {x}"))
+                    .unwrap_or_default();
+                if let Some(remaining) = options.estimate_remaining(&image.state, elapsed) {
+                    info!(
+                        "{:?} for {}s (estimated remaining: {}s){progress}",
+                        image.state,
+                        elapsed.as_secs(),
+                        remaining.as_secs()
+                    );
+                } else {
+                    info!("{:?} for {}s{progress}", image.state, elapsed.as_secs());
                 }
             }
-            sleep(IMAGE_MONITOR_INTERVAL).await;
+            if prev_state.as_ref() == Some(&image.state) {
+                interval = options.backoff(interval);
+            }
+
+            let delay = match options.timeout {
+                Some(timeout) => {
+                    let remaining = timeout.saturating_sub(started.elapsed());
+                    if remaining.is_zero() {
+                        return Err(Error::TimedOut(
+                            format!(
+                                "{image_id} remained {:?} after {}s",
+                                image.state,
+                                timeout.as_secs()
+                            )
+                            .into(),
+                        ));
+                    }
+                    interval.min(remaining)
+                }
+                None => interval,
+            };
+            sleep(delay).await;
 
-            prev_state = image.state;
+            prev_state = Some(image.state);
             image = self.images_get(image_id).await?;
         }
+        if let Some(bar) = &mut bar {
+            bar.finish();
+        }
         Ok(image)
     }
 
+    /// Watch the analysis of an image, yielding the image each time its state
+    /// or the progress of its analysis changes
+    ///
+    /// The stream starts with the current version of the image, and ends once
+    /// the image is `Completed`, `Failed`, or `Deleted`.  Versions of the
+    /// service that do not report the progress of the analysis only yield
+    /// state changes.  The image is polled per `options.interval` and
+    /// `options.max_interval`.
+    ///
+    /// # Errors
+    ///
+    /// The stream returns an error and ends in the following cases:
+    /// 1. Getting the image fails
+    /// 2. The image does not reach a final state within `options.timeout`
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, ImageId, MonitorOptions, Result};
+    /// # use futures::StreamExt;
+    /// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+    /// let options = MonitorOptions::default();
+    /// let mut stream = client.images_watch(image_id, &options);
+    /// while let Some(image) = stream.next().await {
+    ///     let image = image?;
+    ///     match image.progress {
+    ///         Some(progress) => println!("{:?}: {progress}", image.state),
+    ///         None => println!("{:?}", image.state),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn images_watch<'a>(
+        &'a self,
+        image_id: ImageId,
+        options: &'a MonitorOptions,
+    ) -> Pin<Box<impl Stream<Item = std::result::Result<Image, crate::Error>> + Send + 'a>> {
+        Box::pin(async_stream::try_stream! {
+            let started = Instant::now();
+            let mut interval = options.interval;
+            let mut previous = None;
+            loop {
+                let image = self.images_get(image_id).await?;
+                let finished = matches!(
+                    image.state,
+                    ImageState::Completed | ImageState::Failed | ImageState::Deleted
+                );
+                let current = (image.state.clone(), image.progress.clone());
+                if previous.as_ref() == Some(&current) {
+                    interval = options.backoff(interval);
+                } else {
+                    interval = options.interval;
+                    previous = Some(current);
+                    yield image;
+                }
+                if finished {
+                    break;
+                }
+
+                let delay = match options.timeout {
+                    Some(timeout) => {
+                        let remaining = timeout.saturating_sub(started.elapsed());
+                        if remaining.is_zero() {
+                            Err::<(), _>(Error::TimedOut(
+                                format!(
+                                    "{image_id} did not finish after {}s",
+                                    timeout.as_secs()
+                                )
+                                .into(),
+                            ))?;
+                        }
+                        interval.min(remaining)
+                    }
+                    None => interval,
+                };
+                sleep(delay).await;
+            }
+        })
+    }
+
     /// List the configured webhooks
     ///
+    /// If `url_contains` is provided, only webhooks whose URL contains the
+    /// value are included.  If `event_type` is provided, only webhooks that
+    /// monitor the event type are included.
+    ///
     /// # Errors
     ///
     /// This function will return an error in the follow cases:
@@ -575,7 +3059,7 @@ impl Client {
     /// # use freta::{Client, Result};
     /// # use futures::StreamExt;
     /// # async fn example(client: Client) -> Result<()> {
-    /// let mut stream = client.webhooks_list();
+    /// let mut stream = client.webhooks_list(None, None);
     /// while let Some(entry) = stream.next().await {
     ///     let entry = entry?;
     ///     println!("{:?}", entry);
@@ -585,20 +3069,23 @@ impl Client {
     /// ```
     pub fn webhooks_list(
         &self,
+        url_contains: Option<String>,
+        event_type: Option<WebhookEventType>,
     ) -> Pin<Box<impl Stream<Item = std::result::Result<Webhook, crate::Error>> + Send + '_>> {
-        let mut request = WebhooksListRequest { continuation: None };
-        Box::pin(async_stream::try_stream! {
-            loop {
-                let result: WebhooksListResponse = self.backend.get("/api/webhooks", Some(&request)).await?;
-                for webhook in result.webhooks {
-                    yield webhook;
-                }
-                request.continuation = result.continuation;
-                if request.continuation.is_none() {
-                    break;
-                }
-            }
-        })
+        let request = WebhooksListRequest {
+            continuation: None,
+            url_contains,
+            event_type,
+        };
+        let filter = request.clone();
+        let stream = paginate(request, move |request| async move {
+            let result: WebhooksListResponse =
+                self.backend.get("/api/webhooks", Some(&request)).await?;
+            Ok(result)
+        });
+        // filter locally as well, as older versions of the service ignore the
+        // filters
+        Box::pin(stream.try_filter(move |webhook| ready(filter.matches(webhook))))
     }
 
     /// Get information on a webhook
@@ -633,29 +3120,126 @@ impl Client {
 
     /// Update a webhook
     ///
+    /// If `include_tags` is true, events related to an image include the
+    /// image's `tags`.
+    ///
+    /// The `target` is either an HTTP endpoint or an Azure queue.  HTTP
+    /// endpoint URLs may include the placeholder `{event_type}`, which is
+    /// replaced with the type of the event being delivered.  This enables
+    /// delivering events of different types to different paths of the same
+    /// receiver, such as `https://example.com/freta/{event_type}`.
+    ///
+    /// If `hmac_token_previous` is `None` and the webhook is rotating its
+    /// HMAC token, the webhook's previous token is kept such that the
+    /// rotation stays in progress.  Use `webhook_complete_hmac_rotation` to
+    /// complete the rotation.
+    ///
     /// # Errors
     ///
     /// This function will return an error in the following conditions:
-    /// 1. The connection to the Service fails
-    /// 2. The user does not have permission to update the specified webhook
-    pub async fn webhook_update<S>(
+    /// 1. The `target` is invalid
+    /// 2. The connection to the Service fails
+    /// 3. The user does not have permission to update the specified webhook
+    pub async fn webhook_update<T, S>(
         &self,
         webhook_id: WebhookId,
-        url: Url,
+        target: T,
         event_types: BTreeSet<WebhookEventType>,
         hmac_token: Option<S>,
+        hmac_token_previous: Option<S>,
+        include_tags: bool,
     ) -> Result<Webhook>
     where
+        T: Into<WebhookTarget>,
         S: Into<Secret>,
     {
+        let target = target.into();
+        target.validate()?;
         let hmac_token = hmac_token.map(Into::into);
+        let hmac_token_previous = hmac_token_previous.map(Into::into);
 
-        let update = WebhookSubmit {
-            url,
+        let mut update = WebhookSubmit {
+            target,
             hmac_token,
+            hmac_token_previous,
             event_types,
+            include_tags,
+        };
+        if update.hmac_token_previous.is_none() {
+            let webhook = self.webhook_get(webhook_id).await?;
+            update = update.keeping_rotation(&webhook);
+        }
+
+        let res = self
+            .backend
+            .post(&format!("/api/webhooks/{webhook_id}"), update)
+            .await?;
+        Ok(res)
+    }
+
+    /// Start rotating the HMAC token of a webhook
+    ///
+    /// Events are signed using `hmac_token`, while the current token is kept
+    /// as `Webhook::hmac_token_previous`.  Receivers should accept either
+    /// token, as provided by `Webhook::hmac_tokens`, until the rotation is
+    /// completed using `webhook_complete_hmac_rotation`.  This allows
+    /// rotating the token without dropping events or accepting unsigned
+    /// events.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to update the specified webhook
+    pub async fn webhook_rotate_hmac_token<S>(
+        &self,
+        webhook_id: WebhookId,
+        hmac_token: S,
+    ) -> Result<Webhook>
+    where
+        S: Into<Secret>,
+    {
+        let webhook = self.webhook_get(webhook_id).await?;
+        if webhook.hmac_token_previous.is_some() {
+            warn!("webhook {webhook_id} is already rotating its hmac token.  the oldest token is no longer accepted");
+        }
+
+        let update = WebhookSubmit {
+            target: webhook.target,
+            hmac_token: Some(hmac_token.into()),
+            hmac_token_previous: webhook.hmac_token,
+            event_types: webhook.event_types,
+            include_tags: webhook.include_tags,
         };
+        let res = self
+            .backend
+            .post(&format!("/api/webhooks/{webhook_id}"), update)
+            .await?;
+        Ok(res)
+    }
+
+    /// Complete the rotation of the HMAC token of a webhook, such that
+    /// `Webhook::hmac_token_previous` is no longer used
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to update the specified webhook
+    pub async fn webhook_complete_hmac_rotation(&self, webhook_id: WebhookId) -> Result<Webhook> {
+        let webhook = self.webhook_get(webhook_id).await?;
+        if webhook.hmac_token_previous.is_none() {
+            info!("webhook {webhook_id} is not rotating its hmac token");
+            return Ok(webhook);
+        }
 
+        let update = WebhookSubmit {
+            target: webhook.target,
+            hmac_token: webhook.hmac_token,
+            hmac_token_previous: None,
+            event_types: webhook.event_types,
+            include_tags: webhook.include_tags,
+        };
         let res = self
             .backend
             .post(&format!("/api/webhooks/{webhook_id}"), update)
@@ -685,6 +3269,75 @@ impl Client {
         Ok(res)
     }
 
+    /// Send a synthetic event of the specified type to a webhook
+    ///
+    /// This is similar to `webhook_ping`, but allows validating how the
+    /// receiver handles each type of event.
+    ///
+    /// The response is the raw event sent to the webhook.  Versions of the
+    /// service that do not support sending events of other types send a
+    /// `ping` event instead, which `webhooks_ping_all` reports as a failure.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to update the specified webhook
+    pub async fn webhook_ping_event(
+        &self,
+        webhook_id: WebhookId,
+        event_type: WebhookEventType,
+    ) -> Result<Bytes> {
+        let path = format!("/api/webhooks/{webhook_id}");
+        // older versions of the service only support pings without a body
+        if event_type == WebhookEventType::Ping {
+            return self.backend.patch_raw(&path, None::<bool>).await;
+        }
+        let request = WebhookPingRequest { event_type };
+        self.backend.patch_raw(&path, Some(request)).await
+    }
+
+    /// Send synthetic events to every webhook concurrently
+    ///
+    /// Each webhook is sent an event of each of the `event_types` that it
+    /// monitors.  Event types the webhook does not monitor are not sent, such
+    /// that receivers only get the events they expect.  If `event_types` is
+    /// empty, each webhook is sent an event of each type it monitors.
+    ///
+    /// An event is only recorded as delivered if the service responds with
+    /// the event it sent and that event is of the requested type.  Failures
+    /// to deliver an event are recorded in the results rather than returned
+    /// as errors.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if listing the webhooks fails
+    pub async fn webhooks_ping_all(
+        &self,
+        event_types: &[WebhookEventType],
+    ) -> Result<Vec<WebhookPingResults>> {
+        let webhooks: Vec<Webhook> = self.webhooks_list(None, None).try_collect().await?;
+
+        let pings = webhooks.into_iter().map(|webhook| async move {
+            let mut results = vec![];
+            for event_type in &webhook.event_types {
+                if !event_types.is_empty() && !event_types.contains(event_type) {
+                    continue;
+                }
+                let response = self
+                    .webhook_ping_event(webhook.webhook_id, event_type.clone())
+                    .await;
+                results.push(WebhookPingResult::new(event_type.clone(), response));
+            }
+            WebhookPingResults {
+                webhook_id: webhook.webhook_id,
+                results,
+            }
+        });
+
+        Ok(join_all(pings).await)
+    }
+
     /// Resend a webhook event
     ///
     /// This resends a specific event to the webhook.
@@ -712,32 +3365,136 @@ impl Client {
 
     /// Create a webhook
     ///
+    /// If `include_tags` is true, events related to an image include the
+    /// image's `tags`.
+    ///
+    /// The `target` is either an HTTP endpoint or an Azure queue.  HTTP
+    /// endpoint URLs may include the placeholder `{event_type}`, which is
+    /// replaced with the type of the event being delivered.  This enables
+    /// delivering events of different types to different paths of the same
+    /// receiver, such as `https://example.com/freta/{event_type}`.
+    ///
     /// # Errors
     ///
     /// This function will return an error in the following conditions:
-    /// 1. The connection to the Service fails
-    /// 2. The user does not have permission to create a webhook
-    pub async fn webhook_create<S>(
+    /// 1. The `target` is invalid
+    /// 2. The connection to the Service fails
+    /// 3. The user does not have permission to create a webhook
+    pub async fn webhook_create<T, S>(
         &self,
-        url: Url,
+        target: T,
         event_types: BTreeSet<WebhookEventType>,
         hmac_token: Option<S>,
+        include_tags: bool,
     ) -> Result<Webhook>
     where
+        T: Into<WebhookTarget>,
         S: Into<Secret>,
     {
+        let target = target.into();
+        target.validate()?;
         let hmac_token = hmac_token.map(Into::into);
 
         let update = WebhookSubmit {
-            url,
+            target,
             hmac_token,
+            hmac_token_previous: None,
             event_types,
+            include_tags,
         };
 
         let res = self.backend.post("/api/webhooks", update).await?;
         Ok(res)
     }
 
+    /// Create a webhook, unless a webhook with the same target already exists
+    ///
+    /// If a webhook with the same target exists, it is updated to use the
+    /// specified event types, HMAC token, and `include_tags` rather than
+    /// creating a duplicate webhook.  This allows provisioning scripts to be
+    /// run repeatedly.  Webhooks that already match are not modified.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The `target` is invalid
+    /// 2. The connection to the Service fails
+    /// 3. The user does not have permission to create or update a webhook
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Result, Secret};
+    /// # use freta::models::webhooks::WebhookEventType;
+    /// # use url::Url;
+    /// # async fn example(client: Client, url: Url) -> Result<()> {
+    /// let event_types = [WebhookEventType::ImageAnalysisCompleted].into();
+    /// let webhook = client
+    ///     .webhook_ensure(url, event_types, Some(Secret::new("hmac")), false)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn webhook_ensure<T, S>(
+        &self,
+        target: T,
+        event_types: BTreeSet<WebhookEventType>,
+        hmac_token: Option<S>,
+        include_tags: bool,
+    ) -> Result<Webhook>
+    where
+        T: Into<WebhookTarget>,
+        S: Into<Secret>,
+    {
+        let target = target.into();
+        target.validate()?;
+        let hmac_token: Option<Secret> = hmac_token.map(Into::into);
+
+        let existing: Vec<Webhook> = self
+            .webhooks_list(None, None)
+            .try_filter(|webhook| ready(webhook.target == target))
+            .try_collect()
+            .await?;
+        let Some(webhook) = existing.first() else {
+            info!("creating webhook for {target}");
+            return self
+                .webhook_create(target, event_types, hmac_token, include_tags)
+                .await;
+        };
+        if existing.len() > 1 {
+            let duplicates = existing
+                .iter()
+                .skip(1)
+                .map(|x| x.webhook_id.to_string())
+                .collect::<Vec<_>>();
+            warn!(
+                "using webhook {} for {target}.  duplicate webhooks: {}",
+                webhook.webhook_id,
+                duplicates.join(", ")
+            );
+        }
+
+        let unchanged = webhook.event_types == event_types
+            && webhook.include_tags == include_tags
+            && webhook.hmac_token.as_ref().map(Secret::get_secret)
+                == hmac_token.as_ref().map(Secret::get_secret);
+        if unchanged {
+            info!("webhook {} is up to date", webhook.webhook_id);
+            return Ok(webhook.clone());
+        }
+
+        info!("updating webhook {}", webhook.webhook_id);
+        self.webhook_update(
+            webhook.webhook_id,
+            target,
+            event_types,
+            hmac_token,
+            webhook.hmac_token_previous.clone(),
+            include_tags,
+        )
+        .await
+    }
+
     /// List the logs for a specific webhook
     ///
     /// # Errors
@@ -765,18 +3522,228 @@ impl Client {
         webhook_id: WebhookId,
     ) -> Pin<Box<impl Stream<Item = std::result::Result<WebhookLog, crate::Error>> + Send + '_>>
     {
-        let mut request = WebhookLogListRequest { continuation: None };
+        self.webhooks_logs_filtered(webhook_id, WebhookLogListRequest::default())
+    }
+
+    /// List the event logs of a webhook that match the filters of `request`,
+    /// such as failed deliveries of a specific event type
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the follow cases:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to get the webhook
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use freta::{Client, Result};
+    /// # use freta::models::webhooks::{
+    /// #     service::WebhookLogListRequest, WebhookEventState, WebhookId,
+    /// # };
+    /// # use futures::StreamExt;
+    /// # async fn example(client: Client, webhook_id: WebhookId) -> Result<()> {
+    /// let request = WebhookLogListRequest::default().with_state(WebhookEventState::Failure);
+    /// let mut stream = client.webhooks_logs_filtered(webhook_id, request);
+    /// while let Some(entry) = stream.next().await {
+    ///     let entry = entry?;
+    ///     println!("{entry:?}");
+    /// }
+    /// #    Ok(())
+    /// # }
+    /// ```
+    pub fn webhooks_logs_filtered(
+        &self,
+        webhook_id: WebhookId,
+        request: WebhookLogListRequest,
+    ) -> Pin<Box<impl Stream<Item = std::result::Result<WebhookLog, crate::Error>> + Send + '_>>
+    {
+        let filter = request.clone();
+        let stream = paginate(request, move |request| async move {
+            let result: WebhookLogListResponse = self
+                .backend
+                .get(&format!("/api/webhooks/{webhook_id}/logs"), Some(&request))
+                .await?;
+            Ok(result)
+        });
+        // filter locally as well, as older versions of the service ignore the
+        // filters
+        Box::pin(stream.try_filter(move |log| ready(filter.matches(log))))
+    }
+
+    /// Create a collection of related images
+    ///
+    /// If the service does not provide the collections API, membership of the
+    /// collection is recorded using image tags.  See `COLLECTION_TAG_PREFIX`
+    /// for details.  In that case, a collection only exists while it includes
+    /// at least one image.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The user does not have permission to create collections
+    /// 3. The user does not have permission to update the specified images
+    pub async fn collections_create(
+        &self,
+        name: CollectionName,
+        description: Option<String>,
+        image_ids: Vec<ImageId>,
+    ) -> Result<Collection> {
+        let create = CollectionCreate {
+            name,
+            description,
+            image_ids,
+        };
+
+        if self.collections_supported().await? {
+            return self.backend.post("/api/collections", create).await;
+        }
+
+        for image_id in &create.image_ids {
+            self.collections_tag_image(&create.name, create.description.as_deref(), *image_id)
+                .await?;
+        }
+        Ok(Collection {
+            name: create.name,
+            description: create.description,
+            image_ids: create.image_ids,
+        })
+    }
+
+    /// Add an image to a collection
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The collection does not exist
+    /// 3. The user does not have permission to update the specified image
+    pub async fn collections_add_image(
+        &self,
+        name: &CollectionName,
+        image_id: ImageId,
+    ) -> Result<Collection> {
+        if self.collections_supported().await? {
+            let add = CollectionAddImage { image_id };
+            return self
+                .backend
+                .post(&format!("/api/collections/{name}/images"), add)
+                .await;
+        }
+
+        // collections recorded using tags only exist while they include an
+        // image, so adding an image to a new collection creates it
+        let description = self
+            .collections_from_tags()
+            .await?
+            .into_iter()
+            .find(|collection| &collection.name == name)
+            .and_then(|collection| collection.description);
+        self.collections_tag_image(name, description.as_deref(), image_id)
+            .await?;
+        self.collections_get(name).await
+    }
+
+    /// Get a collection
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The collection does not exist
+    pub async fn collections_get(&self, name: &CollectionName) -> Result<Collection> {
+        if self.collections_supported().await? {
+            return self
+                .backend
+                .get(&format!("/api/collections/{name}"), None::<bool>)
+                .await;
+        }
+
+        self.collections_from_tags()
+            .await?
+            .into_iter()
+            .find(|collection| &collection.name == name)
+            .ok_or_else(|| Error::Other("collection not found", name.to_string()))
+    }
+
+    /// List the available collections
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use freta::{Client, Result};
+    /// # use futures::StreamExt;
+    /// # async fn example(client: Client) -> Result<()> {
+    /// let mut stream = client.collections_list();
+    /// while let Some(collection) = stream.next().await {
+    ///     let collection = collection?;
+    ///     println!("{} {}", collection.name, collection.image_ids.len());
+    /// }
+    /// #   Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the connection to the Service
+    /// fails
+    pub fn collections_list(
+        &self,
+    ) -> Pin<Box<impl Stream<Item = std::result::Result<Collection, crate::Error>> + Send + '_>>
+    {
         Box::pin(async_stream::try_stream! {
-            loop {
-                let result: WebhookLogListResponse = self.backend.get(&format!("/api/webhooks/{webhook_id}/logs"), Some(&request)).await?;
-                for webhook in result.webhook_events {
-                    yield webhook;
+            if self.collections_supported().await? {
+                let request = CollectionsListRequest::default();
+                let mut stream = paginate(request, move |request| async move {
+                    let result: CollectionsListResponse =
+                        self.backend.get("/api/collections", Some(&request)).await?;
+                    Ok(result)
+                });
+                while let Some(collection) = stream.next().await {
+                    yield collection?;
                 }
-                request.continuation = result.continuation;
-                if request.continuation.is_none() {
-                    break;
+            } else {
+                for collection in self.collections_from_tags().await? {
+                    yield collection;
                 }
             }
         })
     }
+
+    /// Check if the service provides the collections API
+    async fn collections_supported(&self) -> Result<bool> {
+        let request = CollectionsListRequest::default();
+        let result: Result<CollectionsListResponse> =
+            self.backend.get("/api/collections", Some(&request)).await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(err) if err.is_not_found() => {
+                debug!("collections are not supported by the service.  using image tags");
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Build the collections recorded using image tags
+    async fn collections_from_tags(&self) -> Result<Vec<Collection>> {
+        let images: Vec<Image> = self
+            .images_list(None, None, None, false, false, None)
+            .try_collect()
+            .await?;
+        Ok(Collection::from_tagged_images(&images))
+    }
+
+    /// Record membership of a collection using the tags of an image
+    async fn collections_tag_image(
+        &self,
+        name: &CollectionName,
+        description: Option<&str>,
+        image_id: ImageId,
+    ) -> Result<Image> {
+        let mut tags = self.images_get(image_id).await?.tags;
+        tags.insert(name.tag_key(), description.unwrap_or_default().to_string());
+        self.images_update(image_id, Some(tags), None).await
+    }
 }
@@ -0,0 +1,217 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Endpoints that list records return the records in pages.  Each response
+//! includes an opaque continuation value, which is included in the next
+//! request to get the following page.  Once the response does not include a
+//! continuation value, all of the records have been returned.
+//!
+//! `paginate` converts such an endpoint into a `Stream` of the records, such
+//! that callers do not need to handle the continuation values themselves.
+//!
+//! ```rust,no_run
+//! # use freta::{
+//! #     models::webhooks::service::{WebhookLogListRequest, WebhookLogListResponse},
+//! #     paginate::paginate,
+//! #     Result,
+//! # };
+//! # use futures::StreamExt;
+//! # async fn fetch(_request: WebhookLogListRequest) -> Result<WebhookLogListResponse> {
+//! #     Ok(WebhookLogListResponse {
+//! #         webhook_events: vec![],
+//! #         continuation: None,
+//! #     })
+//! # }
+//! # async fn example() -> Result<()> {
+//! let mut stream = paginate(WebhookLogListRequest::default(), fetch);
+//! while let Some(entry) = stream.next().await {
+//!     let entry = entry?;
+//!     println!("{entry:?}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    models::{
+        base::Image,
+        collections::{Collection, CollectionsListRequest, CollectionsListResponse},
+        service::{ImageList, ImagesListResponse},
+        tokens::{Token, TokensListRequest, TokensListResponse},
+        webhooks::{
+            service::{
+                WebhookLogListRequest, WebhookLogListResponse, WebhooksListRequest,
+                WebhooksListResponse,
+            },
+            Webhook, WebhookLog,
+        },
+    },
+    Result,
+};
+use futures::{Future, Stream};
+use std::pin::Pin;
+
+/// A request for a single page of records
+pub trait PageRequest {
+    /// Set the continuation value returned by the previous page
+    fn set_continuation(&mut self, continuation: String);
+}
+
+/// A single page of records
+pub trait PageResponse {
+    /// The type of record in the page
+    type Item;
+
+    /// Split the page into its records and the continuation value for the
+    /// next page, if any
+    fn into_page(self) -> (Vec<Self::Item>, Option<String>);
+}
+
+/// Convert an endpoint that returns records in pages into a `Stream` of the
+/// records
+///
+/// `fetch` is called with `initial` to get the first page, then with a copy of
+/// `initial` updated with the continuation value of the previous page until
+/// a page does not include a continuation value.
+///
+/// # Errors
+/// The stream returns an error and ends if `fetch` returns an error
+pub fn paginate<'a, F, Fut, Req, Resp, Item>(
+    initial: Req,
+    fetch: F,
+) -> Pin<Box<impl Stream<Item = Result<Item>> + Send + 'a>>
+where
+    F: Fn(Req) -> Fut + Send + 'a,
+    Fut: Future<Output = Result<Resp>> + Send + 'a,
+    Req: PageRequest + Clone + Send + 'a,
+    Resp: PageResponse<Item = Item> + Send + 'a,
+    Item: Send + 'a,
+{
+    let mut request = initial;
+    Box::pin(async_stream::try_stream! {
+        loop {
+            let (items, continuation) = fetch(request.clone()).await?.into_page();
+            for item in items {
+                yield item;
+            }
+            let Some(continuation) = continuation else {
+                break;
+            };
+            request.set_continuation(continuation);
+        }
+    })
+}
+
+impl PageRequest for ImageList {
+    fn set_continuation(&mut self, continuation: String) {
+        self.continuation = Some(continuation);
+    }
+}
+
+impl PageResponse for ImagesListResponse {
+    type Item = Image;
+    fn into_page(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.images, self.continuation)
+    }
+}
+
+impl PageRequest for CollectionsListRequest {
+    fn set_continuation(&mut self, continuation: String) {
+        self.continuation = Some(continuation);
+    }
+}
+
+impl PageResponse for CollectionsListResponse {
+    type Item = Collection;
+    fn into_page(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.collections, self.continuation)
+    }
+}
+
+impl PageRequest for WebhooksListRequest {
+    fn set_continuation(&mut self, continuation: String) {
+        self.continuation = Some(continuation);
+    }
+}
+
+impl PageResponse for WebhooksListResponse {
+    type Item = Webhook;
+    fn into_page(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.webhooks, self.continuation)
+    }
+}
+
+impl PageRequest for WebhookLogListRequest {
+    fn set_continuation(&mut self, continuation: String) {
+        self.continuation = Some(continuation);
+    }
+}
+
+impl PageResponse for WebhookLogListResponse {
+    type Item = WebhookLog;
+    fn into_page(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.webhook_events, self.continuation)
+    }
+}
+
+impl PageRequest for TokensListRequest {
+    fn set_continuation(&mut self, continuation: String) {
+        self.continuation = Some(continuation);
+    }
+}
+
+impl PageResponse for TokensListResponse {
+    type Item = Token;
+    fn into_page(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.tokens, self.continuation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use futures::{future::ready, StreamExt};
+
+    #[derive(Clone)]
+    struct Request(Option<String>);
+
+    impl PageRequest for Request {
+        fn set_continuation(&mut self, continuation: String) {
+            self.0 = Some(continuation);
+        }
+    }
+
+    struct Response(Vec<u32>, Option<String>);
+
+    impl PageResponse for Response {
+        type Item = u32;
+        fn into_page(self) -> (Vec<Self::Item>, Option<String>) {
+            (self.0, self.1)
+        }
+    }
+
+    fn fetch(request: Request) -> impl Future<Output = Result<Response>> {
+        let response = match request.0.as_deref() {
+            None => Ok(Response(vec![1, 2], Some("a".into()))),
+            Some("a") => Ok(Response(vec![], Some("b".into()))),
+            Some("b") => Ok(Response(vec![3], None)),
+            Some(x) => Err(Error::Other("unexpected continuation", x.to_string())),
+        };
+        ready(response)
+    }
+
+    #[tokio::test]
+    async fn test_paginate() -> Result<()> {
+        let items = paginate(Request(None), fetch)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(items, vec![1, 2, 3]);
+
+        let mut failing = paginate(Request(Some("c".into())), fetch);
+        assert!(matches!(failing.next().await, Some(Err(_))));
+        assert!(failing.next().await.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Automatically acts on images once their analysis completes, per rules in
+//! `~/.config/freta/policies.yaml`.
+//!
+//! Policies are evaluated using the checks from the analysis report of an
+//! image, and can tag, delete, set the retention period of, or post a
+//! notification about the image.  See `PolicySet` for the format of the
+//! policy file.
+//!
+//! ```rust,no_run
+//! # use freta::{Client, ImageId, Result};
+//! # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+//! let policies = Client::policies_load(None).await?;
+//! for action in client.policies_apply(&policies, image_id).await? {
+//!     println!("{} applied {:?}", action.policy, action.action);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::{config::get_config_dir, error::io_err},
+    models::{
+        analysis::hook::Check,
+        base::{Image, ImageState},
+        policies::{
+            retention_expired, Action, PolicyAction, PolicyNotification, PolicySet, RETENTION_TAG,
+        },
+    },
+    Client, Error, ImageId, Result,
+};
+use futures::TryStreamExt;
+use std::path::{Path, PathBuf};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tokio::fs;
+use tracing::info;
+use url::Url;
+
+/// Get the path of the default policy file
+///
+/// # Errors
+/// This will return an error if the config directory cannot be determined
+fn default_policies_path() -> Result<PathBuf> {
+    Ok(get_config_dir()?.join("policies.yaml"))
+}
+
+impl Client {
+    /// Load policies from a YAML file, defaulting to
+    /// `~/.config/freta/policies.yaml`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The policy file cannot be read
+    /// 2. The policy file is invalid
+    pub async fn policies_load(path: Option<&Path>) -> Result<PolicySet> {
+        let path = path.map_or_else(default_policies_path, |path| Ok(path.to_path_buf()))?;
+        let contents = fs::read_to_string(&path)
+            .await
+            .map_err(|e| io_err(format!("reading policies: {path:?}"), e))?;
+        serde_yaml::from_str(&contents)
+            .map_err(|e| Error::Other("invalid policy file", format!("{path:?}: {e}")))
+    }
+
+    /// Determine the actions the policies require for an image, without
+    /// taking them
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The analysis of the image has not completed
+    /// 3. The analysis report for the image is unavailable or invalid
+    pub async fn policies_evaluate(
+        &self,
+        policies: &PolicySet,
+        image_id: ImageId,
+    ) -> Result<Vec<PolicyAction>> {
+        let image = self.images_get(image_id).await?;
+        let checks = self.policies_checks(&image).await?;
+        Ok(policies.evaluate(&image, &checks))
+    }
+
+    /// Evaluate the policies for an image and take the required actions,
+    /// returning the actions taken
+    ///
+    /// Actions are taken in the order of the policies.  Once an image is
+    /// deleted, subsequent actions for the image are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The policies cannot be evaluated for the image
+    /// 2. Taking an action fails
+    pub async fn policies_apply(
+        &self,
+        policies: &PolicySet,
+        image_id: ImageId,
+    ) -> Result<Vec<PolicyAction>> {
+        let mut image = self.images_get(image_id).await?;
+        let checks = self.policies_checks(&image).await?;
+        let actions = policies.evaluate(&image, &checks);
+
+        let mut taken = vec![];
+        for action in actions {
+            info!(
+                "policy {} for {}: {:?}",
+                action.policy, image_id, action.action
+            );
+            match &action.action {
+                Action::Tag { key, value } => {
+                    let mut tags = image.tags.clone();
+                    tags.insert(key.clone(), value.clone());
+                    image = self.images_update(image_id, Some(tags), None).await?;
+                }
+                Action::Delete => {
+                    self.images_delete(image_id).await?;
+                    taken.push(action);
+                    break;
+                }
+                Action::SetRetention { days } => {
+                    if !image.tags.contains_key(RETENTION_TAG) {
+                        let expires = OffsetDateTime::now_utc() + Duration::days((*days).into());
+                        let expires = expires
+                            .format(&Rfc3339)
+                            .map_err(|e| Error::Other("invalid retention", e.to_string()))?;
+                        let mut tags = image.tags.clone();
+                        tags.insert(RETENTION_TAG.to_string(), expires);
+                        image = self.images_update(image_id, Some(tags), None).await?;
+                    }
+                }
+                Action::WebhookPost { url } => {
+                    let notification = PolicyNotification {
+                        policy: action.policy.clone(),
+                        image_id,
+                        tags: image.tags.clone(),
+                        issues: checks.iter().map(|check| check.issue.clone()).collect(),
+                    };
+                    self.policies_notify(url.clone(), &notification).await?;
+                }
+            }
+            taken.push(action);
+        }
+        Ok(taken)
+    }
+
+    /// Delete the images whose retention period, as recorded by the
+    /// `set_retention` policy action, has ended
+    ///
+    /// Returns the images that were deleted.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. Listing the images fails
+    /// 2. Deleting an image fails
+    pub async fn policies_enforce_retention(&self) -> Result<Vec<ImageId>> {
+        let now = OffsetDateTime::now_utc();
+        let expired = self
+            .images_list(None, None, None, false, false, None)
+            .try_filter(|image| futures::future::ready(retention_expired(image, now)))
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut deleted = vec![];
+        for image in expired {
+            info!("retention expired for {}", image.image_id);
+            self.images_delete(image.image_id).await?;
+            deleted.push(image.image_id);
+        }
+        Ok(deleted)
+    }
+
+    /// Get the checks from the analysis of an image, used to evaluate
+    /// policies
+    async fn policies_checks(&self, image: &Image) -> Result<Vec<Check>> {
+        if image.state != ImageState::Completed {
+            return Err(Error::Other(
+                "policies require completed analysis",
+                image.image_id.to_string(),
+            ));
+        }
+        Ok(self.report(image.image_id).await?.checks)
+    }
+
+    /// Post a notification about an image for the `webhook_post` action
+    async fn policies_notify(&self, url: Url, notification: &PolicyNotification) -> Result<()> {
+        self.backend
+            .config()
+            .http_client_builder()?
+            .build()?
+            .post(url)
+            .json(notification)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
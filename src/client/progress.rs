@@ -0,0 +1,204 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Reporting the progress of uploads and downloads.
+//!
+//! Transfers report their progress to a `ProgressHandler`.  By default, the
+//! CLI displays a progress bar on stderr using `ProgressBarHandler`.
+//! Applications embedding the client, such as GUIs or services, can provide
+//! their own handler or disable reporting with `NoProgress`.
+//!
+//! ```rust,no_run
+//! # use freta::{Client, ImageId, ProgressHandler, Result};
+//! # use std::sync::atomic::{AtomicU64, Ordering};
+//! #[derive(Debug, Default)]
+//! struct Counter(AtomicU64);
+//!
+//! impl ProgressHandler for Counter {
+//!     fn on_progress(&self, bytes: u64) {
+//!         self.0.fetch_add(bytes, Ordering::Relaxed);
+//!     }
+//! }
+//!
+//! # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+//! let counter = Counter::default();
+//! client
+//!     .images_download_with_progress(image_id, "/tmp/image.lime", &counter)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::base::AnalysisProgress;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressFinish, ProgressStyle};
+use std::fmt;
+
+/// Template of the progress bars displayed by `ProgressBarHandler`
+const PROGRESS_BAR_TEMPLATE: &str =
+    "[{elapsed_precise}] [eta:{eta}] [{wide_bar}] {bytes}/{total_bytes} ({bytes_per_sec})";
+
+/// Receives the progress of an upload or download
+///
+/// Handlers may be called concurrently from multiple tasks.
+pub trait ProgressHandler: fmt::Debug + Send + Sync {
+    /// The transfer started
+    ///
+    /// `total` is the number of bytes to transfer, if known.  `position` is
+    /// the number of bytes transferred previously, such as when resuming an
+    /// upload.
+    fn on_start(&self, total: Option<u64>, position: u64) {
+        let _ = (total, position);
+    }
+
+    /// An additional `bytes` bytes were transferred
+    fn on_progress(&self, bytes: u64);
+
+    /// The transfer stalled and is being retried
+    ///
+    /// `stalls` is the number of times the transfer, or the block being
+    /// uploaded, stalled so far.
+    fn on_stall(&self, stalls: usize) {
+        let _ = stalls;
+    }
+
+    /// The transfer completed successfully
+    fn on_finish(&self) {}
+}
+
+/// Displays the progress of transfers as a progress bar on stderr
+pub struct ProgressBarHandler {
+    /// The progress bar
+    bar: ProgressBar,
+}
+
+impl ProgressBarHandler {
+    /// Create a progress bar that is drawn once the transfer starts
+    #[must_use]
+    pub fn new() -> Self {
+        let style = ProgressStyle::with_template(PROGRESS_BAR_TEMPLATE)
+            .unwrap_or_else(|_| ProgressStyle::default_bar());
+        let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr_with_hz(1))
+            .with_style(style)
+            .with_finish(ProgressFinish::AndLeave);
+        Self { bar }
+    }
+}
+
+impl Default for ProgressBarHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ProgressBarHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressBarHandler")
+            .field("position", &self.bar.position())
+            .field("length", &self.bar.length())
+            .finish()
+    }
+}
+
+impl ProgressHandler for ProgressBarHandler {
+    fn on_start(&self, total: Option<u64>, position: u64) {
+        if let Some(total) = total {
+            self.bar.set_length(total);
+        }
+        self.bar.set_position(position);
+    }
+
+    fn on_progress(&self, bytes: u64) {
+        self.bar.inc(bytes);
+    }
+
+    fn on_finish(&self) {
+        self.bar.finish();
+    }
+}
+
+/// Template of the progress bars displayed by `AnalysisProgressBar`
+const ANALYSIS_BAR_TEMPLATE: &str = "[{elapsed_precise}] {prefix} [{wide_bar}] {pos}% {msg}";
+
+/// Displays the progress of the analysis of an image as a progress bar on
+/// stderr
+///
+/// The bar is only drawn once the service reports the progress of the
+/// analysis, such that nothing is displayed by versions of the service that
+/// do not report it.
+#[derive(Debug)]
+pub(crate) struct AnalysisProgressBar {
+    /// The progress bar, once progress is reported
+    bar: Option<ProgressBar>,
+    /// Label of the bar, such as the image id
+    prefix: String,
+}
+
+impl AnalysisProgressBar {
+    /// Create a progress bar labeled with `prefix`
+    pub(crate) const fn new(prefix: String) -> Self {
+        Self { bar: None, prefix }
+    }
+
+    /// Update the bar with the progress of the analysis
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub(crate) fn update(&mut self, progress: &AnalysisProgress) {
+        let bar = self.bar.get_or_insert_with(|| {
+            let style = ProgressStyle::with_template(ANALYSIS_BAR_TEMPLATE)
+                .unwrap_or_else(|_| ProgressStyle::default_bar());
+            ProgressBar::with_draw_target(Some(100), ProgressDrawTarget::stderr_with_hz(1))
+                .with_style(style)
+                .with_prefix(self.prefix.clone())
+        });
+        if let Some(percent) = progress.percent {
+            // the percentage is clamped, so the conversion does not truncate
+            bar.set_position(percent.clamp(0.0, 100.0).round() as u64);
+        }
+        bar.set_message(progress.stage.clone());
+    }
+
+    /// Remove the bar once the analysis is no longer running
+    pub(crate) fn finish(&mut self) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// Ignores the progress of transfers
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoProgress;
+
+impl ProgressHandler for NoProgress {
+    fn on_progress(&self, _bytes: u64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[derive(Debug, Default)]
+    struct Counter(AtomicU64);
+
+    impl ProgressHandler for Counter {
+        fn on_progress(&self, bytes: u64) {
+            self.0.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_progress_handlers() {
+        let handlers: [&dyn ProgressHandler; 3] =
+            [&Counter::default(), &ProgressBarHandler::new(), &NoProgress];
+        for handler in handlers {
+            handler.on_start(Some(10), 2);
+            handler.on_progress(8);
+            handler.on_finish();
+        }
+
+        let counter = Counter::default();
+        counter.on_start(None, 0);
+        counter.on_progress(3);
+        counter.on_progress(4);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 7);
+    }
+}
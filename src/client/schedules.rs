@@ -0,0 +1,138 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Periodically acts on images, such as reanalyzing a set of known-good
+//! images each week so they benefit from updated detection content.
+//!
+//! Schedules are saved in `~/.config/freta/schedules.json`.  The client does
+//! not run schedules on its own.  Instead, `freta schedule run` checks which
+//! schedules are due and runs them.
+//!
+//! ```rust,no_run
+//! # use freta::{models::schedules::{CronExpression, ScheduledAction}, Client, Result};
+//! # use std::collections::BTreeMap;
+//! # async fn example() -> Result<()> {
+//! let cron: CronExpression = "0 3 * * 1".parse()?;
+//! let action = ScheduledAction::Reanalyze {
+//!     tags: BTreeMap::from([("rescan".to_string(), "weekly".to_string())]),
+//!     engine_version: None,
+//! };
+//! Client::schedules_add(cron, action).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::{
+        config::get_config_dir,
+        io::{create_dir_all, read_json, write_json},
+    },
+    models::schedules::{CronExpression, Schedule, ScheduleId, ScheduleRun, ScheduledAction},
+    Client, Error, Result,
+};
+use futures::TryStreamExt;
+use std::path::PathBuf;
+use time::OffsetDateTime;
+
+/// Get the path of the saved schedules
+///
+/// # Errors
+/// This will return an error if the config directory cannot be determined
+fn schedules_path() -> Result<PathBuf> {
+    Ok(get_config_dir()?.join("schedules.json"))
+}
+
+/// Save the schedules, replacing the existing schedules
+async fn schedules_save(schedules: &[Schedule]) -> Result<()> {
+    let path = schedules_path()?;
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).await?;
+    }
+    write_json(path, schedules).await
+}
+
+impl Client {
+    /// List the saved schedules
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the saved schedules cannot be
+    /// read
+    pub async fn schedules_list() -> Result<Vec<Schedule>> {
+        let path = schedules_path()?;
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        read_json(path).await
+    }
+
+    /// Save a new schedule
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the saved schedules cannot be
+    /// read or written
+    pub async fn schedules_add(cron: CronExpression, action: ScheduledAction) -> Result<Schedule> {
+        let mut schedules = Self::schedules_list().await?;
+        let schedule = Schedule::new(cron, action);
+        schedules.push(schedule.clone());
+        schedules_save(&schedules).await?;
+        Ok(schedule)
+    }
+
+    /// Remove a saved schedule
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The schedule does not exist
+    /// 2. The saved schedules cannot be read or written
+    pub async fn schedules_remove(schedule_id: ScheduleId) -> Result<Schedule> {
+        let mut schedules = Self::schedules_list().await?;
+        let index = schedules
+            .iter()
+            .position(|schedule| schedule.schedule_id == schedule_id)
+            .ok_or_else(|| Error::Other("schedule not found", schedule_id.to_string()))?;
+        let schedule = schedules.remove(index);
+        schedules_save(&schedules).await?;
+        Ok(schedule)
+    }
+
+    /// Record when a schedule ran, such that it is not due again until the
+    /// next time its cron expression matches
+    ///
+    /// Schedules removed while running are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the saved schedules cannot be
+    /// read or written
+    pub async fn schedules_record_run(schedule_id: ScheduleId, at: OffsetDateTime) -> Result<()> {
+        let mut schedules = Self::schedules_list().await?;
+        if let Some(schedule) = schedules
+            .iter_mut()
+            .find(|schedule| schedule.schedule_id == schedule_id)
+        {
+            schedule.last_run = Some(at);
+            schedules_save(&schedules).await?;
+        }
+        Ok(())
+    }
+
+    /// Select the images the action of a schedule applies to
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if listing the images fails
+    pub async fn schedules_select(&self, schedule: &Schedule) -> Result<ScheduleRun> {
+        let image_ids = self
+            .images_list(None, None, None, false, false, None)
+            .try_filter(|image| futures::future::ready(schedule.action.applies_to(image)))
+            .map_ok(|image| image.image_id)
+            .try_collect()
+            .await?;
+        Ok(ScheduleRun {
+            schedule_id: schedule.schedule_id,
+            image_ids,
+        })
+    }
+}
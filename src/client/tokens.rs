@@ -0,0 +1,110 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Personal access tokens allow headless machines, such as lab machines that
+//! capture images, to use the service without an interactive login.  Not
+//! every instance of the service issues personal access tokens.
+//!
+//! ```rust,no_run
+//! # use freta::{Client, Config, Result, Secret};
+//! # async fn example(client: Client) -> Result<()> {
+//! let created = client.tokens_create("lab-01", Some(90)).await?;
+//!
+//! // on the headless machine
+//! let config = Config {
+//!     pat: Some(Secret::new(created.secret)),
+//!     ..Config::load().await?
+//! };
+//! let client = Client::with_config(config).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::paginate::paginate,
+    models::tokens::{
+        Token, TokenCreate, TokenCreateResponse, TokenId, TokenRevokeResponse, TokensListRequest,
+        TokensListResponse,
+    },
+    Client, Error, Result,
+};
+use futures::Stream;
+use std::pin::Pin;
+
+/// Describe a request that failed because the service does not issue
+/// personal access tokens
+fn unsupported(err: Error) -> Error {
+    if err.is_not_found() {
+        Error::Other(
+            "personal access tokens are not supported by the service",
+            err.to_string(),
+        )
+    } else {
+        err
+    }
+}
+
+impl Client {
+    /// Create a personal access token
+    ///
+    /// The secret of the token is only returned by this function.  If
+    /// `expires_in_days` is not provided, the service decides when the token
+    /// expires.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The service does not issue personal access tokens
+    /// 3. The user does not have permission to create tokens
+    pub async fn tokens_create<S>(
+        &self,
+        name: S,
+        expires_in_days: Option<u32>,
+    ) -> Result<TokenCreateResponse>
+    where
+        S: Into<String>,
+    {
+        let create = TokenCreate {
+            name: name.into(),
+            expires_in_days,
+        };
+        self.backend
+            .post("/api/tokens", create)
+            .await
+            .map_err(unsupported)
+    }
+
+    /// List the personal access tokens of the user
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The service does not issue personal access tokens
+    pub fn tokens_list(
+        &self,
+    ) -> Pin<Box<impl Stream<Item = std::result::Result<Token, crate::Error>> + Send + '_>> {
+        paginate(TokensListRequest::default(), move |request| async move {
+            let result: TokensListResponse = self
+                .backend
+                .get("/api/tokens", Some(&request))
+                .await
+                .map_err(unsupported)?;
+            Ok(result)
+        })
+    }
+
+    /// Revoke a personal access token, such that it can no longer be used
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in the following conditions:
+    /// 1. The connection to the Service fails
+    /// 2. The token does not exist, or the service does not issue personal
+    ///    access tokens
+    pub async fn tokens_revoke(&self, token_id: TokenId) -> Result<TokenRevokeResponse> {
+        self.backend
+            .delete(&format!("/api/tokens/{token_id}"))
+            .await
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::{
+    client::{
+        config::get_config_dir,
+        io::{create_dir_all, read_json, remove_file, write_json},
+    },
+    ImageId, Result,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::debug;
+use url::Url;
+
+/// Extension of the files recording the progress of uploads
+const STATE_EXTENSION: &str = "state";
+
+/// A block of an image that was uploaded to Azure Storage, but not yet
+/// committed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UploadedBlock {
+    /// Block ID
+    pub(crate) id: String,
+    /// Size of the block in bytes
+    pub(crate) size: u64,
+}
+
+/// Progress of a resumable upload, saved in
+/// `~/.config/freta/uploads/<image_id>.state` after each block is uploaded
+///
+/// Azure Storage retains uncommitted blocks for 7 days, such that an
+/// interrupted upload can continue from the block after the last one
+/// recorded.  The service only issues a SAS URL that permits uploading the
+/// image when the image is created, such that it is saved along with the
+/// progress and the upload can only continue until it expires.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UploadState {
+    /// Image being uploaded
+    pub(crate) image_id: ImageId,
+    /// SAS URL used to upload the image
+    #[serde(default)]
+    pub(crate) image_url: Option<Url>,
+    /// Canonical path of the file being uploaded
+    pub(crate) path: PathBuf,
+    /// Size of the file when the upload started
+    pub(crate) size: u64,
+    /// Content type detected from the first block
+    pub(crate) content_type: Option<String>,
+    /// Blocks uploaded so far, in the order of the file
+    pub(crate) blocks: Vec<UploadedBlock>,
+}
+
+impl UploadState {
+    /// Directory the progress of uploads is saved in
+    fn dir() -> Result<PathBuf> {
+        Ok(get_config_dir()?.join("uploads"))
+    }
+
+    /// Start tracking the upload of a file
+    pub(crate) const fn new(
+        image_id: ImageId,
+        image_url: Option<Url>,
+        path: PathBuf,
+        size: u64,
+    ) -> Self {
+        Self {
+            image_id,
+            image_url,
+            path,
+            size,
+            content_type: None,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Find the saved progress of an earlier upload of the same file
+    ///
+    /// Progress is only used if the file has the same size as when the
+    /// upload started.  Unreadable state files are ignored.
+    pub(crate) async fn find(path: &Path, size: u64) -> Result<Option<Self>> {
+        let dir = Self::dir()?;
+        let Ok(mut entries) = fs::read_dir(&dir).await else {
+            return Ok(None);
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let entry_path = entry.path();
+            if entry_path.extension().and_then(|x| x.to_str()) != Some(STATE_EXTENSION) {
+                continue;
+            }
+            match read_json::<_, Self>(&entry_path).await {
+                Ok(state) if state.path == path && state.size == size => return Ok(Some(state)),
+                Ok(_) => {}
+                Err(err) => debug!("ignoring upload state {}: {err}", entry_path.display()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Number of bytes of the file uploaded so far
+    pub(crate) fn offset(&self) -> u64 {
+        self.blocks.iter().map(|block| block.size).sum()
+    }
+
+    /// Path of the file the progress is saved in
+    fn state_path(&self) -> Result<PathBuf> {
+        Ok(Self::dir()?.join(format!("{}.{STATE_EXTENSION}", self.image_id)))
+    }
+
+    /// Save the progress of the upload
+    pub(crate) async fn save(&self) -> Result<()> {
+        create_dir_all(Self::dir()?).await?;
+        write_json(self.state_path()?, self).await
+    }
+
+    /// Remove the saved progress once the upload has completed
+    pub(crate) async fn remove(&self) -> Result<()> {
+        let path = self.state_path()?;
+        if path.exists() {
+            remove_file(path).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset() {
+        let mut state =
+            UploadState::new(ImageId::default(), None, PathBuf::from("/image.lime"), 30);
+        assert_eq!(state.offset(), 0);
+        for (i, size) in [10, 15].into_iter().enumerate() {
+            state.blocks.push(UploadedBlock {
+                id: format!("{i:032x}"),
+                size,
+            });
+        }
+        assert_eq!(state.offset(), 25);
+    }
+
+    #[test]
+    fn test_state_without_image_url() -> Result<()> {
+        // progress saved before the SAS URL was recorded cannot be resumed,
+        // but is still readable such that it can be discarded
+        let state: UploadState = serde_json::from_str(
+            r#"{"image_id": "00000000-0000-0000-0000-000000000000", "path": "/image.lime", "size": 30, "content_type": null, "blocks": []}"#,
+        )?;
+        assert_eq!(state.image_url, None);
+        Ok(())
+    }
+}
@@ -7,7 +7,7 @@
 //! # Example
 //!
 //! ```rust,no_run
-//! use freta::{Client, ImageFormat::Lime, Result};
+//! use freta::{models::artifacts::REPORT_JSON, Client, ImageFormat::Lime, Result};
 //! # #[tokio::main]
 //! # async fn main() -> Result<()> {
 //! let mut client = Client::new().await?;
@@ -16,13 +16,15 @@
 //!     .await?;
 //! client.images_monitor(image.image_id).await?;
 //! client
-//!     .artifacts_download(image.image_id, "report.json", "./report.json")
+//!     .artifacts_download(image.image_id, REPORT_JSON, "./report.json")
 //!     .await?;
 //! println!("{:?}", image);
 //! # Ok(())
 //! # }
 //! ```
-#![forbid(unsafe_code)]
+// memory mapping files requires `unsafe`, which is limited to `client::io`
+#![cfg_attr(not(feature = "mmap"), forbid(unsafe_code))]
+#![cfg_attr(feature = "mmap", deny(unsafe_code))]
 #![deny(
     absolute_paths_not_starting_with_crate,
     clippy::dbg_macro,
@@ -90,12 +92,33 @@ pub mod models;
 #[cfg(feature = "client")]
 pub use crate::client::{
     argparse,
-    config::{ClientId, Config, Secret},
+    cache::CacheStats,
+    config::{ClientId, Config, IpFamily, ProxyAuth, ProxyConfig, RetryConfig, Secret},
     error::{Error, Result},
-    Client,
+    progress::{NoProgress, ProgressBarHandler, ProgressHandler},
+    ArtifactEntry, Client, Deprecation, DownloadedArtifact, EulaStatus, ImageBlob, Latency,
+    MonitorOptions, PreflightCheck, PreflightReport, TransferStats, UploadOptions, VerifiedImage,
+    WebhookPingResult, WebhookPingResults,
 };
 
-pub use crate::models::base::{Image, ImageFormat, ImageId, ImageState, OwnerId};
+#[cfg(feature = "client")]
+pub use crate::client::{cli, export, integrations, io, paginate};
+
+/// HTTP method used with `Client::request`
+#[cfg(feature = "client")]
+pub use reqwest::Method;
+
+#[cfg(feature = "chaos")]
+pub use crate::client::chaos;
+
+#[cfg(feature = "ci")]
+pub use crate::client::ci;
+
+#[cfg(feature = "metrics")]
+pub use crate::client::metrics;
+
+pub use crate::models::base::{AnalysisProgress, Image, ImageFormat, ImageId, ImageState, OwnerId};
+pub use crate::models::MODELS_VERSION;
 
 /// Name of the SDK
 const SDK_NAME: &str = env!("CARGO_PKG_NAME");
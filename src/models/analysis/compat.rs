@@ -0,0 +1,98 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Reading analysis reports stored by earlier versions of the models
+//!
+//! Reports are read along with the `models_version` reported by the service
+//! when the report was stored, such as from `Info::models_version`.
+//! `Report::from_slice_any_version` upgrades the serialized form of the report
+//! from that version to the current `MODELS_VERSION` before parsing it.  The
+//! upgrades operate on the JSON form of the report, and do not contact the
+//! service.
+//!
+//! ```rust
+//! # use freta::{models::analysis::{compat::CompatError, report::Report}, MODELS_VERSION};
+//! # fn example() -> Result<(), CompatError> {
+//! let report = Report::from_slice_any_version(br#"{"checks": []}"#, MODELS_VERSION)?;
+//! assert!(report.checks.is_empty());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::{analysis::report::Report, MODELS_VERSION};
+use serde_json::Value;
+
+/// Errors reading reports stored by earlier versions of the models
+#[derive(thiserror::Error, Debug)]
+pub enum CompatError {
+    /// No upgrade is available from the models version
+    #[error("unsupported models version: {0}")]
+    UnsupportedVersion(String),
+
+    /// The report is not valid for the models version
+    #[error(transparent)]
+    Invalid(#[from] serde_json::Error),
+}
+
+/// Upgrade of the serialized form of a report to the next models version
+type Upgrade = fn(&mut Value);
+
+/// Upgrades of the serialized form of reports, ordered by the models version
+/// they upgrade from
+///
+/// When `MODELS_VERSION` is incremented due to a change in the serialized form
+/// of `Report`, an upgrade from the previous version is added here.
+const UPGRADES: &[(&str, Upgrade)] = &[];
+
+/// Upgrade the serialized form of a report from `models_version` to
+/// `MODELS_VERSION`
+fn upgrade(report: &mut Value, models_version: &str) -> Result<(), CompatError> {
+    if models_version == MODELS_VERSION {
+        return Ok(());
+    }
+    let start = UPGRADES
+        .iter()
+        .position(|(from, _)| *from == models_version)
+        .ok_or_else(|| CompatError::UnsupportedVersion(models_version.to_string()))?;
+    for (_, upgrade) in UPGRADES.iter().skip(start) {
+        upgrade(report);
+    }
+    Ok(())
+}
+
+impl Report {
+    /// Parse a report stored by the specified version of the models,
+    /// upgrading it to the current version
+    ///
+    /// # Errors
+    /// This will return an error if no upgrade is available from
+    /// `models_version`, or the report cannot be parsed
+    pub fn from_slice_any_version(data: &[u8], models_version: &str) -> Result<Self, CompatError> {
+        let mut report: Value = serde_json::from_slice(data)?;
+        upgrade(&mut report, models_version)?;
+        Ok(serde_json::from_value(report)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_slice_any_version() -> Result<(), CompatError> {
+        let data = include_bytes!("testdata/report.json");
+        let report = Report::from_slice_any_version(data, MODELS_VERSION)?;
+        assert_eq!(report.tasks.len(), 2);
+
+        for version in ["0", "999", ""] {
+            assert!(matches!(
+                Report::from_slice_any_version(data, version),
+                Err(CompatError::UnsupportedVersion(v)) if v == version
+            ));
+        }
+        assert!(matches!(
+            Report::from_slice_any_version(b"[]", MODELS_VERSION),
+            Err(CompatError::Invalid(_))
+        ));
+        Ok(())
+    }
+}
@@ -5,7 +5,10 @@
 use core::ops::{Add, AddAssign, Sub, SubAssign};
 use num_traits::{CheckedAdd, CheckedSub, WrappingAdd, WrappingSub};
 use serde::{Deserialize, Serialize};
-use std::fmt::{Debug, Display, Formatter, Result};
+use std::{
+    fmt::{Debug, Display, Formatter, Result},
+    io::{self, Read, Seek, SeekFrom},
+};
 
 /// Virtual Memory Address
 #[cfg_attr(feature = "proptest", derive(proptest_derive::Arbitrary))]
@@ -33,6 +36,73 @@ impl VirtualAddress {
     pub const fn from_le_bytes(bytes: [u8; 8]) -> Self {
         Self(u64::from_le_bytes(bytes))
     }
+
+    /// Round the address down to the start of the page containing it
+    ///
+    /// Returns `None` if `page_size` is not a power of two
+    #[must_use]
+    pub const fn align_down(&self, page_size: u64) -> Option<Self> {
+        if !page_size.is_power_of_two() {
+            return None;
+        }
+        Some(Self(self.0 & !page_size.wrapping_sub(1)))
+    }
+
+    /// Round the address up to the start of the next page, unless it is
+    /// already aligned
+    ///
+    /// Returns `None` if `page_size` is not a power of two or if the aligned
+    /// address would overflow
+    #[must_use]
+    pub const fn align_up(&self, page_size: u64) -> Option<Self> {
+        if !page_size.is_power_of_two() {
+            return None;
+        }
+        let mask = page_size.wrapping_sub(1);
+        match self.0.checked_add(mask) {
+            Some(value) => Some(Self(value & !mask)),
+            None => None,
+        }
+    }
+
+    /// Offset of the address within the page containing it
+    ///
+    /// Returns `None` if `page_size` is not a power of two
+    #[must_use]
+    pub const fn page_offset(&self, page_size: u64) -> Option<u64> {
+        if !page_size.is_power_of_two() {
+            return None;
+        }
+        Some(self.0 & page_size.wrapping_sub(1))
+    }
+
+    /// Checks if the address is at the start of a page
+    ///
+    /// Returns false if `page_size` is not a power of two
+    #[must_use]
+    pub const fn is_aligned(&self, page_size: u64) -> bool {
+        matches!(self.page_offset(page_size), Some(0))
+    }
+
+    /// Offset the address by a signed number of bytes
+    ///
+    /// Unlike the `Add` and `Sub` implementations, which wrap by design, this
+    /// returns `None` if the result would overflow
+    #[must_use]
+    pub const fn checked_offset(&self, offset: i64) -> Option<Self> {
+        match self.0.checked_add_signed(offset) {
+            Some(value) => Some(Self(value)),
+            None => None,
+        }
+    }
+
+    /// Number of bytes from this address to `other`
+    ///
+    /// Returns `None` if `other` is before this address
+    #[must_use]
+    pub const fn distance_to(&self, other: Self) -> Option<u64> {
+        other.0.checked_sub(self.0)
+    }
 }
 
 impl WrappingAdd for VirtualAddress {
@@ -163,6 +233,250 @@ impl Debug for VirtualAddress {
     }
 }
 
+/// Build an error for reads outside of the memory available to a reader
+fn out_of_range(addr: VirtualAddress) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!("address out of range: {addr}"),
+    )
+}
+
+/// endian-aware primitive readers for `MemoryReader`
+macro_rules! read_primitive {
+    ($le:ident, $be:ident, $t:ty) => {
+        #[doc = concat!("Read a little-endian `", stringify!($t), "` at `addr`")]
+        ///
+        /// # Errors
+        /// This will return an error if the value cannot be read
+        fn $le(&mut self, addr: VirtualAddress) -> io::Result<$t> {
+            self.read_bytes_at(addr).map(<$t>::from_le_bytes)
+        }
+
+        #[doc = concat!("Read a big-endian `", stringify!($t), "` at `addr`")]
+        ///
+        /// # Errors
+        /// This will return an error if the value cannot be read
+        fn $be(&mut self, addr: VirtualAddress) -> io::Result<$t> {
+            self.read_bytes_at(addr).map(<$t>::from_be_bytes)
+        }
+    };
+}
+
+/// Reads values from a snapshot of memory at a `VirtualAddress`, such as
+/// those resolved by an analysis report
+///
+/// Implementations only need to provide `read_exact_at`.
+pub trait MemoryReader {
+    /// Fill `buf` with the memory starting at `addr`
+    ///
+    /// # Errors
+    /// This will return an error if the memory is not available to the reader
+    fn read_exact_at(&mut self, addr: VirtualAddress, buf: &mut [u8]) -> io::Result<()>;
+
+    /// Read `N` bytes at `addr`
+    ///
+    /// # Errors
+    /// This will return an error if the memory is not available to the reader
+    fn read_bytes_at<const N: usize>(&mut self, addr: VirtualAddress) -> io::Result<[u8; N]> {
+        let mut buf = [0; N];
+        self.read_exact_at(addr, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Read a `u8` at `addr`
+    ///
+    /// # Errors
+    /// This will return an error if the value cannot be read
+    fn read_u8_at(&mut self, addr: VirtualAddress) -> io::Result<u8> {
+        self.read_bytes_at(addr).map(u8::from_le_bytes)
+    }
+
+    read_primitive!(read_u16_le_at, read_u16_be_at, u16);
+    read_primitive!(read_u32_le_at, read_u32_be_at, u32);
+    read_primitive!(read_u64_le_at, read_u64_be_at, u64);
+
+    /// Read a 64-bit pointer at `addr`
+    ///
+    /// # Errors
+    /// This will return an error if the value cannot be read
+    fn read_address_at(&mut self, addr: VirtualAddress) -> io::Result<VirtualAddress> {
+        self.read_bytes_at(addr).map(VirtualAddress::from_le_bytes)
+    }
+
+    /// Read a NUL-terminated string of up to `max_len` bytes at `addr`
+    ///
+    /// The string ends at the first NUL, or after `max_len` bytes if no NUL is
+    /// found.  Invalid UTF-8 is replaced with `U+FFFD`.
+    ///
+    /// # Errors
+    /// This will return an error if the string cannot be read
+    fn read_cstr_at(&mut self, addr: VirtualAddress, max_len: usize) -> io::Result<String> {
+        let mut bytes = vec![];
+        let mut current = addr;
+        while bytes.len() < max_len {
+            let byte = self.read_u8_at(current)?;
+            if byte == 0 {
+                break;
+            }
+            bytes.push(byte);
+            current = current
+                .checked_offset(1)
+                .ok_or_else(|| out_of_range(current))?;
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Read a typed value, such as a kernel structure, at `addr`
+    ///
+    /// # Errors
+    /// This will return an error if the value cannot be read
+    fn read_at<T>(&mut self, addr: VirtualAddress) -> io::Result<T>
+    where
+        T: FromMemory,
+        Self: Sized,
+    {
+        T::read_from(self, addr)
+    }
+}
+
+/// Values that can be extracted from memory using a `MemoryReader`
+///
+/// Structures implement this by reading each of their fields at its offset
+/// from the address of the structure, which allows implementations to be
+/// generated from field offsets.
+///
+/// # Example
+///
+/// ```rust
+/// use freta::models::analysis::memory::{FromMemory, MemoryReader, SliceReader, VirtualAddress};
+/// use std::io;
+///
+/// struct ListHead {
+///     next: VirtualAddress,
+///     prev: VirtualAddress,
+/// }
+///
+/// impl FromMemory for ListHead {
+///     fn read_from<M: MemoryReader>(reader: &mut M, addr: VirtualAddress) -> io::Result<Self> {
+///         Ok(Self {
+///             next: reader.read_at(addr)?,
+///             prev: reader.read_at(addr + 8)?,
+///         })
+///     }
+/// }
+///
+/// # fn main() -> io::Result<()> {
+/// let data = [1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0];
+/// let mut reader = SliceReader::new(VirtualAddress(0x1000), &data);
+/// let head: ListHead = reader.read_at(VirtualAddress(0x1000))?;
+/// assert_eq!(head.prev, VirtualAddress(2));
+/// # Ok(())
+/// # }
+/// ```
+pub trait FromMemory: Sized {
+    /// Read the value at `addr`
+    ///
+    /// # Errors
+    /// This will return an error if the value cannot be read
+    fn read_from<M: MemoryReader>(reader: &mut M, addr: VirtualAddress) -> io::Result<Self>;
+}
+
+/// `FromMemory` implementations for little-endian unsigned types
+macro_rules! from_memory_impl {
+    ($t:ty) => {
+        impl FromMemory for $t {
+            fn read_from<M: MemoryReader>(
+                reader: &mut M,
+                addr: VirtualAddress,
+            ) -> io::Result<Self> {
+                reader.read_bytes_at(addr).map(<$t>::from_le_bytes)
+            }
+        }
+    };
+}
+
+from_memory_impl!(u8);
+from_memory_impl!(u16);
+from_memory_impl!(u32);
+from_memory_impl!(u64);
+
+impl FromMemory for VirtualAddress {
+    fn read_from<M: MemoryReader>(reader: &mut M, addr: VirtualAddress) -> io::Result<Self> {
+        reader.read_address_at(addr)
+    }
+}
+
+impl<const N: usize> FromMemory for [u8; N] {
+    fn read_from<M: MemoryReader>(reader: &mut M, addr: VirtualAddress) -> io::Result<Self> {
+        reader.read_bytes_at(addr)
+    }
+}
+
+/// A `MemoryReader` over a contiguous region of memory held in a byte slice
+#[derive(Debug, Clone, Copy)]
+pub struct SliceReader<'a> {
+    /// Address of the first byte of `data`
+    base: VirtualAddress,
+    /// Contents of the memory region
+    data: &'a [u8],
+}
+
+impl<'a> SliceReader<'a> {
+    /// Create a reader for memory starting at `base`
+    #[must_use]
+    pub const fn new(base: VirtualAddress, data: &'a [u8]) -> Self {
+        Self { base, data }
+    }
+}
+
+impl MemoryReader for SliceReader<'_> {
+    fn read_exact_at(&mut self, addr: VirtualAddress, buf: &mut [u8]) -> io::Result<()> {
+        let bytes = self
+            .base
+            .distance_to(addr)
+            .and_then(|start| usize::try_from(start).ok())
+            .and_then(|start| Some(start..start.checked_add(buf.len())?))
+            .and_then(|range| self.data.get(range))
+            .ok_or_else(|| out_of_range(addr))?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// A `MemoryReader` over a contiguous region of memory held in a `Read` +
+/// `Seek` source, such as a raw snapshot on disk
+#[derive(Debug)]
+pub struct StreamReader<R> {
+    /// Address of the first byte of `inner`
+    base: VirtualAddress,
+    /// Contents of the memory region
+    inner: R,
+}
+
+impl<R> StreamReader<R>
+where
+    R: Read + Seek,
+{
+    /// Create a reader for memory starting at `base`
+    pub const fn new(base: VirtualAddress, inner: R) -> Self {
+        Self { base, inner }
+    }
+}
+
+impl<R> MemoryReader for StreamReader<R>
+where
+    R: Read + Seek,
+{
+    fn read_exact_at(&mut self, addr: VirtualAddress, buf: &mut [u8]) -> io::Result<()> {
+        let offset = self
+            .base
+            .distance_to(addr)
+            .ok_or_else(|| out_of_range(addr))?;
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(buf)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -176,6 +490,87 @@ mod tests {
         assert_eq!(a, b);
     }
 
+    #[test]
+    fn alignment() {
+        let page = 0x1000;
+        let a = VirtualAddress(0xffff_8000_0000_1234);
+
+        assert_eq!(
+            a.align_down(page),
+            Some(VirtualAddress(0xffff_8000_0000_1000))
+        );
+        assert_eq!(
+            a.align_up(page),
+            Some(VirtualAddress(0xffff_8000_0000_2000))
+        );
+        assert_eq!(a.page_offset(page), Some(0x234));
+        assert!(!a.is_aligned(page));
+
+        let aligned = VirtualAddress(0x2000);
+        assert_eq!(aligned.align_up(page), Some(aligned));
+        assert_eq!(aligned.align_down(page), Some(aligned));
+        assert!(aligned.is_aligned(page));
+
+        // page sizes must be powers of two
+        for page_size in [0, 3, 0x1001] {
+            assert_eq!(a.align_down(page_size), None);
+            assert_eq!(a.align_up(page_size), None);
+            assert_eq!(a.page_offset(page_size), None);
+            assert!(!a.is_aligned(page_size));
+        }
+
+        assert_eq!(VirtualAddress(u64::MAX).align_up(page), None);
+    }
+
+    #[test]
+    fn offsets() {
+        let a = VirtualAddress(0x1000);
+        assert_eq!(a.checked_offset(0x10), Some(VirtualAddress(0x1010)));
+        assert_eq!(a.checked_offset(-0x1000), Some(VirtualAddress(0)));
+        assert_eq!(a.checked_offset(-0x1001), None);
+        assert_eq!(VirtualAddress(u64::MAX).checked_offset(1), None);
+
+        assert_eq!(a.distance_to(VirtualAddress(0x1800)), Some(0x800));
+        assert_eq!(a.distance_to(a), Some(0));
+        assert_eq!(a.distance_to(VirtualAddress(0x800)), None);
+    }
+
+    #[test]
+    fn reader() -> io::Result<()> {
+        let mut data = vec![0; 0x20];
+        if let Some(bytes) = data.get_mut(..8) {
+            bytes.copy_from_slice(&0xffff_8000_0000_1234_u64.to_le_bytes());
+        }
+        if let Some(bytes) = data.get_mut(0x10..0x16) {
+            bytes.copy_from_slice(b"init\0x");
+        }
+        let base = VirtualAddress(0xffff_8000_0000_0000);
+
+        let mut slice = SliceReader::new(base, &data);
+        check_reader(&mut slice, base)?;
+        let mut stream = StreamReader::new(base, io::Cursor::new(data.clone()));
+        check_reader(&mut stream, base)?;
+        Ok(())
+    }
+
+    fn check_reader<M: MemoryReader>(reader: &mut M, base: VirtualAddress) -> io::Result<()> {
+        assert_eq!(reader.read_u64_le_at(base)?, 0xffff_8000_0000_1234);
+        assert_eq!(reader.read_u64_be_at(base)?, 0x3412_0000_0080_ffff);
+        assert_eq!(reader.read_u16_le_at(base)?, 0x1234);
+        assert_eq!(
+            reader.read_at::<VirtualAddress>(base)?,
+            VirtualAddress(0xffff_8000_0000_1234)
+        );
+        assert_eq!(reader.read_cstr_at(base + 0x10_u8, 16)?, "init");
+        assert_eq!(reader.read_cstr_at(base + 0x10_u8, 2)?, "in");
+        assert_eq!(reader.read_at::<[u8; 2]>(base + 0x10_u8)?, *b"in");
+
+        // reads must be entirely within the memory available to the reader
+        assert!(reader.read_u64_le_at(base + 0x1c_u8).is_err());
+        assert!(reader.read_u8_at(VirtualAddress(0)).is_err());
+        Ok(())
+    }
+
     #[test]
     fn signed() {
         let mut a = VirtualAddress::from(10_i32);
@@ -1,10 +1,17 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
+/// reading analysis reports stored by earlier versions of the models
+#[cfg(feature = "compat")]
+pub mod compat;
+
 /// models for hooked functions
 pub mod hook;
 
 /// models for physical and virtual memory representation
 pub mod memory;
 
+/// models for the analysis report
+pub mod report;
+
 /// models for debug symbols references
 pub mod symbols;
@@ -0,0 +1,589 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! The analysis report of an image, as described by
+//! `extra/report-schema.json`
+//!
+//! The sections of the report used to triage an image, such as the kernel
+//! information, tasks, kernel modules, and checks, are modeled.  The
+//! remaining sections, such as the mounts and networks, are retained in
+//! `extra`.
+
+use crate::models::analysis::{
+    hook::{Check, Hook},
+    memory::VirtualAddress,
+    symbols::Symbol,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// The analysis report of an image, `report.json`
+///
+/// Sections and fields that are not modeled are retained in `extra`, such
+/// that reports produced by newer versions of the analysis can still be
+/// parsed.  Sections missing from the report are empty.
+///
+/// ```rust
+/// # use freta::models::analysis::report::Report;
+/// # fn example() -> Result<(), serde_json::Error> {
+/// let report: Report = serde_json::from_str(
+///     r#"{
+///         "analysis_info": {"analysis_version": "0.22.0", "tags": {}},
+///         "kernel_modules": {"modules": [], "notifiers": []},
+///         "tasks": [],
+///         "checks": [{"issue": "hidden process", "pids": [4242]}]
+///     }"#,
+/// )?;
+/// assert_eq!(report.analysis_info.analysis_version, "0.22.0");
+/// assert_eq!(report.checks[0].pids, vec![4242]);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Report {
+    /// Information about the analysis that produced the report
+    #[serde(default)]
+    pub analysis_info: AnalysisInfo,
+
+    /// General information about the image, such as the kernel
+    #[serde(default)]
+    pub info: Info,
+
+    /// Tasks found in the image
+    #[serde(default)]
+    pub tasks: Vec<Task>,
+
+    /// Kernel modules found in the image
+    #[serde(default)]
+    pub kernel_modules: KernelModules,
+
+    /// Issues found in the analysis
+    #[serde(default)]
+    pub checks: Vec<Check>,
+
+    /// Sections of the report that are not otherwise modeled
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Report {
+    /// Get the task with the specified process ID
+    #[must_use]
+    pub fn task(&self, pid: u32) -> Option<&Task> {
+        self.tasks.iter().find(|task| task.pid == pid)
+    }
+
+    /// Get the kernel modules, including the hooks found by the analysis
+    /// whose destination is in each module
+    #[must_use]
+    pub fn kernel_modules(&self) -> Vec<KernelModule> {
+        let hooks = self
+            .checks
+            .iter()
+            .filter_map(|check| check.hook.as_ref())
+            .collect::<Vec<_>>();
+        self.kernel_modules
+            .modules
+            .iter()
+            .map(|module| KernelModule {
+                module: module.clone(),
+                hooks: hooks
+                    .iter()
+                    .filter(|hook| module.is_hooked_by(hook))
+                    .map(|hook| (*hook).clone())
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Information about the analysis that produced a report
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnalysisInfo {
+    /// Version of the analysis engine
+    pub analysis_version: String,
+
+    /// Versions of the individual detections
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub detection_versions: BTreeMap<String, String>,
+
+    /// Tags of the image at the time of the analysis
+    pub tags: BTreeMap<String, String>,
+}
+
+/// General information about the image
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Info {
+    /// Kernel banner, such as `Linux version 5.15.0-1019-azure ...`
+    pub banner: String,
+
+    /// Offset applied to the kernel by KASLR
+    pub kernel_aslr_offset: u64,
+
+    /// Physical address of the kernel page tables
+    pub kernel_pml4: u64,
+
+    /// System identification, as reported by `uname`
+    pub machine: UtsName,
+
+    /// Memory usage of the system
+    pub memory_info: MemoryInfo,
+}
+
+/// System identification, as reported by `uname`
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct UtsName {
+    /// NIS domain name
+    pub domainname: String,
+    /// Hardware architecture, such as `x86_64`
+    pub machine: String,
+    /// Host name
+    pub nodename: String,
+    /// Kernel release, such as `5.15.0-1019-azure`
+    pub release: String,
+    /// Operating system name, such as `Linux`
+    pub sysname: String,
+    /// Kernel build version
+    pub version: String,
+}
+
+/// Memory usage of the system
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryInfo {
+    /// Number of swap pages in use
+    pub swap_pages: u64,
+    /// Total memory of the system
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_ram: Option<u64>,
+    /// Total number of swap pages
+    pub total_swap_pages: u64,
+}
+
+/// A task found in the image
+///
+/// Sections of the task that are not otherwise modeled, such as the sockets
+/// and namespaces of the task, are retained in `extra`.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Task {
+    /// Address of the `task_struct`
+    pub addr: VirtualAddress,
+
+    /// Command line arguments
+    pub arg: Vec<String>,
+
+    /// Name of the executable, as truncated by the kernel
+    pub comm: String,
+
+    /// Credentials the task runs as
+    pub cred: Cred,
+
+    /// Current working directory
+    pub cwd: String,
+
+    /// Environment variables, as `KEY=VALUE`
+    pub env: Vec<String>,
+
+    /// The executable of the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe: Option<File>,
+
+    /// Open files
+    pub files: Vec<File>,
+
+    /// Maximum number of open files
+    pub max_fds: u64,
+
+    /// Process ID
+    pub pid: u32,
+
+    /// Process ID of the parent process
+    pub ppid: u32,
+
+    /// Ptrace flags of the task
+    pub ptrace: u64,
+
+    /// Process ID of the real parent process, which differs from `ppid`
+    /// while the task is being traced
+    pub real_ppid: u32,
+
+    /// Kernel structures the task was found in
+    pub source: Vec<TaskSource>,
+
+    /// Start time of the task
+    pub start_time: u64,
+
+    /// Time spent in the kernel
+    pub stime: u64,
+
+    /// Thread group ID
+    pub tgid: u32,
+
+    /// Time spent in user mode
+    pub utime: u64,
+
+    /// Information that is not otherwise modeled
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Task {
+    /// The command line of the task, joining the arguments with spaces
+    #[must_use]
+    pub fn cmdline(&self) -> String {
+        self.arg.join(" ")
+    }
+}
+
+/// Kernel structures a task was found in
+///
+/// Tasks only found in some of these structures may have been hidden.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TaskSource {
+    /// The list of all tasks
+    TaskList,
+    /// The thread group of a task
+    ThreadGroup,
+    /// The thread node of a task
+    ThreadNode,
+    /// The children of a task
+    TaskChildren,
+    /// The siblings of a task
+    TaskSibling,
+}
+
+/// Credentials of a task
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cred {
+    /// User ID
+    pub uid: u32,
+    /// Group ID
+    pub gid: u32,
+    /// Effective user ID
+    pub euid: u32,
+    /// Effective group ID
+    pub egid: u32,
+    /// Saved user ID
+    pub suid: u32,
+    /// Saved group ID
+    pub sgid: u32,
+}
+
+/// A file opened by a task
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    /// Address of the `struct file`
+    pub address: VirtualAddress,
+
+    /// File descriptor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fd: Option<u64>,
+
+    /// Type of the file system, such as `ext4`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fs_type: Option<String>,
+
+    /// Inode of the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inode: Option<Inode>,
+
+    /// Mode the file was opened with
+    pub mode: Vec<Mode>,
+
+    /// Mode the file was opened with, as the raw flags
+    pub mode_raw: u32,
+
+    /// Current offset in the file
+    pub offset: u64,
+
+    /// Path of the file
+    pub path: String,
+}
+
+/// Mode a file was opened with, from the `FMODE_*` flags of the kernel
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// `FMODE_READ`
+    Read,
+    /// `FMODE_WRITE`
+    Write,
+    /// `FMODE_LSEEK`
+    Seekable,
+    /// `FMODE_PREAD`
+    Pread,
+    /// `FMODE_PWRITE`
+    Pwrite,
+    /// `FMODE_EXEC`
+    Exec,
+    /// `FMODE_NDELAY`
+    Ndelay,
+    /// `FMODE_EXCL`
+    Excl,
+    /// `FMODE_WRITE_IOCTL`
+    WriteIoctl,
+    /// `FMODE_32BITHASH`
+    Hash32bit,
+    /// `FMODE_64BITHASH`
+    Hash64bit,
+    /// `FMODE_NOCMTIME`
+    Nocmtime,
+    /// `FMODE_PATH`
+    Path,
+}
+
+/// Inode of a file
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inode {
+    /// Access time
+    pub atime: u64,
+    /// Change time
+    pub ctime: u64,
+    /// Inode flags
+    pub flags: u32,
+    /// Group ID of the owner
+    pub gid: u32,
+    /// Inode number
+    pub ino: u64,
+    /// Modification time
+    pub mtime: u64,
+    /// Size of the file
+    pub size: u64,
+    /// User ID of the owner
+    pub uid: u32,
+}
+
+/// The kernel modules section of a report
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelModules {
+    /// Kernel modules found in the image
+    pub modules: Vec<Module>,
+
+    /// Value of the `kernel.modules_disabled` sysctl
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modules_disabled: Option<i64>,
+
+    /// Callbacks notified when modules are loaded
+    pub notifiers: Vec<ModuleNotifier>,
+}
+
+/// A kernel module found in the image
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Module {
+    /// Address of the `struct module`
+    pub addr: VirtualAddress,
+
+    /// Parameters the module was loaded with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<String>,
+
+    /// Address of the core section of the module
+    pub core_addr: VirtualAddress,
+
+    /// Names of the artifacts the core section of the module was extracted to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub core_artifact_name: Option<Vec<String>>,
+
+    /// Size of the core section of the module
+    pub core_size: u64,
+
+    /// Address of the init section of the module
+    pub init_addr: VirtualAddress,
+
+    /// Names of the artifacts the init section of the module was extracted to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_artifact_name: Option<Vec<String>>,
+
+    /// Size of the init section of the module
+    pub init_size: u64,
+
+    /// Name of the module
+    pub name: String,
+
+    /// Kernel structures the module was found in
+    pub source: Vec<ModuleSource>,
+
+    /// Source version of the module
+    pub srcversion: String,
+
+    /// Symbols exported by the module
+    pub symbols: Vec<ModuleSymbol>,
+
+    /// Version of the module
+    pub version: String,
+}
+
+impl Module {
+    /// Returns true if the address is within the core or init section of the
+    /// module
+    #[must_use]
+    pub fn contains(&self, address: VirtualAddress) -> bool {
+        let within = |start: VirtualAddress, size: u64| {
+            address
+                .0
+                .checked_sub(start.0)
+                .is_some_and(|offset| offset < size)
+        };
+        within(self.core_addr, self.core_size) || within(self.init_addr, self.init_size)
+    }
+
+    /// Returns true if the hook's destination is in the module
+    fn is_hooked_by(&self, hook: &Hook) -> bool {
+        let by_symbol = matches!(
+            &hook.target_module,
+            Some(Symbol::Module(module, _)) if *module == self.name
+        );
+        by_symbol || hook.target_addr.is_some_and(|addr| self.contains(addr))
+    }
+}
+
+/// Kernel structures a module was found in
+///
+/// Modules only found in some of these structures may have been hidden.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ModuleSource {
+    /// The list of loaded modules
+    ModulesList,
+    /// The list of modules registered for reporting bugs
+    BugList,
+    /// The modules a module depends on
+    SourceList,
+    /// The modules that depend on a module
+    TargetList,
+}
+
+/// A symbol exported by a kernel module
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModuleSymbol {
+    /// Address of the symbol
+    pub addr: VirtualAddress,
+    /// Name of the symbol
+    pub name: String,
+}
+
+/// A callback notified when kernel modules are loaded
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModuleNotifier {
+    /// Address of the callback
+    pub callback_address: VirtualAddress,
+    /// Priority of the callback
+    pub priority: i64,
+    /// Symbol of the callback, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<Symbol>,
+}
+
+/// A kernel module, including the hooks found by the analysis whose
+/// destination is in the module
+///
+/// These are created from a report by `Report::kernel_modules`.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelModule {
+    /// The kernel module
+    #[serde(flatten)]
+    pub module: Module,
+
+    /// Hooks whose destination is in the module
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<Hook>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A report that validates against `extra/report-schema.json`
+    const REPORT: &str = include_str!("testdata/report.json");
+
+    #[test]
+    fn test_report() -> Result<(), serde_json::Error> {
+        let report: Report = serde_json::from_str(REPORT)?;
+
+        assert_eq!(report.analysis_info.analysis_version, "0.22.0");
+        assert_eq!(report.info.machine.release, "5.15.0-1019-azure");
+        assert!(report.info.banner.starts_with("Linux version "));
+
+        let nc = report.task(4242);
+        assert_eq!(nc.map(|task| task.comm.as_str()), Some("nc"));
+        assert_eq!(nc.map(Task::cmdline).as_deref(), Some("nc -lvp 4444"));
+        assert_eq!(
+            nc.and_then(|task| task.exe.as_ref())
+                .map(|exe| exe.path.as_str()),
+            Some("/usr/bin/nc.openbsd")
+        );
+        assert_eq!(
+            nc.map(|task| (task.ppid, task.cred.uid, task.cred.euid)),
+            Some((1, 1000, 0))
+        );
+        assert!(nc.is_some_and(|task| task.extra.contains_key("inet_sockets")));
+        assert!(report.task(7).is_none());
+
+        assert_eq!(
+            report
+                .kernel_modules
+                .modules
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["hv_vmbus", "rootkit"]
+        );
+        assert_eq!(report.checks.len(), 2);
+        assert!(report.extra.contains_key("networks"));
+
+        // round trips, retaining the sections that are not modeled
+        let expected: Value = serde_json::from_str(REPORT)?;
+        assert_eq!(serde_json::to_value(&report)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_kernel_modules() -> Result<(), serde_json::Error> {
+        let report: Report = serde_json::from_str(REPORT)?;
+
+        let modules = report.kernel_modules();
+        let hv_vmbus = modules.first().cloned().unwrap_or_default();
+        assert!(hv_vmbus.hooks.is_empty());
+        let rootkit = modules.get(1).cloned().unwrap_or_default();
+        assert_eq!(rootkit.module.source, vec![ModuleSource::SourceList]);
+        assert_eq!(
+            rootkit.hooks.iter().map(|h| h.addr).collect::<Vec<_>>(),
+            vec![VirtualAddress(0xffff_ffff_8100_0000)]
+        );
+        assert!(rootkit
+            .module
+            .contains(VirtualAddress(0xffff_ffff_c000_3fff)));
+        assert!(!rootkit
+            .module
+            .contains(VirtualAddress(0xffff_ffff_c000_4000)));
+
+        // round trips, including the hooks
+        let parsed: KernelModule = serde_json::from_value(serde_json::to_value(&rootkit)?)?;
+        assert_eq!(parsed, rootkit);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_report() -> Result<(), serde_json::Error> {
+        let report: Report = serde_json::from_str("{}")?;
+        assert!(report.info.banner.is_empty());
+        assert!(report.tasks.is_empty());
+        assert!(report.kernel_modules.modules.is_empty());
+        assert!(report.checks.is_empty());
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
+use crate::models::artifacts::join_relative;
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 /// Symbol representation
 #[cfg_attr(feature = "proptest", derive(proptest_derive::Arbitrary))]
@@ -13,3 +16,83 @@ pub enum Symbol {
     /// Kernel module symbol name
     Module(String, String),
 }
+
+/// Prefix of the blobs in the artifacts container that contain debug symbols
+///
+/// Symbol artifacts are stored using the layout
+/// `symbols/<file name>/<identifier>/<file name>`, where `<identifier>` is the
+/// symbol server identifier (such as the PDB GUID and age) for Windows symbols
+/// or the GNU build-id for Linux symbols.
+pub const SYMBOLS_PREFIX: &str = "symbols/";
+
+/// Directory layout used when writing debug symbols to the local filesystem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SymbolLayout {
+    /// Preserve the layout used in the artifacts container, without the
+    /// `symbols/` prefix
+    Flat,
+    /// Windows symbol server layout: `<file name>/<identifier>/<file name>`
+    Symsrv,
+    /// debuginfod cache layout: `<build-id>/debuginfo`
+    Debuginfod,
+}
+
+impl SymbolLayout {
+    /// Determine the relative path for a symbol artifact
+    ///
+    /// Returns `None` if the blob is not a symbol artifact or if the blob name
+    /// would result in a path outside of the destination directory.
+    #[must_use]
+    pub fn path_for(&self, blob_name: &str) -> Option<PathBuf> {
+        let relative = blob_name.strip_prefix(SYMBOLS_PREFIX)?;
+        let path = join_relative(Path::new(""), relative)?;
+
+        let parts = relative.split('/').collect::<Vec<_>>();
+        match (self, parts.as_slice()) {
+            (Self::Symsrv | Self::Debuginfod, [name, id, file_name]) if name == file_name => {
+                if *self == Self::Symsrv {
+                    Some([name, id, file_name].iter().collect())
+                } else {
+                    Some([id, &"debuginfo"].iter().collect())
+                }
+            }
+            _ => Some(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_layout() {
+        let pdb = "symbols/ntkrnlmp.pdb/3844DBB920174967BE7AA4A2C20430FA2/ntkrnlmp.pdb";
+        assert_eq!(
+            SymbolLayout::Symsrv.path_for(pdb),
+            Some("ntkrnlmp.pdb/3844DBB920174967BE7AA4A2C20430FA2/ntkrnlmp.pdb".into())
+        );
+        assert_eq!(
+            SymbolLayout::Flat.path_for(pdb),
+            Some("ntkrnlmp.pdb/3844DBB920174967BE7AA4A2C20430FA2/ntkrnlmp.pdb".into())
+        );
+
+        let vmlinux = "symbols/vmlinux/0123456789abcdef/vmlinux";
+        assert_eq!(
+            SymbolLayout::Debuginfod.path_for(vmlinux),
+            Some("0123456789abcdef/debuginfo".into())
+        );
+
+        assert_eq!(SymbolLayout::Flat.path_for("report.json"), None);
+        assert_eq!(SymbolLayout::Flat.path_for("symbols/../report.json"), None);
+        if cfg!(windows) {
+            for name in ["symbols/..\\report.json", "symbols/C:\\report.json"] {
+                assert_eq!(SymbolLayout::Flat.path_for(name), None, "{name}");
+            }
+        }
+        assert_eq!(
+            SymbolLayout::Debuginfod.path_for("symbols/unknown.sym"),
+            Some("unknown.sym".into())
+        );
+    }
+}
@@ -0,0 +1,483 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::models::base::ParseError;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Error as FmtError, Formatter},
+    num::NonZeroU32,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+use strum_macros::EnumIter;
+
+/// Name of the analysis report artifact
+pub const REPORT_JSON: &str = "report.json";
+
+/// Directory containing the logs produced by the analysis
+pub const LOGS_DIR: &str = "logs";
+
+/// Directory containing the artifacts of individual processes, organized by
+/// process id
+pub const PROCESSES_DIR: &str = "processes";
+
+/// Artifacts produced by the analysis of an image with well-known names
+///
+/// Converts into the artifact name, such that it can be used anywhere an
+/// artifact name is accepted:
+///
+/// ```rust,no_run
+/// # use freta::{models::artifacts::WellKnownArtifact, Client, ImageId, Result};
+/// # async fn example(client: Client, image_id: ImageId) -> Result<()> {
+/// let report = client
+///     .artifacts_get(image_id, WellKnownArtifact::Report)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum WellKnownArtifact {
+    /// The analysis report, `report.json`
+    Report,
+}
+
+impl WellKnownArtifact {
+    /// Get the name of the artifact
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Report => REPORT_JSON,
+        }
+    }
+}
+
+impl FromStr for WellKnownArtifact {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let x = match s {
+            REPORT_JSON => Self::Report,
+            _ => return Err(ParseError {}),
+        };
+        Ok(x)
+    }
+}
+
+impl Display for WellKnownArtifact {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl AsRef<str> for WellKnownArtifact {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<WellKnownArtifact> for String {
+    fn from(artifact: WellKnownArtifact) -> Self {
+        artifact.as_str().to_string()
+    }
+}
+
+/// Category of an artifact, based on its location in the artifacts container
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "category", rename_all = "snake_case")]
+pub enum ArtifactCategory {
+    /// The analysis report, `report.json`
+    Report,
+    /// A log produced by the analysis, `logs/<name>`
+    Log {
+        /// name of the log, relative to the logs directory
+        name: String,
+    },
+    /// An artifact of a single process, `processes/<pid>/<name>`
+    ProcessDump {
+        /// process id
+        pid: u64,
+        /// name of the artifact, relative to the directory of the process
+        name: String,
+    },
+    /// An artifact that does not follow a known layout
+    Unknown,
+}
+
+impl ArtifactCategory {
+    /// Classify an artifact by its name
+    #[must_use]
+    pub fn classify(name: &str) -> Self {
+        if let Ok(artifact) = name.parse::<WellKnownArtifact>() {
+            return match artifact {
+                WellKnownArtifact::Report => Self::Report,
+            };
+        }
+
+        let mut parts = name.splitn(3, '/');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(LOGS_DIR), Some(log), None) if !log.is_empty() => Self::Log {
+                name: log.to_string(),
+            },
+            (Some(PROCESSES_DIR), Some(pid), Some(name)) if !name.is_empty() => {
+                pid.parse().map_or(Self::Unknown, |pid| Self::ProcessDump {
+                    pid,
+                    name: name.to_string(),
+                })
+            }
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Artifacts of an image organized by category
+///
+/// ```rust
+/// # use freta::models::artifacts::Layout;
+/// let layout = ["report.json", "logs/analysis.log", "processes/4/memory.dmp"]
+///     .into_iter()
+///     .collect::<Layout>();
+/// assert_eq!(layout.report.as_deref(), Some("report.json"));
+/// assert_eq!(layout.processes[&4], vec!["processes/4/memory.dmp"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Layout {
+    /// The analysis report, if present
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<String>,
+    /// Logs produced by the analysis
+    pub logs: Vec<String>,
+    /// Artifacts of individual processes, keyed by process id
+    pub processes: BTreeMap<u64, Vec<String>>,
+    /// Artifacts that do not follow a known layout
+    pub unknown: Vec<String>,
+}
+
+impl Layout {
+    /// Add an artifact to the layout
+    pub fn add<S>(&mut self, name: S)
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        match ArtifactCategory::classify(&name) {
+            ArtifactCategory::Report => self.report = Some(name),
+            ArtifactCategory::Log { .. } => self.logs.push(name),
+            ArtifactCategory::ProcessDump { pid, .. } => {
+                self.processes.entry(pid).or_default().push(name);
+            }
+            ArtifactCategory::Unknown => self.unknown.push(name),
+        }
+    }
+}
+
+impl<S> FromIterator<S> for Layout
+where
+    S: Into<String>,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+    {
+        let mut layout = Self::default();
+        for name in iter {
+            layout.add(name);
+        }
+        layout
+    }
+}
+
+/// Filters applied when listing the artifacts of an image
+///
+/// Azure Storage only supports filtering by prefix, so the `prefix` and the
+/// literal prefix of the `glob` pattern are used to limit the part of the
+/// artifacts container that is listed.  The rest of the `glob` pattern is
+/// matched as the artifacts are listed.
+///
+/// ```rust
+/// # use freta::models::artifacts::ArtifactFilter;
+/// let filter = ArtifactFilter::default().with_glob("processes/4/*.dmp");
+/// assert_eq!(filter.list_prefix().as_deref(), Some("processes/4/"));
+/// assert!(filter.matches("processes/4/memory.dmp"));
+/// assert!(!filter.matches("processes/42/memory.dmp"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArtifactFilter {
+    /// Only include artifacts whose name starts with this prefix
+    pub prefix: Option<String>,
+    /// Only include artifacts whose name matches this pattern, where `*`
+    /// matches any sequence of characters, including `/`, and `?` matches any
+    /// single character
+    pub glob: Option<String>,
+    /// Number of artifacts requested from Azure Storage per page.  Azure
+    /// Storage returns at most 5000 per page.
+    pub max_results: Option<NonZeroU32>,
+    /// Stop listing once this many artifacts have been included
+    pub limit: Option<usize>,
+}
+
+impl ArtifactFilter {
+    /// Only include artifacts whose name starts with the prefix
+    #[must_use]
+    pub fn with_prefix<S: Into<String>>(self, prefix: S) -> Self {
+        Self {
+            prefix: Some(prefix.into()),
+            ..self
+        }
+    }
+
+    /// Only include artifacts whose name matches the pattern
+    #[must_use]
+    pub fn with_glob<S: Into<String>>(self, glob: S) -> Self {
+        Self {
+            glob: Some(glob.into()),
+            ..self
+        }
+    }
+
+    /// Request this many artifacts from Azure Storage per page
+    #[must_use]
+    pub const fn with_max_results(self, max_results: NonZeroU32) -> Self {
+        Self {
+            max_results: Some(max_results),
+            ..self
+        }
+    }
+
+    /// Stop listing once this many artifacts have been included
+    #[must_use]
+    pub const fn with_limit(self, limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// The prefix used when listing the artifacts container
+    ///
+    /// This is the longer of `prefix` and the part of `glob` before the first
+    /// wildcard, as long as one starts with the other.
+    #[must_use]
+    pub fn list_prefix(&self) -> Option<String> {
+        let literal = self
+            .glob
+            .as_deref()
+            .map(|glob| glob.split(['*', '?']).next().unwrap_or_default());
+        let prefix = match (self.prefix.as_deref(), literal) {
+            (Some(prefix), Some(literal)) if literal.starts_with(prefix) => Some(literal),
+            (Some(prefix), _) => Some(prefix),
+            (None, literal) => literal,
+        };
+        prefix
+            .filter(|prefix| !prefix.is_empty())
+            .map(ToString::to_string)
+    }
+
+    /// Does the name of the artifact match the filter
+    #[must_use]
+    pub fn matches(&self, name: &str) -> bool {
+        self.prefix
+            .as_deref()
+            .is_none_or(|prefix| name.starts_with(prefix))
+            && self
+                .glob
+                .as_deref()
+                .is_none_or(|glob| glob_matches(glob, name))
+    }
+}
+
+/// Does the name match the pattern, where `*` matches any sequence of
+/// characters and `?` matches any single character
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // the position of the most recent `*` in the pattern and the position in
+    // the name it currently matches up to, used to backtrack on a mismatch
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match (pattern.get(p), name.get(n)) {
+            (Some('*'), _) => {
+                star = Some((p, n));
+                p += 1;
+            }
+            (Some('?'), _) => {
+                p += 1;
+                n += 1;
+            }
+            (Some(expected), Some(actual)) if expected == actual => {
+                p += 1;
+                n += 1;
+            }
+            _ => {
+                let Some((star_p, star_n)) = star else {
+                    return false;
+                };
+                star = Some((star_p, star_n + 1));
+                p = star_p + 1;
+                n = star_n + 1;
+            }
+        }
+    }
+
+    pattern
+        .get(p..)
+        .is_some_and(|rest| rest.iter().all(|c| *c == '*'))
+}
+
+/// Join a `/` separated relative name, such as the name of a blob, to `dir`
+///
+/// Returns `None` if the name is empty or includes components that would
+/// escape `dir`, such as `..` or an absolute path.  Each part of the name must
+/// be a single normal component on the local platform, such that names
+/// including `\` or drive prefixes are rejected on Windows.
+pub(crate) fn join_relative(dir: &Path, name: &str) -> Option<PathBuf> {
+    let parts = name.split('/').collect::<Vec<_>>();
+    let is_normal = parts.iter().all(|part| {
+        let mut components = Path::new(part).components();
+        matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        )
+    });
+    is_normal.then(|| {
+        parts
+            .iter()
+            .fold(dir.to_path_buf(), |path, part| path.join(part))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn test_well_known_artifact() {
+        for artifact in WellKnownArtifact::iter() {
+            assert_eq!(
+                artifact.as_str().parse::<WellKnownArtifact>().ok(),
+                Some(artifact)
+            );
+            assert_eq!(artifact.to_string(), artifact.as_str());
+        }
+        assert_eq!(String::from(WellKnownArtifact::Report), "report.json");
+        assert!("Report.json".parse::<WellKnownArtifact>().is_err());
+    }
+
+    #[test]
+    fn test_artifact_category() {
+        assert_eq!(
+            ArtifactCategory::classify("report.json"),
+            ArtifactCategory::Report
+        );
+        assert_eq!(
+            ArtifactCategory::classify("logs/analysis.log"),
+            ArtifactCategory::Log {
+                name: "analysis.log".into()
+            }
+        );
+        assert_eq!(
+            ArtifactCategory::classify("processes/1234/maps/heap.bin"),
+            ArtifactCategory::ProcessDump {
+                pid: 1234,
+                name: "maps/heap.bin".into()
+            }
+        );
+        for name in [
+            "processes/init/core",
+            "processes/1234/",
+            "logs/",
+            "kernel.log",
+            "other.txt",
+        ] {
+            assert_eq!(ArtifactCategory::classify(name), ArtifactCategory::Unknown);
+        }
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        for (pattern, name) in [
+            ("*", "report.json"),
+            ("*.json", "report.json"),
+            ("processes/*/memory.dmp", "processes/4/memory.dmp"),
+            ("processes/*", "processes/4/maps/heap.bin"),
+            ("logs/?.log", "logs/a.log"),
+            ("*a*b", "xaybab"),
+            ("report.json", "report.json"),
+        ] {
+            assert!(glob_matches(pattern, name), "{pattern} {name}");
+        }
+        for (pattern, name) in [
+            ("*.json", "kernel.log"),
+            ("logs/?.log", "logs/ab.log"),
+            ("report.json", "report.json.bak"),
+            ("*a*b", "xaybac"),
+            ("?", ""),
+        ] {
+            assert!(!glob_matches(pattern, name), "{pattern} {name}");
+        }
+    }
+
+    #[test]
+    fn test_artifact_filter() {
+        let filter = ArtifactFilter::default();
+        assert_eq!(filter.list_prefix(), None);
+        assert!(filter.matches("report.json"));
+
+        let filter = ArtifactFilter::default().with_glob("*.log");
+        assert_eq!(filter.list_prefix(), None);
+        assert!(filter.matches("logs/analysis.log"));
+
+        let filter = ArtifactFilter::default()
+            .with_prefix("processes/")
+            .with_glob("processes/4*/*.dmp");
+        assert_eq!(filter.list_prefix().as_deref(), Some("processes/4"));
+        assert!(filter.matches("processes/42/memory.dmp"));
+        assert!(!filter.matches("processes/42/maps/heap.bin"));
+
+        let filter = ArtifactFilter::default()
+            .with_prefix("logs/")
+            .with_glob("*.log");
+        assert_eq!(filter.list_prefix().as_deref(), Some("logs/"));
+        assert!(filter.matches("logs/analysis.log"));
+        assert!(!filter.matches("kernel.log"));
+    }
+
+    #[test]
+    fn test_join_relative() {
+        let dir = Path::new("/tmp/artifacts");
+        assert_eq!(
+            join_relative(dir, "report.json"),
+            Some(dir.join("report.json"))
+        );
+        assert_eq!(
+            join_relative(dir, "symbols/ntkrnlmp.pdb/ABC1/ntkrnlmp.pdb"),
+            Some(
+                dir.join("symbols")
+                    .join("ntkrnlmp.pdb")
+                    .join("ABC1")
+                    .join("ntkrnlmp.pdb")
+            )
+        );
+        for name in [
+            "",
+            "../report.json",
+            "a/../../b",
+            "/etc/passwd",
+            "a//b",
+            "./a",
+        ] {
+            assert_eq!(join_relative(dir, name), None, "{name}");
+        }
+        if cfg!(windows) {
+            for name in [
+                "a\\..\\..\\b",
+                "C:\\win.ini",
+                "C:win.ini",
+                "\\\\server\\share",
+            ] {
+                assert_eq!(join_relative(dir, name), None, "{name}");
+            }
+        }
+    }
+}
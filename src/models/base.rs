@@ -14,7 +14,7 @@ use uuid::Uuid;
 
 /// Unique identifier for an `Image`
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
 pub struct ImageId(Uuid);
 
 impl ImageId {
@@ -51,29 +51,73 @@ impl From<Uuid> for ImageId {
     }
 }
 
+/// Kind used in the serialized form of service principal `OwnerId`s
+const SERVICE_PRINCIPAL_KIND: &str = "sp";
+
+/// Kind used in the serialized form of group `OwnerId`s
+const GROUP_KIND: &str = "group";
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 /// The owner of an image
-pub struct OwnerId {
-    /// The AAD tenant of the owner
-    pub tenant_id: Uuid,
-    /// The AAD `oid` of the user
-    pub oid: Uuid,
+///
+/// Owners are serialized as strings.  Users are serialized as
+/// `<tenant>_<oid>`, which is the format used by all existing images.  Service
+/// principals and groups are serialized as `<tenant>_sp_<app_id>` and
+/// `<tenant>_group_<gid>` respectively.
+pub enum OwnerId {
+    /// An AAD user
+    User {
+        /// The AAD tenant of the user
+        tenant: Uuid,
+        /// The AAD `oid` of the user
+        oid: Uuid,
+    },
+    /// An AAD service principal
+    ServicePrincipal {
+        /// The AAD tenant of the service principal
+        tenant: Uuid,
+        /// The application id of the service principal
+        app_id: Uuid,
+    },
+    /// An AAD group
+    Group {
+        /// The AAD tenant of the group
+        tenant: Uuid,
+        /// The object id of the group
+        gid: Uuid,
+    },
 }
 
 impl OwnerId {
     /// The `OwnerId` associated with sample images
     #[must_use]
     pub const fn samples() -> Self {
-        Self {
-            tenant_id: Uuid::from_u128(0),
+        Self::User {
+            tenant: Uuid::from_u128(0),
             oid: Uuid::from_u128(0),
         }
     }
+
+    /// The AAD tenant of the owner
+    #[must_use]
+    pub const fn tenant(&self) -> Uuid {
+        match self {
+            Self::User { tenant, .. }
+            | Self::ServicePrincipal { tenant, .. }
+            | Self::Group { tenant, .. } => *tenant,
+        }
+    }
 }
 
 impl Display for OwnerId {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
-        write!(f, "{}_{}", self.tenant_id, self.oid)
+        match self {
+            Self::User { tenant, oid } => write!(f, "{tenant}_{oid}"),
+            Self::ServicePrincipal { tenant, app_id } => {
+                write!(f, "{tenant}_{SERVICE_PRINCIPAL_KIND}_{app_id}")
+            }
+            Self::Group { tenant, gid } => write!(f, "{tenant}_{GROUP_KIND}_{gid}"),
+        }
     }
 }
 
@@ -90,12 +134,25 @@ impl FromStr for OwnerId {
     type Err = Box<dyn std::error::Error + Send + Sync + 'static>;
 
     fn from_str(uuid_str: &str) -> Result<Self, Self::Err> {
-        match uuid_str.split_once('_') {
-            Some((tenant_id, oid)) => Ok(Self {
-                tenant_id: Uuid::parse_str(tenant_id)?,
-                oid: Uuid::parse_str(oid)?,
+        let Some((tenant, rest)) = uuid_str.split_once('_') else {
+            return Err("invalid owner_id".into());
+        };
+        let tenant = Uuid::parse_str(tenant)?;
+
+        match rest.split_once('_') {
+            None => Ok(Self::User {
+                tenant,
+                oid: Uuid::parse_str(rest)?,
             }),
-            None => Err("invalid owner_id".into()),
+            Some((SERVICE_PRINCIPAL_KIND, app_id)) => Ok(Self::ServicePrincipal {
+                tenant,
+                app_id: Uuid::parse_str(app_id)?,
+            }),
+            Some((GROUP_KIND, gid)) => Ok(Self::Group {
+                tenant,
+                gid: Uuid::parse_str(gid)?,
+            }),
+            Some((kind, _)) => Err(format!("invalid owner_id kind: {kind}").into()),
         }
     }
 }
@@ -130,6 +187,9 @@ pub enum ImageState {
     Failed,
     /// The image and it's related artifacts are currently being deleted
     Deleting,
+    /// The image and it's related artifacts have been deleted.  Only a
+    /// tombstone record of the image is retained.
+    Deleted,
 }
 
 impl ImageState {
@@ -140,6 +200,7 @@ impl ImageState {
             ImageState::WaitingForUpload
             | ImageState::Running
             | ImageState::Deleting
+            | ImageState::Deleted
             | ImageState::ToQueue
             | ImageState::Queued => false,
             ImageState::Failed | ImageState::Completed | ImageState::Finalizing => true,
@@ -167,13 +228,13 @@ pub enum ImageFormat {
     Vmrs,
     /// RAW memory dumps, such as created with `dd`
     Raw,
-    /// Lime memory dumps, as created with AVML or LiME
+    /// Lime memory dumps, as created with AVML or `LiME`
     Lime,
-    /// Full-system Linux core dumps, such as memory dumps as created by VirtualBox or Dumpit for Linux
+    /// Full-system Linux core dumps, such as memory dumps as created by `VirtualBox` or `Dumpit` for Linux
     Core,
     /// Internal memory snapshot feature
     Avmh,
-    /// Page Dump, as created by `.dump /f <filename>` in WinDbg
+    /// Page Dump, as created by `.dump /f <filename>` in `WinDbg`
     Pagedump,
 }
 
@@ -216,6 +277,51 @@ impl Display for ImageFormat {
     }
 }
 
+/// Progress of the analysis of an image
+///
+/// This is only reported while the image is `Running`, by versions of the
+/// service that report it.  Services may report the stage, the percentage,
+/// or both.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnalysisProgress {
+    /// Name of the current stage of the analysis
+    pub stage: String,
+
+    /// Position of the current stage, starting at 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage_index: Option<u32>,
+
+    /// Total number of stages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage_count: Option<u32>,
+
+    /// Percentage of the analysis that completed, between 0 and 100
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+}
+
+impl Display for AnalysisProgress {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "{}", self.stage)?;
+        if let (Some(index), Some(count)) = (self.stage_index, self.stage_count) {
+            write!(f, " ({index}/{count})")?;
+        }
+        if let Some(percent) = self.percent {
+            write!(f, " {percent:.0}%")?;
+        }
+        Ok(())
+    }
+}
+
+/// Image tag that records the hex encoded SHA-256 digest of the uploaded
+/// image, such as `sha256=9f86d0...`
+///
+/// The client adds this tag when uploading an image, such that downloaded
+/// copies of the image can be verified.  The digest is of the image as it is
+/// stored, which for images compressed while uploading is the compressed
+/// image.
+pub const IMAGE_SHA256_TAG: &str = "sha256";
+
 /// Image entry in the Freta service
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Image {
@@ -265,9 +371,33 @@ pub struct Image {
     #[serde(default = "BTreeMap::new")]
     pub tags: BTreeMap<String, String>,
 
-    /// Is the image accessible by authenticated users that know the ImageId
+    /// Is the image accessible by authenticated users that know the `ImageId`
     #[serde(default)]
     pub shareable: bool,
+
+    /// Version of the analysis engine that produced the current analysis
+    ///
+    /// NOTE: This is only provided once the analysis has completed, by
+    /// versions of the service that report it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis_version: Option<String>,
+
+    /// Progress of the analysis
+    ///
+    /// NOTE: This is only provided while the image is `Running`, by versions
+    /// of the service that report it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<AnalysisProgress>,
+
+    /// Timestamp of when the image was deleted
+    ///
+    /// NOTE: This is only provided for tombstone records of deleted images
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        default,
+        with = "time::serde::rfc3339::option"
+    )]
+    pub deleted_at: Option<OffsetDateTime>,
 }
 
 impl Image {
@@ -285,6 +415,57 @@ impl Image {
             artifacts_url: None,
             tags,
             shareable: false,
+            analysis_version: None,
+            progress: None,
+            deleted_at: None,
+        }
+    }
+
+    /// Is this a tombstone record of a deleted image
+    #[must_use]
+    pub const fn is_tombstone(&self) -> bool {
+        matches!(self.state, ImageState::Deleted)
+    }
+
+    /// The hex encoded SHA-256 digest of the uploaded image, if it was
+    /// recorded using `IMAGE_SHA256_TAG`
+    #[must_use]
+    pub fn sha256(&self) -> Option<&str> {
+        self.tags.get(IMAGE_SHA256_TAG).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_id() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let tenant = Uuid::from_u128(1);
+        let id = Uuid::from_u128(2);
+        let owners = [
+            (OwnerId::User { tenant, oid: id }, format!("{tenant}_{id}")),
+            (
+                OwnerId::ServicePrincipal { tenant, app_id: id },
+                format!("{tenant}_sp_{id}"),
+            ),
+            (
+                OwnerId::Group { tenant, gid: id },
+                format!("{tenant}_group_{id}"),
+            ),
+        ];
+
+        for (owner, expected) in owners {
+            assert_eq!(owner.to_string(), expected);
+            assert_eq!(expected.parse::<OwnerId>()?, owner);
+            assert_eq!(owner.tenant(), tenant);
+
+            let as_json = serde_json::to_string(&owner)?;
+            assert_eq!(serde_json::from_str::<OwnerId>(&as_json)?, owner);
         }
+
+        assert!(format!("{tenant}_other_{id}").parse::<OwnerId>().is_err());
+        assert!(id.to_string().parse::<OwnerId>().is_err());
+        Ok(())
     }
 }
@@ -0,0 +1,226 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::models::{analysis::report::Report, base::ImageId};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Prefix of the kernel banner of Linux images
+const LINUX_BANNER_PREFIX: &str = "Linux version ";
+
+/// Summary of the analysis of an image, used as the baseline for detecting
+/// drift in subsequent images of the same system
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BaselineSummary {
+    /// Image the summary was created from
+    pub image_id: ImageId,
+
+    /// Version of the kernel, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_version: Option<String>,
+
+    /// Names of the loaded kernel modules
+    #[serde(default)]
+    pub modules: BTreeSet<String>,
+
+    /// Issues describing hooked functions
+    #[serde(default)]
+    pub hooks: BTreeSet<String>,
+
+    /// Issues found in the analysis, other than hooked functions
+    #[serde(default)]
+    pub findings: BTreeSet<String>,
+}
+
+impl BaselineSummary {
+    /// Summarize the analysis report of an image
+    ///
+    /// The kernel version is the release reported by the kernel, or if that
+    /// is unavailable, the version in the kernel banner.
+    #[must_use]
+    pub fn from_report(image_id: ImageId, report: &Report) -> Self {
+        let info = &report.info;
+        let kernel_version = if info.machine.release.is_empty() {
+            let banner = info.banner.trim();
+            (!banner.is_empty()).then(|| {
+                banner
+                    .strip_prefix(LINUX_BANNER_PREFIX)
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .unwrap_or(banner)
+                    .to_string()
+            })
+        } else {
+            Some(info.machine.release.clone())
+        };
+
+        let modules = report
+            .kernel_modules
+            .modules
+            .iter()
+            .map(|module| module.name.clone())
+            .collect();
+
+        let (hooks, findings) = report
+            .checks
+            .iter()
+            .partition::<Vec<_>, _>(|check| check.hook.is_some());
+
+        Self {
+            image_id,
+            kernel_version,
+            modules,
+            hooks: hooks.into_iter().map(|check| check.issue.clone()).collect(),
+            findings: findings
+                .into_iter()
+                .map(|check| check.issue.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Items added and removed relative to the baseline
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    /// Items that are not in the baseline
+    pub added: BTreeSet<String>,
+    /// Items in the baseline that are no longer present
+    pub removed: BTreeSet<String>,
+}
+
+impl Changes {
+    /// Compare the items of an image to those of the baseline
+    fn new(baseline: &BTreeSet<String>, current: &BTreeSet<String>) -> Self {
+        Self {
+            added: current.difference(baseline).cloned().collect(),
+            removed: baseline.difference(current).cloned().collect(),
+        }
+    }
+
+    /// Returns true if there are no changes
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Differences between the analysis of an image and a baseline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DriftReport {
+    /// Image used as the baseline
+    pub baseline_image_id: ImageId,
+
+    /// Image compared to the baseline
+    pub image_id: ImageId,
+
+    /// Kernel version of the baseline, if it differs from that of the image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline_kernel_version: Option<String>,
+
+    /// Kernel version of the image, if it differs from that of the baseline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_version: Option<String>,
+
+    /// Changes to the loaded kernel modules
+    pub modules: Changes,
+
+    /// Changes to the hooked functions
+    pub hooks: Changes,
+
+    /// Changes to the other issues found in the analysis
+    pub findings: Changes,
+}
+
+impl DriftReport {
+    /// Compare the summary of an image to a baseline
+    #[must_use]
+    pub fn new(baseline: &BaselineSummary, current: &BaselineSummary) -> Self {
+        let (baseline_kernel_version, kernel_version) =
+            if baseline.kernel_version == current.kernel_version {
+                (None, None)
+            } else {
+                (
+                    baseline.kernel_version.clone(),
+                    current.kernel_version.clone(),
+                )
+            };
+
+        Self {
+            baseline_image_id: baseline.image_id,
+            image_id: current.image_id,
+            baseline_kernel_version,
+            kernel_version,
+            modules: Changes::new(&baseline.modules, &current.modules),
+            hooks: Changes::new(&baseline.hooks, &current.hooks),
+            findings: Changes::new(&baseline.findings, &current.findings),
+        }
+    }
+
+    /// Returns true if the image differs from the baseline
+    #[must_use]
+    pub fn has_drift(&self) -> bool {
+        self.baseline_kernel_version.is_some()
+            || self.kernel_version.is_some()
+            || !self.modules.is_empty()
+            || !self.hooks.is_empty()
+            || !self.findings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_drift() -> Result<(), serde_json::Error> {
+        let baseline_image_id = ImageId::default();
+        let image_id = ImageId::default();
+
+        // a report that validates against `extra/report-schema.json`
+        let baseline: Value = serde_json::from_str(include_str!("analysis/testdata/report.json"))?;
+        let mut current = baseline.clone();
+        current["info"]["machine"]["release"] = json!("5.15.0-1020-azure");
+        current["kernel_modules"]["modules"][0]["name"] = json!("diamorphine");
+        if let Some(checks) = current["checks"].as_array_mut() {
+            checks.retain(|check| check.get("hook_type").is_some());
+        }
+        let mut no_hooks = baseline.clone();
+        if let Some(checks) = no_hooks["checks"].as_array_mut() {
+            checks.retain(|check| check.get("hook_type").is_none());
+        }
+
+        let baseline =
+            BaselineSummary::from_report(baseline_image_id, &serde_json::from_value(no_hooks)?);
+        let current = BaselineSummary::from_report(image_id, &serde_json::from_value(current)?);
+        assert_eq!(
+            baseline.kernel_version.as_deref(),
+            Some("5.15.0-1019-azure")
+        );
+        assert_eq!(
+            baseline.modules,
+            BTreeSet::from(["hv_vmbus".to_string(), "rootkit".to_string()])
+        );
+
+        let drift = DriftReport::new(&baseline, &current);
+        assert!(drift.has_drift());
+        assert_eq!(drift.kernel_version.as_deref(), Some("5.15.0-1020-azure"));
+        assert_eq!(
+            drift.modules.added,
+            BTreeSet::from(["diamorphine".to_string()])
+        );
+        assert_eq!(
+            drift.modules.removed,
+            BTreeSet::from(["hv_vmbus".to_string()])
+        );
+        assert_eq!(
+            drift.hooks.added,
+            BTreeSet::from(["syscall table entry hooked".to_string()])
+        );
+        assert_eq!(
+            drift.findings.removed,
+            BTreeSet::from(["hidden process".to_string()])
+        );
+
+        assert!(!DriftReport::new(&current, &current).has_drift());
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Compact binary encodings of the models, such as for archiving large
+//! numbers of webhook events.
+//!
+//! The models are encoded using their `serde` implementations, such that
+//! decoding a binary encoding produces the same value as decoding the JSON
+//! form.  MessagePack values are encoded as maps with field names, as fields
+//! that are omitted when empty would otherwise shift the remaining fields.
+//!
+//! ```rust
+//! # use freta::models::{binary::{from_cbor, to_cbor}, webhooks::{WebhookEvent, WebhookEventType}};
+//! # fn example() -> Result<(), freta::models::binary::BinaryFormatError> {
+//! let event = WebhookEvent::new(WebhookEventType::Ping, time::OffsetDateTime::now_utc(), None);
+//! let encoded = to_cbor(&event)?;
+//! let decoded: WebhookEvent = from_cbor(&encoded)?;
+//! assert_eq!(event.event_id, decoded.event_id);
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Errors encoding or decoding models using binary formats
+#[derive(thiserror::Error, Debug)]
+pub enum BinaryFormatError {
+    /// Encoding as CBOR failed
+    #[error("encoding cbor failed: {0}")]
+    CborEncode(String),
+
+    /// Decoding CBOR failed
+    #[error("decoding cbor failed: {0}")]
+    CborDecode(String),
+
+    /// Encoding as MessagePack failed
+    #[error(transparent)]
+    MsgpackEncode(#[from] rmp_serde::encode::Error),
+
+    /// Decoding MessagePack failed
+    #[error(transparent)]
+    MsgpackDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// Encode a value as CBOR
+///
+/// # Errors
+/// This will return an error if the value cannot be serialized
+pub fn to_cbor<T>(value: &T) -> Result<Vec<u8>, BinaryFormatError>
+where
+    T: Serialize + ?Sized,
+{
+    let mut encoded = vec![];
+    ciborium::into_writer(value, &mut encoded)
+        .map_err(|e| BinaryFormatError::CborEncode(e.to_string()))?;
+    Ok(encoded)
+}
+
+/// Decode a value from CBOR
+///
+/// # Errors
+/// This will return an error if the data is not a valid encoding of `T`
+pub fn from_cbor<T>(data: &[u8]) -> Result<T, BinaryFormatError>
+where
+    T: DeserializeOwned,
+{
+    ciborium::from_reader(data).map_err(|e| BinaryFormatError::CborDecode(e.to_string()))
+}
+
+/// Encode a value as MessagePack
+///
+/// # Errors
+/// This will return an error if the value cannot be serialized
+pub fn to_msgpack<T>(value: &T) -> Result<Vec<u8>, BinaryFormatError>
+where
+    T: Serialize + ?Sized,
+{
+    Ok(rmp_serde::to_vec_named(value)?)
+}
+
+/// Decode a value from MessagePack
+///
+/// # Errors
+/// This will return an error if the data is not a valid encoding of `T`
+pub fn from_msgpack<T>(data: &[u8]) -> Result<T, BinaryFormatError>
+where
+    T: DeserializeOwned,
+{
+    Ok(rmp_serde::from_slice(data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        analysis::{
+            hook::{Check, Hook},
+            memory::VirtualAddress,
+            symbols::Symbol,
+        },
+        base::{Image, ImageFormat, OwnerId},
+        webhooks::{WebhookEvent, WebhookEventType},
+    };
+    use std::collections::BTreeMap;
+    use time::OffsetDateTime;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    /// Ensure the value decoded from each binary format matches the JSON form
+    /// of the original value
+    fn check_round_trip<T>(value: &T) -> TestResult
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let expected = serde_json::to_value(value)?;
+
+        let cbor: T = from_cbor(&to_cbor(value)?)?;
+        assert_eq!(serde_json::to_value(cbor)?, expected);
+
+        let msgpack: T = from_msgpack(&to_msgpack(value)?)?;
+        assert_eq!(serde_json::to_value(msgpack)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_webhook_event() -> TestResult {
+        let image = Image::new(OwnerId::samples(), ImageFormat::Lime, BTreeMap::new());
+        let plain = WebhookEvent::new(
+            WebhookEventType::ImageAnalysisCompleted,
+            OffsetDateTime::now_utc(),
+            Some(image.image_id),
+        );
+        check_round_trip(&plain)?;
+        check_round_trip(&plain.with_tags(BTreeMap::from([("a".into(), "b".into())])))
+    }
+
+    #[test]
+    fn test_image() -> TestResult {
+        let tags = BTreeMap::from([("name".to_string(), "test".to_string())]);
+        check_round_trip(&Image::new(OwnerId::samples(), ImageFormat::Vmrs, tags))
+    }
+
+    #[test]
+    fn test_analysis() -> TestResult {
+        check_round_trip(&Check::default())?;
+        check_round_trip(&Check {
+            issue: "hooked function".into(),
+            details: Some("details".into()),
+            hook: Some(Hook {
+                addr: VirtualAddress(0xffff_8000_0000_1000),
+                hook_type: "inline".into(),
+                disassembly: "jmp 0x1234".into(),
+                target_addr: Some(VirtualAddress(0x1234)),
+                ..Hook::default()
+            }),
+            address: Some(VirtualAddress(0x1000)),
+            symbol: Some(Symbol::Module("ntoskrnl.exe".into(), "NtOpenFile".into())),
+            pids: vec![4, 1024],
+            paths: vec!["/proc/1".into()],
+            exported_path: None,
+        })
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::models::base::{Image, ImageId, ParseError};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Error as FmtError, Formatter},
+    str::FromStr,
+};
+
+/// Prefix of the image tags used to record membership of a collection when
+/// the service does not provide the collections API
+///
+/// As an example, an image in the collection `incident-1234` has the tag
+/// `collection.incident-1234`.  The value of the tag is the description of
+/// the collection, if any.
+pub const COLLECTION_TAG_PREFIX: &str = "collection.";
+
+/// Maximum length of a `CollectionName`
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Unique name of a `Collection`
+///
+/// Names are between 1 and 64 characters, and only include ASCII letters,
+/// digits, `-`, `_`, and `.`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct CollectionName(String);
+
+impl CollectionName {
+    /// The name as a str
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// The image tag used to record membership of the collection
+    #[must_use]
+    pub fn tag_key(&self) -> String {
+        format!("{COLLECTION_TAG_PREFIX}{}", self.0)
+    }
+
+    /// Parse the name of a collection from an image tag, if the tag records
+    /// membership of a collection
+    #[must_use]
+    pub fn from_tag_key(key: &str) -> Option<Self> {
+        key.strip_prefix(COLLECTION_TAG_PREFIX)?.parse().ok()
+    }
+}
+
+impl FromStr for CollectionName {
+    type Err = ParseError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_NAME_LENGTH
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if valid {
+            Ok(Self(name.to_string()))
+        } else {
+            Err(ParseError)
+        }
+    }
+}
+
+impl TryFrom<String> for CollectionName {
+    type Error = ParseError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+impl From<CollectionName> for String {
+    fn from(name: CollectionName) -> Self {
+        name.0
+    }
+}
+
+impl Display for CollectionName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A group of related images, such as the images collected during an
+/// incident
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Collection {
+    /// Unique name of the collection
+    pub name: CollectionName,
+
+    /// Description of the collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Images in the collection
+    #[serde(default)]
+    pub image_ids: Vec<ImageId>,
+}
+
+impl Collection {
+    /// Build collections from the membership tags of the provided images
+    ///
+    /// The collections are sorted by name and the images of each collection
+    /// are in the order they are provided.
+    #[must_use]
+    pub fn from_tagged_images<'a, I>(images: I) -> Vec<Self>
+    where
+        I: IntoIterator<Item = &'a Image>,
+    {
+        let mut collections: BTreeMap<CollectionName, Self> = BTreeMap::new();
+        for image in images {
+            for (key, value) in &image.tags {
+                let Some(name) = CollectionName::from_tag_key(key) else {
+                    continue;
+                };
+                let collection = collections.entry(name.clone()).or_insert_with(|| Self {
+                    name,
+                    description: None,
+                    image_ids: vec![],
+                });
+                if collection.description.is_none() && !value.is_empty() {
+                    collection.description = Some(value.clone());
+                }
+                if !collection.image_ids.contains(&image.image_id) {
+                    collection.image_ids.push(image.image_id);
+                }
+            }
+        }
+        collections.into_values().collect()
+    }
+}
+
+/// Request to create a collection
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollectionCreate {
+    /// Unique name of the collection
+    pub name: CollectionName,
+
+    /// Description of the collection
+    pub description: Option<String>,
+
+    /// Images initially in the collection
+    pub image_ids: Vec<ImageId>,
+}
+
+/// Request to add an image to a collection
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollectionAddImage {
+    /// Image to add to the collection
+    pub image_id: ImageId,
+}
+
+/// Request to list collections
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CollectionsListRequest {
+    /// The continuation value used for paging
+    pub continuation: Option<String>,
+}
+
+/// Response to listing collections
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollectionsListResponse {
+    /// List of collections
+    pub collections: Vec<Collection>,
+
+    /// continuation value used for paging
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::base::{ImageFormat, OwnerId};
+
+    #[test]
+    fn test_collection_name() {
+        for name in ["incident-1234", "a", "case_2.triage"] {
+            assert!(name.parse::<CollectionName>().is_ok(), "{name}");
+        }
+        let too_long = "a".repeat(MAX_NAME_LENGTH + 1);
+        for name in ["", "with space", "a/b", too_long.as_str()] {
+            assert!(name.parse::<CollectionName>().is_err(), "{name}");
+        }
+        assert!(serde_json::from_str::<CollectionName>("\"a/b\"").is_err());
+    }
+
+    #[test]
+    fn test_from_tagged_images() -> Result<(), ParseError> {
+        let incident: CollectionName = "incident".parse()?;
+        let triage: CollectionName = "triage".parse()?;
+
+        let mut first = Image::new(OwnerId::samples(), ImageFormat::Raw, BTreeMap::new());
+        first
+            .tags
+            .insert(incident.tag_key(), "host compromise".into());
+        first.tags.insert("other".into(), "value".into());
+        let mut second = Image::new(OwnerId::samples(), ImageFormat::Raw, BTreeMap::new());
+        second.tags.insert(incident.tag_key(), String::new());
+        second.tags.insert(triage.tag_key(), String::new());
+
+        let collections = Collection::from_tagged_images([&first, &second]);
+        assert_eq!(
+            collections,
+            vec![
+                Collection {
+                    name: incident,
+                    description: Some("host compromise".into()),
+                    image_ids: vec![first.image_id, second.image_id],
+                },
+                Collection {
+                    name: triage,
+                    description: None,
+                    image_ids: vec![second.image_id],
+                },
+            ]
+        );
+        Ok(())
+    }
+}
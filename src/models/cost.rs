@@ -0,0 +1,312 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Estimates of the cost of storing and analyzing images, attributed to the
+//! owners or tags of the images.
+//!
+//! Costs are estimated from the size of each image, how long it was stored
+//! within the reporting period, its retention period, and whether it was
+//! analyzed within the period, using rates provided by the caller.
+
+use crate::models::{
+    base::{Image, ImageId, ImageState, OwnerId},
+    policies::RETENTION_TAG,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Bytes in a GiB
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Days in the month used by `CostRates::storage_per_gib_month`
+const DAYS_PER_MONTH: f64 = 30.0;
+
+/// Seconds in a day
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Group used for images that do not have the tag used to group images
+pub const UNTAGGED_GROUP: &str = "(untagged)";
+
+/// Rates used to estimate costs, in the currency of the caller's choosing
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CostRates {
+    /// Cost of storing 1 GiB for 30 days
+    pub storage_per_gib_month: f64,
+    /// Cost of analyzing 1 GiB of image
+    pub analysis_per_gib: f64,
+}
+
+impl Default for CostRates {
+    /// Rough approximations of Azure hot tier storage and compute costs, in
+    /// USD
+    fn default() -> Self {
+        Self {
+            storage_per_gib_month: 0.02,
+            analysis_per_gib: 0.01,
+        }
+    }
+}
+
+/// How images are grouped in a cost breakdown
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CostGroup {
+    /// Group images by their owner
+    #[default]
+    Owner,
+    /// Group images by the value of the specified tag
+    Tag(String),
+}
+
+/// Images and rates used to produce a cost report
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostFilter {
+    /// Start of the reporting period, which ends when the report is produced
+    pub since: OffsetDateTime,
+    /// Only include images owned by the specified owner
+    pub owner_id: Option<OwnerId>,
+    /// Include the sample images
+    pub include_samples: bool,
+    /// How images are grouped
+    pub group: CostGroup,
+    /// Rates used to estimate costs
+    pub rates: CostRates,
+}
+
+impl CostFilter {
+    /// Create a filter for the period from `since` until the report is
+    /// produced, grouping images by owner at the default rates
+    #[must_use]
+    pub fn new(since: OffsetDateTime) -> Self {
+        Self {
+            since,
+            owner_id: None,
+            include_samples: false,
+            group: CostGroup::default(),
+            rates: CostRates::default(),
+        }
+    }
+}
+
+/// Usage of an image within a reporting period
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImageUsage {
+    /// Image the usage is for
+    pub image_id: ImageId,
+    /// Owner of the image
+    pub owner_id: OwnerId,
+    /// Tags of the image
+    pub tags: BTreeMap<String, String>,
+    /// Size of the image in bytes
+    pub bytes: u64,
+    /// Days the image was stored within the reporting period
+    pub stored_days: f64,
+    /// Days the image will remain stored after the end of the reporting
+    /// period, per its retention period.  Images without a retention period
+    /// are not projected.
+    pub retained_days: f64,
+    /// Number of analyses of the image within the reporting period
+    pub analyses: u64,
+}
+
+/// Days between two times, or zero if `end` is before `start`
+fn days_between(start: OffsetDateTime, end: OffsetDateTime) -> f64 {
+    ((end - start).as_seconds_f64() / SECONDS_PER_DAY).max(0.0)
+}
+
+impl ImageUsage {
+    /// Estimate the usage of an image of `bytes` bytes within the period
+    /// from `since` until `now`
+    ///
+    /// `uploaded` is when the image was uploaded, if known.  Images uploaded
+    /// prior to the period are considered stored for the entire period, and
+    /// their analysis is attributed to an earlier period.
+    #[must_use]
+    pub fn new(
+        image: &Image,
+        bytes: u64,
+        uploaded: Option<OffsetDateTime>,
+        since: OffsetDateTime,
+        now: OffsetDateTime,
+    ) -> Self {
+        let stored_from = uploaded.map_or(since, |time| time.max(since));
+        let stored_until = image.deleted_at.map_or(now, |deleted| deleted.min(now));
+        let retained_days = image
+            .tags
+            .get(RETENTION_TAG)
+            .and_then(|expires| OffsetDateTime::parse(expires, &Rfc3339).ok())
+            .filter(|_| image.deleted_at.is_none())
+            .map_or(0.0, |expires| days_between(now, expires));
+        let analyzed = matches!(image.state, ImageState::Completed | ImageState::Failed);
+        let in_period = uploaded.is_none_or(|time| time >= since);
+
+        Self {
+            image_id: image.image_id,
+            owner_id: image.owner_id,
+            tags: image.tags.clone(),
+            bytes,
+            stored_days: days_between(stored_from, stored_until),
+            retained_days,
+            analyses: u64::from(analyzed && in_period),
+        }
+    }
+
+    /// Name of the group the image belongs to
+    fn group(&self, group: &CostGroup) -> String {
+        match group {
+            CostGroup::Owner => self.owner_id.to_string(),
+            CostGroup::Tag(key) => self
+                .tags
+                .get(key)
+                .map_or_else(|| UNTAGGED_GROUP.to_string(), Clone::clone),
+        }
+    }
+}
+
+/// Estimated cost of a group of images
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CostEntry {
+    /// Owner or tag value shared by the images
+    pub group: String,
+    /// Number of images
+    pub images: u64,
+    /// Total size of the images in bytes
+    pub bytes: u64,
+    /// Number of analyses within the reporting period
+    pub analyses: u64,
+    /// Cost of storing the images within the reporting period
+    pub storage_cost: f64,
+    /// Cost of analyzing the images within the reporting period
+    pub analysis_cost: f64,
+    /// Total cost within the reporting period
+    pub total_cost: f64,
+    /// Cost of storing the images until their retention periods end
+    pub projected_storage_cost: f64,
+}
+
+/// Estimate the cost of each group of images, ordered by group
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn cost_breakdown(
+    usage: &[ImageUsage],
+    group: &CostGroup,
+    rates: &CostRates,
+) -> Vec<CostEntry> {
+    let mut groups = BTreeMap::<String, CostEntry>::new();
+    for image in usage {
+        let name = image.group(group);
+        let gib = image.bytes as f64 / GIB;
+        let storage_rate = gib * rates.storage_per_gib_month / DAYS_PER_MONTH;
+
+        let entry = groups.entry(name.clone()).or_insert_with(|| CostEntry {
+            group: name,
+            ..CostEntry::default()
+        });
+        entry.images += 1;
+        entry.bytes += image.bytes;
+        entry.analyses += image.analyses;
+        entry.storage_cost += storage_rate * image.stored_days;
+        entry.analysis_cost += gib * rates.analysis_per_gib * image.analyses as f64;
+        entry.total_cost = entry.storage_cost + entry.analysis_cost;
+        entry.projected_storage_cost += storage_rate * image.retained_days;
+    }
+    groups.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::base::ImageFormat;
+    use time::Duration;
+
+    /// Tolerance for rounding errors when comparing estimates
+    const TOLERANCE: f64 = 1e-9;
+
+    fn image(team: Option<&str>, state: ImageState) -> Image {
+        let tags = team
+            .map(|value| BTreeMap::from([("team".to_string(), value.to_string())]))
+            .unwrap_or_default();
+        let mut image = Image::new(OwnerId::samples(), ImageFormat::Lime, tags);
+        image.state = state;
+        image
+    }
+
+    #[test]
+    fn test_image_usage() {
+        let approx_eq = |a: f64, b: f64| (a - b).abs() < TOLERANCE;
+        let now = OffsetDateTime::now_utc();
+        let since = now - Duration::days(30);
+
+        // uploaded before the period: stored for the whole period, and the
+        // analysis is attributed to the earlier period
+        let old = image(None, ImageState::Completed);
+        let old_usage = ImageUsage::new(&old, 1, Some(now - Duration::days(60)), since, now);
+        assert!(approx_eq(old_usage.stored_days, 30.0));
+        assert_eq!(old_usage.analyses, 0);
+
+        let mut recent = image(None, ImageState::Completed);
+        recent.tags.insert(
+            RETENTION_TAG.to_string(),
+            (now + Duration::days(7))
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+        );
+        let recent_usage = ImageUsage::new(&recent, 1, Some(now - Duration::days(10)), since, now);
+        assert!(approx_eq(recent_usage.stored_days, 10.0));
+        assert!(approx_eq(recent_usage.retained_days, 7.0));
+        assert_eq!(recent_usage.analyses, 1);
+
+        let pending = image(None, ImageState::WaitingForUpload);
+        let pending_usage = ImageUsage::new(&pending, 0, None, since, now);
+        assert_eq!(pending_usage.analyses, 0);
+    }
+
+    #[test]
+    fn test_cost_breakdown() {
+        let approx_eq = |a: f64, b: f64| (a - b).abs() < TOLERANCE;
+        let now = OffsetDateTime::now_utc();
+        let since = now - Duration::days(30);
+        let gib = 1024 * 1024 * 1024;
+        let usage = [
+            ImageUsage::new(
+                &image(Some("red"), ImageState::Completed),
+                gib,
+                None,
+                since,
+                now,
+            ),
+            ImageUsage::new(
+                &image(Some("red"), ImageState::Failed),
+                gib,
+                None,
+                since,
+                now,
+            ),
+            ImageUsage::new(&image(None, ImageState::Queued), 2 * gib, None, since, now),
+        ];
+        let rates = CostRates {
+            storage_per_gib_month: 1.0,
+            analysis_per_gib: 0.5,
+        };
+
+        let by_tag = cost_breakdown(&usage, &CostGroup::Tag("team".into()), &rates);
+        assert_eq!(
+            by_tag.iter().map(|x| x.group.as_str()).collect::<Vec<_>>(),
+            vec![UNTAGGED_GROUP, "red"]
+        );
+        let untagged = by_tag.first().cloned().unwrap_or_default();
+        assert_eq!(untagged.analyses, 0);
+        assert!(approx_eq(untagged.storage_cost, 2.0));
+        assert!(approx_eq(untagged.total_cost, 2.0));
+        let red = by_tag.get(1).cloned().unwrap_or_default();
+        assert_eq!((red.images, red.analyses), (2, 2));
+        assert!(approx_eq(red.storage_cost, 2.0));
+        assert!(approx_eq(red.analysis_cost, 1.0));
+        assert!(approx_eq(red.total_cost, 3.0));
+
+        let by_owner = cost_breakdown(&usage, &CostGroup::Owner, &rates);
+        assert_eq!(by_owner.len(), 1);
+        assert!(by_owner
+            .first()
+            .is_some_and(|owner| owner.images == 3 && approx_eq(owner.total_cost, 5.0)));
+    }
+}
@@ -0,0 +1,589 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+//! Lightweight validation of memory images, such that truncated or
+//! mislabeled captures are found before spending hours uploading them.
+//!
+//! Only the headers of the image are read.  For `LiME` and ELF core images,
+//! the headers describe the memory ranges included in the image, which are
+//! checked against the size of the file.  `LiME` images compressed by AVML
+//! with `--compress` do not record the size of the compressed ranges in
+//! their headers, such that only the first range header is checked.  VMRS,
+//! AVMH, and page dump images are only checked to not be empty or in another
+//! known format.
+//!
+//! The same headers are used to detect the format of an image, such that
+//! misnamed images are uploaded as the correct format.  `LiME`, ELF core, and
+//! page dump images are detected by their magic values.  VMRS and AVMH images
+//! do not have a documented magic value, and are not detected.
+//!
+//! ```rust,no_run
+//! # use freta::models::{base::ImageFormat, formats::{validate_image, FormatError}};
+//! # fn example() -> Result<(), FormatError> {
+//! let validation = validate_image(ImageFormat::Lime, "memory.lime")?;
+//! println!("{} ranges in {} bytes", validation.ranges.unwrap_or_default(), validation.size);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::base::ImageFormat;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Magic value at the start of each `LiME` range header
+const LIME_MAGIC: u32 = 0x4C69_4D45;
+
+/// Version of the `LiME` range header
+const LIME_VERSION: u32 = 1;
+
+/// Size of a `LiME` range header
+const LIME_HEADER_SIZE: u64 = 32;
+
+/// Magic value at the start of each range header of `LiME` images compressed
+/// by AVML
+const AVML_MAGIC: u32 = 0x4C4D_5641;
+
+/// Version of the range headers of `LiME` images compressed by AVML
+const AVML_VERSION: u32 = 2;
+
+/// Magic value at the start of ELF files
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+/// Magic values at the start of 32-bit and 64-bit Windows page dumps
+const PAGEDUMP_MAGICS: [&[u8]; 2] = [b"PAGEDUMP", b"PAGEDU64"];
+
+/// ELF file type of core files
+const ET_CORE: u64 = 4;
+
+/// ELF program header type of loadable segments, which hold memory ranges
+const PT_LOAD: u64 = 1;
+
+/// Size of the header read from the start of the image, which covers the ELF
+/// header of 64-bit files
+const HEADER_SIZE: usize = 64;
+
+/// Page size that RAW images are expected to be a multiple of
+const PAGE_SIZE: u64 = 4096;
+
+/// Errors found validating an image
+#[derive(thiserror::Error, Debug)]
+pub enum FormatError {
+    /// Reading the image failed
+    #[error("unable to read image")]
+    Io(#[from] std::io::Error),
+
+    /// The image is empty
+    #[error("the image is empty")]
+    Empty,
+
+    /// The headers of the image are not valid for the format
+    #[error("invalid {format} header at offset {offset}: {reason}")]
+    InvalidHeader {
+        /// Format of the image
+        format: ImageFormat,
+        /// Offset of the invalid header
+        offset: u64,
+        /// Description of the problem
+        reason: &'static str,
+    },
+
+    /// The image is shorter than described by its headers
+    #[error("{format} image is truncated: the headers describe {expected} bytes, but the image is {actual} bytes")]
+    Truncated {
+        /// Format of the image
+        format: ImageFormat,
+        /// Size described by the headers of the image
+        expected: u64,
+        /// Size of the image
+        actual: u64,
+    },
+
+    /// The size of a RAW image is not a multiple of the page size
+    #[error("raw image size of {0} bytes is not a multiple of the page size, which suggests the capture was truncated")]
+    Unaligned(u64),
+
+    /// The image appears to be in a different format
+    #[error("image appears to be {actual} rather than {expected}")]
+    Mismatch {
+        /// Format the image was expected to be in
+        expected: ImageFormat,
+        /// Format the image appears to be in
+        actual: ImageFormat,
+    },
+}
+
+/// Summary of a validated image
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ImageValidation {
+    /// Format of the image
+    pub format: ImageFormat,
+    /// Size of the image in bytes
+    pub size: u64,
+    /// Number of memory ranges described by the headers, for formats that
+    /// describe them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ranges: Option<u64>,
+}
+
+/// Validate the image at `path` as the specified format
+///
+/// # Errors
+/// This will return an error if the image cannot be read or is not valid for
+/// the format
+pub fn validate_image<P: AsRef<Path>>(
+    format: ImageFormat,
+    path: P,
+) -> Result<ImageValidation, FormatError> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    validate(format, &mut file, size)
+}
+
+/// Validate an image of `size` bytes read from `reader` as the specified
+/// format
+///
+/// # Errors
+/// This will return an error if the image cannot be read or is not valid for
+/// the format
+pub fn validate<R: Read + Seek>(
+    format: ImageFormat,
+    reader: &mut R,
+    size: u64,
+) -> Result<ImageValidation, FormatError> {
+    if size == 0 {
+        return Err(FormatError::Empty);
+    }
+
+    let header_len = usize::try_from(size).map_or(HEADER_SIZE, |size| size.min(HEADER_SIZE));
+    let header = read_at(reader, 0, header_len)?;
+    // the layout of AVMH images is not known, so they are not compared to
+    // other formats
+    let detected = detect(&header).filter(|_| format != ImageFormat::Avmh);
+    if let Some(actual) = detected.filter(|actual| *actual != format) {
+        return Err(FormatError::Mismatch {
+            expected: format,
+            actual,
+        });
+    }
+
+    let ranges = match format {
+        ImageFormat::Lime => validate_lime(reader, size)?,
+        ImageFormat::Core => Some(validate_core(reader, &header, size)?),
+        ImageFormat::Raw if size % PAGE_SIZE != 0 => return Err(FormatError::Unaligned(size)),
+        ImageFormat::Raw | ImageFormat::Vmrs | ImageFormat::Avmh | ImageFormat::Pagedump => None,
+    };
+
+    Ok(ImageValidation {
+        format,
+        size,
+        ranges,
+    })
+}
+
+impl ImageFormat {
+    /// Detect the format of the image at `path` from its contents
+    ///
+    /// Returns `None` if the image is not in a format with a well-known magic
+    /// value, such as RAW and VMRS images.
+    ///
+    /// # Errors
+    /// This will return an error if the image cannot be read
+    pub fn detect<P: AsRef<Path>>(path: P) -> Result<Option<Self>, FormatError> {
+        let mut file = File::open(path)?;
+        Ok(Self::detect_from_reader(&mut file)?)
+    }
+
+    /// Detect the format of an image from the start of its contents, read
+    /// from `reader`
+    ///
+    /// Returns `None` if the image is not in a format with a well-known magic
+    /// value, such as RAW and VMRS images.
+    ///
+    /// # Errors
+    /// This will return an error if reading from `reader` fails
+    pub fn detect_from_reader<R: Read>(reader: &mut R) -> std::io::Result<Option<Self>> {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        reader.take(HEADER_SIZE as u64).read_to_end(&mut header)?;
+        Ok(detect(&header))
+    }
+}
+
+/// Identify the format of an image from its header, for formats with a
+/// well-known magic value
+fn detect(header: &[u8]) -> Option<ImageFormat> {
+    if header.starts_with(&LIME_MAGIC.to_le_bytes())
+        || header.starts_with(&AVML_MAGIC.to_le_bytes())
+    {
+        Some(ImageFormat::Lime)
+    } else if header.starts_with(ELF_MAGIC) && is_core(header) {
+        Some(ImageFormat::Core)
+    } else if PAGEDUMP_MAGICS
+        .iter()
+        .any(|magic| header.starts_with(magic))
+    {
+        Some(ImageFormat::Pagedump)
+    } else {
+        None
+    }
+}
+
+/// Returns true if the ELF header is for a core file, rather than an
+/// executable or shared library
+fn is_core(header: &[u8]) -> bool {
+    let big_endian = header.get(5) == Some(&2);
+    read_uint(header, 16, 2, big_endian) == Some(ET_CORE)
+}
+
+/// Read `len` bytes at `offset`
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Read an unsigned integer of `len` bytes at `offset` within `bytes`
+fn read_uint(bytes: &[u8], offset: usize, len: usize, big_endian: bool) -> Option<u64> {
+    let field = bytes.get(offset..offset.checked_add(len)?)?;
+    let mut value = [0; 8];
+    if big_endian {
+        value.get_mut(8 - len..)?.copy_from_slice(field);
+        Some(u64::from_be_bytes(value))
+    } else {
+        value.get_mut(..len)?.copy_from_slice(field);
+        Some(u64::from_le_bytes(value))
+    }
+}
+
+/// Walk the range headers of a `LiME` image, returning the number of ranges
+///
+/// Images compressed by AVML are only checked to start with a valid range
+/// header, and the number of ranges is not returned.
+fn validate_lime<R: Read + Seek>(reader: &mut R, size: u64) -> Result<Option<u64>, FormatError> {
+    let format = ImageFormat::Lime;
+    let mut offset = 0;
+    let mut ranges = 0;
+
+    while offset < size {
+        let invalid = |reason| FormatError::InvalidHeader {
+            format,
+            offset,
+            reason,
+        };
+        let header_end = offset.saturating_add(LIME_HEADER_SIZE);
+        if header_end > size {
+            return Err(FormatError::Truncated {
+                format,
+                expected: header_end,
+                actual: size,
+            });
+        }
+
+        let header = read_at(reader, offset, 32)?;
+        let field =
+            |at, len| read_uint(&header, at, len, false).ok_or_else(|| invalid("header too short"));
+        let compressed = match (field(0, 4)?, offset) {
+            (magic, _) if magic == u64::from(LIME_MAGIC) => false,
+            (magic, 0) if magic == u64::from(AVML_MAGIC) => true,
+            _ => return Err(invalid("missing magic")),
+        };
+        let version = if compressed {
+            AVML_VERSION
+        } else {
+            LIME_VERSION
+        };
+        if field(4, 4)? != u64::from(version) {
+            return Err(invalid("unsupported version"));
+        }
+        let (start, end) = (field(8, 8)?, field(16, 8)?);
+        let len = end
+            .checked_sub(start)
+            .and_then(|len| len.checked_add(1))
+            .ok_or_else(|| invalid("range ends before it starts"))?;
+        if compressed {
+            return Ok(None);
+        }
+
+        offset = header_end.saturating_add(len);
+        ranges += 1;
+    }
+
+    if offset > size {
+        return Err(FormatError::Truncated {
+            format,
+            expected: offset,
+            actual: size,
+        });
+    }
+
+    Ok(Some(ranges))
+}
+
+/// Check the program headers of an ELF core image against the size of the
+/// image, returning the number of loadable segments
+fn validate_core<R: Read + Seek>(
+    reader: &mut R,
+    header: &[u8],
+    size: u64,
+) -> Result<u64, FormatError> {
+    let format = ImageFormat::Core;
+    let invalid = |offset, reason| FormatError::InvalidHeader {
+        format,
+        offset,
+        reason,
+    };
+
+    if !header.starts_with(ELF_MAGIC) {
+        return Err(invalid(0, "missing magic"));
+    }
+    let is_64 = match header.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err(invalid(0, "invalid class")),
+    };
+    let big_endian = match header.get(5) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err(invalid(0, "invalid byte order")),
+    };
+    let field = |bytes: &[u8], offset, at, len| {
+        read_uint(bytes, at, len, big_endian).ok_or_else(|| invalid(offset, "header too short"))
+    };
+
+    if field(header, 0, 16, 2)? != ET_CORE {
+        return Err(invalid(0, "not a core file"));
+    }
+
+    // offsets of e_phoff, e_phentsize, and e_phnum, and the size of e_phoff
+    let (phoff_at, phoff_len, phentsize_at, phnum_at) = if is_64 {
+        (32, 8, 54, 56)
+    } else {
+        (28, 4, 42, 44)
+    };
+    let phoff = field(header, 0, phoff_at, phoff_len)?;
+    let phentsize = field(header, 0, phentsize_at, 2)?;
+    let phnum = field(header, 0, phnum_at, 2)?;
+
+    // offsets of p_offset and p_filesz, and the size of each
+    let (offset_at, filesz_at, len, min_phentsize) = if is_64 {
+        (8, 32, 8, 56)
+    } else {
+        (4, 16, 4, 32)
+    };
+    if phnum == 0 {
+        return Err(invalid(0, "no program headers"));
+    }
+    if phentsize < min_phentsize {
+        return Err(invalid(0, "program headers too small"));
+    }
+
+    let table_len = phentsize.saturating_mul(phnum);
+    let table_end = phoff.saturating_add(table_len);
+    if table_end > size {
+        return Err(FormatError::Truncated {
+            format,
+            expected: table_end,
+            actual: size,
+        });
+    }
+    let too_large = |_| invalid(phoff, "program header table too large");
+    let table = read_at(
+        reader,
+        phoff,
+        usize::try_from(table_len).map_err(too_large)?,
+    )?;
+
+    let mut expected = table_end;
+    let mut ranges = 0;
+    let mut entry_offset = phoff;
+    for entry in table.chunks(usize::try_from(phentsize).map_err(too_large)?) {
+        if field(entry, entry_offset, 0, 4)? == PT_LOAD {
+            ranges += 1;
+        }
+        let end = field(entry, entry_offset, offset_at, len)?.saturating_add(field(
+            entry,
+            entry_offset,
+            filesz_at,
+            len,
+        )?);
+        expected = expected.max(end);
+        entry_offset = entry_offset.saturating_add(phentsize);
+    }
+
+    if expected > size {
+        return Err(FormatError::Truncated {
+            format,
+            expected,
+            actual: size,
+        });
+    }
+
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn lime_range(start: u64, end: u64) -> Vec<u8> {
+        let mut range = vec![];
+        range.extend(LIME_MAGIC.to_le_bytes());
+        range.extend(LIME_VERSION.to_le_bytes());
+        range.extend(start.to_le_bytes());
+        range.extend(end.to_le_bytes());
+        range.extend([0; 8]);
+        range.resize(
+            range.len() + usize::try_from(end - start + 1).unwrap_or_default(),
+            0,
+        );
+        range
+    }
+
+    fn core(filesz: u64) -> Vec<u8> {
+        let mut image = vec![0; 120];
+        image.splice(0..4, ELF_MAGIC.iter().copied());
+        image.splice(4..6, [2, 1]);
+        image.splice(16..18, 4_u16.to_le_bytes());
+        image.splice(32..40, 64_u64.to_le_bytes());
+        image.splice(54..56, 56_u16.to_le_bytes());
+        image.splice(56..58, 1_u16.to_le_bytes());
+        image.splice(64..68, 1_u32.to_le_bytes());
+        image.splice(72..80, 120_u64.to_le_bytes());
+        image.splice(96..104, filesz.to_le_bytes());
+        image
+    }
+
+    fn check(format: ImageFormat, image: &[u8]) -> Result<ImageValidation, FormatError> {
+        validate(format, &mut Cursor::new(image), image.len() as u64)
+    }
+
+    #[test]
+    fn test_lime() -> Result<(), FormatError> {
+        let mut image = lime_range(0, 0xfff);
+        image.extend(lime_range(0x10_0000, 0x10_1fff));
+        assert_eq!(check(ImageFormat::Lime, &image)?.ranges, Some(2));
+
+        let truncated = image.get(..image.len() - 1).unwrap_or_default();
+        assert!(matches!(
+            check(ImageFormat::Lime, truncated),
+            Err(FormatError::Truncated { .. })
+        ));
+
+        image.extend([0; 8]);
+        assert!(matches!(
+            check(ImageFormat::Lime, &image),
+            Err(FormatError::Truncated { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_avml_compressed_lime() -> Result<(), FormatError> {
+        let mut image = vec![];
+        image.extend(AVML_MAGIC.to_le_bytes());
+        image.extend(AVML_VERSION.to_le_bytes());
+        image.extend(0_u64.to_le_bytes());
+        image.extend(0xfff_u64.to_le_bytes());
+        image.extend([0; 8]);
+        image.extend([0xff, 6, 0, 0]);
+        image.extend(b"sNaPpY");
+        image.extend(10_u64.to_le_bytes());
+
+        assert_eq!(
+            ImageFormat::detect_from_reader(&mut Cursor::new(&image))?,
+            Some(ImageFormat::Lime)
+        );
+        assert_eq!(check(ImageFormat::Lime, &image)?.ranges, None);
+        assert!(matches!(
+            check(ImageFormat::Raw, &image),
+            Err(FormatError::Mismatch {
+                actual: ImageFormat::Lime,
+                ..
+            })
+        ));
+
+        image.splice(4..8, 1_u32.to_le_bytes());
+        assert!(matches!(
+            check(ImageFormat::Lime, &image),
+            Err(FormatError::InvalidHeader {
+                reason: "unsupported version",
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_core() -> Result<(), FormatError> {
+        let mut image = core(4096);
+        image.resize(120 + 4096, 0);
+        assert_eq!(check(ImageFormat::Core, &image)?.ranges, Some(1));
+
+        image.truncate(120 + 1024);
+        assert!(matches!(
+            check(ImageFormat::Core, &image),
+            Err(FormatError::Truncated {
+                expected: 4216,
+                actual: 1144,
+                ..
+            })
+        ));
+
+        let mut image = core(0);
+        image.splice(16..18, 2_u16.to_le_bytes());
+        assert!(matches!(
+            check(ImageFormat::Core, &image),
+            Err(FormatError::InvalidHeader {
+                reason: "not a core file",
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect() -> std::io::Result<()> {
+        let detect = |image: &[u8]| ImageFormat::detect_from_reader(&mut Cursor::new(image));
+        assert_eq!(detect(&lime_range(0, 0xfff))?, Some(ImageFormat::Lime));
+        assert_eq!(detect(&core(0))?, Some(ImageFormat::Core));
+        assert_eq!(detect(b"PAGEDU64")?, Some(ImageFormat::Pagedump));
+
+        let mut executable = core(0);
+        executable.splice(16..18, 2_u16.to_le_bytes());
+        assert_eq!(detect(&executable)?, None);
+        assert_eq!(detect(&[0; 8192])?, None);
+        assert_eq!(detect(&[])?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mismatch() -> Result<(), FormatError> {
+        let image = lime_range(0, 0xfff);
+        assert!(matches!(
+            check(ImageFormat::Raw, &image),
+            Err(FormatError::Mismatch {
+                expected: ImageFormat::Raw,
+                actual: ImageFormat::Lime
+            })
+        ));
+        assert!(matches!(
+            check(ImageFormat::Lime, &core(0)),
+            Err(FormatError::Mismatch { .. })
+        ));
+        assert!(matches!(
+            check(ImageFormat::Vmrs, &[]),
+            Err(FormatError::Empty)
+        ));
+
+        assert_eq!(check(ImageFormat::Raw, &[0; 8192])?.ranges, None);
+        assert!(matches!(
+            check(ImageFormat::Raw, &[0; 1000]),
+            Err(FormatError::Unaligned(1000))
+        ));
+        Ok(())
+    }
+}
@@ -1,8 +1,20 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
+/// Version of the serialized form of the models
+///
+/// The serialized form of the models is a public contract, as models are
+/// stored by the service and by users.  This is incremented whenever a model
+/// is serialized differently, such as when a field is renamed or removed.
+/// Such changes are caught by the golden files of the `wire_format` tests,
+/// which must be updated along with this version.
+pub const MODELS_VERSION: &str = "1";
+
 /// Basic Freta models
 pub mod base;
 
+/// Models for detecting drift in the analysis of images
+pub mod baseline;
+
 /// Models for interacting with the Freta service
 pub mod service;
 
@@ -11,3 +23,32 @@ pub mod webhooks;
 
 /// Models for Freta analysis
 pub mod analysis;
+
+/// Well-known names and layout of the artifacts produced by analysis
+pub mod artifacts;
+
+/// Models for grouping related images into collections
+pub mod collections;
+
+/// Models for rules that automatically act on analyzed images
+pub mod policies;
+
+/// Models for estimating the cost of images
+pub mod cost;
+
+/// Models for periodically acting on images
+pub mod schedules;
+
+/// Models for personal access tokens issued by the service
+pub mod tokens;
+
+/// Offline validation of the formats of memory images
+pub mod formats;
+
+/// Golden-file tests of the serialized form of the models
+#[cfg(test)]
+mod wire_format;
+
+/// Compact binary encodings of the models
+#[cfg(feature = "binary-formats")]
+pub mod binary;
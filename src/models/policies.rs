@@ -0,0 +1,308 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::models::{
+    analysis::hook::Check,
+    base::{Image, ImageId},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use url::Url;
+
+/// Image tag recording when an image may be deleted, as set by the
+/// `set_retention` action
+///
+/// The value of the tag is an RFC 3339 timestamp.
+pub const RETENTION_TAG: &str = "retention.expires_at";
+
+/// Rules that automatically act on images once their analysis completes
+///
+/// As an example, the following keeps images with hooked functions and
+/// notifies an incident system, while other images are deleted after 7 days:
+///
+/// ```yaml
+/// policies:
+///   - name: hooked-functions
+///     when:
+///       hooks: true
+///     then:
+///       - tag:
+///           key: triage
+///           value: critical
+///       - webhook_post:
+///           url: https://example.com/incidents
+///     else:
+///       - set_retention:
+///           days: 7
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolicySet {
+    /// Policies, evaluated in order
+    #[serde(default)]
+    pub policies: Vec<Policy>,
+}
+
+impl PolicySet {
+    /// Determine the actions to take for an image, given the checks from the
+    /// analysis of the image
+    #[must_use]
+    pub fn evaluate(&self, image: &Image, checks: &[Check]) -> Vec<PolicyAction> {
+        self.policies
+            .iter()
+            .flat_map(|policy| {
+                policy
+                    .actions(image, checks)
+                    .iter()
+                    .map(|action| PolicyAction {
+                        policy: policy.name.clone(),
+                        image_id: image.image_id,
+                        action: action.clone(),
+                    })
+            })
+            .collect()
+    }
+}
+
+/// A rule that acts on images depending on whether they match a condition
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    /// Name of the policy, used when reporting actions
+    pub name: String,
+
+    /// Condition the image must match
+    #[serde(default)]
+    pub when: Condition,
+
+    /// Actions taken if the image matches the condition
+    #[serde(default)]
+    pub then: Vec<Action>,
+
+    /// Actions taken if the image does not match the condition
+    #[serde(default, rename = "else")]
+    pub otherwise: Vec<Action>,
+}
+
+impl Policy {
+    /// Determine the actions to take for an image
+    #[must_use]
+    pub fn actions(&self, image: &Image, checks: &[Check]) -> &[Action] {
+        if self.when.matches(image, checks) {
+            &self.then
+        } else {
+            &self.otherwise
+        }
+    }
+}
+
+/// Criteria describing the analysis of an image
+///
+/// An image matches if it meets all of the specified criteria, such that an
+/// empty condition matches every image.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Condition {
+    /// Minimum number of checks raised by the analysis
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_checks: Option<usize>,
+
+    /// Whether the analysis found hooked functions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<bool>,
+
+    /// Text of which at least one must be included in the issue of a check,
+    /// ignoring case
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub issue_contains: Vec<String>,
+
+    /// Tags the image must have
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+impl Condition {
+    /// Returns true if the image and the checks from its analysis meet all of
+    /// the criteria
+    #[must_use]
+    pub fn matches(&self, image: &Image, checks: &[Check]) -> bool {
+        let min_checks = self.min_checks.is_none_or(|min| checks.len() >= min);
+        let hooks = self
+            .hooks
+            .is_none_or(|hooks| checks.iter().any(|c| c.hook.is_some()) == hooks);
+        let issue_contains = self.issue_contains.is_empty()
+            || checks.iter().any(|check| {
+                let issue = check.issue.to_lowercase();
+                self.issue_contains
+                    .iter()
+                    .any(|text| issue.contains(&text.to_lowercase()))
+            });
+        let tags = self
+            .tags
+            .iter()
+            .all(|(key, value)| image.tags.get(key) == Some(value));
+        min_checks && hooks && issue_contains && tags
+    }
+}
+
+/// Action taken on an image by a policy
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Add a tag to the image
+    Tag {
+        /// Key of the tag
+        key: String,
+        /// Value of the tag
+        value: String,
+    },
+
+    /// Delete the image
+    Delete,
+
+    /// Record that the image may be deleted after a number of days, unless a
+    /// retention period was already recorded
+    SetRetention {
+        /// Days from now until the image may be deleted
+        days: u32,
+    },
+
+    /// Send a `PolicyNotification` to a URL
+    WebhookPost {
+        /// URL the notification is posted to
+        url: Url,
+    },
+}
+
+/// An action to take on an image, along with the policy requiring it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PolicyAction {
+    /// Name of the policy
+    pub policy: String,
+    /// Image the action applies to
+    pub image_id: ImageId,
+    /// Action to take
+    pub action: Action,
+}
+
+/// Body of the request sent by the `webhook_post` action
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PolicyNotification {
+    /// Name of the policy
+    pub policy: String,
+    /// Image the policy was evaluated for
+    pub image_id: ImageId,
+    /// Tags of the image
+    pub tags: BTreeMap<String, String>,
+    /// Issues of the checks raised by the analysis of the image
+    pub issues: Vec<String>,
+}
+
+/// Returns true if the retention period recorded on the image by the
+/// `set_retention` action ended before `now`
+///
+/// Images without a valid retention tag are retained.
+#[must_use]
+pub fn retention_expired(image: &Image, now: OffsetDateTime) -> bool {
+    image
+        .tags
+        .get(RETENTION_TAG)
+        .and_then(|expires| OffsetDateTime::parse(expires, &Rfc3339).ok())
+        .is_some_and(|expires| expires <= now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        analysis::hook::Hook,
+        base::{ImageFormat, OwnerId},
+    };
+    use time::Duration;
+
+    fn image() -> Image {
+        Image::new(OwnerId::samples(), ImageFormat::Raw, BTreeMap::new())
+    }
+
+    #[test]
+    fn test_evaluate() -> Result<(), serde_json::Error> {
+        let policies: PolicySet = serde_json::from_value(serde_json::json!({
+            "policies": [
+                {
+                    "name": "hooked",
+                    "when": {"hooks": true},
+                    "then": [{"tag": {"key": "triage", "value": "critical"}}],
+                    "else": [{"set_retention": {"days": 7}}],
+                },
+                {
+                    "name": "rootkit",
+                    "when": {"issue_contains": ["ROOTKIT"]},
+                    "then": ["delete"],
+                },
+            ],
+        }))?;
+
+        let image = image();
+        let clean = policies.evaluate(&image, &[]);
+        assert_eq!(
+            clean,
+            vec![PolicyAction {
+                policy: "hooked".into(),
+                image_id: image.image_id,
+                action: Action::SetRetention { days: 7 },
+            }]
+        );
+
+        let checks = vec![Check {
+            issue: "possible rootkit".into(),
+            hook: Some(Hook::default()),
+            ..Check::default()
+        }];
+        let actions = policies
+            .evaluate(&image, &checks)
+            .into_iter()
+            .map(|action| action.action)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                Action::Tag {
+                    key: "triage".into(),
+                    value: "critical".into()
+                },
+                Action::Delete,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_condition_tags() {
+        let mut image = image();
+        let condition = Condition {
+            tags: BTreeMap::from([("env".to_string(), "prod".to_string())]),
+            ..Condition::default()
+        };
+        assert!(!condition.matches(&image, &[]));
+        image.tags.insert("env".into(), "prod".into());
+        assert!(condition.matches(&image, &[]));
+        assert!(Condition::default().matches(&image, &[]));
+    }
+
+    #[test]
+    fn test_retention_expired() -> Result<(), time::error::Format> {
+        let now = OffsetDateTime::now_utc();
+        let mut image = image();
+        assert!(!retention_expired(&image, now));
+
+        image.tags.insert(
+            RETENTION_TAG.into(),
+            (now - Duration::days(1)).format(&Rfc3339)?,
+        );
+        assert!(retention_expired(&image, now));
+
+        image.tags.insert(
+            RETENTION_TAG.into(),
+            (now + Duration::days(1)).format(&Rfc3339)?,
+        );
+        assert!(!retention_expired(&image, now));
+        Ok(())
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::models::base::{Image, ImageId};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Error as FmtError, Formatter},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use uuid::Uuid;
+
+/// Number of days searched for the next time a cron expression matches
+///
+/// Expressions such as `0 0 30 2 *` never match, and every valid expression
+/// that can match does so within 8 years, including leap days.
+const MAX_SEARCH_DAYS: u32 = 366 * 8;
+
+/// Errors parsing a cron expression
+#[derive(thiserror::Error, Debug)]
+pub enum CronError {
+    /// The expression does not have exactly 5 fields
+    #[error(
+        "cron expressions require 5 fields (minute hour day-of-month month day-of-week), found {0}"
+    )]
+    FieldCount(usize),
+
+    /// A field of the expression is invalid
+    #[error("invalid {name} field `{value}`.  expected values between {min} and {max}")]
+    InvalidField {
+        /// Name of the field
+        name: &'static str,
+        /// Value of the field
+        value: String,
+        /// Minimum value of the field
+        min: u8,
+        /// Maximum value of the field
+        max: u8,
+    },
+}
+
+/// Parse a field of a cron expression into the set of values it matches
+///
+/// Fields are a comma separated list of `*`, values, or ranges such as
+/// `1-5`, each of which may include a step such as `*/15`.
+fn parse_field(
+    name: &'static str,
+    value: &str,
+    range: RangeInclusive<u8>,
+) -> Result<BTreeSet<u8>, CronError> {
+    let (min, max) = (*range.start(), *range.end());
+    let invalid = || CronError::InvalidField {
+        name,
+        value: value.to_string(),
+        min,
+        max,
+    };
+    let number = |x: &str| {
+        x.parse::<u8>()
+            .ok()
+            .filter(|x| range.contains(x))
+            .ok_or_else(invalid)
+    };
+
+    let mut values = BTreeSet::new();
+    for part in value.split(',') {
+        let (span, step) = match part.split_once('/') {
+            Some((span, step)) => (span, step.parse::<u8>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (start, end) = if span == "*" {
+            (min, max)
+        } else if let Some((start, end)) = span.split_once('-') {
+            (number(start)?, number(end)?)
+        } else {
+            let start = number(span)?;
+            // `5/15` matches every 15th value starting at 5
+            (start, if part.contains('/') { max } else { start })
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        values.extend((start..=end).step_by(usize::from(step)));
+    }
+    Ok(values)
+}
+
+/// A standard 5 field cron expression, such as `0 3 * * 1` for 03:00 every
+/// Monday
+///
+/// Expressions are evaluated in UTC.  As with cron, if both the day of the
+/// month and the day of the week are restricted, a day matches if either of
+/// them match.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronExpression {
+    /// The expression as provided
+    expression: String,
+    /// Minutes of the hour, 0 to 59
+    minutes: BTreeSet<u8>,
+    /// Hours of the day, 0 to 23
+    hours: BTreeSet<u8>,
+    /// Days of the month, 1 to 31, or None if unrestricted
+    days_of_month: Option<BTreeSet<u8>>,
+    /// Months of the year, 1 to 12
+    months: BTreeSet<u8>,
+    /// Days of the week, 0 to 6 starting on Sunday, or None if unrestricted
+    days_of_week: Option<BTreeSet<u8>>,
+}
+
+impl CronExpression {
+    /// Returns true if the expression matches the day
+    fn matches_date(&self, date: Date) -> bool {
+        if !self.months.contains(&u8::from(date.month())) {
+            return false;
+        }
+        let day_of_month = self
+            .days_of_month
+            .as_ref()
+            .map(|days| days.contains(&date.day()));
+        let day_of_week = self
+            .days_of_week
+            .as_ref()
+            .map(|days| days.contains(&date.weekday().number_days_from_sunday()));
+        match (day_of_month, day_of_week) {
+            (Some(day_of_month), Some(day_of_week)) => day_of_month || day_of_week,
+            (Some(matches), None) | (None, Some(matches)) => matches,
+            (None, None) => true,
+        }
+    }
+
+    /// Get the first time after `after` that matches the expression
+    ///
+    /// Returns None if the expression never matches, such as `0 0 30 2 *`.
+    #[must_use]
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let after = after.to_offset(UtcOffset::UTC);
+        let mut date = after.date();
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_date(date) {
+                for hour in &self.hours {
+                    for minute in &self.minutes {
+                        let time = Time::from_hms(*hour, *minute, 0).ok()?;
+                        let candidate = PrimitiveDateTime::new(date, time).assume_utc();
+                        if candidate > after {
+                            return Some(candidate);
+                        }
+                    }
+                }
+            }
+            date = date.next_day()?;
+        }
+        None
+    }
+}
+
+impl FromStr for CronExpression {
+    type Err = CronError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day_of_month, month, day_of_week] = fields.as_slice() else {
+            return Err(CronError::FieldCount(fields.len()));
+        };
+        let unrestricted = |field: &str| field == "*";
+
+        let days_of_month = if unrestricted(day_of_month) {
+            None
+        } else {
+            Some(parse_field("day-of-month", day_of_month, 1..=31)?)
+        };
+        // both 0 and 7 are Sunday
+        let days_of_week = if unrestricted(day_of_week) {
+            None
+        } else {
+            let days = parse_field("day-of-week", day_of_week, 0..=7)?;
+            Some(days.into_iter().map(|day| day % 7).collect())
+        };
+
+        Ok(Self {
+            expression: fields.join(" "),
+            minutes: parse_field("minute", minute, 0..=59)?,
+            hours: parse_field("hour", hour, 0..=23)?,
+            days_of_month,
+            months: parse_field("month", month, 1..=12)?,
+            days_of_week,
+        })
+    }
+}
+
+impl TryFrom<String> for CronExpression {
+    type Error = CronError;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        expression.parse()
+    }
+}
+
+impl From<CronExpression> for String {
+    fn from(expression: CronExpression) -> Self {
+        expression.expression
+    }
+}
+
+impl Display for CronExpression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.expression)
+    }
+}
+
+/// Unique identifier for a `Schedule`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub struct ScheduleId(Uuid);
+
+impl ScheduleId {
+    /// Generate a new `ScheduleId`
+    #[must_use]
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for ScheduleId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for ScheduleId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for ScheduleId {
+    type Err = uuid::Error;
+
+    fn from_str(uuid_str: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(uuid_str).map(Self)
+    }
+}
+
+/// Action taken each time a schedule runs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum ScheduledAction {
+    /// Reanalyze the images that have all of the tags, such that they
+    /// benefit from updated detection content
+    Reanalyze {
+        /// Tags the images must have
+        tags: BTreeMap<String, String>,
+
+        /// Version of the analysis engine to use, rather than the current
+        /// version
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        engine_version: Option<String>,
+    },
+}
+
+impl ScheduledAction {
+    /// Returns true if the action applies to the image
+    #[must_use]
+    pub fn applies_to(&self, image: &Image) -> bool {
+        match self {
+            Self::Reanalyze { tags, .. } => {
+                image.state.can_reimage()
+                    && tags
+                        .iter()
+                        .all(|(key, value)| image.tags.get(key) == Some(value))
+            }
+        }
+    }
+}
+
+/// An action that is periodically taken by `freta schedule run`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    /// Unique identifier of the schedule
+    pub schedule_id: ScheduleId,
+
+    /// When the schedule runs
+    pub cron: CronExpression,
+
+    /// Action taken each time the schedule runs
+    #[serde(flatten)]
+    pub action: ScheduledAction,
+
+    /// When the schedule was created
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+
+    /// When the schedule last ran
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub last_run: Option<OffsetDateTime>,
+}
+
+impl Schedule {
+    /// Create a new schedule
+    #[must_use]
+    pub fn new(cron: CronExpression, action: ScheduledAction) -> Self {
+        Self {
+            schedule_id: ScheduleId::new(),
+            cron,
+            action,
+            created_at: OffsetDateTime::now_utc(),
+            last_run: None,
+        }
+    }
+
+    /// Get the next time the schedule is due to run
+    ///
+    /// Runs missed while no scheduler was running are due immediately, such
+    /// that they are run once the scheduler starts.
+    #[must_use]
+    pub fn next_run(&self) -> Option<OffsetDateTime> {
+        self.cron
+            .next_after(self.last_run.unwrap_or(self.created_at))
+    }
+
+    /// Returns true if the schedule is due to run
+    #[must_use]
+    pub fn is_due(&self, now: OffsetDateTime) -> bool {
+        self.next_run().is_some_and(|next| next <= now)
+    }
+}
+
+/// Images selected by a run of a schedule
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleRun {
+    /// The schedule that ran
+    pub schedule_id: ScheduleId,
+
+    /// Images the action applies to
+    pub image_ids: Vec<ImageId>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::format_description::well_known::Rfc3339;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn utc(timestamp: &str) -> Result<OffsetDateTime, time::error::Parse> {
+        OffsetDateTime::parse(timestamp, &Rfc3339)
+    }
+
+    #[test]
+    fn test_cron_parse() -> TestResult {
+        let cron: CronExpression = "*/15  3-5,22 * 1 1-5/2".parse()?;
+        assert_eq!(cron.to_string(), "*/15 3-5,22 * 1 1-5/2");
+        assert_eq!(cron.minutes, BTreeSet::from([0, 15, 30, 45]));
+        assert_eq!(cron.hours, BTreeSet::from([3, 4, 5, 22]));
+        assert_eq!(cron.days_of_month, None);
+        assert_eq!(cron.days_of_week, Some(BTreeSet::from([1, 3, 5])));
+
+        let sunday: CronExpression = "0 0 * * 7".parse()?;
+        assert_eq!(sunday.days_of_week, Some(BTreeSet::from([0])));
+
+        for invalid in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+        ] {
+            assert!(invalid.parse::<CronExpression>().is_err(), "{invalid}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cron_next_after() -> TestResult {
+        // 2023-09-13 is a Wednesday
+        let now = utc("2023-09-13T10:30:00Z")?;
+
+        let weekly: CronExpression = "0 3 * * 1".parse()?;
+        assert_eq!(weekly.next_after(now), Some(utc("2023-09-18T03:00:00Z")?));
+
+        let hourly: CronExpression = "30 * * * *".parse()?;
+        assert_eq!(hourly.next_after(now), Some(utc("2023-09-13T11:30:00Z")?));
+
+        // the day of the month or the day of the week may match
+        let either: CronExpression = "0 0 1 * 5".parse()?;
+        assert_eq!(either.next_after(now), Some(utc("2023-09-15T00:00:00Z")?));
+
+        let leap: CronExpression = "0 0 29 2 *".parse()?;
+        assert_eq!(leap.next_after(now), Some(utc("2024-02-29T00:00:00Z")?));
+
+        let never: CronExpression = "0 0 30 2 *".parse()?;
+        assert_eq!(never.next_after(now), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_schedule_due() -> TestResult {
+        let mut schedule = Schedule::new(
+            "0 3 * * 1".parse()?,
+            ScheduledAction::Reanalyze {
+                tags: BTreeMap::from([("rescan".to_string(), "weekly".to_string())]),
+                engine_version: None,
+            },
+        );
+        schedule.created_at = utc("2023-09-13T10:30:00Z")?;
+        assert!(!schedule.is_due(utc("2023-09-18T02:59:00Z")?));
+        assert!(schedule.is_due(utc("2023-09-18T03:00:00Z")?));
+
+        // missed runs are only run once
+        schedule.last_run = Some(utc("2023-10-02T12:00:00Z")?);
+        assert_eq!(schedule.next_run(), Some(utc("2023-10-09T03:00:00Z")?));
+        Ok(())
+    }
+}
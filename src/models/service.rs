@@ -4,6 +4,7 @@ use crate::models::base::{Image, ImageFormat, ImageId, ImageState, OwnerId};
 use clap::Parser;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use url::Url;
 
 /// Result for getting an image
 #[derive(Debug, Serialize, Deserialize)]
@@ -13,12 +14,27 @@ pub struct ImageResponse(pub Image);
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImageReanalyzeResponse(pub bool);
 
+/// Result for signaling that the upload of an image completed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageUploadCompleteResponse(pub bool);
+
 /// Result for requesting an image be deleted
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImageDeleteResponse(pub bool);
 
 #[derive(Serialize, Deserialize, Default, Debug, Parser, Clone)]
 /// list images
+///
+/// By default, every image other than sample images and deleted images is
+/// included.
+///
+/// ```
+/// # use freta::models::{base::ImageState, service::ImageList};
+/// let request = ImageList::default()
+///     .with_state(ImageState::Completed)
+///     .with_include_samples(true);
+/// assert_eq!(request.state, Some(ImageState::Completed));
+/// ```
 pub struct ImageList {
     #[arg(long)]
     /// image id
@@ -41,6 +57,19 @@ pub struct ImageList {
     #[serde(default)]
     pub include_samples: bool,
 
+    #[arg(long)]
+    /// include tombstone records of deleted images
+    #[serde(default)]
+    pub include_deleted: bool,
+
+    #[arg(skip)]
+    /// only include images with all of the specified tags
+    ///
+    /// the service does not support filtering by tags, so the filter is
+    /// applied by the client as the images are listed.
+    #[serde(skip)]
+    pub tags: Option<BTreeMap<String, String>>,
+
     #[arg(skip)]
     /// continuation value used for paging.
     ///
@@ -49,6 +78,72 @@ pub struct ImageList {
     pub continuation: Option<String>,
 }
 
+impl ImageList {
+    /// Only include the specified image
+    #[must_use]
+    pub const fn with_image_id(self, image_id: ImageId) -> Self {
+        Self {
+            image_id: Some(image_id),
+            ..self
+        }
+    }
+
+    /// Only include images owned by the specified owner
+    #[must_use]
+    pub const fn with_owner_id(self, owner_id: OwnerId) -> Self {
+        Self {
+            owner_id: Some(owner_id),
+            ..self
+        }
+    }
+
+    /// Only include images in the specified state
+    #[must_use]
+    pub const fn with_state(self, state: ImageState) -> Self {
+        Self {
+            state: Some(state),
+            ..self
+        }
+    }
+
+    /// Include sample images
+    #[must_use]
+    pub const fn with_include_samples(self, include_samples: bool) -> Self {
+        Self {
+            include_samples,
+            ..self
+        }
+    }
+
+    /// Include tombstone records of deleted images
+    #[must_use]
+    pub const fn with_include_deleted(self, include_deleted: bool) -> Self {
+        Self {
+            include_deleted,
+            ..self
+        }
+    }
+
+    /// Only include images with all of the specified tags
+    #[must_use]
+    pub fn with_tags(self, tags: BTreeMap<String, String>) -> Self {
+        Self {
+            tags: Some(tags),
+            ..self
+        }
+    }
+
+    /// Does the image match the filters of the request that are not applied
+    /// by the service
+    #[must_use]
+    pub fn matches(&self, image: &Image) -> bool {
+        self.tags.as_ref().is_none_or(|tags| {
+            tags.iter()
+                .all(|(key, value)| image.tags.get(key) == Some(value))
+        })
+    }
+}
+
 /// Image List response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImagesListResponse {
@@ -61,17 +156,117 @@ pub struct ImagesListResponse {
 }
 
 /// Image Create
-
+///
+/// ```
+/// # use freta::models::{base::ImageFormat, service::ImageCreate};
+/// # use std::collections::BTreeMap;
+/// let request = ImageCreate::new(ImageFormat::Lime)
+///     .with_tags(BTreeMap::from([("team".to_string(), "red".to_string())]))
+///     .with_requested_analysis_version("1.2.3");
+/// assert_eq!(request.requested_analysis_version.as_deref(), Some("1.2.3"));
+/// ```
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImageCreate {
     /// image format
     pub format: ImageFormat,
     /// image metadata tags
     pub tags: BTreeMap<String, String>,
+    /// URL notified of the events for this image only, without creating a
+    /// webhook.
+    ///
+    /// The URL may include the `{event_type}` placeholder, as with webhooks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<Url>,
+    /// Version of the analysis engine to analyze the image with, rather than
+    /// the current version.
+    ///
+    /// Versions the service does not support are rejected.  Older versions of
+    /// the service ignore the requested version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_analysis_version: Option<String>,
+}
+
+impl ImageCreate {
+    /// Create a request for an image of the specified format, without tags
+    #[must_use]
+    pub const fn new(format: ImageFormat) -> Self {
+        Self {
+            format,
+            tags: BTreeMap::new(),
+            callback_url: None,
+            requested_analysis_version: None,
+        }
+    }
+
+    /// Set the metadata tags of the image
+    #[must_use]
+    pub fn with_tags(self, tags: BTreeMap<String, String>) -> Self {
+        Self { tags, ..self }
+    }
+
+    /// Notify the URL of the events for this image
+    #[must_use]
+    pub fn with_callback_url(self, callback_url: Url) -> Self {
+        Self {
+            callback_url: Some(callback_url),
+            ..self
+        }
+    }
+
+    /// Analyze the image using the specified version of the analysis engine
+    #[must_use]
+    pub fn with_requested_analysis_version<S>(self, version: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            requested_analysis_version: Some(version.into()),
+            ..self
+        }
+    }
+}
+
+/// Request to reanalyze an image
+///
+/// By default, the image is reanalyzed using the current version of the
+/// analysis engine.
+///
+/// ```
+/// # use freta::models::service::ImageReanalyze;
+/// let request = ImageReanalyze::default().with_requested_analysis_version("1.2.3");
+/// assert_eq!(request.requested_analysis_version.as_deref(), Some("1.2.3"));
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImageReanalyze {
+    /// Version of the analysis engine to reanalyze the image with, rather
+    /// than the current version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_analysis_version: Option<String>,
+}
+
+impl ImageReanalyze {
+    /// Reanalyze the image using the specified version of the analysis engine
+    #[must_use]
+    pub fn with_requested_analysis_version<S>(mut self, version: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.requested_analysis_version = Some(version.into());
+        self
+    }
 }
 
 /// Image Update
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// By default, the image is not modified.
+///
+/// ```
+/// # use freta::models::service::ImageUpdate;
+/// let request = ImageUpdate::default().with_shareable(true);
+/// assert_eq!(request.shareable, Some(true));
+/// assert!(request.tags.is_none());
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ImageUpdate {
     /// If provided, overwrite the `tags` for the image
     pub tags: Option<BTreeMap<String, String>>,
@@ -79,8 +274,29 @@ pub struct ImageUpdate {
     pub shareable: Option<bool>,
 }
 
+impl ImageUpdate {
+    /// Overwrite the tags of the image
+    #[must_use]
+    pub fn with_tags(self, tags: BTreeMap<String, String>) -> Self {
+        Self {
+            tags: Some(tags),
+            ..self
+        }
+    }
+
+    /// Set whether the image is shareable
+    #[must_use]
+    pub const fn with_shareable(self, shareable: bool) -> Self {
+        Self {
+            shareable: Some(shareable),
+            ..self
+        }
+    }
+}
+
 /// Freta service information
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Info {
     /// current API version
     pub api_version: String,
@@ -0,0 +1,144 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Error as FmtError, Formatter},
+    str::FromStr,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Unique identifier for a `Token`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub struct TokenId(Uuid);
+
+impl Display for TokenId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for TokenId {
+    type Err = uuid::Error;
+
+    fn from_str(uuid_str: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(uuid_str).map(Self)
+    }
+}
+
+/// Personal access token issued by the service
+///
+/// Personal access tokens authenticate as the user that created them, which
+/// allows headless machines to use the service without an interactive login.
+/// The secret of a token is only returned when the token is created.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// Unique identifier of the token
+    pub token_id: TokenId,
+
+    /// Name of the token, such as the machine that uses it
+    pub name: String,
+
+    /// When the token was created
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+
+    /// When the token expires, if it expires
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+impl Token {
+    /// Returns true if the token expired before `now`
+    #[must_use]
+    pub fn is_expired(&self, now: OffsetDateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at < now)
+    }
+}
+
+/// Request to create a personal access token
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TokenCreate {
+    /// Name of the token
+    pub name: String,
+
+    /// Number of days until the token expires.  If unset, the service
+    /// decides when the token expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in_days: Option<u32>,
+}
+
+/// Response to creating a personal access token
+#[derive(Serialize, Deserialize)]
+pub struct TokenCreateResponse {
+    /// The created token
+    #[serde(flatten)]
+    pub token: Token,
+
+    /// Secret used to authenticate with the token.  This cannot be retrieved
+    /// again.
+    pub secret: String,
+}
+
+impl fmt::Debug for TokenCreateResponse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenCreateResponse")
+            .field("token", &self.token)
+            .field("secret", &"[redacted secret]")
+            .finish()
+    }
+}
+
+/// Request to list personal access tokens
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TokensListRequest {
+    /// The continuation value used for paging
+    pub continuation: Option<String>,
+}
+
+/// Response to listing personal access tokens
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TokensListResponse {
+    /// List of tokens
+    pub tokens: Vec<Token>,
+
+    /// continuation value used for paging
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation: Option<String>,
+}
+
+/// Result for revoking a personal access token
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenRevokeResponse(pub bool);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token() -> Result<(), Box<dyn std::error::Error>> {
+        let created_at = OffsetDateTime::from_unix_timestamp(1_700_000_000)?;
+        let mut token = Token {
+            token_id: "00000000-0000-0000-0000-000000000009".parse()?,
+            name: "lab-01".into(),
+            created_at,
+            expires_at: None,
+        };
+        assert!(!token.is_expired(created_at + time::Duration::days(365)));
+
+        token.expires_at = Some(created_at + time::Duration::days(30));
+        assert!(!token.is_expired(created_at + time::Duration::days(29)));
+        assert!(token.is_expired(created_at + time::Duration::days(31)));
+
+        let created = TokenCreateResponse {
+            token,
+            secret: "hunter2".into(),
+        };
+        assert!(!format!("{created:?}").contains("hunter2"));
+        Ok(())
+    }
+}
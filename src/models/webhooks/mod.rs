@@ -1,5 +1,7 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
+/// Helpers for services that receive webhook events
+pub mod receiver;
 /// REST API models for Webhooks
 pub mod service;
 
@@ -10,7 +12,7 @@ use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
 use sha2::Sha512;
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     fmt::{Display, Error as FmtError, Formatter, Write},
     str::FromStr,
     time::SystemTime,
@@ -22,6 +24,18 @@ use uuid::Uuid;
 /// HTTP Header used to validate HMAC-SHA512 signatures of the webhook payloads
 pub const DIGEST_HEADER: &str = "x-freta-digest";
 
+/// Placeholder in a webhook URL that is replaced with the event type of the
+/// event being delivered
+///
+/// As an example, the webhook URL `https://example.com/freta/{event_type}`
+/// delivers `image_analysis_completed` events to
+/// `https://example.com/freta/image_analysis_completed`.
+pub const EVENT_TYPE_PLACEHOLDER: &str = "{event_type}";
+
+/// Percent-encoded form of `EVENT_TYPE_PLACEHOLDER`, as `{` and `}` are
+/// encoded when parsing a URL path.
+const EVENT_TYPE_PLACEHOLDER_ENCODED: &str = "%7Bevent_type%7D";
+
 /// Unique identifier for a `Webhook`
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct WebhookId(Uuid);
@@ -56,7 +70,7 @@ impl FromStr for WebhookId {
 
 /// Unique identifier for a `WebhookEvent` entry
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct WebhookEventId(Uuid);
 
 impl WebhookEventId {
@@ -108,6 +122,270 @@ pub enum WebhookEventType {
     ImageStateUpdated,
 }
 
+impl WebhookEventType {
+    /// The event type as used in serialized events
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ping => "ping",
+            Self::ImageCreated => "image_created",
+            Self::ImageDeleted => "image_deleted",
+            Self::ImageAnalysisCompleted => "image_analysis_completed",
+            Self::ImageAnalysisFailed => "image_analysis_failed",
+            Self::ImageStateUpdated => "image_state_updated",
+        }
+    }
+}
+
+impl Display for WebhookEventType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Errors validating a webhook URL template
+#[derive(thiserror::Error, Debug)]
+pub enum WebhookUrlError {
+    /// The URL host includes a template placeholder
+    #[error("placeholders are not supported in the webhook host")]
+    PlaceholderInHost,
+
+    /// The URL includes a placeholder other than `{event_type}`
+    #[error("unsupported placeholder in webhook url.  only `{{event_type}}` is supported")]
+    UnsupportedPlaceholder,
+}
+
+/// Validate a webhook URL template
+///
+/// Webhook URLs may include the placeholder `{event_type}` in the path or
+/// query, which is replaced with the type of the event being delivered.
+///
+/// # Errors
+/// This returns an error if the URL includes a placeholder in the host or
+/// includes placeholders other than `{event_type}`
+pub fn validate_url_template(url: &Url) -> Result<(), WebhookUrlError> {
+    if url
+        .host_str()
+        .is_some_and(|host| host.contains(['{', '}']) || host.contains("%7B"))
+    {
+        return Err(WebhookUrlError::PlaceholderInHost);
+    }
+
+    let remaining = url
+        .as_str()
+        .replace(EVENT_TYPE_PLACEHOLDER, "")
+        .replace(EVENT_TYPE_PLACEHOLDER_ENCODED, "");
+    let upper = remaining.to_ascii_uppercase();
+    if remaining.contains(['{', '}']) || upper.contains("%7B") || upper.contains("%7D") {
+        return Err(WebhookUrlError::UnsupportedPlaceholder);
+    }
+
+    Ok(())
+}
+
+/// Expand a webhook URL template for a given event type
+///
+/// URLs without the `{event_type}` placeholder are returned unmodified.
+///
+/// # Errors
+/// This returns an error if the expanded URL is not a valid URL
+pub fn expand_url_template(
+    url: &Url,
+    event_type: &WebhookEventType,
+) -> Result<Url, url::ParseError> {
+    let expanded = url
+        .as_str()
+        .replace(EVENT_TYPE_PLACEHOLDER, event_type.as_str())
+        .replace(EVENT_TYPE_PLACEHOLDER_ENCODED, event_type.as_str());
+    Url::parse(&expanded)
+}
+
+/// URL scheme used for the string form of Azure Service Bus webhook targets
+const SERVICE_BUS_SCHEME: &str = "servicebus";
+
+/// URL scheme used for the string form of Azure Storage Queue webhook targets
+const STORAGE_QUEUE_SCHEME: &str = "storagequeue";
+
+/// Errors validating a webhook target
+#[derive(thiserror::Error, Debug)]
+pub enum WebhookTargetError {
+    /// The webhook URL is invalid
+    #[error(transparent)]
+    Url(#[from] WebhookUrlError),
+
+    /// The Azure Service Bus namespace is invalid
+    #[error("invalid service bus namespace: {0}")]
+    ServiceBusNamespace(String),
+
+    /// The Azure Service Bus queue is invalid
+    #[error("invalid service bus queue: {0}")]
+    ServiceBusQueue(String),
+
+    /// The Azure Storage account is invalid
+    #[error("invalid storage account: {0}")]
+    StorageAccount(String),
+
+    /// The Azure Storage queue is invalid
+    #[error("invalid storage queue: {0}")]
+    StorageQueue(String),
+
+    /// The webhook target could not be parsed
+    #[error("unable to parse webhook target: {0}")]
+    Parse(String),
+}
+
+/// Destination to which webhook events are delivered
+///
+/// Webhook targets are represented as strings as follows:
+/// * HTTP endpoints use their URL, such as `https://example.com/freta`
+/// * Azure Service Bus queues use `servicebus://<namespace>/<queue>`
+/// * Azure Storage queues use `storagequeue://<account>/<queue>`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum WebhookTarget {
+    /// Deliver events via HTTP POST requests
+    Https {
+        /// The webhook url
+        ///
+        /// The URL may include the placeholder `{event_type}`, which is
+        /// replaced with the type of the event being delivered.
+        url: Url,
+    },
+    /// Deliver events as messages to an Azure Service Bus queue
+    AzureServiceBus {
+        /// Service Bus namespace, such as `contoso` for
+        /// `contoso.servicebus.windows.net`
+        #[serde(rename = "service_bus_namespace")]
+        namespace: String,
+        /// Name of the Service Bus queue
+        #[serde(rename = "service_bus_queue")]
+        queue: String,
+    },
+    /// Deliver events as messages to an Azure Storage queue
+    StorageQueue {
+        /// Name of the Azure Storage account
+        #[serde(rename = "storage_account")]
+        account: String,
+        /// Name of the Azure Storage queue
+        #[serde(rename = "storage_queue")]
+        queue: String,
+    },
+}
+
+impl WebhookTarget {
+    /// Validate the webhook target
+    ///
+    /// # Errors
+    /// This returns an error if the URL is not a valid URL template or if the
+    /// names of the Azure resources are invalid
+    pub fn validate(&self) -> Result<(), WebhookTargetError> {
+        match self {
+            Self::Https { url } => validate_url_template(url)?,
+            Self::AzureServiceBus { namespace, queue } => {
+                // namespaces are 6-50 letters, numbers, and hyphens, starting
+                // with a letter and ending with a letter or number
+                let valid_namespace = (6..=50).contains(&namespace.len())
+                    && namespace.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && namespace.ends_with(|c: char| c.is_ascii_alphanumeric())
+                    && namespace
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-');
+                if !valid_namespace {
+                    return Err(WebhookTargetError::ServiceBusNamespace(namespace.clone()));
+                }
+
+                // queues are 1-260 letters, numbers, periods, hyphens,
+                // underscores, and slashes
+                let valid_queue = (1..=260).contains(&queue.len())
+                    && queue
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '/'));
+                if !valid_queue {
+                    return Err(WebhookTargetError::ServiceBusQueue(queue.clone()));
+                }
+            }
+            Self::StorageQueue { account, queue } => {
+                // accounts are 3-24 lowercase letters and numbers
+                let valid_account = (3..=24).contains(&account.len())
+                    && account
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+                if !valid_account {
+                    return Err(WebhookTargetError::StorageAccount(account.clone()));
+                }
+
+                // queues are 3-63 lowercase letters, numbers, and hyphens,
+                // starting and ending with a letter or number, without
+                // consecutive hyphens
+                let valid_queue = (3..=63).contains(&queue.len())
+                    && !queue.starts_with('-')
+                    && !queue.ends_with('-')
+                    && !queue.contains("--")
+                    && queue
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+                if !valid_queue {
+                    return Err(WebhookTargetError::StorageQueue(queue.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The URL of the target, if events are delivered via HTTP
+    #[must_use]
+    pub const fn url(&self) -> Option<&Url> {
+        match self {
+            Self::Https { url } => Some(url),
+            Self::AzureServiceBus { .. } | Self::StorageQueue { .. } => None,
+        }
+    }
+}
+
+impl From<Url> for WebhookTarget {
+    fn from(url: Url) -> Self {
+        Self::Https { url }
+    }
+}
+
+impl Display for WebhookTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        match self {
+            Self::Https { url } => write!(f, "{url}"),
+            Self::AzureServiceBus { namespace, queue } => {
+                write!(f, "{SERVICE_BUS_SCHEME}://{namespace}/{queue}")
+            }
+            Self::StorageQueue { account, queue } => {
+                write!(f, "{STORAGE_QUEUE_SCHEME}://{account}/{queue}")
+            }
+        }
+    }
+}
+
+impl FromStr for WebhookTarget {
+    type Err = WebhookTargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_queue = |rest: &str| -> Result<(String, String), WebhookTargetError> {
+            rest.split_once('/')
+                .filter(|(resource, queue)| !resource.is_empty() && !queue.is_empty())
+                .map(|(resource, queue)| (resource.to_string(), queue.to_string()))
+                .ok_or_else(|| WebhookTargetError::Parse(s.to_string()))
+        };
+
+        if let Some(rest) = s.strip_prefix(&format!("{SERVICE_BUS_SCHEME}://")) {
+            let (namespace, queue) = parse_queue(rest)?;
+            return Ok(Self::AzureServiceBus { namespace, queue });
+        }
+        if let Some(rest) = s.strip_prefix(&format!("{STORAGE_QUEUE_SCHEME}://")) {
+            let (account, queue) = parse_queue(rest)?;
+            return Ok(Self::StorageQueue { account, queue });
+        }
+        let url = Url::parse(s).map_err(|e| WebhookTargetError::Parse(e.to_string()))?;
+        Ok(Self::Https { url })
+    }
+}
+
 /// Freta Webhook Event
 ///
 /// This struct defines the structure of a webhook event sent to user's
@@ -129,6 +407,14 @@ pub struct WebhookEvent {
     /// The image that triggered the event, if applicable
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image: Option<ImageId>,
+
+    /// Key-Value pair of metadata associated with the image that triggered
+    /// the event.
+    ///
+    /// NOTE: This is only provided if the webhook was configured with
+    /// `include_tags` and the event is related to an image.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub tags: Option<BTreeMap<String, String>>,
 }
 
 impl WebhookEvent {
@@ -144,8 +430,30 @@ impl WebhookEvent {
             event_type,
             timestamp,
             image,
+            tags: None,
         }
     }
+
+    /// Include the tags of the image that triggered the event
+    #[must_use]
+    pub fn with_tags(self, tags: BTreeMap<String, String>) -> Self {
+        Self {
+            tags: Some(tags),
+            ..self
+        }
+    }
+
+    /// Get the value of a tag of the image that triggered the event
+    ///
+    /// This returns `None` if the webhook was not configured to include tags,
+    /// or if the image does not have the specified tag.
+    #[must_use]
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .as_ref()
+            .and_then(|tags| tags.get(key))
+            .map(String::as_str)
+    }
 }
 
 /// Freta errors
@@ -161,13 +469,151 @@ pub enum HmacError {
 }
 
 impl WebhookEvent {
+    /// Serialize the event using the canonical JSON serialization
+    ///
+    /// See `canonical_json` for details of the serialization.  Webhook
+    /// payloads are sent using this serialization.
+    ///
+    /// # Errors
+    /// This could fail if the event cannot be serialized
+    pub fn to_canonical_json(&self) -> Result<Vec<u8>, HmacError> {
+        canonical_json(self)
+    }
+
     /// Generate a HMAC for the event using the provided token
     ///
+    /// The HMAC is generated from the canonical JSON serialization of the
+    /// event, such that the HMAC does not depend on how the event was
+    /// serialized by intermediaries.
+    ///
     /// # Errors
     /// This could fail if the provided token is invalid or if the event cannot be serialized
     pub fn hmac_sha512(&self, hmac_token: &Secret) -> Result<String, HmacError> {
-        let event_as_bytes = serde_json::to_string(&self)?.as_bytes().to_vec();
-        hmac_sha512(&event_as_bytes, hmac_token)
+        hmac_sha512(&self.to_canonical_json()?, hmac_token)
+    }
+}
+
+/// Serialize a value as canonical JSON
+///
+/// The canonical serialization is compact JSON, without any insignificant
+/// whitespace, with the keys of all objects sorted lexicographically by their
+/// UTF-8 bytes.  Numbers are formatted using their shortest round-trip
+/// representation, and timestamps are formatted as RFC 3339 strings by the
+/// models themselves.
+///
+/// # Errors
+/// This could fail if the value cannot be serialized
+pub fn canonical_json<T>(value: &T) -> Result<Vec<u8>, HmacError>
+where
+    T: Serialize + ?Sized,
+{
+    let value = canonicalize(serde_json::to_value(value)?);
+    Ok(serde_json::to_vec(&value)?)
+}
+
+/// Recursively sort the keys of all objects within a JSON value
+fn canonicalize(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let sorted = map
+                .into_iter()
+                .map(|(key, entry)| (key, canonicalize(entry)))
+                .collect::<BTreeMap<_, _>>();
+            serde_json::Value::Object(sorted.into_iter().collect())
+        }
+        serde_json::Value::Array(values) => {
+            serde_json::Value::Array(values.into_iter().map(canonicalize).collect())
+        }
+        other => other,
+    }
+}
+
+/// Errors verifying the HMAC of a webhook payload
+#[derive(thiserror::Error, Debug)]
+pub enum VerifyError {
+    /// Unable to create an HMAC from the provided token
+    #[error("invalid hmac token")]
+    InvalidHmacToken,
+
+    /// The digest is not a hex encoded HMAC SHA512
+    #[error("invalid digest")]
+    InvalidDigest,
+
+    /// The digest does not match the payload
+    #[error("hmac does not match")]
+    Mismatch,
+}
+
+/// Verify the HMAC SHA512 of the raw bytes of a webhook payload
+///
+/// `header_value` is the hex encoded digest from the `DIGEST_HEADER` header
+/// of the webhook request.  The digest is compared in constant time.
+///
+/// # Errors
+/// This will return an error in the following cases:
+/// 1. The provided token is invalid
+/// 2. The digest is not a hex encoded HMAC SHA512
+/// 3. The digest does not match the payload
+pub fn verify_hmac_sha512(
+    bytes: &[u8],
+    header_value: &str,
+    hmac_token: &Secret,
+) -> Result<(), VerifyError> {
+    let digest = decode_hex(header_value.trim()).ok_or(VerifyError::InvalidDigest)?;
+    let mut mac = Hmac::<Sha512>::new_from_slice(hmac_token.get_secret().as_bytes())
+        .map_err(|_| VerifyError::InvalidHmacToken)?;
+    mac.update(bytes);
+    mac.verify_slice(&digest).map_err(|_| VerifyError::Mismatch)
+}
+
+/// Decode a hex encoded string, returning `None` if the string is not valid hex
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+/// Verify the HMAC SHA512 of a webhook payload
+///
+/// The digest is first verified against the raw bytes of the payload.  If that
+/// fails, such as when an intermediary re-serialized the payload, the digest
+/// is verified against the canonical JSON serialization of the payload.
+///
+/// Returns `Ok(true)` if either verification succeeds.
+///
+/// # Errors
+/// This could fail if the provided token is invalid
+pub fn verify_canonical(
+    bytes: &[u8],
+    digest: &str,
+    hmac_token: &Secret,
+) -> Result<bool, HmacError> {
+    if verified(verify_hmac_sha512(bytes, digest, hmac_token))? {
+        return Ok(true);
+    }
+
+    // payloads that are not valid JSON cannot be canonicalized
+    let Ok(value) = serde_json::from_slice::<serde_json::Value>(bytes) else {
+        return Ok(false);
+    };
+    let canonical = canonical_json(&value)?;
+    verified(verify_hmac_sha512(&canonical, digest, hmac_token))
+}
+
+/// Convert the result of `verify_hmac_sha512` into whether the digest matched
+const fn verified(result: Result<(), VerifyError>) -> Result<bool, HmacError> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(VerifyError::InvalidHmacToken) => Err(HmacError::InvalidHmacToken),
+        Err(VerifyError::InvalidDigest | VerifyError::Mismatch) => Ok(false),
     }
 }
 
@@ -191,7 +637,7 @@ pub fn hmac_sha512(bytes: &[u8], hmac_token: &Secret) -> Result<String, HmacErro
 ///
 /// This enum defines the current state of sending the event to the configured
 /// webhook.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ValueEnum)]
 pub enum WebhookEventState {
     /// The event has not been sent to the webhook
     Pending,
@@ -223,8 +669,9 @@ pub struct Webhook {
     #[serde(rename(deserialize = "RowKey"), alias = "webhook_id")]
     pub webhook_id: WebhookId,
 
-    /// The webhook url
-    pub url: Url,
+    /// The destination to which events are delivered
+    #[serde(flatten)]
+    pub target: WebhookTarget,
 
     /// The webhook events that should be included in the
     pub event_types: BTreeSet<WebhookEventType>,
@@ -232,24 +679,63 @@ pub struct Webhook {
     /// If provided, the value will be used to generate an HMAC-SHA512 of the
     /// payload, which will be added to the HTTP Headers as `X-Freta-Digest`.
     pub hmac_token: Option<Secret>,
+
+    /// The token replaced by `hmac_token` during a rotation of the HMAC
+    /// token.  Until the rotation is completed, receivers should accept
+    /// payloads signed using either token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hmac_token_previous: Option<Secret>,
+
+    /// If true, events related to an image include the image's `tags`
+    #[serde(default)]
+    pub include_tags: bool,
 }
 
 impl Webhook {
+    /// The HMAC tokens receivers should accept for this webhook, current
+    /// token first
+    ///
+    /// During a rotation of the HMAC token, this includes the previous token.
+    #[must_use]
+    pub fn hmac_tokens(&self) -> Vec<Secret> {
+        self.hmac_token
+            .iter()
+            .chain(&self.hmac_token_previous)
+            .cloned()
+            .collect()
+    }
+
+    /// The URL to which events of the given type are delivered
+    ///
+    /// Returns `None` if events are not delivered via HTTP.
+    ///
+    /// # Errors
+    /// This returns an error if the expanded URL template is not a valid URL
+    pub fn url_for(&self, event_type: &WebhookEventType) -> Result<Option<Url>, url::ParseError> {
+        self.target
+            .url()
+            .map(|url| expand_url_template(url, event_type))
+            .transpose()
+    }
+
     /// Create a new Webhook
     #[must_use]
     pub fn new(
         owner_id: OwnerId,
-        url: Url,
+        target: WebhookTarget,
         event_types: BTreeSet<WebhookEventType>,
         hmac_token: Option<Secret>,
+        include_tags: bool,
     ) -> Self {
         Self {
             last_updated: None,
             owner_id,
             webhook_id: WebhookId::new(),
-            url,
+            target,
             event_types,
             hmac_token,
+            hmac_token_previous: None,
+            include_tags,
         }
     }
 }
@@ -383,7 +869,7 @@ const fn fmt_uuid_v7(millis: u64, random_bytes: [u8; 10]) -> Uuid {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use super::{service::WebhookSubmit, *};
     use std::{thread::sleep, time::Duration};
 
     type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -439,6 +925,7 @@ mod tests {
             event_type: WebhookEventType::ImageCreated,
             timestamp: OffsetDateTime::UNIX_EPOCH,
             image: Some(Uuid::from_u128(0).into()),
+            tags: None,
         };
 
         let hmac = event.hmac_sha512(&Secret::new("testing"))?;
@@ -448,4 +935,164 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_canonical_hmac() -> Result<()> {
+        let token = Secret::new("testing");
+        let event = WebhookEvent {
+            event_id: WebhookEventId(Uuid::from_u128(1)),
+            event_type: WebhookEventType::ImageCreated,
+            timestamp: OffsetDateTime::UNIX_EPOCH,
+            image: Some(Uuid::from_u128(0).into()),
+            tags: Some([("b".into(), "2".into()), ("a".into(), "1".into())].into()),
+        };
+
+        let canonical = event.to_canonical_json()?;
+        assert_eq!(
+            String::from_utf8(canonical.clone())?,
+            r#"{"event_id":"00000000-0000-0000-0000-000000000001","event_type":"image_created","image":"00000000-0000-0000-0000-000000000000","tags":{"a":"1","b":"2"},"timestamp":"1970-01-01T00:00:00Z"}"#
+        );
+
+        let digest = event.hmac_sha512(&token)?;
+        assert!(verify_canonical(&canonical, &digest, &token)?);
+
+        // the payload re-serialized by an intermediary with different key
+        // ordering and whitespace
+        let reserialized = serde_json::to_vec_pretty(&event)?;
+        assert_ne!(reserialized, canonical);
+        assert!(verify_canonical(&reserialized, &digest, &token)?);
+
+        // any other digest is rejected
+        let invalid = hmac_sha512(b"other", &token)?;
+        assert!(!verify_canonical(&canonical, &invalid, &token)?);
+        assert!(!verify_canonical(b"not json", &digest, &token)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_hmac_sha512() -> Result<()> {
+        let token = Secret::new("testing");
+        let digest = hmac_sha512(b"payload", &token)?;
+
+        assert!(verify_hmac_sha512(b"payload", &digest, &token).is_ok());
+        assert!(verify_hmac_sha512(b"payload", &digest.to_uppercase(), &token).is_ok());
+        assert!(matches!(
+            verify_hmac_sha512(b"other payload", &digest, &token),
+            Err(VerifyError::Mismatch)
+        ));
+        assert!(matches!(
+            verify_hmac_sha512(
+                b"payload",
+                &digest.replacen(char::is_alphanumeric, "z", 1),
+                &token
+            ),
+            Err(VerifyError::InvalidDigest)
+        ));
+        assert!(matches!(
+            verify_hmac_sha512(b"payload", "abc", &token),
+            Err(VerifyError::InvalidDigest)
+        ));
+        assert!(matches!(
+            verify_hmac_sha512(b"payload", "00", &token),
+            Err(VerifyError::Mismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_webhook_target() -> Result<()> {
+        // webhooks created prior to the introduction of targets
+        let legacy: WebhookSubmit = serde_json::from_str(
+            r#"{"url": "https://example.com/freta", "hmac_token": null, "event_types": []}"#,
+        )?;
+        let https = WebhookTarget::from(Url::parse("https://example.com/freta")?);
+        assert_eq!(legacy.target, https);
+        assert_eq!(
+            serde_json::to_value(&legacy)?.get("url"),
+            Some(&serde_json::json!("https://example.com/freta"))
+        );
+
+        let service_bus: WebhookTarget = "servicebus://contoso/freta-events".parse()?;
+        assert_eq!(
+            service_bus,
+            WebhookTarget::AzureServiceBus {
+                namespace: "contoso".into(),
+                queue: "freta-events".into(),
+            }
+        );
+        let storage_queue: WebhookTarget = "storagequeue://contoso/freta-events".parse()?;
+        for target in [&https, &service_bus, &storage_queue] {
+            target.validate()?;
+            assert_eq!(&target.to_string().parse::<WebhookTarget>()?, target);
+            let as_json = serde_json::to_string(target)?;
+            assert_eq!(&serde_json::from_str::<WebhookTarget>(&as_json)?, target);
+        }
+        assert!(https.url().is_some());
+        assert!(service_bus.url().is_none());
+
+        for invalid in [
+            "servicebus://abc/queue",
+            "servicebus://contoso/queue name",
+            "storagequeue://Contoso/queue",
+            "storagequeue://contoso/a--b",
+            "https://example.com/{other}",
+        ] {
+            assert!(invalid.parse::<WebhookTarget>()?.validate().is_err());
+        }
+        assert!("servicebus://contoso".parse::<WebhookTarget>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_url_template() -> Result<()> {
+        let template = Url::parse("https://example.com/freta/{event_type}?type={event_type}")?;
+        validate_url_template(&template)?;
+        let expanded = expand_url_template(&template, &WebhookEventType::ImageAnalysisCompleted)?;
+        assert_eq!(
+            expanded.as_str(),
+            "https://example.com/freta/image_analysis_completed?type=image_analysis_completed"
+        );
+
+        let plain = Url::parse("https://example.com/freta")?;
+        validate_url_template(&plain)?;
+        assert_eq!(
+            expand_url_template(&plain, &WebhookEventType::Ping)?.as_str(),
+            plain.as_str()
+        );
+
+        for invalid in [
+            "https://example.com/freta/{image_id}",
+            "https://example.com/freta/{event_type}/{",
+        ] {
+            let invalid = Url::parse(invalid)?;
+            assert!(validate_url_template(&invalid).is_err(), "{invalid}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_tags() -> Result<()> {
+        let event = WebhookEvent {
+            event_id: WebhookEventId(Uuid::from_u128(1)),
+            event_type: WebhookEventType::ImageAnalysisCompleted,
+            timestamp: OffsetDateTime::UNIX_EPOCH,
+            image: Some(Uuid::from_u128(0).into()),
+            tags: None,
+        };
+        assert_eq!(event.tag("team"), None);
+
+        let tags = BTreeMap::from([("team".to_string(), "red".to_string())]);
+        let event = event.with_tags(tags);
+        assert_eq!(event.tag("team"), Some("red"));
+        assert_eq!(event.tag("environment"), None);
+
+        let as_string = serde_json::to_string(&event)?;
+        let decoded: WebhookEvent = serde_json::from_str(&as_string)?;
+        assert_eq!(decoded.tag("team"), Some("red"));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,832 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::{
+    models::webhooks::{
+        verify_canonical, HmacError, WebhookEvent, WebhookEventId, WebhookEventType, DIGEST_HEADER,
+    },
+    Client, Error, ImageId, Result, Secret,
+};
+#[cfg(feature = "receiver")]
+use axum::{
+    async_trait,
+    body::{Bytes, HttpBody},
+    extract::{FromRef, FromRequest},
+    http::{Request, StatusCode},
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use futures::future::{join_all, BoxFuture, FutureExt};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
+    fmt,
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::{self, BufWriter, Error as IoError, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+};
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+/// Errors receiving a webhook event
+#[derive(thiserror::Error, Debug)]
+pub enum ReceiveError {
+    /// The request does not include the `DIGEST_HEADER` header
+    #[error("missing x-freta-digest header")]
+    MissingSignature,
+
+    /// The signature does not match the payload
+    #[error("invalid signature")]
+    InvalidSignature,
+
+    /// Unable to create an HMAC from the provided token
+    #[error("invalid hmac token")]
+    InvalidHmacToken,
+
+    /// The payload is not a webhook event
+    #[error("invalid payload")]
+    InvalidPayload(#[source] serde_json::Error),
+}
+
+impl ReceiveError {
+    /// Returns true if the error is due to the signature of the request, as
+    /// opposed to its payload
+    #[must_use]
+    pub const fn is_unauthorized(&self) -> bool {
+        matches!(self, Self::MissingSignature | Self::InvalidSignature)
+    }
+}
+
+/// Verify the signature of a webhook request
+///
+/// The signature is read from the `DIGEST_HEADER` header, and verified as
+/// described by `verify_canonical`.  `body` must be the raw body of the
+/// request, prior to deserializing it.
+///
+/// The signature is accepted if it was generated using any of
+/// `hmac_tokens`.  While rotating the HMAC token of a webhook, include both
+/// the current and the previous token, as provided by `Webhook::hmac_tokens`,
+/// such that events signed using either token are accepted.
+///
+/// # Errors
+/// This will return an error in the following cases:
+/// 1. The signature header is missing or is not valid UTF-8
+/// 2. The signature does not match the payload using any of the tokens
+/// 3. One of the provided tokens is invalid
+pub fn verify_signature(
+    headers: &reqwest::header::HeaderMap,
+    body: &[u8],
+    hmac_tokens: &[Secret],
+) -> std::result::Result<(), ReceiveError> {
+    let digest = headers
+        .get(DIGEST_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(ReceiveError::MissingSignature)?;
+    for (index, hmac_token) in hmac_tokens.iter().enumerate() {
+        match verify_canonical(body, digest, hmac_token) {
+            Ok(true) => {
+                if index > 0 {
+                    debug!("webhook signature verified using a previous hmac token");
+                }
+                return Ok(());
+            }
+            Ok(false) => {}
+            Err(HmacError::InvalidHmacToken) => return Err(ReceiveError::InvalidHmacToken),
+            Err(HmacError::Serialization(err)) => return Err(ReceiveError::InvalidPayload(err)),
+        }
+    }
+    Err(ReceiveError::InvalidSignature)
+}
+
+/// Parse the body of a webhook request
+///
+/// # Errors
+/// This will return an error if the body is not a webhook event
+pub fn parse_event(body: &[u8]) -> std::result::Result<WebhookEvent, ReceiveError> {
+    serde_json::from_slice(body).map_err(ReceiveError::InvalidPayload)
+}
+
+/// Verify the signature of a webhook request using `verify_signature`, then
+/// parse the event
+///
+/// If no HMAC tokens are provided, the signature is not verified.
+///
+/// # Errors
+/// This will return an error if the signature cannot be verified or the body
+/// is not a webhook event
+pub fn receive(
+    headers: &reqwest::header::HeaderMap,
+    body: &[u8],
+    hmac_tokens: &[Secret],
+) -> std::result::Result<WebhookEvent, ReceiveError> {
+    if !hmac_tokens.is_empty() {
+        verify_signature(headers, body, hmac_tokens)?;
+    }
+    parse_event(body)
+}
+
+/// Extract verified webhook events from requests received by `axum`
+///
+/// Requests whose signature cannot be verified are rejected with `401
+/// Unauthorized`, and requests that are not webhook events are rejected with
+/// `400 Bad Request`.  The HMAC tokens used to verify the signatures are taken
+/// from the state of the router, as `Vec<Secret>`, such that both tokens can
+/// be accepted while rotating the HMAC token.  If there are no tokens,
+/// signatures are not verified.
+///
+/// ```rust,no_run
+/// # use axum::{routing::post, Router};
+/// # use freta::{models::webhooks::receiver::VerifiedEvent, Secret};
+/// async fn handler(VerifiedEvent(event): VerifiedEvent) -> &'static str {
+///     println!("received {}", event.event_id);
+///     "thanks"
+/// }
+///
+/// let tokens = vec![Secret::new("current token"), Secret::new("previous token")];
+/// let app: Router = Router::new()
+///     .route("/webhook", post(handler))
+///     .with_state(tokens);
+/// ```
+#[cfg(feature = "receiver")]
+#[derive(Debug)]
+pub struct VerifiedEvent(pub WebhookEvent);
+
+#[cfg(feature = "receiver")]
+#[async_trait]
+impl<S, B> FromRequest<S, B> for VerifiedEvent
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    S: Send + Sync,
+    Vec<Secret>: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request(
+        req: Request<B>,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let tokens = Vec::<Secret>::from_ref(state);
+        let headers = req.headers().clone();
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        receive(&headers, &body, &tokens).map(Self).map_err(|err| {
+            warn!("rejecting webhook delivery: {err}");
+            let status = if err.is_unauthorized() {
+                StatusCode::UNAUTHORIZED
+            } else {
+                StatusCode::BAD_REQUEST
+            };
+            (status, err.to_string()).into_response()
+        })
+    }
+}
+
+/// Default number of event ids retained by a `DeliveryDeduplicator`
+pub const DEFAULT_DEDUPLICATION_CAPACITY: usize = 10_000;
+
+/// Default number of handlers a `Router` runs concurrently
+pub const DEFAULT_ROUTER_CONCURRENCY: usize = 16;
+
+/// The event ids retained by a `DeliveryDeduplicator`, in the order they were
+/// first seen
+#[derive(Debug, Default)]
+struct SeenEvents {
+    /// event ids, oldest first
+    order: VecDeque<WebhookEventId>,
+    /// event ids, for fast lookups
+    ids: HashSet<WebhookEventId>,
+}
+
+/// Detects repeated deliveries of webhook events
+///
+/// The service retries deliveries that fail or time out, so receivers may be
+/// sent the same event more than once.  The deduplicator retains the most
+/// recently seen event ids, such that handlers can skip events they already
+/// processed.
+///
+/// If a path is provided, new event ids are appended to the file by a
+/// background thread, such that duplicates are detected across restarts of
+/// the receiver without blocking the handlers.  The file is compacted to the
+/// retained event ids once it grows to twice the capacity.
+///
+/// ```rust
+/// # use freta::models::webhooks::{receiver::DeliveryDeduplicator, WebhookEventId};
+/// let deduplicator = DeliveryDeduplicator::new(100);
+/// let event_id = WebhookEventId::new();
+/// assert!(deduplicator.check_and_record(event_id));
+/// assert!(!deduplicator.check_and_record(event_id));
+/// ```
+#[derive(Debug)]
+pub struct DeliveryDeduplicator {
+    /// maximum number of event ids retained
+    capacity: usize,
+    /// sends new event ids to the thread persisting them
+    persistence: Option<Persistence>,
+    /// the seen event ids
+    seen: Mutex<SeenEvents>,
+}
+
+/// Background thread appending new event ids to the file used to persist
+/// them
+#[derive(Debug)]
+struct Persistence {
+    /// sends new event ids to `writer`.  dropped to stop the thread
+    sender: Option<Sender<WebhookEventId>>,
+    /// the thread persisting the event ids
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Drop for Persistence {
+    /// Wait for the event ids sent so far to be persisted
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                warn!("unable to save seen webhook events: writer panicked");
+            }
+        }
+    }
+}
+
+impl DeliveryDeduplicator {
+    /// Create a deduplicator that retains up to `capacity` event ids in
+    /// memory
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            persistence: None,
+            seen: Mutex::new(SeenEvents::default()),
+        }
+    }
+
+    /// Create a deduplicator that persists the seen event ids to `path`
+    ///
+    /// If the file exists, the previously seen event ids are loaded from it.
+    /// The file contains one JSON encoded event id per line.
+    ///
+    /// # Errors
+    /// This will return an error if the file exists but cannot be read or
+    /// parsed, or if the thread persisting the event ids cannot be started
+    pub fn with_persistence<P>(capacity: usize, path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut deduplicator = Self::new(capacity);
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let seen = deduplicator
+            .seen
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        for line in contents.lines().filter(|line| !line.is_empty()) {
+            let id =
+                serde_json::from_str(line).map_err(|e| IoError::new(ErrorKind::InvalidData, e))?;
+            insert_seen(seen, deduplicator.capacity, id);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let writer = PersistenceWriter {
+            path: path.to_path_buf(),
+            capacity: deduplicator.capacity,
+            retained: seen.order.clone(),
+        };
+        let handle = thread::Builder::new()
+            .name("freta-dedup".into())
+            .spawn(move || writer.run(&receiver))?;
+
+        deduplicator.persistence = Some(Persistence {
+            sender: Some(sender),
+            writer: Some(handle),
+        });
+        Ok(deduplicator)
+    }
+
+    /// Record that an event was delivered
+    ///
+    /// Returns true if the event was not previously seen, and as such should
+    /// be processed.
+    ///
+    /// The event id is persisted in the background.  Failing to persist the
+    /// seen event ids is logged rather than treated as an error, as the event
+    /// ids are still retained in memory.
+    pub fn check_and_record(&self, event_id: WebhookEventId) -> bool {
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        if !insert_seen(&mut seen, self.capacity, event_id) {
+            return false;
+        }
+
+        // sent while holding the lock, such that the ids are persisted in the
+        // order they were seen
+        let sender = self
+            .persistence
+            .as_ref()
+            .and_then(|persistence| persistence.sender.as_ref());
+        if let Some(sender) = sender {
+            if sender.send(event_id).is_err() {
+                warn!("unable to save seen webhook events: writer stopped");
+            }
+        }
+
+        true
+    }
+}
+
+impl Default for DeliveryDeduplicator {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEDUPLICATION_CAPACITY)
+    }
+}
+
+/// Predicates selecting the events dispatched to a handler by a `Router`
+///
+/// Events must match every predicate that is specified.  A route without
+/// predicates matches every event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Route {
+    /// event types to match, or all event types if `None`
+    event_types: Option<BTreeSet<WebhookEventType>>,
+    /// images to match, or all images if `None`
+    images: Option<BTreeSet<ImageId>>,
+    /// tags the image must have, with the specified values
+    tags: BTreeMap<String, String>,
+}
+
+impl Route {
+    /// Create a route that matches every event
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match events of the specified type.  This may be specified multiple
+    /// times to match multiple event types.
+    #[must_use]
+    pub fn event_type(mut self, event_type: WebhookEventType) -> Self {
+        self.event_types
+            .get_or_insert_with(BTreeSet::new)
+            .insert(event_type);
+        self
+    }
+
+    /// Match events for any of the specified images
+    #[must_use]
+    pub fn images<I>(mut self, images: I) -> Self
+    where
+        I: IntoIterator<Item = ImageId>,
+    {
+        self.images.get_or_insert_with(BTreeSet::new).extend(images);
+        self
+    }
+
+    /// Match events for images with the tag `key` set to `value`.  This may
+    /// be specified multiple times to require multiple tags.
+    #[must_use]
+    pub fn tag<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Does the event match the predicates that do not require the tags of
+    /// the image
+    fn matches_event(&self, event: &WebhookEvent) -> bool {
+        let event_type = self
+            .event_types
+            .as_ref()
+            .is_none_or(|types| types.contains(&event.event_type));
+        let image = self
+            .images
+            .as_ref()
+            .is_none_or(|images| event.image.is_some_and(|id| images.contains(&id)));
+        event_type && image
+    }
+
+    /// Do the tags of the image match the tag predicates
+    fn matches_tags(&self, tags: &BTreeMap<String, String>) -> bool {
+        self.tags
+            .iter()
+            .all(|(key, value)| tags.get(key) == Some(value))
+    }
+}
+
+/// Handles the events dispatched by a `Router`
+type Handler = Box<dyn Fn(WebhookEvent) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// Dispatches webhook events to the handlers whose `Route` matches the event,
+/// such as when a shared receiver serves multiple teams
+///
+/// Routes matching on tags use the tags included in the event.  If the
+/// webhook was not configured with `include_tags`, the tags are retrieved
+/// using `Client::images_get` when the router has a client, at most once per
+/// event and only if a route requires them.
+///
+/// At most `concurrency` handlers run at once across all dispatched events.
+///
+/// ```rust,no_run
+/// # use freta::{models::webhooks::{receiver::{Route, Router}, WebhookEvent, WebhookEventType}, Client, Result};
+/// # async fn example(client: Client, event: WebhookEvent) -> Result<()> {
+/// let router = Router::new()
+///     .with_client(client)
+///     .route(
+///         Route::new()
+///             .event_type(WebhookEventType::ImageAnalysisCompleted)
+///             .tag("team", "red"),
+///         |event| async move {
+///             println!("red team image analyzed: {:?}", event.image);
+///             Ok(())
+///         },
+///     );
+/// let handled = router.dispatch(&event).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Router {
+    /// registered routes and their handlers, in the order they were added
+    routes: Vec<(Route, Handler)>,
+    /// client used to retrieve the tags of images
+    client: Option<Client>,
+    /// limits the number of handlers running at once
+    permits: Semaphore,
+}
+
+impl Router {
+    /// Create a router without any routes
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_concurrency(DEFAULT_ROUTER_CONCURRENCY)
+    }
+
+    /// Create a router that runs at most `concurrency` handlers at once
+    #[must_use]
+    pub fn with_concurrency(concurrency: usize) -> Self {
+        Self {
+            routes: vec![],
+            client: None,
+            permits: Semaphore::new(concurrency.max(1)),
+        }
+    }
+
+    /// Retrieve the tags of images using `client` for events that do not
+    /// include them
+    #[must_use]
+    pub fn with_client(self, client: Client) -> Self {
+        Self {
+            client: Some(client),
+            ..self
+        }
+    }
+
+    /// Dispatch events matching `route` to `handler`
+    #[must_use]
+    pub fn route<F, Fut>(mut self, route: Route, handler: F) -> Self
+    where
+        F: Fn(WebhookEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.routes
+            .push((route, Box::new(move |event| handler(event).boxed())));
+        self
+    }
+
+    /// Get the tags of the image that triggered the event
+    async fn image_tags(&self, event: &WebhookEvent) -> Result<BTreeMap<String, String>> {
+        if let Some(tags) = &event.tags {
+            return Ok(tags.clone());
+        }
+        match (&self.client, event.image) {
+            (Some(client), Some(image_id)) => Ok(client.images_get(image_id).await?.tags),
+            _ => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// Dispatch an event to every handler whose route matches the event,
+    /// running the handlers concurrently
+    ///
+    /// Returns the number of handlers the event was dispatched to.
+    ///
+    /// # Errors
+    /// This will return an error if retrieving the tags of the image fails,
+    /// or the first error returned by a handler.  Every matching handler is
+    /// run, even if another handler fails.
+    pub async fn dispatch(&self, event: &WebhookEvent) -> Result<usize> {
+        let mut tags = None;
+        let mut handlers = vec![];
+        for (route, handler) in &self.routes {
+            if !route.matches_event(event) {
+                continue;
+            }
+            if !route.tags.is_empty() {
+                if tags.is_none() {
+                    tags = Some(self.image_tags(event).await?);
+                }
+                if !tags
+                    .as_ref()
+                    .is_some_and(|image_tags| route.matches_tags(image_tags))
+                {
+                    continue;
+                }
+            }
+            handlers.push(handler);
+        }
+        debug!(
+            "dispatching event {} to {} handlers",
+            event.event_id,
+            handlers.len()
+        );
+
+        let results = join_all(handlers.iter().map(|handler| async {
+            let _permit = self
+                .permits
+                .acquire()
+                .await
+                .map_err(|e| Error::Other("dispatching webhook event", e.to_string()))?;
+            handler(event.clone()).await
+        }))
+        .await;
+
+        let count = results.len();
+        let mut first_error = None;
+        for err in results.into_iter().filter_map(std::result::Result::err) {
+            warn!("webhook event handler failed: {}: {err}", event.event_id);
+            first_error.get_or_insert(err);
+        }
+        first_error.map_or(Ok(count), Err)
+    }
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field(
+                "routes",
+                &self
+                    .routes
+                    .iter()
+                    .map(|(route, _)| route)
+                    .collect::<Vec<_>>(),
+            )
+            .field("client", &self.client)
+            .field("permits", &self.permits)
+            .finish()
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Add an event id to the seen events, evicting the oldest event ids beyond
+/// `capacity`
+///
+/// Returns true if the event id was not previously seen
+fn insert_seen(seen: &mut SeenEvents, capacity: usize, event_id: WebhookEventId) -> bool {
+    if !seen.ids.insert(event_id) {
+        return false;
+    }
+    seen.order.push_back(event_id);
+    while seen.order.len() > capacity {
+        if let Some(oldest) = seen.order.pop_front() {
+            seen.ids.remove(&oldest);
+        }
+    }
+    true
+}
+
+/// Appends the event ids seen by a `DeliveryDeduplicator` to a file
+struct PersistenceWriter {
+    /// file used to persist the seen event ids
+    path: PathBuf,
+    /// maximum number of event ids retained
+    capacity: usize,
+    /// event ids retained by the deduplicator, oldest first
+    retained: VecDeque<WebhookEventId>,
+}
+
+impl PersistenceWriter {
+    /// Persist the event ids received from `receiver` until the deduplicator
+    /// is dropped
+    ///
+    /// Event ids received together are written at once.  The file is
+    /// compacted when it starts, and each time it grows to twice the
+    /// capacity.
+    fn run(mut self, receiver: &Receiver<WebhookEventId>) {
+        let mut file = None;
+        let mut lines = 0;
+        while let Ok(first) = receiver.recv() {
+            let ids: Vec<_> = std::iter::once(first).chain(receiver.try_iter()).collect();
+            for id in &ids {
+                self.retained.push_back(*id);
+                while self.retained.len() > self.capacity {
+                    self.retained.pop_front();
+                }
+            }
+
+            if file.is_none() || lines + ids.len() > self.capacity.saturating_mul(2) {
+                lines = self.retained.len();
+                file = self.compact().map_err(|err| self.warn(&err)).ok();
+            } else if let Some(writer) = &mut file {
+                lines += ids.len();
+                if let Err(err) = append(writer, &ids) {
+                    self.warn(&err);
+                    file = None;
+                }
+            }
+        }
+    }
+
+    /// Replace the file with the retained event ids, atomically such that a
+    /// crash does not leave a partially written file, then open it for
+    /// appending
+    fn compact(&self) -> io::Result<File> {
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut writer = File::create(&tmp)?;
+        append(&mut writer, self.retained.iter())?;
+        fs::rename(&tmp, &self.path)?;
+        OpenOptions::new().append(true).open(&self.path)
+    }
+
+    /// Log a failure to persist the event ids
+    fn warn(&self, err: &IoError) {
+        warn!(
+            "unable to save seen webhook events: {}: {err}",
+            self.path.display()
+        );
+    }
+}
+
+/// Append event ids to `file`, one per line
+fn append<'a, I>(file: &mut File, ids: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = &'a WebhookEventId>,
+{
+    let mut writer = BufWriter::new(file);
+    for id in ids {
+        serde_json::to_writer(&mut writer, id)
+            .map_err(|e| IoError::new(ErrorKind::InvalidData, e))?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: WebhookEventType, image: Option<ImageId>) -> WebhookEvent {
+        WebhookEvent::new(event_type, time::OffsetDateTime::now_utc(), image)
+    }
+
+    #[test]
+    fn test_receive() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::models::webhooks::hmac_sha512;
+        use reqwest::header::{HeaderMap, HeaderValue};
+
+        let token = Secret::new("hmac token");
+        let sent = event(WebhookEventType::Ping, None);
+        let body = sent.to_canonical_json()?;
+
+        let mut headers = HeaderMap::new();
+        assert!(matches!(
+            receive(&headers, &body, &[token.clone()]),
+            Err(ReceiveError::MissingSignature)
+        ));
+        assert_eq!(receive(&headers, &body, &[])?.event_id, sent.event_id);
+
+        headers.insert(DIGEST_HEADER, HeaderValue::from_str(&"0".repeat(128))?);
+        let err = verify_signature(&headers, &body, &[token.clone()]).err();
+        assert!(err.is_some_and(|err| err.is_unauthorized()));
+
+        let digest = hmac_sha512(&body, &token)?;
+        headers.insert(DIGEST_HEADER, HeaderValue::from_str(&digest)?);
+        assert_eq!(
+            receive(&headers, &body, &[token.clone()])?.event_id,
+            sent.event_id
+        );
+
+        // during a rotation, events signed using the previous token are
+        // accepted
+        let rotated = Secret::new("new token");
+        assert!(verify_signature(&headers, &body, &[rotated.clone()]).is_err());
+        assert!(verify_signature(&headers, &body, &[rotated, token]).is_ok());
+
+        assert!(matches!(
+            parse_event(b"{}"),
+            Err(ReceiveError::InvalidPayload(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_route_matches() {
+        let image_id = ImageId::new();
+        let completed = event(WebhookEventType::ImageAnalysisCompleted, Some(image_id));
+        let ping = event(WebhookEventType::Ping, None);
+
+        assert!(Route::new().matches_event(&ping));
+
+        let route = Route::new()
+            .event_type(WebhookEventType::ImageAnalysisCompleted)
+            .event_type(WebhookEventType::ImageAnalysisFailed);
+        assert!(route.matches_event(&completed));
+        assert!(!route.matches_event(&ping));
+
+        let route = Route::new().images([image_id]);
+        assert!(route.matches_event(&completed));
+        assert!(!route.matches_event(&ping));
+        assert!(!Route::new()
+            .images([ImageId::new()])
+            .matches_event(&completed));
+
+        let route = Route::new().tag("team", "red");
+        let tags = BTreeMap::from([("team".to_string(), "red".to_string())]);
+        assert!(route.matches_tags(&tags));
+        assert!(!route.matches_tags(&BTreeMap::new()));
+        assert!(!Route::new().tag("team", "blue").matches_tags(&tags));
+    }
+
+    #[tokio::test]
+    async fn test_router_dispatch() -> Result<()> {
+        let image_id = ImageId::new();
+        let tags = BTreeMap::from([("team".to_string(), "red".to_string())]);
+        let router = Router::new()
+            .route(Route::new(), |_| async { Ok(()) })
+            .route(Route::new().tag("team", "red"), |_| async { Ok(()) })
+            .route(Route::new().tag("team", "blue"), |_| async {
+                Err(Error::Other("unexpected dispatch", String::new()))
+            });
+
+        let tagged = event(WebhookEventType::ImageCreated, Some(image_id)).with_tags(tags);
+        assert_eq!(router.dispatch(&tagged).await?, 2);
+
+        // without a client, events without tags do not match tag routes
+        let untagged = event(WebhookEventType::ImageCreated, Some(image_id));
+        assert_eq!(router.dispatch(&untagged).await?, 1);
+
+        let failing = Router::new().route(Route::new(), |_| async {
+            Err(Error::Other("handler failed", String::new()))
+        });
+        assert!(failing.dispatch(&untagged).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_eviction() {
+        let deduplicator = DeliveryDeduplicator::new(2);
+        let ids = [
+            WebhookEventId::new(),
+            WebhookEventId::new(),
+            WebhookEventId::new(),
+        ];
+        for id in ids {
+            assert!(deduplicator.check_and_record(id));
+        }
+        assert!(!deduplicator.check_and_record(ids[2]));
+        assert!(!deduplicator.check_and_record(ids[1]));
+        // the oldest event id was evicted
+        assert!(deduplicator.check_and_record(ids[0]));
+    }
+
+    #[test]
+    fn test_persistence() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("freta-dedup-{}.json", WebhookEventId::new()));
+        let event_id = WebhookEventId::new();
+
+        let first = DeliveryDeduplicator::with_persistence(10, &path)?;
+        assert!(first.check_and_record(event_id));
+        // dropping the deduplicator waits for the event ids to be persisted
+        drop(first);
+
+        let second = DeliveryDeduplicator::with_persistence(10, &path)?;
+        assert!(!second.check_and_record(event_id));
+
+        // the file is compacted to the retained event ids
+        for _ in 0..30 {
+            assert!(second.check_and_record(WebhookEventId::new()));
+        }
+        drop(second);
+        let lines = fs::read_to_string(&path)?.lines().count();
+        fs::remove_file(&path)?;
+        assert!(lines <= 20, "{lines}");
+        Ok(())
+    }
+}
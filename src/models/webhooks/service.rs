@@ -1,32 +1,191 @@
 // Copyright (C) Microsoft Corporation. All rights reserved.
 
 use crate::{
-    models::webhooks::{Webhook, WebhookEventId, WebhookEventType, WebhookLog},
+    models::webhooks::{
+        Webhook, WebhookEventId, WebhookEventState, WebhookEventType, WebhookLog, WebhookTarget,
+    },
     Secret,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
-use url::Url;
+use time::OffsetDateTime;
 
 /// Web request to create or update a webhook
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WebhookSubmit {
-    /// The webhook url
-    pub url: Url,
+    /// The destination to which events are delivered
+    #[serde(flatten)]
+    pub target: WebhookTarget,
 
     /// If provided, the value will be used to generate an HMAC-SHA512 of the
     /// payload, which will be added to the HTTP Headers as `X-Freta-Digest`.
     pub hmac_token: Option<Secret>,
 
+    /// The token being replaced by `hmac_token` while rotating the HMAC
+    /// token.  Setting this to `None` completes the rotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hmac_token_previous: Option<Secret>,
+
     /// The webhook events that should be included in the
     pub event_types: BTreeSet<WebhookEventType>,
+
+    /// If true, events related to an image include the image's `tags`
+    #[serde(default)]
+    pub include_tags: bool,
 }
 
-/// Request to list webhooks
+impl WebhookSubmit {
+    /// Create a request for a webhook that delivers the specified events to
+    /// `target`, without an HMAC token and without image tags
+    ///
+    /// ```
+    /// # use freta::{models::webhooks::{service::WebhookSubmit, WebhookEventType}, Secret};
+    /// # use std::collections::BTreeSet;
+    /// # fn main() -> Result<(), url::ParseError> {
+    /// let url = url::Url::parse("https://example.com/freta")?;
+    /// let request = WebhookSubmit::new(url, BTreeSet::from([WebhookEventType::ImageCreated]))
+    ///     .with_hmac_token(Secret::new("my-token"))
+    ///     .with_include_tags(true);
+    /// assert!(request.include_tags);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn new<T>(target: T, event_types: BTreeSet<WebhookEventType>) -> Self
+    where
+        T: Into<WebhookTarget>,
+    {
+        Self {
+            target: target.into(),
+            hmac_token: None,
+            hmac_token_previous: None,
+            event_types,
+            include_tags: false,
+        }
+    }
+
+    /// Sign the events delivered to the webhook using the token
+    #[must_use]
+    pub fn with_hmac_token(self, hmac_token: Secret) -> Self {
+        Self {
+            hmac_token: Some(hmac_token),
+            ..self
+        }
+    }
+
+    /// Include the tags of the image in events related to an image
+    #[must_use]
+    pub const fn with_include_tags(self, include_tags: bool) -> Self {
+        Self {
+            include_tags,
+            ..self
+        }
+    }
+
+    /// Accept events signed using the token while rotating the HMAC token
+    #[must_use]
+    pub fn with_hmac_token_previous(self, hmac_token_previous: Secret) -> Self {
+        Self {
+            hmac_token_previous: Some(hmac_token_previous),
+            ..self
+        }
+    }
+
+    /// Keep the previous HMAC token of `webhook` if it is rotating its HMAC
+    /// token, unless the request sets a previous token
+    ///
+    /// Requests to update a webhook replace the whole webhook, such that
+    /// updates during a rotation would otherwise complete the rotation.
+    #[must_use]
+    pub fn keeping_rotation(self, webhook: &Webhook) -> Self {
+        Self {
+            hmac_token_previous: self
+                .hmac_token_previous
+                .or_else(|| webhook.hmac_token_previous.clone()),
+            ..self
+        }
+    }
+}
+
+/// Request to send a synthetic event to a webhook
 #[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookPingRequest {
+    /// The type of the synthetic event
+    pub event_type: WebhookEventType,
+}
+
+impl WebhookPingRequest {
+    /// Create a request to send a synthetic event of the specified type
+    ///
+    /// ```
+    /// # use freta::models::webhooks::{service::WebhookPingRequest, WebhookEventType};
+    /// let request = WebhookPingRequest::new(WebhookEventType::ImageAnalysisCompleted);
+    /// assert_eq!(request.event_type, WebhookEventType::ImageAnalysisCompleted);
+    /// ```
+    #[must_use]
+    pub const fn new(event_type: WebhookEventType) -> Self {
+        Self { event_type }
+    }
+}
+
+/// Request to list webhooks
+///
+/// By default, every webhook is included.
+///
+/// ```
+/// # use freta::models::webhooks::{service::WebhooksListRequest, WebhookEventType};
+/// let request = WebhooksListRequest::default()
+///     .with_url_contains("example.com")
+///     .with_event_type(WebhookEventType::ImageDeleted);
+/// assert_eq!(request.url_contains.as_deref(), Some("example.com"));
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WebhooksListRequest {
     /// The continuation value used for paging
     pub continuation: Option<String>,
+
+    /// If provided, only include webhooks whose target contains this value
+    pub url_contains: Option<String>,
+
+    /// If provided, only include webhooks that monitor this event type
+    pub event_type: Option<WebhookEventType>,
+}
+
+impl WebhooksListRequest {
+    /// Only include webhooks whose target contains the value
+    #[must_use]
+    pub fn with_url_contains<S>(self, url_contains: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            url_contains: Some(url_contains.into()),
+            ..self
+        }
+    }
+
+    /// Only include webhooks that monitor the event type
+    #[must_use]
+    pub const fn with_event_type(self, event_type: WebhookEventType) -> Self {
+        Self {
+            event_type: Some(event_type),
+            ..self
+        }
+    }
+
+    /// Does the webhook match the filters of the request
+    #[must_use]
+    pub fn matches(&self, webhook: &Webhook) -> bool {
+        let url_matches = self
+            .url_contains
+            .as_ref()
+            .is_none_or(|url_contains| webhook.target.to_string().contains(url_contains.as_str()));
+        let event_type_matches = self
+            .event_type
+            .as_ref()
+            .is_none_or(|event_type| webhook.event_types.contains(event_type));
+        url_matches && event_type_matches
+    }
 }
 
 /// Response to listing webhooks
@@ -45,10 +204,94 @@ pub struct WebhooksListResponse {
 pub struct WebhookBoolResponse(pub bool);
 
 /// Request to list webhook event logs for a specific webhook
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// By default, every event is included.
+///
+/// ```
+/// # use freta::models::webhooks::{service::WebhookLogListRequest, WebhookEventState, WebhookEventType};
+/// let request = WebhookLogListRequest::default()
+///     .with_event_type(WebhookEventType::ImageAnalysisCompleted)
+///     .with_state(WebhookEventState::Failure);
+/// assert!(request.continuation.is_none());
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WebhookLogListRequest {
     /// The continuation value used for paging
     pub continuation: Option<String>,
+
+    /// If provided, only include events of this type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<WebhookEventType>,
+
+    /// If provided, only include events in this delivery state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<WebhookEventState>,
+
+    /// If provided, only include events that occurred at or after this time
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub since: Option<OffsetDateTime>,
+
+    /// If provided, only include events that occurred before this time
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub until: Option<OffsetDateTime>,
+}
+
+impl WebhookLogListRequest {
+    /// Only include events of the type
+    #[must_use]
+    pub const fn with_event_type(self, event_type: WebhookEventType) -> Self {
+        Self {
+            event_type: Some(event_type),
+            ..self
+        }
+    }
+
+    /// Only include events in the delivery state
+    #[must_use]
+    pub const fn with_state(self, state: WebhookEventState) -> Self {
+        Self {
+            state: Some(state),
+            ..self
+        }
+    }
+
+    /// Only include events that occurred at or after the time
+    #[must_use]
+    pub const fn with_since(self, since: OffsetDateTime) -> Self {
+        Self {
+            since: Some(since),
+            ..self
+        }
+    }
+
+    /// Only include events that occurred before the time
+    #[must_use]
+    pub const fn with_until(self, until: OffsetDateTime) -> Self {
+        Self {
+            until: Some(until),
+            ..self
+        }
+    }
+
+    /// Does the event log entry match the filters of the request
+    #[must_use]
+    pub fn matches(&self, log: &WebhookLog) -> bool {
+        let timestamp = log.event.timestamp;
+        self.event_type
+            .as_ref()
+            .is_none_or(|event_type| &log.event.event_type == event_type)
+            && self.state.as_ref().is_none_or(|state| &log.state == state)
+            && self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp < until)
+    }
 }
 
 /// Response to listing webhook event logs
@@ -68,3 +311,65 @@ pub struct WebhookEventReplayRequest {
     /// Webhook Event ID
     pub webhook_event_id: WebhookEventId,
 }
+
+impl WebhookEventReplayRequest {
+    /// Create a request to replay the specified event
+    ///
+    /// ```
+    /// # use freta::models::webhooks::{service::WebhookEventReplayRequest, WebhookEventId};
+    /// let webhook_event_id = WebhookEventId::new();
+    /// let request = WebhookEventReplayRequest::new(webhook_event_id);
+    /// assert_eq!(request.webhook_event_id, webhook_event_id);
+    /// ```
+    #[must_use]
+    pub const fn new(webhook_event_id: WebhookEventId) -> Self {
+        Self { webhook_event_id }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OwnerId;
+
+    #[test]
+    fn test_update_during_rotation() -> Result<(), url::ParseError> {
+        let url = url::Url::parse("https://example.com/freta")?;
+        let event_types = BTreeSet::from([WebhookEventType::ImageCreated]);
+        let webhook = Webhook {
+            hmac_token_previous: Some(Secret::new("previous")),
+            ..Webhook::new(
+                OwnerId::samples(),
+                url.clone().into(),
+                event_types.clone(),
+                Some(Secret::new("current")),
+                false,
+            )
+        };
+
+        let update = WebhookSubmit::new(url.clone(), event_types.clone())
+            .with_hmac_token(Secret::new("current"))
+            .with_include_tags(true)
+            .keeping_rotation(&webhook);
+        assert_eq!(
+            update.hmac_token_previous.as_ref().map(Secret::get_secret),
+            Some("previous")
+        );
+
+        let update = WebhookSubmit::new(url.clone(), event_types.clone())
+            .with_hmac_token_previous(Secret::new("other"))
+            .keeping_rotation(&webhook);
+        assert_eq!(
+            update.hmac_token_previous.as_ref().map(Secret::get_secret),
+            Some("other")
+        );
+
+        let webhook = Webhook {
+            hmac_token_previous: None,
+            ..webhook
+        };
+        let update = WebhookSubmit::new(url, event_types).keeping_rotation(&webhook);
+        assert!(update.hmac_token_previous.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,620 @@
+// Copyright (C) Microsoft Corporation. All rights reserved.
+
+use crate::{
+    models::{
+        analysis::{
+            hook::{Check, Hook},
+            memory::VirtualAddress,
+            report::{
+                self, AnalysisInfo, Cred, File, KernelModules, MemoryInfo, Mode, Module,
+                ModuleSource, ModuleSymbol, Report, Task, TaskSource, UtsName,
+            },
+            symbols::Symbol,
+        },
+        base::{AnalysisProgress, Image, ImageFormat, ImageId, ImageState, OwnerId},
+        baseline::{BaselineSummary, Changes, DriftReport},
+        collections::{
+            Collection, CollectionAddImage, CollectionCreate, CollectionsListRequest,
+            CollectionsListResponse,
+        },
+        cost::{CostEntry, CostRates, ImageUsage},
+        policies::{Action, Condition, Policy, PolicyAction, PolicyNotification, PolicySet},
+        schedules::{Schedule, ScheduleRun, ScheduledAction},
+        service::{
+            ImageCreate, ImageDeleteResponse, ImageList, ImageReanalyze, ImageReanalyzeResponse,
+            ImageUpdate, ImageUploadCompleteResponse, ImagesListResponse, Info, UserConfig,
+            UserConfigUpdateResponse,
+        },
+        tokens::{
+            Token, TokenCreate, TokenCreateResponse, TokenRevokeResponse, TokensListRequest,
+            TokensListResponse,
+        },
+        webhooks::{
+            service::{
+                WebhookBoolResponse, WebhookEventReplayRequest, WebhookLogListRequest,
+                WebhookLogListResponse, WebhookPingRequest, WebhookSubmit, WebhooksListRequest,
+                WebhooksListResponse,
+            },
+            Webhook, WebhookEvent, WebhookEventState, WebhookEventType, WebhookLog, WebhookTarget,
+        },
+    },
+    Secret, MODELS_VERSION,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Map;
+use std::collections::{BTreeMap, BTreeSet};
+use time::{Duration, OffsetDateTime};
+use url::Url;
+use uuid::Uuid;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+/// Snapshot the serialized form of `value`, and ensure deserializing it does
+/// not lose any information
+macro_rules! assert_wire_format {
+    ($name:literal, $value:expr) => {{
+        let value = $value;
+        assert_round_trip(&value)?;
+        insta::assert_json_snapshot!($name, value);
+    }};
+}
+
+/// Ensure the serialized form of `value` is unchanged by deserializing and
+/// serializing it again
+fn assert_round_trip<T>(value: &T) -> TestResult
+where
+    T: Serialize + DeserializeOwned,
+{
+    let expected = serde_json::to_value(value)?;
+    let parsed: T = serde_json::from_value(expected.clone())?;
+    assert_eq!(serde_json::to_value(parsed)?, expected);
+    Ok(())
+}
+
+fn timestamp() -> Result<OffsetDateTime, time::error::ComponentRange> {
+    OffsetDateTime::from_unix_timestamp(1_700_000_000)
+}
+
+const fn owner_id() -> OwnerId {
+    OwnerId::User {
+        tenant: Uuid::from_u128(1),
+        oid: Uuid::from_u128(2),
+    }
+}
+
+fn image_id() -> ImageId {
+    ImageId::from(Uuid::from_u128(3))
+}
+
+fn tags() -> BTreeMap<String, String> {
+    BTreeMap::from([("team".to_string(), "red".to_string())])
+}
+
+fn image() -> Result<Image, Box<dyn std::error::Error>> {
+    Ok(Image {
+        last_updated: Some(timestamp()?),
+        owner_id: owner_id(),
+        image_id: image_id(),
+        state: ImageState::Completed,
+        format: ImageFormat::Lime,
+        error: None,
+        image_url: Some(Url::parse("https://example.com/images/image.lime?sig=x")?),
+        artifacts_url: Some(Url::parse("https://example.com/artifacts?sig=x")?),
+        tags: tags(),
+        shareable: true,
+        analysis_version: Some("1.2.3".to_string()),
+        progress: None,
+        deleted_at: None,
+    })
+}
+
+fn target() -> Result<WebhookTarget, url::ParseError> {
+    Ok(WebhookTarget::Https {
+        url: Url::parse("https://example.com/freta")?,
+    })
+}
+
+fn event_types() -> BTreeSet<WebhookEventType> {
+    BTreeSet::from([
+        WebhookEventType::ImageCreated,
+        WebhookEventType::ImageAnalysisCompleted,
+    ])
+}
+
+fn webhook() -> Result<Webhook, Box<dyn std::error::Error>> {
+    Ok(Webhook {
+        last_updated: Some(timestamp()?),
+        owner_id: owner_id(),
+        webhook_id: "00000000-0000-0000-0000-000000000006".parse()?,
+        target: target()?,
+        event_types: event_types(),
+        hmac_token: Some(Secret::new("testing")),
+        hmac_token_previous: None,
+        include_tags: true,
+    })
+}
+
+fn webhook_event() -> Result<WebhookEvent, Box<dyn std::error::Error>> {
+    Ok(WebhookEvent {
+        event_id: "00000000-0000-0000-0000-000000000007".parse()?,
+        event_type: WebhookEventType::ImageCreated,
+        timestamp: timestamp()?,
+        image: Some(image_id()),
+        tags: Some(tags()),
+    })
+}
+
+fn webhook_log() -> Result<WebhookLog, Box<dyn std::error::Error>> {
+    let event = webhook_event()?;
+    Ok(WebhookLog {
+        last_updated: Some(timestamp()?),
+        webhook_id: "00000000-0000-0000-0000-000000000006".parse()?,
+        event_id: event.event_id,
+        event,
+        state: WebhookEventState::Failure,
+        error: Some("connection refused".to_string()),
+    })
+}
+
+fn collection() -> Result<Collection, Box<dyn std::error::Error>> {
+    Ok(Collection {
+        name: "incident-42".parse()?,
+        description: Some("hosts from incident 42".to_string()),
+        image_ids: vec![image_id()],
+    })
+}
+
+fn report() -> Report {
+    Report {
+        analysis_info: AnalysisInfo {
+            analysis_version: "0.22.0".to_string(),
+            detection_versions: BTreeMap::new(),
+            tags: BTreeMap::from([("host".to_string(), "web-01".to_string())]),
+        },
+        info: report::Info {
+            banner: "Linux version 5.15.0-91-generic".to_string(),
+            kernel_aslr_offset: 0x1a00_0000,
+            kernel_pml4: 0x10c6_a000,
+            machine: UtsName {
+                domainname: "(none)".to_string(),
+                machine: "x86_64".to_string(),
+                nodename: "web-01".to_string(),
+                release: "5.15.0-91-generic".to_string(),
+                sysname: "Linux".to_string(),
+                version: "#101-Ubuntu SMP".to_string(),
+            },
+            memory_info: MemoryInfo {
+                swap_pages: 0,
+                total_ram: Some(2_097_152),
+                total_swap_pages: 0,
+            },
+        },
+        tasks: vec![Task {
+            addr: VirtualAddress(0xffff_8881_0000_1000),
+            arg: vec!["/sbin/init".to_string()],
+            comm: "systemd".to_string(),
+            cred: Cred::default(),
+            cwd: "/".to_string(),
+            env: vec![],
+            exe: Some(File {
+                address: VirtualAddress(0xffff_8882_0000_0100),
+                fd: None,
+                fs_type: Some("ext4".to_string()),
+                inode: None,
+                mode: vec![Mode::Read, Mode::Exec],
+                mode_raw: 0x8000,
+                offset: 0,
+                path: "/usr/lib/systemd/systemd".to_string(),
+            }),
+            files: vec![],
+            max_fds: 1024,
+            pid: 1,
+            ppid: 0,
+            ptrace: 0,
+            real_ppid: 0,
+            source: vec![TaskSource::TaskList],
+            start_time: 0,
+            stime: 10,
+            tgid: 1,
+            utime: 20,
+            extra: Map::new(),
+        }],
+        kernel_modules: KernelModules {
+            modules: vec![Module {
+                addr: VirtualAddress(0xffff_ffff_c000_3000),
+                args: None,
+                core_addr: VirtualAddress(0xffff_ffff_c000_0000),
+                core_artifact_name: None,
+                core_size: 0x4000,
+                init_addr: VirtualAddress(0),
+                init_artifact_name: None,
+                init_size: 0,
+                name: "rootkit".to_string(),
+                source: vec![ModuleSource::SourceList],
+                srcversion: String::new(),
+                symbols: vec![ModuleSymbol {
+                    addr: VirtualAddress(0xffff_ffff_c000_1000),
+                    name: "hook_read".to_string(),
+                }],
+                version: String::new(),
+            }],
+            modules_disabled: Some(0),
+            notifiers: vec![],
+        },
+        checks: vec![Check {
+            issue: "syscall table entry hooked".to_string(),
+            details: None,
+            hook: Some(Hook {
+                addr: VirtualAddress(0xffff_ffff_8100_0000),
+                hook_type: "inline".to_string(),
+                disassembly: "jmp 0xffffffffc0001000".to_string(),
+                target_addr: Some(VirtualAddress(0xffff_ffff_c000_1000)),
+                target_disassembly: None,
+                target_module: Some(Symbol::Module(
+                    "rootkit".to_string(),
+                    "hook_read".to_string(),
+                )),
+            }),
+            address: None,
+            symbol: Some(Symbol::Kernel("sys_read".to_string())),
+            pids: vec![],
+            paths: vec![],
+            exported_path: None,
+        }],
+        extra: Map::new(),
+    }
+}
+
+fn baseline_summary() -> BaselineSummary {
+    BaselineSummary {
+        image_id: image_id(),
+        kernel_version: Some("5.15.0-91-generic".to_string()),
+        modules: BTreeSet::from(["ext4".to_string(), "rootkit".to_string()]),
+        hooks: BTreeSet::from(["syscall table entry hooked".to_string()]),
+        findings: BTreeSet::new(),
+    }
+}
+
+#[test]
+fn test_image_wire_format() -> TestResult {
+    assert_wire_format!("image", image()?);
+    assert_wire_format!(
+        "analysis_progress",
+        AnalysisProgress {
+            stage: "processes".to_string(),
+            stage_index: Some(2),
+            stage_count: Some(5),
+            percent: Some(42.5),
+        }
+    );
+    assert_wire_format!(
+        "image_list",
+        ImageList {
+            owner_id: Some(owner_id()),
+            state: Some(ImageState::Completed),
+            include_samples: true,
+            ..ImageList::default()
+        }
+    );
+    assert_wire_format!(
+        "images_list_response",
+        ImagesListResponse {
+            images: vec![image()?],
+            continuation: Some("next".to_string()),
+        }
+    );
+    assert_wire_format!(
+        "image_create",
+        ImageCreate::new(ImageFormat::Lime)
+            .with_tags(tags())
+            .with_callback_url(Url::parse("https://example.com/callback")?)
+            .with_requested_analysis_version("1.2.3")
+    );
+    assert_wire_format!(
+        "image_reanalyze",
+        ImageReanalyze::default().with_requested_analysis_version("1.2.3")
+    );
+    assert_wire_format!("image_update", ImageUpdate::default().with_shareable(true));
+    Ok(())
+}
+
+#[test]
+fn test_service_wire_format() -> TestResult {
+    assert_wire_format!(
+        "info",
+        Info {
+            api_version: "1".to_string(),
+            models_version: MODELS_VERSION.to_string(),
+            current_eula: "0123456789abcdef".to_string(),
+            formats: vec![ImageFormat::Lime, ImageFormat::Pagedump],
+        }
+    );
+    assert_wire_format!("user_config", UserConfig::default());
+    assert_wire_format!(
+        "bool_responses",
+        (
+            ImageReanalyzeResponse(true),
+            ImageUploadCompleteResponse(true),
+            ImageDeleteResponse(true),
+            UserConfigUpdateResponse(true),
+            WebhookBoolResponse(false),
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_webhook_wire_format() -> TestResult {
+    assert_wire_format!("webhook", webhook()?);
+    assert_wire_format!(
+        "webhook_rotating",
+        Webhook {
+            hmac_token_previous: Some(Secret::new("previous")),
+            ..webhook()?
+        }
+    );
+    assert_wire_format!(
+        "webhook_targets",
+        vec![
+            target()?,
+            WebhookTarget::AzureServiceBus {
+                namespace: "contoso".to_string(),
+                queue: "freta".to_string(),
+            },
+            WebhookTarget::StorageQueue {
+                account: "contoso".to_string(),
+                queue: "freta".to_string(),
+            },
+        ]
+    );
+    assert_wire_format!("webhook_event", webhook_event()?);
+    assert_wire_format!("webhook_log", webhook_log()?);
+    assert_wire_format!(
+        "webhook_submit",
+        WebhookSubmit::new(target()?, event_types())
+            .with_hmac_token(Secret::new("testing"))
+            .with_include_tags(true)
+    );
+    assert_wire_format!(
+        "webhook_ping_request",
+        WebhookPingRequest::new(WebhookEventType::Ping)
+    );
+    assert_wire_format!(
+        "webhooks_list_request",
+        WebhooksListRequest::default()
+            .with_url_contains("example")
+            .with_event_type(WebhookEventType::ImageCreated)
+    );
+    assert_wire_format!(
+        "webhooks_list_response",
+        WebhooksListResponse {
+            webhooks: vec![webhook()?],
+            continuation: None,
+        }
+    );
+    assert_wire_format!(
+        "webhook_log_list_request",
+        WebhookLogListRequest::default()
+            .with_event_type(WebhookEventType::ImageCreated)
+            .with_state(WebhookEventState::Failure)
+            .with_since(timestamp()?)
+            .with_until(timestamp()? + Duration::days(1))
+    );
+    assert_wire_format!(
+        "webhook_log_list_response",
+        WebhookLogListResponse {
+            webhook_events: vec![webhook_log()?],
+            continuation: Some("next".to_string()),
+        }
+    );
+    assert_wire_format!(
+        "webhook_event_replay_request",
+        WebhookEventReplayRequest::new("00000000-0000-0000-0000-000000000007".parse()?)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_collection_wire_format() -> TestResult {
+    assert_wire_format!("collection", collection()?);
+    assert_wire_format!(
+        "collection_create",
+        CollectionCreate {
+            name: "incident-42".parse()?,
+            description: None,
+            image_ids: vec![image_id()],
+        }
+    );
+    assert_wire_format!(
+        "collection_add_image",
+        CollectionAddImage {
+            image_id: image_id(),
+        }
+    );
+    assert_wire_format!(
+        "collections_list_request",
+        CollectionsListRequest::default()
+    );
+    assert_wire_format!(
+        "collections_list_response",
+        CollectionsListResponse {
+            collections: vec![collection()?],
+            continuation: None,
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_analysis_wire_format() -> TestResult {
+    assert_wire_format!("report", report());
+    assert_wire_format!("baseline_summary", baseline_summary());
+    assert_wire_format!(
+        "drift_report",
+        DriftReport {
+            baseline_image_id: ImageId::from(Uuid::from_u128(4)),
+            image_id: image_id(),
+            baseline_kernel_version: Some("5.15.0-89-generic".to_string()),
+            kernel_version: Some("5.15.0-91-generic".to_string()),
+            modules: Changes {
+                added: BTreeSet::from(["rootkit".to_string()]),
+                removed: BTreeSet::new(),
+            },
+            hooks: Changes {
+                added: BTreeSet::from(["syscall table entry hooked".to_string()]),
+                removed: BTreeSet::new(),
+            },
+            findings: Changes::default(),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_schedule_wire_format() -> TestResult {
+    let schedule_id = "00000000-0000-0000-0000-000000000008".parse()?;
+    assert_wire_format!(
+        "schedule",
+        Schedule {
+            schedule_id,
+            cron: "0 3 * * 1".parse()?,
+            action: ScheduledAction::Reanalyze {
+                tags: tags(),
+                engine_version: Some("1.2.3".to_string()),
+            },
+            created_at: timestamp()?,
+            last_run: Some(timestamp()? + Duration::days(1)),
+        }
+    );
+    assert_wire_format!(
+        "schedule_run",
+        ScheduleRun {
+            schedule_id,
+            image_ids: vec![image_id()],
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_policy_wire_format() -> TestResult {
+    let action = Action::Tag {
+        key: "quarantine".to_string(),
+        value: "true".to_string(),
+    };
+    assert_wire_format!(
+        "policy_set",
+        PolicySet {
+            policies: vec![Policy {
+                name: "quarantine".to_string(),
+                when: Condition {
+                    min_checks: Some(1),
+                    hooks: Some(true),
+                    issue_contains: vec!["hooked".to_string()],
+                    tags: tags(),
+                },
+                then: vec![
+                    action.clone(),
+                    Action::WebhookPost {
+                        url: Url::parse("https://example.com/policies")?,
+                    },
+                ],
+                otherwise: vec![Action::SetRetention { days: 30 }, Action::Delete],
+            }],
+        }
+    );
+    assert_wire_format!(
+        "policy_action",
+        PolicyAction {
+            policy: "quarantine".to_string(),
+            image_id: image_id(),
+            action,
+        }
+    );
+    assert_wire_format!(
+        "policy_notification",
+        PolicyNotification {
+            policy: "quarantine".to_string(),
+            image_id: image_id(),
+            tags: tags(),
+            issues: vec!["syscall table entry hooked".to_string()],
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_cost_wire_format() -> TestResult {
+    assert_wire_format!(
+        "cost_rates",
+        CostRates {
+            storage_per_gib_month: 0.5,
+            analysis_per_gib: 0.25,
+        }
+    );
+    assert_wire_format!(
+        "image_usage",
+        ImageUsage {
+            image_id: image_id(),
+            owner_id: owner_id(),
+            tags: tags(),
+            bytes: 1 << 30,
+            stored_days: 1.5,
+            retained_days: 0.5,
+            analyses: 1,
+        }
+    );
+    assert_wire_format!(
+        "cost_entry",
+        CostEntry {
+            group: "red".to_string(),
+            images: 1,
+            bytes: 1 << 30,
+            analyses: 1,
+            storage_cost: 0.25,
+            analysis_cost: 0.25,
+            total_cost: 0.5,
+            projected_storage_cost: 0.125,
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_token_wire_format() -> TestResult {
+    let token = Token {
+        token_id: "00000000-0000-0000-0000-000000000009".parse()?,
+        name: "lab-01".to_string(),
+        created_at: timestamp()?,
+        expires_at: Some(timestamp()? + Duration::days(90)),
+    };
+    assert_wire_format!(
+        "token_create",
+        TokenCreate {
+            name: "lab-01".to_string(),
+            expires_in_days: Some(90),
+        }
+    );
+    assert_wire_format!(
+        "token_create_response",
+        TokenCreateResponse {
+            token: token.clone(),
+            secret: "secret".to_string(),
+        }
+    );
+    assert_wire_format!(
+        "tokens_list_request",
+        TokensListRequest {
+            continuation: Some("continuation".to_string()),
+        }
+    );
+    assert_wire_format!(
+        "tokens_list_response",
+        TokensListResponse {
+            tokens: vec![token],
+            continuation: Some("continuation".to_string()),
+        }
+    );
+    assert_wire_format!("token_revoke_response", TokenRevokeResponse(true));
+    Ok(())
+}